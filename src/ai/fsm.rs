@@ -0,0 +1,48 @@
+use std::time::Duration;
+
+// C is the context the states act upon, typically the agent they drive.
+pub trait State<C> {
+    fn enter(&mut self, _context: &mut C) {}
+
+    // Returning a state switches to it once this one has exited.
+    fn update(&mut self, context: &mut C, dt: Duration) -> Option<Box<dyn State<C>>>;
+
+    fn exit(&mut self, _context: &mut C) {}
+}
+
+// Runs one state at a time, tick has to be called once per frame.
+pub struct StateMachine<C> {
+    current: Box<dyn State<C>>,
+    entered: bool,
+}
+
+impl<C> StateMachine<C> {
+    pub fn new(initial: Box<dyn State<C>>) -> Self {
+        StateMachine {
+            current: initial,
+            entered: false,
+        }
+    }
+
+    pub fn tick(&mut self, context: &mut C, dt: Duration) {
+        if !self.entered {
+            self.current.enter(context);
+            self.entered = true;
+        }
+
+        if let Some(next) = self.current.update(context, dt) {
+            self.switch(context, next);
+        }
+    }
+
+    // Forces a transition from outside of the states, e.g. when the agent gets hit.
+    pub fn switch(&mut self, context: &mut C, next: Box<dyn State<C>>) {
+        if self.entered {
+            self.current.exit(context);
+        }
+
+        self.current = next;
+        self.current.enter(context);
+        self.entered = true;
+    }
+}
@@ -0,0 +1,158 @@
+use std::f64::consts::PI;
+
+use crate::{math::Vec2Ext, Vec2};
+
+pub use fsm::{State, StateMachine};
+
+mod fsm;
+
+// Steering behaviors return forces to be summed and applied with SteeringAgent::apply.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct SteeringAgent {
+    pub position: Vec2,
+    pub velocity: Vec2,
+    pub max_speed: f64,
+    pub max_force: f64,
+}
+
+// Keeps the target of the wander behavior from one frame to the next, so the agent turns
+// smoothly instead of jittering.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Wander {
+    pub distance: f64,
+    pub radius: f64,
+    // Maximum change of angle per second, in radians.
+    pub jitter: f64,
+    angle: f64,
+    seed: u64,
+}
+
+impl SteeringAgent {
+    pub fn new(position: Vec2, max_speed: f64, max_force: f64) -> Self {
+        SteeringAgent {
+            position,
+            velocity: Vec2::ZERO,
+            max_speed,
+            max_force,
+        }
+    }
+
+    pub fn seek(&self, target: &Vec2) -> Vec2 {
+        let desired = (target - self.position).normalize_or_zero() * self.max_speed;
+        self.steer(desired)
+    }
+
+    pub fn flee(&self, threat: &Vec2) -> Vec2 {
+        let desired = (self.position - threat).normalize_or_zero() * self.max_speed;
+        self.steer(desired)
+    }
+
+    // Seeks the target, slowing down once within slowing_radius to stop on it.
+    pub fn arrive(&self, target: &Vec2, slowing_radius: f64) -> Vec2 {
+        let offset = target - self.position;
+        let distance = offset.length();
+        let speed = if distance < slowing_radius {
+            self.max_speed * distance / slowing_radius
+        } else {
+            self.max_speed
+        };
+
+        self.steer(offset.normalize_or_zero() * speed)
+    }
+
+    pub fn wander(&self, wander: &mut Wander, dt: f64) -> Vec2 {
+        wander.angle += wander.next_random() * wander.jitter * dt;
+
+        let heading = self.velocity.normalize_or(Vec2::X);
+        let circle_center = self.position + heading * wander.distance;
+        let target = circle_center + heading.rotated(wander.angle) * wander.radius;
+        self.seek(&target)
+    }
+
+    // Pushes away from the neighbors closer than radius, the closest ones the hardest.
+    pub fn separation(&self, neighbors: &[Vec2], radius: f64) -> Vec2 {
+        let push: Vec2 = neighbors
+            .iter()
+            .map(|n| self.position - n)
+            .filter(|d| d.length() > 0. && d.length() < radius)
+            .map(|d| d.normalize() / d.length())
+            .sum();
+
+        if push == Vec2::ZERO {
+            return Vec2::ZERO;
+        }
+        self.steer(push.normalize() * self.max_speed)
+    }
+
+    // Matches the average velocity of the neighbors.
+    pub fn alignment(&self, neighbor_velocities: &[Vec2]) -> Vec2 {
+        if neighbor_velocities.is_empty() {
+            return Vec2::ZERO;
+        }
+
+        let average = neighbor_velocities.iter().sum::<Vec2>() / neighbor_velocities.len() as f64;
+        self.steer(average.normalize_or_zero() * self.max_speed)
+    }
+
+    // Seeks the center of the neighbors.
+    pub fn cohesion(&self, neighbors: &[Vec2]) -> Vec2 {
+        if neighbors.is_empty() {
+            return Vec2::ZERO;
+        }
+
+        let center = neighbors.iter().sum::<Vec2>() / neighbors.len() as f64;
+        self.seek(&center)
+    }
+
+    // Classic flocking, mixing separation, alignment and cohesion with the given weights.
+    pub fn flock(
+        &self,
+        neighbors: &[SteeringAgent],
+        separation_radius: f64,
+        weights: (f64, f64, f64),
+    ) -> Vec2 {
+        let positions: Vec<Vec2> = neighbors.iter().map(|n| n.position).collect();
+        let velocities: Vec<Vec2> = neighbors.iter().map(|n| n.velocity).collect();
+
+        self.separation(&positions, separation_radius) * weights.0
+            + self.alignment(&velocities) * weights.1
+            + self.cohesion(&positions) * weights.2
+    }
+
+    // Applies the force, clamped to max_force, then moves the agent.
+    pub fn apply(&mut self, force: Vec2, dt: f64) {
+        self.velocity = (self.velocity + force.clamp_length_max(self.max_force) * dt)
+            .clamp_length_max(self.max_speed);
+        self.position += self.velocity * dt;
+    }
+
+    fn steer(&self, desired_velocity: Vec2) -> Vec2 {
+        (desired_velocity - self.velocity).clamp_length_max(self.max_force)
+    }
+}
+
+impl Wander {
+    pub fn new(distance: f64, radius: f64, jitter: f64, seed: u64) -> Self {
+        Wander {
+            distance,
+            radius,
+            jitter,
+            angle: 0.,
+            seed: seed.max(1),
+        }
+    }
+
+    // Between -1 and 1, from a xorshift so that wandering is reproducible from the seed.
+    fn next_random(&mut self) -> f64 {
+        self.seed ^= self.seed << 13;
+        self.seed ^= self.seed >> 7;
+        self.seed ^= self.seed << 17;
+        (self.seed as f64 / u64::MAX as f64) * 2. - 1.
+    }
+}
+
+impl Default for Wander {
+    fn default() -> Self {
+        Wander::new(2., 1., PI, 1)
+    }
+}
@@ -0,0 +1,216 @@
+use std::{collections::HashMap, time::Duration};
+
+use super::{Animation, AnimationPlayer};
+use crate::{
+    graphics::{DrawParams, GraphicsPipeline, PixelRect},
+    Vec2,
+};
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Debug)]
+pub struct StateId(usize);
+
+// Parameters missing from the animator count as false or 0.
+#[derive(Clone, PartialEq, Debug)]
+pub enum Condition {
+    Bool(String, bool),
+    Greater(String, f64),
+    Less(String, f64),
+    // Set by set_trigger and consumed by the transition it allows.
+    Trigger(String),
+    // The animation of the current state played to its end at least once.
+    Finished,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum Parameter {
+    Bool(bool),
+    Float(f64),
+    Trigger,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+struct Transition {
+    // None for transitions from any state.
+    from: Option<StateId>,
+    to: StateId,
+    conditions: Vec<Condition>,
+}
+
+// Plays the animation of its current state and switches states through transitions whose
+// conditions are all true. Transitions are only taken at the end of a frame, so that animations
+// don't cut in the middle of one, and are tried in the order they were added. update has to be
+// called once per frame.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Animator {
+    states: Vec<(String, Animation)>,
+    transitions: Vec<Transition>,
+    parameters: HashMap<String, Parameter>,
+    state: StateId,
+    player: AnimationPlayer,
+}
+
+impl Condition {
+    fn holds(&self, parameters: &HashMap<String, Parameter>, player: &AnimationPlayer) -> bool {
+        let float = |name: &str| match parameters.get(name) {
+            Some(Parameter::Float(x)) => *x,
+            _ => 0.,
+        };
+
+        match self {
+            Condition::Bool(name, value) => {
+                matches!(parameters.get(name), Some(Parameter::Bool(true))) == *value
+            }
+            Condition::Greater(name, threshold) => float(name) > *threshold,
+            Condition::Less(name, threshold) => float(name) < *threshold,
+            Condition::Trigger(name) => parameters.get(name) == Some(&Parameter::Trigger),
+            Condition::Finished => player.finished(),
+        }
+    }
+}
+
+impl Animator {
+    // The first state added is the initial one.
+    pub fn new(name: &str, animation: Animation) -> Self {
+        Animator {
+            states: vec![(name.to_string(), animation)],
+            transitions: Vec::new(),
+            parameters: HashMap::new(),
+            state: StateId(0),
+            player: AnimationPlayer::new(),
+        }
+    }
+
+    pub fn add_state(&mut self, name: &str, animation: Animation) -> StateId {
+        self.states.push((name.to_string(), animation));
+        StateId(self.states.len() - 1)
+    }
+
+    // The first state is always 0, see new.
+    pub fn find_state(&self, name: &str) -> Option<StateId> {
+        self.states.iter().position(|(n, _)| n == name).map(StateId)
+    }
+
+    pub fn add_transition(&mut self, from: StateId, to: StateId, conditions: Vec<Condition>) {
+        self.transitions.push(Transition {
+            from: Some(from),
+            to,
+            conditions,
+        });
+    }
+
+    // Taken from every state but the target one, e.g. to the hurt state.
+    pub fn add_any_transition(&mut self, to: StateId, conditions: Vec<Condition>) {
+        self.transitions.push(Transition {
+            from: None,
+            to,
+            conditions,
+        });
+    }
+
+    pub fn set_bool(&mut self, name: &str, value: bool) {
+        self.parameters
+            .insert(name.to_string(), Parameter::Bool(value));
+    }
+
+    pub fn set_float(&mut self, name: &str, value: f64) {
+        self.parameters
+            .insert(name.to_string(), Parameter::Float(value));
+    }
+
+    // Stays set until a transition using it is taken.
+    pub fn set_trigger(&mut self, name: &str) {
+        self.parameters.insert(name.to_string(), Parameter::Trigger);
+    }
+
+    pub fn reset_trigger(&mut self, name: &str) {
+        if self.parameters.get(name) == Some(&Parameter::Trigger) {
+            self.parameters.remove(name);
+        }
+    }
+
+    // Returns the events started by the frames entered, in order, including those of the states
+    // left during the update.
+    pub fn update(&mut self, dt: Duration) -> Vec<String> {
+        let mut events = Vec::new();
+        let mut dt = dt;
+        // Bounded so that transitions between states without duration can't loop forever
+        for _ in 0..=self.states.len() {
+            let (transitions, parameters, state) =
+                (&self.transitions, &self.parameters, self.state);
+            let animation = &self.states[state.0].1;
+            let mut taken = None;
+            let mut entered = Vec::new();
+            let left = self.player.advance(animation, dt, &mut entered, |player| {
+                taken = transitions.iter().position(|t| {
+                    t.from.is_none_or(|from| from == state)
+                        && (t.from.is_some() || t.to != state)
+                        && t.conditions.iter().all(|c| c.holds(parameters, player))
+                });
+                taken.is_some()
+            });
+            events.extend(animation.events_entered(&entered));
+
+            let (Some(left), Some(taken)) = (left, taken) else {
+                break;
+            };
+            self.take(taken);
+            dt = left;
+        }
+        events
+    }
+
+    // Whether the current frame is within one of the events of that name.
+    pub fn is_active(&self, event: &str) -> bool {
+        self.player.is_active(self.animation(), event)
+    }
+
+    // Switches state right away, ignoring transitions.
+    pub fn play(&mut self, state: StateId) {
+        if state.0 < self.states.len() {
+            self.state = state;
+            self.player.restart();
+        }
+    }
+
+    pub fn state(&self) -> StateId {
+        self.state
+    }
+
+    pub fn state_name(&self) -> &str {
+        &self.states[self.state.0].0
+    }
+
+    pub fn animation(&self) -> &Animation {
+        &self.states[self.state.0].1
+    }
+
+    pub fn frame(&self) -> usize {
+        self.player.frame()
+    }
+
+    pub fn src(&self) -> Option<PixelRect> {
+        self.player.src(self.animation())
+    }
+
+    pub fn draw(
+        &self,
+        graphics_ppl: &mut GraphicsPipeline,
+        position: &Vec2,
+        size: &Vec2,
+        params: &DrawParams,
+    ) {
+        self.player
+            .draw(self.animation(), graphics_ppl, position, size, params);
+    }
+
+    fn take(&mut self, transition: usize) {
+        let transition = &self.transitions[transition];
+        for condition in &transition.conditions {
+            if let Condition::Trigger(name) = condition {
+                self.parameters.remove(name);
+            }
+        }
+        self.state = transition.to;
+        self.player.restart();
+    }
+}
@@ -0,0 +1,607 @@
+use std::{path::Path, time::Duration};
+
+use parry2d_f64::shape::SharedShape;
+
+use super::{inflate, Animation, Frame};
+use crate::{
+    graphics::{GraphicsPipeline, PixelRect, TextureId},
+    Point, Vec2,
+};
+
+const MAGIC: u16 = 0xa5e0;
+const FRAME_MAGIC: u16 = 0xf1fa;
+
+const OLD_PALETTE_CHUNK: u16 = 0x0004;
+const LAYER_CHUNK: u16 = 0x2004;
+const CEL_CHUNK: u16 = 0x2005;
+const TAGS_CHUNK: u16 = 0x2018;
+const PALETTE_CHUNK: u16 = 0x2019;
+const USER_DATA_CHUNK: u16 = 0x2020;
+const SLICE_CHUNK: u16 = 0x2022;
+
+// Slices whose name starts with it or whose user data text contains it are hitboxes.
+const HITBOX: &str = "hitbox";
+
+// Sprite made with Aseprite, its visible layers flattened into one image per frame.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Aseprite {
+    pub width: u32,
+    pub height: u32,
+    pub frames: Vec<AsepriteFrame>,
+    pub tags: Vec<Tag>,
+    pub slices: Vec<Slice>,
+}
+
+// RGBA pixels, row by row.
+#[derive(Clone, PartialEq, Debug)]
+pub struct AsepriteFrame {
+    pub pixels: Vec<u8>,
+    pub duration: Duration,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum TagDirection {
+    Forward,
+    Reverse,
+    PingPong,
+    PingPongReverse,
+}
+
+// Named range of frames, played as an animation. It loops unless the tag repeats a set number
+// of times.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Tag {
+    pub name: String,
+    pub from: usize,
+    pub to: usize,
+    pub direction: TagDirection,
+    pub repeat: u16,
+}
+
+// Named area of the sprite, e.g. a pivot or a hitbox, that can move and resize over the frames.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Slice {
+    pub name: String,
+    pub hitbox: bool,
+    pub keys: Vec<SliceKey>,
+}
+
+// rect applies from frame until the next key, an empty rect hiding the slice. The pivot is
+// relative to the rect's top left corner.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct SliceKey {
+    pub frame: usize,
+    pub rect: PixelRect,
+    pub pivot: Option<Point>,
+}
+
+struct Layer {
+    visible: bool,
+    group: bool,
+    background: bool,
+    opacity: u8,
+}
+
+#[derive(Clone)]
+struct Cel {
+    layer: usize,
+    position: Point,
+    opacity: u8,
+    width: usize,
+    height: usize,
+    pixels: Vec<u8>,
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+
+impl Aseprite {
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, String> {
+        let path = path.as_ref();
+        let data = std::fs::read(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        Aseprite::parse(&data).map_err(|e| format!("{}: {}", path.display(), e))
+    }
+
+    // Layers are composited in order with normal blending whatever their blend mode. Tilemap
+    // layers are skipped.
+    pub fn parse(data: &[u8]) -> Result<Self, String> {
+        let mut reader = Reader { data, position: 0 };
+        reader.skip(4)?;
+        if reader.u16()? != MAGIC {
+            return Err("not an aseprite file".to_string());
+        }
+        let frame_count = reader.u16()? as usize;
+        let width = reader.u16()? as u32;
+        let height = reader.u16()? as u32;
+        let depth = reader.u16()?;
+        if ![8, 16, 32].contains(&depth) {
+            return Err(format!("unsupported color depth {depth}"));
+        }
+        let layer_opacity = reader.u32()? & 1 != 0;
+        reader.skip(10)?;
+        let transparent = reader.u8()?;
+        reader.position = 128;
+
+        let mut sprite = Aseprite {
+            width,
+            height,
+            frames: Vec::with_capacity(frame_count),
+            tags: Vec::new(),
+            slices: Vec::new(),
+        };
+        let mut layers: Vec<Layer> = Vec::new();
+        // Visibility of the groups containing the next layer, by depth
+        let mut groups: Vec<bool> = Vec::new();
+        let mut palette = vec![[0u8; 4]; 256];
+        let mut cels: Vec<Vec<Cel>> = Vec::with_capacity(frame_count);
+
+        for frame in 0..frame_count {
+            let start = reader.position;
+            let size = reader.u32()? as usize;
+            if reader.u16()? != FRAME_MAGIC {
+                return Err(format!("invalid frame {frame}"));
+            }
+            let old_chunks = reader.u16()? as u32;
+            let duration = Duration::from_millis(reader.u16()? as u64);
+            reader.skip(2)?;
+            let chunks = match reader.u32()? {
+                0 => old_chunks,
+                chunks => chunks,
+            };
+
+            let mut frame_cels: Vec<Cel> = Vec::new();
+            // Slice described by the next user data chunk
+            let mut user_data = None;
+            for _ in 0..chunks {
+                let chunk_start = reader.position;
+                let chunk_size = reader.u32()? as usize;
+                let kind = reader.u16()?;
+                let mut chunk = Reader {
+                    data: reader.bytes(chunk_size.saturating_sub(6))?,
+                    position: 0,
+                };
+
+                match kind {
+                    OLD_PALETTE_CHUNK => {
+                        let mut index = 0;
+                        for _ in 0..chunk.u16()? {
+                            index += chunk.u8()? as usize;
+                            let count = match chunk.u8()? {
+                                0 => 256,
+                                count => count as usize,
+                            };
+                            for _ in 0..count {
+                                let [r, g, b] = [chunk.u8()?, chunk.u8()?, chunk.u8()?];
+                                if let Some(color) = palette.get_mut(index) {
+                                    *color = [r, g, b, 255];
+                                }
+                                index += 1;
+                            }
+                        }
+                    }
+                    PALETTE_CHUNK => {
+                        let size = chunk.u32()? as usize;
+                        let (first, last) = (chunk.u32()? as usize, chunk.u32()? as usize);
+                        chunk.skip(8)?;
+                        palette.resize(size.max(palette.len()), [0; 4]);
+                        for index in first..=last {
+                            let flags = chunk.u16()?;
+                            let color = [chunk.u8()?, chunk.u8()?, chunk.u8()?, chunk.u8()?];
+                            if flags & 1 != 0 {
+                                chunk.string()?;
+                            }
+                            if let Some(entry) = palette.get_mut(index) {
+                                *entry = color;
+                            }
+                        }
+                    }
+                    LAYER_CHUNK => {
+                        let flags = chunk.u16()?;
+                        let kind = chunk.u16()?;
+                        let level = chunk.u16()? as usize;
+                        chunk.skip(6)?;
+                        let opacity = chunk.u8()?;
+
+                        let visible =
+                            flags & 1 != 0 && groups.get(level.wrapping_sub(1)) != Some(&false);
+                        groups.truncate(level);
+                        groups.push(visible);
+                        layers.push(Layer {
+                            visible,
+                            group: kind != 0,
+                            background: flags & 8 != 0,
+                            opacity: if layer_opacity { opacity } else { 255 },
+                        });
+                        user_data = None;
+                    }
+                    CEL_CHUNK => {
+                        let layer = chunk.u16()? as usize;
+                        let position = Point::new(chunk.i16()? as i32, chunk.i16()? as i32);
+                        let opacity = chunk.u8()?;
+                        let kind = chunk.u16()?;
+                        chunk.skip(7)?;
+
+                        let cel = match kind {
+                            0 | 2 => {
+                                let width = chunk.u16()? as usize;
+                                let height = chunk.u16()? as usize;
+                                let rest = &chunk.data[chunk.position..];
+                                let pixels = if kind == 0 {
+                                    rest.to_vec()
+                                } else {
+                                    inflate::zlib(rest)?
+                                };
+                                if pixels.len() < width * height * depth as usize / 8 {
+                                    return Err(format!("truncated cel in frame {frame}"));
+                                }
+                                Some(Cel {
+                                    layer,
+                                    position,
+                                    opacity,
+                                    width,
+                                    height,
+                                    pixels,
+                                })
+                            }
+                            // Same cel as that layer's in an earlier frame
+                            1 => {
+                                let linked = chunk.u16()? as usize;
+                                cels.get(linked)
+                                    .and_then(|c| c.iter().find(|c| c.layer == layer))
+                                    .cloned()
+                            }
+                            _ => None,
+                        };
+                        frame_cels.extend(cel);
+                        user_data = None;
+                    }
+                    TAGS_CHUNK => {
+                        let count = chunk.u16()?;
+                        chunk.skip(8)?;
+                        for _ in 0..count {
+                            let from = chunk.u16()? as usize;
+                            let to = chunk.u16()? as usize;
+                            let direction = match chunk.u8()? {
+                                1 => TagDirection::Reverse,
+                                2 => TagDirection::PingPong,
+                                3 => TagDirection::PingPongReverse,
+                                _ => TagDirection::Forward,
+                            };
+                            let repeat = chunk.u16()?;
+                            chunk.skip(10)?;
+                            let name = chunk.string()?;
+                            sprite.tags.push(Tag {
+                                name,
+                                from,
+                                to: to.max(from),
+                                direction,
+                                repeat,
+                            });
+                        }
+                        user_data = None;
+                    }
+                    SLICE_CHUNK => {
+                        let count = chunk.u32()?;
+                        let flags = chunk.u32()?;
+                        chunk.skip(4)?;
+                        let name = chunk.string()?;
+
+                        let mut keys = Vec::new();
+                        for _ in 0..count {
+                            let frame = chunk.u32()? as usize;
+                            let (x, y) = (chunk.i32()?, chunk.i32()?);
+                            let (width, height) = (chunk.u32()?, chunk.u32()?);
+                            // Nine slice center
+                            if flags & 1 != 0 {
+                                chunk.skip(16)?;
+                            }
+                            let pivot = if flags & 2 != 0 {
+                                Some(Point::new(chunk.i32()?, chunk.i32()?))
+                            } else {
+                                None
+                            };
+                            keys.push(SliceKey {
+                                frame,
+                                rect: PixelRect::new(x, y, width, height),
+                                pivot,
+                            });
+                        }
+
+                        sprite.slices.push(Slice {
+                            hitbox: name.starts_with(HITBOX),
+                            name,
+                            keys,
+                        });
+                        user_data = Some(sprite.slices.len() - 1);
+                    }
+                    USER_DATA_CHUNK => {
+                        let text = if chunk.u32()? & 1 != 0 {
+                            chunk.string()?
+                        } else {
+                            String::new()
+                        };
+                        if let Some(slice) = user_data.take() {
+                            sprite.slices[slice].hitbox |= text.contains(HITBOX);
+                        }
+                    }
+                    _ => {}
+                }
+
+                reader.position = chunk_start + chunk_size.max(6);
+            }
+
+            reader.position = start + size;
+            cels.push(frame_cels);
+            sprite.frames.push(AsepriteFrame {
+                pixels: Vec::new(),
+                duration,
+            });
+        }
+
+        // Flattened once every frame is read as the palette can come after the first cels
+        for (frame, frame_cels) in sprite.frames.iter_mut().zip(&mut cels) {
+            let mut pixels = vec![0; (width * height * 4) as usize];
+            frame_cels.sort_by_key(|c| c.layer);
+            for cel in frame_cels.iter() {
+                let Some(layer) = layers.get(cel.layer).filter(|l| l.visible && !l.group) else {
+                    continue;
+                };
+                let opacity = cel.opacity as u32 * layer.opacity as u32 / 255;
+                let indexed_transparent = (!layer.background).then_some(transparent);
+                composite(
+                    &mut pixels,
+                    (width as usize, height as usize),
+                    cel,
+                    opacity,
+                    |p: &[u8]| match depth {
+                        32 => [p[0], p[1], p[2], p[3]],
+                        16 => [p[0], p[0], p[0], p[1]],
+                        _ if Some(p[0]) == indexed_transparent => [0; 4],
+                        _ => palette.get(p[0] as usize).copied().unwrap_or_default(),
+                    },
+                    depth as usize / 8,
+                );
+            }
+            frame.pixels = pixels;
+        }
+
+        Ok(sprite)
+    }
+
+    // Texture of every frame, laid out left to right then top to bottom in a square grid.
+    pub fn texture(&self, graphics_ppl: &mut GraphicsPipeline) -> Result<TextureId, String> {
+        let columns = self.columns();
+        let rows = self.frames.len().div_ceil(columns).max(1);
+        let sheet_width = self.width as usize * columns;
+        let row_size = self.width as usize * 4;
+
+        let mut pixels = vec![0; sheet_width * self.height as usize * rows * 4];
+        for (frame, image) in self.frames.iter().enumerate() {
+            let src = self.src(frame);
+            for (y, row) in image.pixels.chunks_exact(row_size).enumerate() {
+                let start = ((src.y() as usize + y) * sheet_width + src.x() as usize) * 4;
+                pixels[start..start + row_size].copy_from_slice(row);
+            }
+        }
+
+        graphics_ppl.create_texture(sheet_width as u32, self.height * rows as u32, &pixels)
+    }
+
+    // Area of the frame in the texture.
+    pub fn src(&self, frame: usize) -> PixelRect {
+        let columns = self.columns();
+        PixelRect::new(
+            ((frame % columns) as u32 * self.width) as i32,
+            ((frame / columns) as u32 * self.height) as i32,
+            self.width,
+            self.height,
+        )
+    }
+
+    // Frames played by the tag in order, every frame when tag is None.
+    pub fn frames_of(&self, tag: Option<&str>) -> Option<Vec<usize>> {
+        let Some(tag) = tag else {
+            return Some((0..self.frames.len()).collect());
+        };
+        let tag = self.tags.iter().find(|t| t.name == tag)?;
+        let forward = tag.from..=tag.to.min(self.frames.len().checked_sub(1)?);
+
+        let frames = match tag.direction {
+            TagDirection::Forward => forward.collect(),
+            TagDirection::Reverse => forward.rev().collect(),
+            // The ends aren't repeated when turning around
+            TagDirection::PingPong => {
+                let back = forward.clone().rev().skip(1);
+                let back = back.take(forward.clone().count().saturating_sub(2));
+                forward.chain(back).collect()
+            }
+            TagDirection::PingPongReverse => {
+                let back = forward.clone().skip(1);
+                let back = back.take(forward.clone().count().saturating_sub(2));
+                forward.rev().chain(back).collect()
+            }
+        };
+        Some(frames)
+    }
+
+    // Animation of the tag, or of every frame when None. Each hitbox gets an event of its name
+    // active on the frames where it's visible.
+    pub fn animation(&self, texture: TextureId, tag: Option<&str>) -> Option<Animation> {
+        let frames = self.frames_of(tag)?;
+        let looping = tag
+            .and_then(|name| self.tags.iter().find(|t| t.name == name))
+            .is_none_or(|t| t.repeat == 0);
+
+        let mut animation = Animation::new(
+            texture,
+            frames
+                .iter()
+                .map(|&frame| Frame {
+                    src: self.src(frame),
+                    duration: self.frames[frame].duration,
+                })
+                .collect(),
+            looping,
+        );
+
+        for slice in self.slices.iter().filter(|s| s.hitbox) {
+            let mut start = None;
+            for i in 0..=frames.len() {
+                let visible = frames.get(i).is_some_and(|&f| slice.key(f).is_some());
+                match (start, visible) {
+                    (None, true) => start = Some(i),
+                    (Some(first), false) => {
+                        animation = animation.with_event(&slice.name, first..=i - 1);
+                        start = None;
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        Some(animation)
+    }
+
+    // Animation of every tag, by name.
+    pub fn animations(&self, texture: TextureId) -> Vec<(String, Animation)> {
+        self.tags
+            .iter()
+            .filter_map(|tag| Some((tag.name.clone(), self.animation(texture, Some(&tag.name))?)))
+            .collect()
+    }
+
+    pub fn slice(&self, name: &str) -> Option<&Slice> {
+        self.slices.iter().find(|s| s.name == name)
+    }
+
+    fn columns(&self) -> usize {
+        (self.frames.len() as f64).sqrt().ceil().max(1.) as usize
+    }
+}
+
+impl Slice {
+    // Key of the frame, None when the slice is hidden on it.
+    pub fn key(&self, frame: usize) -> Option<&SliceKey> {
+        self.keys
+            .iter()
+            .rev()
+            .find(|k| k.frame <= frame)
+            .filter(|k| k.rect.width() > 0 && k.rect.height() > 0)
+    }
+
+    // Pivot of the frame in sprite pixels, the rect's center when it doesn't have one.
+    pub fn pivot(&self, frame: usize) -> Option<Point> {
+        let key = self.key(frame)?;
+        let pivot = key.pivot.unwrap_or(Point::new(
+            key.rect.width() as i32 / 2,
+            key.rect.height() as i32 / 2,
+        ));
+        Some(Point::new(key.rect.x(), key.rect.y()) + pivot)
+    }
+
+    // Box of the frame for physics bodies and areas, with its center relative to origin, both
+    // in sprite pixels, converted to world units. The offset points down for positive y as in
+    // the image and has to be flipped in y up worlds.
+    pub fn shape(
+        &self,
+        frame: usize,
+        origin: Point,
+        pixel_per_unit: f64,
+    ) -> Option<(SharedShape, Vec2)> {
+        let rect = self.key(frame)?.rect;
+        let size = Vec2::new(rect.width() as f64, rect.height() as f64) / pixel_per_unit;
+        let center = Vec2::new(
+            rect.x() as f64 + rect.width() as f64 / 2. - origin.x as f64,
+            rect.y() as f64 + rect.height() as f64 / 2. - origin.y as f64,
+        );
+        Some((
+            SharedShape::cuboid(size.x / 2., size.y / 2.),
+            center / pixel_per_unit,
+        ))
+    }
+}
+
+// Draws the cel over the frame's pixels, color reading a pixel of the cel.
+fn composite(
+    pixels: &mut [u8],
+    (width, height): (usize, usize),
+    cel: &Cel,
+    opacity: u32,
+    color: impl Fn(&[u8]) -> [u8; 4],
+    bytes_per_pixel: usize,
+) {
+    for y in 0..cel.height {
+        let Ok(row) = usize::try_from(cel.position.y + y as i32) else {
+            continue;
+        };
+        if row >= height {
+            break;
+        }
+        for x in 0..cel.width {
+            let Ok(column) = usize::try_from(cel.position.x + x as i32) else {
+                continue;
+            };
+            if column >= width {
+                break;
+            }
+
+            let start = (y * cel.width + x) * bytes_per_pixel;
+            let [r, g, b, a] = color(&cel.pixels[start..start + bytes_per_pixel]);
+            let source_alpha = a as u32 * opacity / 255;
+            if source_alpha == 0 {
+                continue;
+            }
+
+            let dest = &mut pixels[(row * width + column) * 4..][..4];
+            let dest_alpha = dest[3] as u32 * (255 - source_alpha) / 255;
+            let alpha = source_alpha + dest_alpha;
+            for (channel, source) in dest.iter_mut().zip([r, g, b]) {
+                *channel =
+                    ((source as u32 * source_alpha + *channel as u32 * dest_alpha) / alpha) as u8;
+            }
+            dest[3] = alpha as u8;
+        }
+    }
+}
+
+impl<'a> Reader<'a> {
+    fn bytes(&mut self, count: usize) -> Result<&'a [u8], String> {
+        let bytes = self
+            .data
+            .get(self.position..self.position + count)
+            .ok_or("unexpected end of file")?;
+        self.position += count;
+        Ok(bytes)
+    }
+
+    fn skip(&mut self, count: usize) -> Result<(), String> {
+        self.bytes(count).map(|_| ())
+    }
+
+    fn u8(&mut self) -> Result<u8, String> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16, String> {
+        Ok(u16::from_le_bytes(self.bytes(2)?.try_into().unwrap()))
+    }
+
+    fn i16(&mut self) -> Result<i16, String> {
+        Ok(i16::from_le_bytes(self.bytes(2)?.try_into().unwrap()))
+    }
+
+    fn u32(&mut self) -> Result<u32, String> {
+        Ok(u32::from_le_bytes(self.bytes(4)?.try_into().unwrap()))
+    }
+
+    fn i32(&mut self) -> Result<i32, String> {
+        Ok(i32::from_le_bytes(self.bytes(4)?.try_into().unwrap()))
+    }
+
+    fn string(&mut self) -> Result<String, String> {
+        let length = self.u16()? as usize;
+        Ok(String::from_utf8_lossy(self.bytes(length)?).into_owned())
+    }
+}
@@ -0,0 +1,274 @@
+// Decompresses zlib data, as found in Aseprite cels, and raw deflate data, as found in zip
+// archives.
+
+const LENGTH_BASE: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131,
+    163, 195, 227, 258,
+];
+const LENGTH_EXTRA: [u8; 29] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0,
+];
+const DISTANCE_BASE: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537,
+    2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577,
+];
+const DISTANCE_EXTRA: [u8; 30] = [
+    0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13,
+    13,
+];
+// Order in which the code lengths of the code length alphabet are stored.
+const CODE_LENGTH_ORDER: [usize; 19] = [
+    16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15,
+];
+
+struct Bits<'a> {
+    data: &'a [u8],
+    position: usize,
+    bit: u32,
+}
+
+// Canonical Huffman code, as the number of codes of each length and the symbols sorted by code.
+struct Huffman {
+    counts: [u16; 16],
+    symbols: Vec<u16>,
+}
+
+pub(super) fn zlib(data: &[u8]) -> Result<Vec<u8>, String> {
+    if data.len() < 2
+        || data[0] & 0x0f != 8
+        || !u16::from_be_bytes([data[0], data[1]]).is_multiple_of(31)
+    {
+        return Err("invalid zlib header".to_string());
+    }
+    inflate(&data[2..])
+}
+
+pub(crate) fn inflate(data: &[u8]) -> Result<Vec<u8>, String> {
+    let mut bits = Bits {
+        data,
+        position: 0,
+        bit: 0,
+    };
+    let mut output = Vec::new();
+
+    loop {
+        let last = bits.read(1)? == 1;
+        match bits.read(2)? {
+            0 => {
+                bits.align();
+                let length = bits.read(16)? as usize;
+                let _complement = bits.read(16)?;
+                let start = bits.position;
+                let block = data
+                    .get(start..start + length)
+                    .ok_or("truncated stored block")?;
+                output.extend_from_slice(block);
+                bits.position += length;
+            }
+            1 => {
+                let (lengths, distances) = fixed_codes();
+                decode_block(&mut bits, &mut output, &lengths, &distances)?;
+            }
+            2 => {
+                let (lengths, distances) = dynamic_codes(&mut bits)?;
+                decode_block(&mut bits, &mut output, &lengths, &distances)?;
+            }
+            _ => return Err("invalid deflate block".to_string()),
+        }
+
+        if last {
+            return Ok(output);
+        }
+    }
+}
+
+fn decode_block(
+    bits: &mut Bits,
+    output: &mut Vec<u8>,
+    lengths: &Huffman,
+    distances: &Huffman,
+) -> Result<(), String> {
+    loop {
+        let symbol = lengths.decode(bits)? as usize;
+        match symbol {
+            0..=255 => output.push(symbol as u8),
+            256 => return Ok(()),
+            _ => {
+                let index = symbol - 257;
+                let length = *LENGTH_BASE.get(index).ok_or("invalid length")? as usize
+                    + bits.read(LENGTH_EXTRA[index] as u32)? as usize;
+
+                let index = distances.decode(bits)? as usize;
+                let distance = *DISTANCE_BASE.get(index).ok_or("invalid distance")? as usize
+                    + bits.read(DISTANCE_EXTRA[index] as u32)? as usize;
+                if distance > output.len() {
+                    return Err("distance too far back".to_string());
+                }
+
+                // Copied byte by byte as the source may overlap what's being written
+                let start = output.len() - distance;
+                for i in 0..length {
+                    output.push(output[start + i]);
+                }
+            }
+        }
+    }
+}
+
+fn fixed_codes() -> (Huffman, Huffman) {
+    let mut lengths = [0u8; 288];
+    lengths[..144].fill(8);
+    lengths[144..256].fill(9);
+    lengths[256..280].fill(7);
+    lengths[280..].fill(8);
+    (Huffman::new(&lengths), Huffman::new(&[5; 30]))
+}
+
+fn dynamic_codes(bits: &mut Bits) -> Result<(Huffman, Huffman), String> {
+    let length_count = bits.read(5)? as usize + 257;
+    let distance_count = bits.read(5)? as usize + 1;
+    let code_length_count = bits.read(4)? as usize + 4;
+
+    let mut code_lengths = [0u8; 19];
+    for &index in &CODE_LENGTH_ORDER[..code_length_count] {
+        code_lengths[index] = bits.read(3)? as u8;
+    }
+    let code_lengths = Huffman::new(&code_lengths);
+
+    let mut lengths = Vec::with_capacity(length_count + distance_count);
+    while lengths.len() < length_count + distance_count {
+        let (value, repeat) = match code_lengths.decode(bits)? {
+            symbol @ 0..=15 => (symbol as u8, 1),
+            16 => {
+                let previous = *lengths.last().ok_or("repeat without previous length")?;
+                (previous, 3 + bits.read(2)?)
+            }
+            17 => (0, 3 + bits.read(3)?),
+            18 => (0, 11 + bits.read(7)?),
+            _ => return Err("invalid code length".to_string()),
+        };
+        lengths.extend(std::iter::repeat_n(value, repeat as usize));
+    }
+    if lengths.len() > length_count + distance_count {
+        return Err("too many code lengths".to_string());
+    }
+
+    let (literals, distances) = lengths.split_at(length_count);
+    Ok((Huffman::new(literals), Huffman::new(distances)))
+}
+
+impl Bits<'_> {
+    // Least significant bit first.
+    fn read(&mut self, count: u32) -> Result<u32, String> {
+        let mut value = 0;
+        for i in 0..count {
+            let byte = *self
+                .data
+                .get(self.position)
+                .ok_or("truncated deflate data")?;
+            value |= (((byte >> self.bit) & 1) as u32) << i;
+            self.bit += 1;
+            if self.bit == 8 {
+                self.bit = 0;
+                self.position += 1;
+            }
+        }
+        Ok(value)
+    }
+
+    fn align(&mut self) {
+        if self.bit > 0 {
+            self.bit = 0;
+            self.position += 1;
+        }
+    }
+}
+
+impl Huffman {
+    fn new(lengths: &[u8]) -> Self {
+        let mut counts = [0u16; 16];
+        for &length in lengths {
+            counts[length as usize] += 1;
+        }
+        counts[0] = 0;
+
+        let mut symbols = Vec::new();
+        for length in 1..16 {
+            for (symbol, _) in lengths.iter().enumerate().filter(|(_, l)| **l == length) {
+                symbols.push(symbol as u16);
+            }
+        }
+        Huffman { counts, symbols }
+    }
+
+    // Reads the code bit by bit, codes of each length following those of the previous one.
+    fn decode(&self, bits: &mut Bits) -> Result<u16, String> {
+        let (mut code, mut first, mut index) = (0i32, 0i32, 0i32);
+        for length in 1..16 {
+            code |= bits.read(1)? as i32;
+            let count = self.counts[length] as i32;
+            if code - first < count {
+                return Ok(self.symbols[(index + code - first) as usize]);
+            }
+            index += count;
+            first = (first + count) << 1;
+            code <<= 1;
+        }
+        Err("invalid huffman code".to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hex(text: &str) -> Vec<u8> {
+        (0..text.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&text[i..i + 2], 16).unwrap())
+            .collect()
+    }
+
+    // Compressed with Python's zlib.
+    #[test]
+    fn blocks_of_every_kind_are_inflated() {
+        let stored = hex("7801010500faff68656c6c6f062c0215");
+        assert_eq!(zlib(&stored).unwrap(), b"hello");
+
+        let fixed = hex("78dacb48cdc9c957c840908a0040cc069e");
+        assert_eq!(zlib(&fixed).unwrap(), b"hello hello hello!");
+
+        let dynamic =
+            hex("78da15c8c701003008c3c0595d88bdff0410e977c16579f08a61ef04fd4a1d28b6402c3eff0f88");
+        let mut x = 1u32;
+        let expected: Vec<u8> = (0..40)
+            .map(|_| {
+                x = x.wrapping_mul(1103515245).wrapping_add(12345);
+                let r = x >> 16;
+                if r.is_multiple_of(3) {
+                    b'a'
+                } else {
+                    b'a' + (r % 8) as u8
+                }
+            })
+            .collect();
+        assert_eq!(zlib(&dynamic).unwrap(), expected);
+
+        // Raw deflate as in zip archives, the matches overlapping what they copy
+        assert_eq!(inflate(&hex("4b4c4a4e444200")).unwrap(), b"abcabcabcabcabc");
+    }
+
+    #[test]
+    fn invalid_data_is_an_error() {
+        assert_eq!(zlib(&[0x78]).unwrap_err(), "invalid zlib header");
+        assert_eq!(zlib(&[0x78, 0x00]).unwrap_err(), "invalid zlib header");
+
+        let fixed = hex("78dacb48cdc9c957c840908a0040cc069e");
+        assert_eq!(zlib(&fixed[..8]).unwrap_err(), "truncated deflate data");
+        assert_eq!(inflate(&[0x07]).unwrap_err(), "invalid deflate block");
+        assert_eq!(
+            inflate(&[0x01, 0x05, 0x00, 0xfa, 0xff, b'h']).unwrap_err(),
+            "truncated stored block"
+        );
+    }
+}
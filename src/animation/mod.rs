@@ -0,0 +1,215 @@
+use std::{ops::RangeInclusive, time::Duration};
+
+use crate::{
+    graphics::{DrawParams, GraphicsPipeline, PixelRect, TextureId},
+    Vec2,
+};
+
+pub use animator::{Animator, Condition, StateId};
+pub use aseprite::{Aseprite, AsepriteFrame, Slice, SliceKey, Tag, TagDirection};
+
+pub(crate) use inflate::inflate;
+
+mod animator;
+mod aseprite;
+mod inflate;
+
+// Image of an animation, src being its area of the texture in pixels.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Frame {
+    pub src: PixelRect,
+    pub duration: Duration,
+}
+
+// Named span of frames, e.g. "footstep" on frame 3 or "hitbox_active" from frame 5 to 8.
+// Updates return its name when playback enters its first frame, and it's active on the others.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct FrameEvent {
+    pub name: String,
+    pub frames: RangeInclusive<usize>,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct Animation {
+    pub texture: TextureId,
+    pub frames: Vec<Frame>,
+    // Animations that don't loop stay on their last frame.
+    pub looping: bool,
+    pub events: Vec<FrameEvent>,
+}
+
+// Plays an animation, update has to be called once per frame.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub struct AnimationPlayer {
+    frame: usize,
+    time: Duration,
+    finished: bool,
+    // Whether the first frame was entered.
+    started: bool,
+}
+
+impl Animation {
+    pub fn new(texture: TextureId, frames: Vec<Frame>, looping: bool) -> Self {
+        Animation {
+            texture,
+            frames,
+            looping,
+            events: Vec::new(),
+        }
+    }
+
+    pub fn with_event(mut self, name: &str, frames: RangeInclusive<usize>) -> Self {
+        self.events.push(FrameEvent {
+            name: name.to_string(),
+            frames,
+        });
+        self
+    }
+
+    // Names of the events starting on the frames, in order.
+    fn events_entered(&self, frames: &[usize]) -> Vec<String> {
+        frames
+            .iter()
+            .flat_map(|frame| {
+                self.events
+                    .iter()
+                    .filter(move |e| e.frames.start() == frame)
+                    .map(|e| e.name.clone())
+            })
+            .collect()
+    }
+
+    // count frames of the same size and duration, laid out left to right then top to bottom in
+    // a texture of the given columns, starting from the first one.
+    pub fn from_grid(
+        texture: TextureId,
+        (width, height): (u32, u32),
+        columns: u32,
+        (first, count): (u32, u32),
+        duration: Duration,
+        looping: bool,
+    ) -> Self {
+        let columns = columns.max(1);
+        let frames = (first..first + count)
+            .map(|i| Frame {
+                src: PixelRect::new(
+                    ((i % columns) * width) as i32,
+                    ((i / columns) * height) as i32,
+                    width,
+                    height,
+                ),
+                duration,
+            })
+            .collect();
+        Animation::new(texture, frames, looping)
+    }
+
+    pub fn duration(&self) -> Duration {
+        self.frames.iter().map(|f| f.duration).sum()
+    }
+}
+
+impl AnimationPlayer {
+    pub fn new() -> Self {
+        AnimationPlayer::default()
+    }
+
+    // Returns the events started by the frames entered, in order.
+    pub fn update(&mut self, animation: &Animation, dt: Duration) -> Vec<String> {
+        let mut entered = Vec::new();
+        self.advance(animation, dt, &mut entered, |_| false);
+        animation.events_entered(&entered)
+    }
+
+    // Whether the current frame is within one of the events of that name.
+    pub fn is_active(&self, animation: &Animation, event: &str) -> bool {
+        animation
+            .events
+            .iter()
+            .any(|e| e.name == event && e.frames.contains(&self.frame))
+    }
+
+    // Index of the frame being shown.
+    pub fn frame(&self) -> usize {
+        self.frame
+    }
+
+    // Whether the animation played to its end at least once.
+    pub fn finished(&self) -> bool {
+        self.finished
+    }
+
+    pub fn restart(&mut self) {
+        *self = AnimationPlayer::default();
+    }
+
+    // None for animations without frames.
+    pub fn src(&self, animation: &Animation) -> Option<PixelRect> {
+        Some(animation.frames.get(self.frame)?.src)
+    }
+
+    pub fn draw(
+        &self,
+        animation: &Animation,
+        graphics_ppl: &mut GraphicsPipeline,
+        position: &Vec2,
+        size: &Vec2,
+        params: &DrawParams,
+    ) {
+        if let Some(src) = self.src(animation) {
+            graphics_ppl.draw_sprite(animation.texture, Some(src), position, size, params);
+        }
+    }
+
+    // Moves the animation forward by dt, calling at_end at the end of every frame and adding
+    // the frames entered to entered. When at_end returns true the animation stops there and the
+    // time left is returned.
+    fn advance<F: FnMut(&AnimationPlayer) -> bool>(
+        &mut self,
+        animation: &Animation,
+        dt: Duration,
+        entered: &mut Vec<usize>,
+        mut at_end: F,
+    ) -> Option<Duration> {
+        let last = animation.frames.len().checked_sub(1)?;
+        self.frame = self.frame.min(last);
+        if !self.started {
+            self.started = true;
+            entered.push(self.frame);
+        }
+        self.time += dt;
+        if animation.duration().is_zero() {
+            self.time = Duration::ZERO;
+        }
+
+        loop {
+            let duration = animation.frames[self.frame].duration;
+            let held = self.finished && !animation.looping && self.frame == last;
+            if !held && self.time < duration {
+                return None;
+            }
+
+            if !held {
+                self.time -= duration;
+                self.finished |= self.frame == last;
+            }
+            if at_end(self) {
+                return Some(std::mem::take(&mut self.time));
+            }
+
+            if self.frame < last {
+                self.frame += 1;
+            } else if animation.looping {
+                self.frame = 0;
+            } else {
+                self.time = Duration::ZERO;
+                return None;
+            }
+            entered.push(self.frame);
+            // Frames without duration are shown for one update
+            if duration.is_zero() {
+                return None;
+            }
+        }
+    }
+}
@@ -0,0 +1,125 @@
+use std::path::PathBuf;
+
+// Help text of the flags, for games to print along with their own.
+pub const USAGE: &str = "\
+--windowed             start in a window, whatever the settings
+--fullscreen           start fullscreen
+--resolution WxH       size of the window, e.g. 1280x720
+--scene PATH           scene to start with
+--headless             run without showing a window nor playing audio, e.g. for tests
+--record-input PATH    write the inputs' changes to the file
+--seed N               seed of Engine::random";
+
+// Flags common to every game, for dev workflows and automated runs, applied with
+// EngineBuilder::args. Values follow their flag either as the next argument or after "=",
+// which is an error for the flags without values.
+// Other arguments are kept in rest for the game, in order.
+#[derive(Clone, PartialEq, Eq, Debug, Default)]
+pub struct Args {
+    // None when neither --windowed nor --fullscreen was given, the last one winning.
+    pub fullscreen: Option<bool>,
+    pub resolution: Option<(u32, u32)>,
+    // Only parsed, the game loads it.
+    pub scene: Option<PathBuf>,
+    pub headless: bool,
+    pub record_input: Option<PathBuf>,
+    pub seed: Option<u64>,
+    pub rest: Vec<String>,
+}
+
+impl Args {
+    // Arguments of the process, without the program's name.
+    pub fn from_env() -> Result<Self, String> {
+        Args::parse(std::env::args().skip(1))
+    }
+
+    pub fn parse<I: IntoIterator<Item = String>>(args: I) -> Result<Self, String> {
+        let mut parsed = Args::default();
+        let mut args = args.into_iter();
+
+        while let Some(arg) = args.next() {
+            let (flag, inline) = match arg.split_once('=') {
+                Some((flag, value)) if arg.starts_with("--") => (flag, Some(value.to_string())),
+                _ => (arg.as_str(), None),
+            };
+            let mut value = || {
+                inline
+                    .clone()
+                    .or_else(|| args.next())
+                    .ok_or_else(|| format!("{flag} expects a value"))
+            };
+
+            match flag {
+                "--windowed" | "--fullscreen" | "--headless" if inline.is_some() => {
+                    return Err(format!("{flag} doesn't take a value"));
+                }
+                "--windowed" => parsed.fullscreen = Some(false),
+                "--fullscreen" => parsed.fullscreen = Some(true),
+                "--resolution" => {
+                    let value = value()?;
+                    parsed.resolution = Some(
+                        parse_resolution(&value)
+                            .ok_or_else(|| format!("invalid resolution \"{value}\""))?,
+                    );
+                }
+                "--scene" => parsed.scene = Some(value()?.into()),
+                "--headless" => parsed.headless = true,
+                "--record-input" => parsed.record_input = Some(value()?.into()),
+                "--seed" => {
+                    let value = value()?;
+                    parsed.seed = Some(
+                        value
+                            .parse()
+                            .map_err(|_| format!("invalid seed \"{value}\""))?,
+                    );
+                }
+                _ => parsed.rest.push(arg.clone()),
+            }
+        }
+        Ok(parsed)
+    }
+}
+
+fn parse_resolution(value: &str) -> Option<(u32, u32)> {
+    let (width, height) = value.split_once(['x', 'X'])?;
+    let size = (width.parse().ok()?, height.parse().ok()?);
+    (size.0 > 0 && size.1 > 0).then_some(size)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Args, String> {
+        Args::parse(args.iter().map(|a| a.to_string()))
+    }
+
+    #[test]
+    fn values_follow_their_flag() {
+        let args = parse(&[
+            "--resolution",
+            "1280x720",
+            "--seed=7",
+            "--windowed",
+            "level",
+        ])
+        .unwrap();
+        assert_eq!(args.resolution, Some((1280, 720)));
+        assert_eq!(args.seed, Some(7));
+        assert_eq!(args.fullscreen, Some(false));
+        assert_eq!(args.rest, vec!["level".to_string()]);
+
+        assert_eq!(parse(&["--scene"]).unwrap_err(), "--scene expects a value");
+        assert!(parse(&["--resolution=0x720"]).is_err());
+    }
+
+    #[test]
+    fn flags_without_values_reject_one() {
+        assert_eq!(
+            parse(&["--headless=false"]).unwrap_err(),
+            "--headless doesn't take a value"
+        );
+        assert!(parse(&["--fullscreen=1"]).is_err());
+        assert!(parse(&["--windowed="]).is_err());
+    }
+}
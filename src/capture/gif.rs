@@ -0,0 +1,146 @@
+use std::{collections::HashMap, io::Write};
+
+use crate::graphics::Screenshot;
+
+// 6 levels of red and blue and 7 of green, the eye being more sensitive to it.
+const LEVELS: (u32, u32, u32) = (6, 7, 6);
+const MAX_CODE: u16 = 4096;
+
+// Animated GIF looping forever, frames sharing a fixed palette and being written as they come.
+pub(super) struct GifEncoder<W: Write> {
+    writer: W,
+    // Hundredths of a second per frame.
+    delay: u16,
+}
+
+impl<W: Write> GifEncoder<W> {
+    pub(super) fn new(mut writer: W, width: u32, height: u32, fps: u32) -> Result<Self, String> {
+        let size = |value: u32| {
+            u16::try_from(value).map_err(|_| format!("frames of {width}x{height} are too large"))
+        };
+        let (width, height) = (size(width)?, size(height)?);
+
+        let mut header = b"GIF89a".to_vec();
+        header.extend(width.to_le_bytes());
+        header.extend(height.to_le_bytes());
+        // Global palette of 256 colors, 8 bits per channel
+        header.extend([0xf7, 0, 0]);
+        for index in 0..256 {
+            header.extend(palette_color(index));
+        }
+        // Netscape extension, looping forever
+        header.extend([0x21, 0xff, 0x0b]);
+        header.extend(b"NETSCAPE2.0");
+        header.extend([0x03, 0x01, 0x00, 0x00, 0x00]);
+        writer.write_all(&header).map_err(|e| e.to_string())?;
+
+        Ok(GifEncoder {
+            writer,
+            delay: (100 / fps.max(1)).max(2) as u16,
+        })
+    }
+
+    pub(super) fn add_frame(&mut self, frame: &Screenshot) -> Result<(), String> {
+        let indices: Vec<u8> = frame
+            .pixels
+            .chunks_exact(4)
+            .map(|p| palette_index(p[0], p[1], p[2]))
+            .collect();
+
+        let mut block = vec![0x21, 0xf9, 0x04, 0x00];
+        block.extend(self.delay.to_le_bytes());
+        block.extend([0x00, 0x00]);
+        block.push(0x2c);
+        block.extend([0, 0, 0, 0]);
+        block.extend((frame.width as u16).to_le_bytes());
+        block.extend((frame.height as u16).to_le_bytes());
+        block.push(0x00);
+        block.push(8);
+        for chunk in compress(&indices).chunks(255) {
+            block.push(chunk.len() as u8);
+            block.extend(chunk);
+        }
+        block.push(0x00);
+        self.writer.write_all(&block).map_err(|e| e.to_string())
+    }
+
+    pub(super) fn finish(mut self) -> Result<(), String> {
+        self.writer.write_all(&[0x3b]).map_err(|e| e.to_string())?;
+        self.writer.flush().map_err(|e| e.to_string())
+    }
+}
+
+fn palette_color(index: u32) -> [u8; 3] {
+    let (r, g, b) = LEVELS;
+    if index >= r * g * b {
+        return [0, 0, 0];
+    }
+    let level = |value: u32, levels: u32| (value * 255 / (levels - 1)) as u8;
+    [
+        level(index / (g * b), r),
+        level(index / b % g, g),
+        level(index % b, b),
+    ]
+}
+
+fn palette_index(red: u8, green: u8, blue: u8) -> u8 {
+    let (r, g, b) = LEVELS;
+    let level = |value: u8, levels: u32| (value as u32 * (levels - 1) + 127) / 255;
+    (level(red, r) * g * b + level(green, g) * b + level(blue, b)) as u8
+}
+
+// Variable length LZW of 8 bit indices, codes packed from the least significant bit.
+fn compress(indices: &[u8]) -> Vec<u8> {
+    const CLEAR: u16 = 256;
+    const END: u16 = 257;
+
+    let mut output = Vec::new();
+    let mut buffer = 0u32;
+    let mut bits = 0;
+    let mut emit = |code: u16, size: u32, output: &mut Vec<u8>| {
+        buffer |= (code as u32) << bits;
+        bits += size;
+        while bits >= 8 {
+            output.push(buffer as u8);
+            buffer >>= 8;
+            bits -= 8;
+        }
+    };
+
+    let mut table: HashMap<(u16, u8), u16> = HashMap::new();
+    let mut next = END + 1;
+    let mut size = 9;
+    emit(CLEAR, size, &mut output);
+
+    let Some((&first, rest)) = indices.split_first() else {
+        emit(END, size, &mut output);
+        return output;
+    };
+    let mut prefix = first as u16;
+    for &index in rest {
+        if let Some(&code) = table.get(&(prefix, index)) {
+            prefix = code;
+            continue;
+        }
+
+        emit(prefix, size, &mut output);
+        if next < MAX_CODE {
+            table.insert((prefix, index), next);
+            next += 1;
+            if next > 1 << size && size < 12 {
+                size += 1;
+            }
+        } else {
+            emit(CLEAR, size, &mut output);
+            table.clear();
+            next = END + 1;
+            size = 9;
+        }
+        prefix = index as u16;
+    }
+    emit(prefix, size, &mut output);
+    emit(END, size, &mut output);
+    // Remaining bits
+    emit(0, 7, &mut output);
+    output
+}
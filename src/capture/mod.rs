@@ -0,0 +1,333 @@
+use std::{
+    fs::File,
+    io::BufWriter,
+    path::{Path, PathBuf},
+    sync::mpsc::{self, Receiver, Sender},
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use gif::GifEncoder;
+
+use crate::{
+    graphics::Screenshot,
+    inputs::{ButtonControl, InputScheme, Scancode},
+    Engine,
+};
+
+mod gif;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum CaptureFormat {
+    #[default]
+    Gif,
+    // Encoded by ffmpeg, which has to be installed.
+    #[cfg(feature = "video")]
+    Mp4,
+}
+
+pub struct CaptureOptions {
+    pub format: CaptureFormat,
+    pub fps: u32,
+    // Frames are shrunk by this factor, GIFs of the full window being large.
+    pub downscale: u32,
+    // Where captures are written, capture-<unix time>.gif or .mp4.
+    pub directory: PathBuf,
+}
+
+// Records the main window's presented frames at options.fps, started and stopped with
+// toggle_key. Frames are encoded on another thread and written as they come, the file being
+// complete once stop returns its path.
+pub struct Capture {
+    pub options: CaptureOptions,
+    pub toggle_key: Scancode,
+    recording: Option<Recording>,
+    held: bool,
+}
+
+struct Recording {
+    path: PathBuf,
+    frames: Sender<Screenshot>,
+    encoder: std::thread::JoinHandle<Result<(), String>>,
+    started: Instant,
+    captured: u32,
+    // Of the first frame, the following ones having to match it.
+    size: Option<(u32, u32)>,
+}
+
+enum Encoder {
+    Gif(GifEncoder<BufWriter<File>>),
+    #[cfg(feature = "video")]
+    Mp4(std::process::Child),
+}
+
+impl Default for CaptureOptions {
+    fn default() -> Self {
+        CaptureOptions {
+            format: CaptureFormat::Gif,
+            fps: 15,
+            downscale: 1,
+            directory: PathBuf::from("captures"),
+        }
+    }
+}
+
+impl CaptureFormat {
+    fn extension(&self) -> &'static str {
+        match self {
+            CaptureFormat::Gif => "gif",
+            #[cfg(feature = "video")]
+            CaptureFormat::Mp4 => "mp4",
+        }
+    }
+}
+
+impl Capture {
+    pub fn new(options: CaptureOptions) -> Self {
+        Capture {
+            options,
+            toggle_key: Scancode::F9,
+            recording: None,
+            held: false,
+        }
+    }
+
+    pub fn is_recording(&self) -> bool {
+        self.recording.is_some()
+    }
+
+    pub fn start(&mut self) -> Result<(), String> {
+        if self.recording.is_some() {
+            return Err("already recording".to_string());
+        }
+        if self.options.fps == 0 || self.options.downscale == 0 {
+            return Err("fps and downscale have to be positive".to_string());
+        }
+
+        #[cfg(feature = "video")]
+        if self.options.format == CaptureFormat::Mp4 {
+            check_ffmpeg()?;
+        }
+
+        std::fs::create_dir_all(&self.options.directory).map_err(|e| e.to_string())?;
+        let time = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_secs());
+        let path = self.options.directory.join(format!(
+            "capture-{}.{}",
+            time,
+            self.options.format.extension()
+        ));
+
+        let (frames, received) = mpsc::channel();
+        let (format, fps, encoder_path) = (self.options.format, self.options.fps, path.clone());
+        let encoder = std::thread::Builder::new()
+            .name("capture".to_string())
+            .spawn(move || encode(received, format, fps, &encoder_path))
+            .map_err(|e| e.to_string())?;
+
+        log::info!("capturing to {}", path.display());
+        self.recording = Some(Recording {
+            path,
+            frames,
+            encoder,
+            started: Instant::now(),
+            captured: 0,
+            size: None,
+        });
+        Ok(())
+    }
+
+    // Waits for the remaining frames to be encoded.
+    pub fn stop(&mut self) -> Result<PathBuf, String> {
+        let recording = self.recording.take().ok_or("not recording")?;
+        drop(recording.frames);
+        recording
+            .encoder
+            .join()
+            .map_err(|_| "the encoder panicked".to_string())??;
+
+        log::info!("capture written to {}", recording.path.display());
+        Ok(recording.path)
+    }
+
+    // To be called every frame after Engine::update, the frames being read as the main window
+    // is presented.
+    pub fn update<T: InputScheme>(&mut self, engine: &mut Engine<T>) {
+        let held = engine
+            .inputs_ppl
+            .is_held(&ButtonControl::Keyboard(self.toggle_key));
+        if held && !self.held {
+            let result = match self.recording {
+                Some(_) => self.stop().map(|_| ()),
+                None => self.start(),
+            };
+            if let Err(e) = result {
+                log::warn!("capture failed: {e}");
+            }
+        }
+        self.held = held;
+
+        let downscale = self.options.downscale;
+        let fps = self.options.fps;
+        let Some(recording) = &mut self.recording else {
+            return;
+        };
+
+        if let Some(frame) = engine.graphics_ppl.take_screenshot() {
+            let frame = shrink(frame, downscale);
+            let size = *recording.size.get_or_insert((frame.width, frame.height));
+            // Skipped while the window is being resized
+            if size == (frame.width, frame.height) && recording.frames.send(frame).is_err() {
+                let error = self.stop().err().unwrap_or_default();
+                log::warn!("capture failed: {error}");
+                return;
+            }
+        }
+
+        // Frames are taken at fixed times, so that the capture keeps the game's pace
+        let next = Duration::from_secs_f64(recording.captured as f64 / fps as f64);
+        if recording.started.elapsed() >= next {
+            recording.captured += 1;
+            engine.graphics_ppl.request_screenshot();
+        }
+    }
+}
+
+fn encode(
+    frames: Receiver<Screenshot>,
+    format: CaptureFormat,
+    fps: u32,
+    path: &Path,
+) -> Result<(), String> {
+    let mut encoder = None;
+    for frame in frames {
+        let encoder = match &mut encoder {
+            Some(encoder) => encoder,
+            None => encoder.insert(Encoder::new(format, fps, path, &frame)?),
+        };
+        encoder.add_frame(&frame)?;
+    }
+
+    match encoder {
+        Some(encoder) => encoder.finish(),
+        None => Err("no frame was captured".to_string()),
+    }
+}
+
+impl Encoder {
+    fn new(
+        format: CaptureFormat,
+        fps: u32,
+        path: &Path,
+        first: &Screenshot,
+    ) -> Result<Self, String> {
+        match format {
+            CaptureFormat::Gif => {
+                let file = File::create(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+                let encoder =
+                    GifEncoder::new(BufWriter::new(file), first.width, first.height, fps)?;
+                Ok(Encoder::Gif(encoder))
+            }
+            #[cfg(feature = "video")]
+            CaptureFormat::Mp4 => {
+                use std::process::{Command, Stdio};
+
+                let child = Command::new("ffmpeg")
+                    .args(["-loglevel", "error", "-y"])
+                    .args(["-f", "rawvideo", "-pix_fmt", "rgba"])
+                    .args(["-s", &format!("{}x{}", first.width, first.height)])
+                    .args(["-r", &fps.to_string(), "-i", "-"])
+                    // H.264 needs even dimensions
+                    .args(["-vf", "pad=ceil(iw/2)*2:ceil(ih/2)*2"])
+                    .args(["-c:v", "libx264", "-pix_fmt", "yuv420p"])
+                    .arg(path)
+                    .stdin(Stdio::piped())
+                    .spawn()
+                    .map_err(ffmpeg_error)?;
+                Ok(Encoder::Mp4(child))
+            }
+        }
+    }
+
+    fn add_frame(&mut self, frame: &Screenshot) -> Result<(), String> {
+        match self {
+            Encoder::Gif(encoder) => encoder.add_frame(frame),
+            #[cfg(feature = "video")]
+            Encoder::Mp4(child) => {
+                use std::io::Write;
+
+                let stdin = child.stdin.as_mut().ok_or("ffmpeg's input is closed")?;
+                let Err(e) = stdin.write_all(&frame.pixels) else {
+                    return Ok(());
+                };
+                // The pipe breaks when ffmpeg stops, its status telling why
+                drop(child.stdin.take());
+                match child.wait() {
+                    Ok(status) if !status.success() => Err(format!("ffmpeg exited with {status}")),
+                    _ => Err(format!("ffmpeg: {e}")),
+                }
+            }
+        }
+    }
+
+    fn finish(self) -> Result<(), String> {
+        match self {
+            Encoder::Gif(encoder) => encoder.finish(),
+            #[cfg(feature = "video")]
+            Encoder::Mp4(mut child) => {
+                drop(child.stdin.take());
+                let status = child.wait().map_err(|e| format!("ffmpeg: {e}"))?;
+                if !status.success() {
+                    return Err(format!("ffmpeg exited with {status}"));
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+// Fails early, recordings otherwise only failing once the first frame is encoded.
+#[cfg(feature = "video")]
+fn check_ffmpeg() -> Result<(), String> {
+    use std::process::{Command, Stdio};
+
+    let status = Command::new("ffmpeg")
+        .arg("-version")
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .map_err(ffmpeg_error)?;
+    if !status.success() {
+        return Err(format!("ffmpeg -version exited with {status}"));
+    }
+    Ok(())
+}
+
+#[cfg(feature = "video")]
+fn ffmpeg_error(error: std::io::Error) -> String {
+    match error.kind() {
+        std::io::ErrorKind::NotFound => "ffmpeg isn't installed or isn't in PATH".to_string(),
+        _ => format!("ffmpeg: {error}"),
+    }
+}
+
+// Keeps one pixel out of factor along both axes.
+fn shrink(frame: Screenshot, factor: u32) -> Screenshot {
+    if factor <= 1 {
+        return frame;
+    }
+
+    let (width, height) = (frame.width / factor, frame.height / factor);
+    let mut pixels = Vec::with_capacity((width * height * 4) as usize);
+    for y in 0..height {
+        for x in 0..width {
+            let index = ((y * factor * frame.width + x * factor) * 4) as usize;
+            pixels.extend_from_slice(&frame.pixels[index..index + 4]);
+        }
+    }
+    Screenshot {
+        width,
+        height,
+        pixels,
+    }
+}
@@ -0,0 +1,330 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    graphics::{ColorFilter, GraphicsOptions},
+    inputs::{Control, InputScheme},
+    Engine,
+};
+
+pub use toml::{Document, Value};
+
+pub(crate) use toml::{parse as parse_toml, write as write_toml};
+
+mod toml;
+
+// Settings players change from the options menu. Volumes are between 0 and 1 and are only
+// stored, for the game's audio to read.
+#[derive(Clone, PartialEq)]
+pub struct Settings {
+    pub window_size: (u32, u32),
+    pub fullscreen: bool,
+    pub vsync: bool,
+    pub master_volume: f64,
+    pub music_volume: f64,
+    pub effects_volume: f64,
+    // Controls by input, the inputs being named by their Display.
+    pub bindings: BTreeMap<String, Vec<Control>>,
+    // Accessibility, see ColorGrade::filter, GraphicsOptions::text_scale, Camera::shake_scale
+    // and InputsPipeline::set_toggled.
+    pub color_filter: ColorFilter,
+    pub text_scale: f64,
+    pub shake_scale: f64,
+    pub toggled_inputs: BTreeSet<String>,
+    // Game specific values kept in the file, by table and key.
+    pub extra: Document,
+}
+
+// Settings stored in a TOML file, by default in the platform's config directory.
+pub struct Config {
+    pub settings: Settings,
+    path: PathBuf,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        let graphics = GraphicsOptions::default();
+        Settings {
+            window_size: graphics.window_size,
+            fullscreen: graphics.fullscreen,
+            vsync: graphics.vsync,
+            master_volume: 1.,
+            music_volume: 1.,
+            effects_volume: 1.,
+            bindings: BTreeMap::new(),
+            color_filter: ColorFilter::None,
+            text_scale: graphics.text_scale,
+            shake_scale: 1.,
+            toggled_inputs: BTreeSet::new(),
+            extra: Document::new(),
+        }
+    }
+}
+
+impl Settings {
+    // Missing or invalid values keep their default.
+    pub fn parse(source: &str) -> Result<Self, String> {
+        let mut document = toml::parse(source)?;
+        let mut settings = Settings::default();
+
+        let mut take = |table: &str, key: &str| document.get_mut(table)?.remove(key);
+
+        for (key, size) in [
+            ("width", &mut settings.window_size.0),
+            ("height", &mut settings.window_size.1),
+        ] {
+            if let Some(value) = take("window", key).and_then(|v| u32::try_from(v.as_i64()?).ok()) {
+                *size = value;
+            }
+        }
+        for (key, flag) in [
+            ("fullscreen", &mut settings.fullscreen),
+            ("vsync", &mut settings.vsync),
+        ] {
+            if let Some(value) = take("window", key).and_then(|v| v.as_bool()) {
+                *flag = value;
+            }
+        }
+
+        for (key, volume) in [
+            ("master", &mut settings.master_volume),
+            ("music", &mut settings.music_volume),
+            ("effects", &mut settings.effects_volume),
+        ] {
+            if let Some(value) = take("audio", key).and_then(|v| v.as_f64()) {
+                *volume = value.clamp(0., 1.);
+            }
+        }
+
+        match take("accessibility", "color_filter") {
+            Some(Value::Text(name)) => {
+                if let Some(filter) = ColorFilter::from_name(&name) {
+                    settings.color_filter = filter;
+                }
+            }
+            // Rows of a custom matrix, one after the other
+            Some(Value::Array(values)) => {
+                let values: Vec<f32> = values
+                    .iter()
+                    .filter_map(|v| Some(v.as_f64()? as f32))
+                    .collect();
+                if let Ok(values) = <[f32; 9]>::try_from(values) {
+                    let row = |i: usize| [values[i * 3], values[i * 3 + 1], values[i * 3 + 2]];
+                    settings.color_filter = ColorFilter::Matrix([row(0), row(1), row(2)]);
+                }
+            }
+            _ => {}
+        }
+        for (key, scale) in [
+            ("text_scale", &mut settings.text_scale),
+            ("shake_scale", &mut settings.shake_scale),
+        ] {
+            if let Some(value) = take("accessibility", key).and_then(|v| v.as_f64()) {
+                *scale = value.max(0.);
+            }
+        }
+        if let Some(Value::Array(inputs)) = take("accessibility", "toggled_inputs") {
+            settings.toggled_inputs = inputs
+                .iter()
+                .filter_map(|i| Some(i.as_str()?.to_string()))
+                .collect();
+        }
+
+        for (input, controls) in document.remove("bindings").unwrap_or_default() {
+            let controls = controls
+                .as_array()
+                .unwrap_or_default()
+                .iter()
+                .filter_map(|c| c.as_str()?.parse().ok())
+                .collect();
+            settings.bindings.insert(input, controls);
+        }
+
+        document.retain(|_, values| !values.is_empty());
+        settings.extra = document;
+        Ok(settings)
+    }
+
+    pub fn to_toml(&self) -> String {
+        let mut document = self.extra.clone();
+
+        let window = document.entry("window".to_string()).or_default();
+        window.insert("width".into(), Value::Integer(self.window_size.0 as i64));
+        window.insert("height".into(), Value::Integer(self.window_size.1 as i64));
+        window.insert("fullscreen".into(), Value::Bool(self.fullscreen));
+        window.insert("vsync".into(), Value::Bool(self.vsync));
+
+        let audio = document.entry("audio".to_string()).or_default();
+        audio.insert("master".into(), Value::Float(self.master_volume));
+        audio.insert("music".into(), Value::Float(self.music_volume));
+        audio.insert("effects".into(), Value::Float(self.effects_volume));
+
+        let accessibility = document.entry("accessibility".to_string()).or_default();
+        let color_filter = match (self.color_filter.name(), self.color_filter.matrix()) {
+            (Some(name), _) => Value::Text(name.to_string()),
+            (None, matrix) => Value::Array(
+                matrix
+                    .into_iter()
+                    .flatten()
+                    .flatten()
+                    .map(|v| Value::Float(v as f64))
+                    .collect(),
+            ),
+        };
+        accessibility.insert("color_filter".into(), color_filter);
+        accessibility.insert("text_scale".into(), Value::Float(self.text_scale));
+        accessibility.insert("shake_scale".into(), Value::Float(self.shake_scale));
+        let toggled = self.toggled_inputs.iter().map(|i| Value::Text(i.clone()));
+        accessibility.insert("toggled_inputs".into(), Value::Array(toggled.collect()));
+
+        if !self.bindings.is_empty() {
+            let bindings = document.entry("bindings".to_string()).or_default();
+            for (input, controls) in &self.bindings {
+                let controls = controls.iter().map(|c| Value::Text(c.to_string()));
+                bindings.insert(input.clone(), Value::Array(controls.collect()));
+            }
+        }
+
+        toml::write(&document)
+    }
+
+    // Options to create the engine with, the other fields being kept.
+    pub fn graphics_options(&self, options: GraphicsOptions) -> GraphicsOptions {
+        GraphicsOptions {
+            window_size: self.window_size,
+            fullscreen: self.fullscreen,
+            vsync: self.vsync,
+            text_scale: self.text_scale,
+            ..options
+        }
+    }
+}
+
+impl Config {
+    // Directory where games store their settings, None if it can't be found.
+    pub fn directory(game: &str) -> Option<PathBuf> {
+        let var = |name| std::env::var_os(name).filter(|v| !v.is_empty());
+
+        let base = if cfg!(target_os = "windows") {
+            var("APPDATA").map(PathBuf::from)
+        } else if cfg!(target_os = "macos") {
+            var("HOME").map(|home| PathBuf::from(home).join("Library/Application Support"))
+        } else {
+            var("XDG_CONFIG_HOME")
+                .map(PathBuf::from)
+                .or_else(|| var("HOME").map(|home| PathBuf::from(home).join(".config")))
+        };
+
+        Some(base?.join(game))
+    }
+
+    // Loads settings.toml from the game's config directory, or the defaults if it doesn't exist.
+    pub fn load(game: &str) -> Result<Self, String> {
+        let directory = Config::directory(game).ok_or("no config directory")?;
+        Config::load_from(directory.join("settings.toml"))
+    }
+
+    pub fn load_from<P: AsRef<Path>>(path: P) -> Result<Self, String> {
+        let path = path.as_ref().to_path_buf();
+        let settings = match std::fs::read_to_string(&path) {
+            Ok(source) => {
+                Settings::parse(&source).map_err(|e| format!("{}: {}", path.display(), e))?
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Settings::default(),
+            Err(e) => return Err(e.to_string()),
+        };
+
+        Ok(Config { settings, path })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn save(&self) -> Result<(), String> {
+        if let Some(directory) = self.path.parent() {
+            std::fs::create_dir_all(directory).map_err(|e| e.to_string())?;
+        }
+        std::fs::write(&self.path, self.settings.to_toml()).map_err(|e| e.to_string())
+    }
+
+    // Applies the window and accessibility settings and the bindings of the registered inputs.
+    // Inputs without bindings in the settings keep their controls.
+    pub fn apply<T: InputScheme>(&self, engine: &mut Engine<T>) -> Result<(), String> {
+        let graphics = &mut engine.graphics_ppl;
+        if graphics.options.fullscreen != self.settings.fullscreen {
+            graphics.set_fullscreen(self.settings.fullscreen)?;
+        }
+        if !self.settings.fullscreen && graphics.options.window_size != self.settings.window_size {
+            let (width, height) = self.settings.window_size;
+            graphics.set_window_size(width, height)?;
+        }
+        if graphics.options.vsync != self.settings.vsync {
+            graphics.set_vsync(self.settings.vsync)?;
+        }
+        graphics.color_grade.filter = self.settings.color_filter;
+        graphics.options.text_scale = self.settings.text_scale;
+        graphics.camera.shake_scale = self.settings.shake_scale;
+
+        let inputs = &mut engine.inputs_ppl;
+        let ids: Vec<T> = inputs.inputs().collect();
+        let bound: Vec<(T, &Vec<Control>)> = ids
+            .into_iter()
+            .filter_map(|id| Some((id, self.settings.bindings.get(&id.to_string())?)))
+            .filter(|(id, controls)| inputs.controls(id) != **controls)
+            .collect();
+
+        let previous: Vec<(T, Vec<Control>)> = bound
+            .iter()
+            .map(|(id, _)| (*id, inputs.controls(id)))
+            .collect();
+
+        // Every rebound input is freed first so that controls can be swapped between them
+        for (id, _) in &bound {
+            inputs.deregister(id);
+        }
+        let registered = bound
+            .iter()
+            .try_for_each(|(id, controls)| inputs.register(*id, controls));
+        if let Err(e) = registered {
+            // Invalid bindings, e.g. edited by hand, leave the inputs bound as they were
+            for (id, _) in &previous {
+                inputs.deregister(id);
+            }
+            for (id, controls) in &previous {
+                if let Err(e) = inputs.register(*id, controls) {
+                    log::error!("failed to restore the bindings of {id}: {e}");
+                }
+            }
+            return Err(e.to_string());
+        }
+        for id in inputs.inputs().collect::<Vec<_>>() {
+            let toggled = self.settings.toggled_inputs.contains(&id.to_string());
+            inputs.set_toggled(id, toggled);
+        }
+
+        Ok(())
+    }
+
+    // Changes the settings, applies and saves them, for options menus.
+    pub fn change<T: InputScheme, F: FnOnce(&mut Settings)>(
+        &mut self,
+        engine: &mut Engine<T>,
+        change: F,
+    ) -> Result<(), String> {
+        change(&mut self.settings);
+        self.apply(engine)?;
+        self.save()
+    }
+
+    // Stores the current controls of every registered input, e.g. after rebinding them in game.
+    pub fn store_bindings<T: InputScheme>(&mut self, engine: &Engine<T>) {
+        for id in engine.inputs_ppl.inputs() {
+            self.settings
+                .bindings
+                .insert(id.to_string(), engine.inputs_ppl.controls(&id));
+        }
+    }
+}
@@ -0,0 +1,333 @@
+use std::{collections::BTreeMap, fmt::Write as _};
+
+use crate::Vec2;
+
+// Subset of TOML used by settings files: tables of keys whose values are strings, numbers,
+// booleans or single line arrays of them. Inline tables, dates and multiline values aren't
+// supported.
+#[derive(Clone, PartialEq, Debug)]
+pub enum Value {
+    Bool(bool),
+    Integer(i64),
+    Float(f64),
+    Text(String),
+    Array(Vec<Value>),
+}
+
+// Values by table then key, keys outside of any table being in the "" table.
+pub type Document = BTreeMap<String, BTreeMap<String, Value>>;
+
+pub fn parse(source: &str) -> Result<Document, String> {
+    let mut document = Document::new();
+    let mut table = String::new();
+
+    for (number, line) in source.lines().enumerate() {
+        let error = |message: &str| format!("line {}: {}", number + 1, message);
+        let line = strip_comment(line).trim();
+        if line.is_empty() {
+            continue;
+        }
+
+        if let Some(name) = line.strip_prefix('[') {
+            let name = name
+                .strip_suffix(']')
+                .ok_or_else(|| error("expected \"]\""))?;
+            table = unquote(name.trim()).ok_or_else(|| error("invalid table name"))?;
+            continue;
+        }
+
+        let (key, value) = line
+            .split_once('=')
+            .ok_or_else(|| error("expected \"key = value\""))?;
+        let key = unquote(key.trim()).ok_or_else(|| error("invalid key"))?;
+        let (value, rest) = parse_value(value.trim()).ok_or_else(|| error("invalid value"))?;
+        if !rest.trim().is_empty() {
+            return Err(error("unexpected characters after the value"));
+        }
+
+        document
+            .entry(table.clone())
+            .or_default()
+            .insert(key, value);
+    }
+
+    Ok(document)
+}
+
+pub fn write(document: &Document) -> String {
+    let mut text = String::new();
+    for (table, values) in document {
+        if !table.is_empty() {
+            if !text.is_empty() {
+                text.push('\n');
+            }
+            let _ = writeln!(text, "[{}]", key(table));
+        }
+        for (name, value) in values {
+            let _ = writeln!(text, "{} = {}", key(name), value);
+        }
+    }
+    text
+}
+
+impl std::fmt::Display for Value {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Value::Bool(b) => write!(f, "{b}"),
+            Value::Integer(i) => write!(f, "{i}"),
+            // Always written with a fraction so that it's read back as a float
+            Value::Float(x) if x.fract() == 0. && x.is_finite() => write!(f, "{x:.1}"),
+            Value::Float(x) => write!(f, "{x}"),
+            Value::Text(t) => write!(f, "{}", quote(t)),
+            Value::Array(values) => {
+                let values: Vec<String> = values.iter().map(Value::to_string).collect();
+                write!(f, "[{}]", values.join(", "))
+            }
+        }
+    }
+}
+
+impl Value {
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            Value::Bool(b) => Some(*b),
+            _ => None,
+        }
+    }
+
+    // Integers are accepted as floats.
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            Value::Float(x) => Some(*x),
+            Value::Integer(i) => Some(*i as f64),
+            _ => None,
+        }
+    }
+
+    pub fn as_i64(&self) -> Option<i64> {
+        match self {
+            Value::Integer(i) => Some(*i),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Value::Text(t) => Some(t),
+            _ => None,
+        }
+    }
+
+    pub fn as_array(&self) -> Option<&[Value]> {
+        match self {
+            Value::Array(values) => Some(values),
+            _ => None,
+        }
+    }
+}
+
+impl From<bool> for Value {
+    fn from(b: bool) -> Self {
+        Value::Bool(b)
+    }
+}
+
+impl From<i64> for Value {
+    fn from(i: i64) -> Self {
+        Value::Integer(i)
+    }
+}
+
+impl From<f64> for Value {
+    fn from(x: f64) -> Self {
+        Value::Float(x)
+    }
+}
+
+impl From<&str> for Value {
+    fn from(text: &str) -> Self {
+        Value::Text(text.to_string())
+    }
+}
+
+impl From<String> for Value {
+    fn from(text: String) -> Self {
+        Value::Text(text)
+    }
+}
+
+// As an array of its coordinates.
+impl From<Vec2> for Value {
+    fn from(v: Vec2) -> Self {
+        Value::Array(vec![Value::Float(v.x), Value::Float(v.y)])
+    }
+}
+
+// Returns the value and what follows it.
+fn parse_value(s: &str) -> Option<(Value, &str)> {
+    if let Some(rest) = s.strip_prefix('"') {
+        let mut text = String::new();
+        let mut chars = rest.char_indices();
+        while let Some((i, c)) = chars.next() {
+            match c {
+                '"' => return Some((Value::Text(text), &rest[i + 1..])),
+                '\\' => text.push(match chars.next()?.1 {
+                    'n' => '\n',
+                    't' => '\t',
+                    c @ ('"' | '\\') => c,
+                    _ => return None,
+                }),
+                c => text.push(c),
+            }
+        }
+        return None;
+    }
+
+    if let Some(mut rest) = s.strip_prefix('[') {
+        let mut values = Vec::new();
+        loop {
+            rest = rest.trim_start();
+            if let Some(after) = rest.strip_prefix(']') {
+                return Some((Value::Array(values), after));
+            }
+
+            let (value, after) = parse_value(rest)?;
+            values.push(value);
+            rest = after.trim_start();
+            if let Some(after) = rest.strip_prefix(',') {
+                rest = after;
+            } else if !rest.starts_with(']') {
+                return None;
+            }
+        }
+    }
+
+    let end = s.find([',', ']']).unwrap_or(s.len());
+    let (token, rest) = s.split_at(end);
+    let token = token.trim();
+    let value = match token {
+        "true" => Value::Bool(true),
+        "false" => Value::Bool(false),
+        _ => {
+            let number = token.replace('_', "");
+            match number.parse::<i64>() {
+                Ok(i) => Value::Integer(i),
+                Err(_) => Value::Float(number.parse().ok()?),
+            }
+        }
+    };
+    Some((value, rest))
+}
+
+// Removes the comment from a line, ignoring the "#" inside strings.
+fn strip_comment(line: &str) -> &str {
+    let mut in_string = false;
+    let mut chars = line.char_indices();
+    while let Some((i, c)) = chars.next() {
+        match c {
+            // Skips the escaped char
+            '\\' if in_string => {
+                chars.next();
+            }
+            '"' => in_string = !in_string,
+            '#' if !in_string => return &line[..i],
+            _ => {}
+        }
+    }
+    line
+}
+
+fn unquote(key: &str) -> Option<String> {
+    if key.starts_with('"') {
+        match parse_value(key)? {
+            (Value::Text(key), rest) if rest.trim().is_empty() => Some(key),
+            _ => None,
+        }
+    } else if !key.is_empty() && key.chars().all(is_bare) {
+        Some(key.to_string())
+    } else {
+        None
+    }
+}
+
+fn key(name: &str) -> String {
+    if !name.is_empty() && name.chars().all(is_bare) {
+        name.to_string()
+    } else {
+        quote(name)
+    }
+}
+
+fn quote(text: &str) -> String {
+    let escaped = text
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+        .replace('\t', "\\t");
+    format!("\"{escaped}\"")
+}
+
+fn is_bare(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '_' || c == '-'
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn values_are_parsed() {
+        let source = r#"
+            title = "Game # 1" # comment
+            [window]
+            width = 1_280
+            vsync = false
+            "scale factor" = 1.5
+            [bindings]
+            jump = ["key:Space", "pad:a"]
+            empty = []
+        "#;
+        let document = parse(source).unwrap();
+        assert_eq!(document[""]["title"], Value::from("Game # 1"));
+        assert_eq!(document["window"]["width"], Value::Integer(1280));
+        assert_eq!(document["window"]["vsync"], Value::Bool(false));
+        assert_eq!(document["window"]["scale factor"], Value::Float(1.5));
+        assert_eq!(
+            document["bindings"]["jump"],
+            Value::Array(vec!["key:Space".into(), "pad:a".into()])
+        );
+        assert_eq!(document["bindings"]["empty"], Value::Array(Vec::new()));
+    }
+
+    #[test]
+    fn errors_name_the_line() {
+        assert_eq!(
+            parse("a = 1\n[window").unwrap_err(),
+            "line 2: expected \"]\""
+        );
+        assert_eq!(parse("a").unwrap_err(), "line 1: expected \"key = value\"");
+        assert_eq!(parse("a = \"open").unwrap_err(), "line 1: invalid value");
+        assert_eq!(parse("a b = 1").unwrap_err(), "line 1: invalid key");
+        assert_eq!(
+            parse("a = \"x\" y").unwrap_err(),
+            "line 1: unexpected characters after the value"
+        );
+    }
+
+    #[test]
+    fn written_documents_are_read_back() {
+        let mut document = Document::new();
+        let root = document.entry(String::new()).or_default();
+        root.insert("version".into(), Value::Integer(2));
+        let extra = document.entry("my table".to_string()).or_default();
+        extra.insert("whole".into(), Value::Float(3.));
+        extra.insert("text".into(), "say \"hi\"\\\n\tbye".into());
+        extra.insert("position".into(), Vec2::new(1., -2.5).into());
+        extra.insert("on".into(), true.into());
+
+        let text = write(&document);
+        assert!(text.starts_with("version = 2\n\n[\"my table\"]\n"));
+        assert!(text.contains("whole = 3.0\n"));
+        assert_eq!(parse(&text).unwrap(), document);
+    }
+}
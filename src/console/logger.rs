@@ -0,0 +1,84 @@
+use std::{
+    collections::VecDeque,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use log::{Level, LevelFilter, Log, Metadata, Record};
+
+// Lines kept for the console, older ones are dropped.
+const MAX_LINES: usize = 512;
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct LogLine {
+    pub level: Level,
+    pub target: String,
+    pub message: String,
+    // Since the logger was installed.
+    pub time: Duration,
+}
+
+struct ConsoleLogger;
+
+static LOGGER: ConsoleLogger = ConsoleLogger;
+static LINES: Mutex<VecDeque<LogLine>> = Mutex::new(VecDeque::new());
+static START: Mutex<Option<Instant>> = Mutex::new(None);
+
+impl Log for ConsoleLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= log::max_level()
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+
+        let time = START
+            .lock()
+            .unwrap()
+            .map_or(Duration::ZERO, |start| start.elapsed());
+        eprintln!(
+            "[{:>8.3} {:<5} {}] {}",
+            time.as_secs_f64(),
+            record.level(),
+            record.target(),
+            record.args()
+        );
+
+        let mut lines = LINES.lock().unwrap();
+        lines.push_back(LogLine {
+            level: record.level(),
+            target: record.target().to_string(),
+            message: record.args().to_string(),
+            time,
+        });
+        if lines.len() > MAX_LINES {
+            lines.pop_front();
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+// Installs the logger printing to stderr and keeping the lines for the console. Fails if a
+// logger is already installed, in which case the console shows no log lines.
+pub fn init(level: LevelFilter) -> Result<(), String> {
+    log::set_logger(&LOGGER).map_err(|e| e.to_string())?;
+    log::set_max_level(level);
+    *START.lock().unwrap() = Some(Instant::now());
+    Ok(())
+}
+
+pub(super) fn lines() -> Vec<LogLine> {
+    LINES.lock().unwrap().iter().cloned().collect()
+}
+
+// None while the lines are locked, e.g. when panicking while logging.
+pub(crate) fn try_lines() -> Option<Vec<LogLine>> {
+    Some(LINES.try_lock().ok()?.iter().cloned().collect())
+}
+
+pub(super) fn clear() {
+    LINES.lock().unwrap().clear();
+}
@@ -0,0 +1,192 @@
+use std::collections::BTreeMap;
+
+use log::{Level, LevelFilter};
+
+use crate::{
+    graphics::{BitmapFont, Color, DrawParams, GraphicsPipeline, PixelRect},
+    inputs::{InputScheme, InputsPipeline},
+    Point,
+};
+
+pub use logger::{init, LogLine};
+
+pub(crate) use logger::try_lines as try_log_lines;
+
+mod logger;
+
+// Debug command taking the game state and the words typed after the command name, returns the
+// text to print.
+pub type Command<C> = Box<dyn FnMut(&mut C, &[&str]) -> Result<String, String>>;
+
+// In-game overlay showing recent log lines and running debug commands. Log lines only show up
+// once the logger is installed with console::init, command output is logged too.
+pub struct Console<C> {
+    pub open: bool,
+    // Lines above this level are hidden, independently of the logger's own level.
+    pub level: LevelFilter,
+    // Only shows the lines whose target, usually the module path, starts with it.
+    pub module: Option<String>,
+    // Height of the overlay as a fraction of the window's.
+    pub height: f64,
+    input: String,
+    commands: BTreeMap<String, (String, Command<C>)>,
+}
+
+impl<C> Console<C> {
+    pub fn new() -> Self {
+        Console {
+            open: false,
+            level: LevelFilter::Trace,
+            module: None,
+            height: 0.5,
+            input: String::new(),
+            commands: BTreeMap::new(),
+        }
+    }
+
+    pub fn register(&mut self, name: &str, help: &str, command: Command<C>) {
+        self.commands
+            .insert(name.to_string(), (help.to_string(), command));
+    }
+
+    // Opens or closes the console, text input is captured while it's open.
+    pub fn toggle<T: InputScheme>(&mut self, inputs: &mut InputsPipeline<T>) {
+        self.open = !self.open;
+        if self.open {
+            inputs.start_text_input();
+        } else {
+            inputs.stop_text_input();
+        }
+    }
+
+    // Has to be called once per frame, after InputsPipeline::process_events.
+    pub fn update<T: InputScheme>(&mut self, inputs: &InputsPipeline<T>, ctx: &mut C) {
+        if !self.open {
+            return;
+        }
+
+        if inputs.text_input().edit(&mut self.input) {
+            let line = std::mem::take(&mut self.input);
+            self.execute(ctx, &line);
+        }
+    }
+
+    // Runs a command line, "help", "clear", "level <level>" and "module [prefix]" are built in.
+    pub fn execute(&mut self, ctx: &mut C, line: &str) {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        let Some((name, args)) = words.split_first() else {
+            return;
+        };
+        log::info!(target: "console", "> {line}");
+
+        let result = match *name {
+            "help" => Ok(self
+                .commands
+                .iter()
+                .map(|(name, (help, _))| format!("{name}: {help}"))
+                .collect::<Vec<_>>()
+                .join("\n")),
+            "clear" => {
+                logger::clear();
+                Ok(String::new())
+            }
+            "level" => match args.first().map(|level| level.parse::<LevelFilter>()) {
+                Some(Ok(level)) => {
+                    self.level = level;
+                    Ok(String::new())
+                }
+                _ => Err("expected one of off, error, warn, info, debug, trace".to_string()),
+            },
+            "module" => {
+                self.module = args.first().map(|m| m.to_string());
+                Ok(String::new())
+            }
+            name => match self.commands.get_mut(name) {
+                Some((_, command)) => command(ctx, args),
+                None => Err(format!("unknown command \"{name}\", see help")),
+            },
+        };
+
+        match result {
+            Ok(output) => {
+                for line in output.lines() {
+                    log::info!(target: "console", "{line}");
+                }
+            }
+            Err(e) => log::error!(target: "console", "{e}"),
+        }
+    }
+
+    // Log lines passing the filters, oldest first. Command output is never filtered out by
+    // module.
+    pub fn lines(&self) -> Vec<LogLine> {
+        logger::lines()
+            .into_iter()
+            .filter(|line| line.level <= self.level)
+            .filter(|line| {
+                line.target == "console"
+                    || self
+                        .module
+                        .as_ref()
+                        .is_none_or(|module| line.target.starts_with(module.as_str()))
+            })
+            .collect()
+    }
+
+    // Draws the console at the top of the window, with the most recent lines above the input.
+    pub fn draw(&self, graphics_ppl: &mut GraphicsPipeline, font: &BitmapFont, scale: f64) {
+        if !self.open {
+            return;
+        }
+
+        let (width, height) = graphics_ppl.options.window_size;
+        let height = (height as f64 * self.height) as u32;
+        graphics_ppl.draw_rect_screen(
+            PixelRect::new(0, 0, width, height),
+            &Color::RGBA(10, 10, 15, 220),
+            true,
+            &DrawParams::default(),
+        );
+
+        let line_height = ((font.glyph_height as f64 * scale).round() as i32).max(1);
+        let mut y = height as i32 - line_height;
+        let input = format!("> {}_", self.input);
+        graphics_ppl.draw_text_screen(
+            font,
+            &input,
+            Point::new(4, y),
+            scale,
+            &DrawParams::default(),
+        );
+
+        for line in self.lines().iter().rev() {
+            y -= line_height;
+            if y < 0 {
+                break;
+            }
+
+            let color = match line.level {
+                Level::Error => Color::RGB(240, 80, 80),
+                Level::Warn => Color::RGB(240, 200, 80),
+                Level::Info => Color::WHITE,
+                Level::Debug | Level::Trace => Color::RGB(150, 150, 160),
+            };
+            let text = if line.target == "console" {
+                line.message.clone()
+            } else {
+                format!("[{}] {}", line.target, line.message)
+            };
+            let params = DrawParams {
+                tint: color,
+                ..Default::default()
+            };
+            graphics_ppl.draw_text_screen(font, &text, Point::new(4, y), scale, &params);
+        }
+    }
+}
+
+impl<C> Default for Console<C> {
+    fn default() -> Self {
+        Console::new()
+    }
+}
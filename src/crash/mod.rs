@@ -0,0 +1,200 @@
+use std::{
+    backtrace::Backtrace,
+    fmt::Write as _,
+    panic::PanicHookInfo,
+    path::{Path, PathBuf},
+    sync::Mutex,
+    thread::ThreadId,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use sdl2::{event::Event, messagebox::MessageBoxFlag, sys};
+
+use crate::{config::Config, graphics::WindowId};
+
+// Events kept for the report, older ones are dropped.
+const MAX_EVENTS: usize = 32;
+
+pub struct CrashOptions {
+    // Where reports are written, crash-<unix time>.txt.
+    pub directory: PathBuf,
+    // Tells the player where the report is, once the windows are hidden.
+    pub message_box: bool,
+}
+
+// What the engine was doing, updated by Engine::update once the handler is installed.
+struct State {
+    game: String,
+    options: CrashOptions,
+    main_thread: ThreadId,
+    frame: u64,
+    events: Vec<String>,
+    windows: Vec<WindowId>,
+}
+
+static STATE: Mutex<Option<State>> = Mutex::new(None);
+
+impl CrashOptions {
+    // Reports go to the crashes directory next to the game's settings, or the working directory
+    // without one, see Config::directory.
+    pub fn new(game: &str) -> Self {
+        CrashOptions {
+            directory: Config::directory(game)
+                .map_or_else(|| PathBuf::from("crashes"), |d| d.join("crashes")),
+            message_box: true,
+        }
+    }
+}
+
+// Replaces the panic hook so that panics write a report with the frame, the last events, the
+// log and the backtrace, then give the display back: windows leave fullscreen and are hidden,
+// the cursor is shown and released. The previous hook still runs afterwards. Has to be called
+// from the thread running the engine, SDL being only touched from there.
+pub fn install(game: &str, options: CrashOptions) {
+    *STATE.lock().unwrap() = Some(State {
+        game: game.to_string(),
+        options,
+        main_thread: std::thread::current().id(),
+        frame: 0,
+        events: Vec::new(),
+        windows: Vec::new(),
+    });
+
+    let previous = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        handle(info);
+        previous(info);
+    }));
+}
+
+pub(crate) fn record_frame(events: &[Event], windows: Vec<WindowId>) {
+    let Ok(mut state) = STATE.lock() else {
+        return;
+    };
+    let Some(state) = state.as_mut() else {
+        return;
+    };
+
+    state.frame += 1;
+    state.windows = windows;
+    for event in events {
+        state.events.push(format!("{} {:?}", state.frame, event));
+    }
+    let excess = state.events.len().saturating_sub(MAX_EVENTS);
+    state.events.drain(..excess);
+}
+
+fn handle(info: &PanicHookInfo) {
+    // A panic while the state is locked can't tell more than the default hook
+    let Ok(state) = STATE.try_lock() else {
+        return;
+    };
+    let Some(state) = state.as_ref() else {
+        return;
+    };
+
+    log::logger().flush();
+    let report = report(state, info);
+    let path = write_report(&state.options.directory, &report);
+    match &path {
+        Ok(path) => eprintln!("crash report written to {}", path.display()),
+        Err(e) => eprintln!("failed to write the crash report: {e}"),
+    }
+
+    if std::thread::current().id() != state.main_thread {
+        return;
+    }
+    release_display(&state.windows);
+    if state.options.message_box {
+        let message = match &path {
+            Ok(path) => format!(
+                "{} crashed. A report was written to {}.",
+                state.game,
+                path.display()
+            ),
+            Err(_) => format!("{} crashed.", state.game),
+        };
+        let title = format!("{} crashed", state.game);
+        let _ = sdl2::messagebox::show_simple_message_box(
+            MessageBoxFlag::ERROR,
+            &title,
+            &message,
+            None,
+        );
+    }
+}
+
+fn report(state: &State, info: &PanicHookInfo) -> String {
+    let message = info
+        .payload()
+        .downcast_ref::<&str>()
+        .map(|m| m.to_string())
+        .or_else(|| info.payload().downcast_ref::<String>().cloned())
+        .unwrap_or_else(|| "unknown".to_string());
+    let location = info
+        .location()
+        .map_or(String::new(), |l| format!(" at {}:{}", l.file(), l.line()));
+    let thread = std::thread::current();
+
+    let mut text = String::new();
+    let _ = writeln!(text, "{} crashed", state.game);
+    let _ = writeln!(
+        text,
+        "engine: {} {}",
+        env!("CARGO_PKG_NAME"),
+        env!("CARGO_PKG_VERSION")
+    );
+    let _ = writeln!(text, "time: {}", unix_time());
+    let _ = writeln!(text, "thread: {}", thread.name().unwrap_or("unnamed"));
+    let _ = writeln!(text, "frame: {}", state.frame);
+    let _ = writeln!(text, "panic: {message}{location}");
+
+    let _ = writeln!(text, "\nlast events:");
+    for event in &state.events {
+        let _ = writeln!(text, "{event}");
+    }
+
+    let _ = writeln!(text, "\nlog:");
+    for line in crate::console::try_log_lines().unwrap_or_default() {
+        let _ = writeln!(
+            text,
+            "[{:>8.3} {:<5} {}] {}",
+            line.time.as_secs_f64(),
+            line.level,
+            line.target,
+            line.message
+        );
+    }
+
+    let _ = writeln!(text, "\nbacktrace:\n{}", Backtrace::force_capture());
+    text
+}
+
+fn write_report(directory: &Path, report: &str) -> Result<PathBuf, String> {
+    std::fs::create_dir_all(directory).map_err(|e| e.to_string())?;
+    let path = directory.join(format!("crash-{}.txt", unix_time()));
+    std::fs::write(&path, report).map_err(|e| e.to_string())?;
+    Ok(path)
+}
+
+fn release_display(windows: &[WindowId]) {
+    unsafe {
+        sys::SDL_SetRelativeMouseMode(sys::SDL_bool::SDL_FALSE);
+        sys::SDL_ShowCursor(sys::SDL_ENABLE as i32);
+        for window in windows {
+            let window = sys::SDL_GetWindowFromID(window.0);
+            if window.is_null() {
+                continue;
+            }
+            sys::SDL_SetWindowGrab(window, sys::SDL_bool::SDL_FALSE);
+            sys::SDL_SetWindowFullscreen(window, 0);
+            sys::SDL_HideWindow(window);
+        }
+    }
+}
+
+fn unix_time() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs())
+}
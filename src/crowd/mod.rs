@@ -0,0 +1,266 @@
+use std::{collections::HashMap, ops::Range};
+
+use crate::{physics::PhysicsWorld, Point, Vec2};
+
+// Below this number of agents, spawning threads costs more than it saves.
+#[cfg(feature = "parallel")]
+const PARALLEL_AGENTS: usize = 1024;
+
+// Flock of simple agents steering with the boids rules: separation from close neighbors,
+// alignment with and cohesion towards the others within radius, and avoidance of the physics
+// bodies ahead. Agents are stored as arrays of positions and velocities, indices of removed
+// agents being taken by the last one, and neighbors are found through a grid of radius sized
+// cells rebuilt each update.
+pub struct Crowd {
+    // Distance within which agents see each other.
+    pub radius: f64,
+    // Distance under which agents move apart.
+    pub separation_radius: f64,
+    pub separation: f64,
+    pub alignment: f64,
+    pub cohesion: f64,
+    pub avoidance: f64,
+    // Distance ahead of an agent where bodies are avoided.
+    pub look_ahead: f64,
+    // Where the agents head to, e.g. a swarm chasing the player.
+    pub target: Option<Vec2>,
+    pub seek: f64,
+    pub max_speed: f64,
+    // Largest change of velocity per second.
+    pub max_force: f64,
+    positions: Vec<Vec2>,
+    velocities: Vec<Vec2>,
+    // Agents sorted by cell, and where each cell's are.
+    sorted: Vec<usize>,
+    cells: HashMap<Point, Range<usize>>,
+}
+
+impl Crowd {
+    pub fn new(radius: f64, max_speed: f64) -> Self {
+        Crowd {
+            radius,
+            separation_radius: radius / 2.,
+            separation: 1.5,
+            alignment: 1.,
+            cohesion: 1.,
+            avoidance: 3.,
+            look_ahead: radius,
+            target: None,
+            seek: 1.,
+            max_speed,
+            max_force: max_speed * 2.,
+            positions: Vec::new(),
+            velocities: Vec::new(),
+            sorted: Vec::new(),
+            cells: HashMap::new(),
+        }
+    }
+
+    pub fn add(&mut self, position: Vec2, velocity: Vec2) -> usize {
+        self.positions.push(position);
+        self.velocities.push(velocity);
+        self.positions.len() - 1
+    }
+
+    // The last agent takes the index of the one removed.
+    pub fn remove(&mut self, index: usize) {
+        if index < self.positions.len() {
+            self.positions.swap_remove(index);
+            self.velocities.swap_remove(index);
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.positions.clear();
+        self.velocities.clear();
+    }
+
+    pub fn len(&self) -> usize {
+        self.positions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.positions.is_empty()
+    }
+
+    // Positions and velocities by agent index, to draw them with.
+    pub fn positions(&self) -> &[Vec2] {
+        &self.positions
+    }
+
+    pub fn velocities(&self) -> &[Vec2] {
+        &self.velocities
+    }
+
+    // Also to move agents, e.g. to teleport them back into the level.
+    pub fn positions_mut(&mut self) -> &mut [Vec2] {
+        &mut self.positions
+    }
+
+    pub fn velocities_mut(&mut self) -> &mut [Vec2] {
+        &mut self.velocities
+    }
+
+    // Indices of the agents within distance of a point, in no particular order, among those in
+    // the grid of the last update.
+    pub fn agents_near(&self, point: Vec2, distance: f64) -> Vec<usize> {
+        let reach = (distance / self.cell_size()).ceil() as i32;
+        let center = self.cell(point);
+        let mut agents = Vec::new();
+        for y in -reach..=reach {
+            for x in -reach..=reach {
+                let Some(range) = self.cells.get(&(center + Point::new(x, y))) else {
+                    continue;
+                };
+                agents.extend(self.sorted[range.clone()].iter().filter(|&&i| {
+                    self.positions
+                        .get(i)
+                        .is_some_and(|p| p.distance_squared(point) <= distance * distance)
+                }));
+            }
+        }
+        agents
+    }
+
+    // Steers then moves every agent. Bodies are only avoided when physics is given.
+    pub fn update(&mut self, dt: f64, physics: Option<&PhysicsWorld>) {
+        self.build_cells();
+        let forces = self.forces(physics);
+
+        for ((position, velocity), force) in self
+            .positions
+            .iter_mut()
+            .zip(&mut self.velocities)
+            .zip(forces)
+        {
+            *velocity = (*velocity + force * dt).clamp_length_max(self.max_speed);
+            *position += *velocity * dt;
+        }
+    }
+
+    #[cfg(not(feature = "parallel"))]
+    fn forces(&self, physics: Option<&PhysicsWorld>) -> Vec<Vec2> {
+        (0..self.len()).map(|i| self.force(i, physics)).collect()
+    }
+
+    // Agents are split between threads, the result staying the same.
+    #[cfg(feature = "parallel")]
+    fn forces(&self, physics: Option<&PhysicsWorld>) -> Vec<Vec2> {
+        let threads = std::thread::available_parallelism().map_or(1, |n| n.get());
+        if threads == 1 || self.len() < PARALLEL_AGENTS {
+            return (0..self.len()).map(|i| self.force(i, physics)).collect();
+        }
+
+        let chunk = self.len().div_ceil(threads);
+        std::thread::scope(|scope| {
+            let handles: Vec<_> = (0..self.len())
+                .step_by(chunk)
+                .map(|start| {
+                    let agents = start..(start + chunk).min(self.len());
+                    scope.spawn(move || agents.map(|i| self.force(i, physics)).collect::<Vec<_>>())
+                })
+                .collect();
+            handles
+                .into_iter()
+                .flat_map(|handle| handle.join().expect("crowd thread panicked"))
+                .collect()
+        })
+    }
+
+    fn force(&self, i: usize, physics: Option<&PhysicsWorld>) -> Vec2 {
+        let (position, velocity) = (self.positions[i], self.velocities[i]);
+        let mut separation = Vec2::ZERO;
+        let mut positions = Vec2::ZERO;
+        let mut velocities = Vec2::ZERO;
+        let mut count = 0;
+
+        let center = self.cell(position);
+        for y in -1..=1 {
+            for x in -1..=1 {
+                let Some(range) = self.cells.get(&(center + Point::new(x, y))) else {
+                    continue;
+                };
+                for &j in &self.sorted[range.clone()] {
+                    let offset = self.positions[j] - position;
+                    let distance_squared = offset.length_squared();
+                    if j == i || distance_squared > self.radius * self.radius {
+                        continue;
+                    }
+
+                    count += 1;
+                    positions += self.positions[j];
+                    velocities += self.velocities[j];
+                    // Pushed harder the closer they are, agents on top of each other not at all
+                    if distance_squared < self.separation_radius * self.separation_radius
+                        && distance_squared > 0.
+                    {
+                        separation -= offset / distance_squared;
+                    }
+                }
+            }
+        }
+
+        let mut force = self.steer(separation, velocity) * self.separation;
+        if count > 0 {
+            let count = count as f64;
+            force += self.steer(velocities / count, velocity) * self.alignment;
+            force += self.steer(positions / count - position, velocity) * self.cohesion;
+        }
+        if let Some(target) = self.target {
+            force += self.steer(target - position, velocity) * self.seek;
+        }
+        if let Some(physics) = physics {
+            force += self.avoid(physics, position, velocity) * self.avoidance;
+        }
+        force.clamp_length_max(self.max_force)
+    }
+
+    // Turns away from the body ahead, harder the closer it is.
+    fn avoid(&self, physics: &PhysicsWorld, position: Vec2, velocity: Vec2) -> Vec2 {
+        let Some(hit) = physics.cast_ray(position, velocity, self.look_ahead) else {
+            return Vec2::ZERO;
+        };
+        let urgency = 1. - hit.distance / self.look_ahead.max(f64::EPSILON);
+        // Sliding along the body rather than bouncing off it
+        let along = velocity - hit.normal * velocity.dot(hit.normal);
+        self.steer(along + hit.normal * velocity.length(), velocity) * urgency
+    }
+
+    // Change of velocity towards moving at full speed in a direction.
+    fn steer(&self, direction: Vec2, velocity: Vec2) -> Vec2 {
+        if direction == Vec2::ZERO {
+            return Vec2::ZERO;
+        }
+        (direction.normalize_or_zero() * self.max_speed - velocity).clamp_length_max(self.max_force)
+    }
+
+    fn build_cells(&mut self) {
+        let mut sorted: Vec<(Point, usize)> = self
+            .positions
+            .iter()
+            .enumerate()
+            .map(|(i, p)| (self.cell(*p), i))
+            .collect();
+        sorted.sort_unstable_by_key(|(cell, i)| (cell.x, cell.y, *i));
+
+        self.cells.clear();
+        self.sorted.clear();
+        let mut start = 0;
+        for (index, (cell, agent)) in sorted.iter().enumerate() {
+            self.sorted.push(*agent);
+            if sorted.get(index + 1).is_none_or(|(next, _)| next != cell) {
+                self.cells.insert(*cell, start..index + 1);
+                start = index + 1;
+            }
+        }
+    }
+
+    fn cell_size(&self) -> f64 {
+        self.radius.max(f64::EPSILON)
+    }
+
+    fn cell(&self, position: Vec2) -> Point {
+        let cell = (position / self.cell_size()).floor();
+        Point::new(cell.x as i32, cell.y as i32)
+    }
+}
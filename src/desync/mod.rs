@@ -0,0 +1,517 @@
+use std::{
+    collections::VecDeque,
+    fmt::Display,
+    fs::File,
+    io::{BufWriter, Write},
+    path::Path,
+};
+
+use crate::{Point, Vec2};
+
+type Source<C> = Box<dyn Fn(&C, &mut StateHasher)>;
+
+// State that can be checksummed, written field by field in a fixed order. Floats are hashed by
+// their bits, the slightest divergence changing the hash.
+pub trait StateHash {
+    fn hash_state(&self, hasher: &mut StateHasher);
+}
+
+// FNV-1a over the bytes written, the same on every platform.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct StateHasher {
+    hash: u64,
+}
+
+// Checksums of the registered sources at a fixed step, total combining them in order.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct FrameHash {
+    pub step: u64,
+    pub total: u64,
+    pub sources: Vec<(String, u64)>,
+}
+
+// First step two runs diverged at, with the sources whose hashes differ.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct DesyncReport {
+    pub step: u64,
+    pub sources: Vec<SourceDiff>,
+}
+
+// None for a source only hashed by the other side.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct SourceDiff {
+    pub name: String,
+    pub local: Option<u64>,
+    pub remote: Option<u64>,
+}
+
+// Hashes the simulation state registered once per fixed step, e.g. from an exclusive fixed
+// update system, keeping the last hashes to compare with another machine's, e.g. sent by the
+// peer of a rollback session, and optionally logging them to compare two runs with
+// compare_logs. C is what the sources read the state from, usually the engine.
+pub struct FrameHasher<C> {
+    // Number of hashes kept.
+    pub history: usize,
+    sources: Vec<(String, Source<C>)>,
+    hashes: VecDeque<FrameHash>,
+    log: Option<BufWriter<File>>,
+}
+
+impl StateHasher {
+    pub fn new() -> Self {
+        StateHasher {
+            hash: 0xcbf29ce484222325,
+        }
+    }
+
+    pub fn write_bytes(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.hash = (self.hash ^ *byte as u64).wrapping_mul(0x100000001b3);
+        }
+    }
+
+    pub fn write<T: StateHash + ?Sized>(&mut self, state: &T) {
+        state.hash_state(self);
+    }
+
+    pub fn finish(&self) -> u64 {
+        self.hash
+    }
+}
+
+impl Default for StateHasher {
+    fn default() -> Self {
+        StateHasher::new()
+    }
+}
+
+impl<C> FrameHasher<C> {
+    pub fn new() -> Self {
+        FrameHasher {
+            history: 600,
+            sources: Vec::new(),
+            hashes: VecDeque::new(),
+            log: None,
+        }
+    }
+
+    // Sources are hashed in the order they're registered, which has to be the same on both
+    // sides. Whitespace in names is replaced for them to be written to logs.
+    pub fn register<F: Fn(&C, &mut StateHasher) + 'static>(&mut self, name: &str, source: F) {
+        let name = name.replace(char::is_whitespace, "_");
+        self.sources.push((name, Box::new(source)));
+    }
+
+    // Writes every hash from now on to the file, one line per step: "<step> <total>
+    // <source>=<hash>...", hashes being in hexadecimal.
+    pub fn log_to<P: AsRef<Path>>(&mut self, path: P) -> Result<(), String> {
+        let path = path.as_ref();
+        let file = File::create(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        self.log = Some(BufWriter::new(file));
+        Ok(())
+    }
+
+    pub fn stop_logging(&mut self) {
+        self.log = None;
+    }
+
+    // Hashes the sources at a step, replacing the hash of the step if it was hashed before,
+    // e.g. when resimulated after a rollback.
+    pub fn hash(&mut self, step: u64, context: &C) -> &FrameHash {
+        let mut total = StateHasher::new();
+        let sources: Vec<(String, u64)> = self
+            .sources
+            .iter()
+            .map(|(name, source)| {
+                let mut hasher = StateHasher::new();
+                source(context, &mut hasher);
+                total.write(&hasher.finish());
+                (name.clone(), hasher.finish())
+            })
+            .collect();
+        let hash = FrameHash {
+            step,
+            total: total.finish(),
+            sources,
+        };
+
+        if let Some(log) = &mut self.log {
+            // Kept on disk as the game goes, in case it crashes
+            if let Err(e) = writeln!(log, "{hash}").and_then(|_| log.flush()) {
+                log::warn!("failed to log frame hashes: {e}");
+            }
+        }
+
+        self.hashes.retain(|h| h.step < step);
+        self.hashes.push_back(hash);
+        while self.hashes.len() > self.history.max(1) {
+            self.hashes.pop_front();
+        }
+        self.hashes.back().expect("hash just pushed")
+    }
+
+    pub fn get(&self, step: u64) -> Option<&FrameHash> {
+        self.hashes.iter().find(|h| h.step == step)
+    }
+
+    pub fn latest(&self) -> Option<&FrameHash> {
+        self.hashes.back()
+    }
+
+    // Compares a hash from elsewhere with this side's at the same step. None if they match or
+    // if the step isn't in the history anymore, or not yet.
+    pub fn check(&self, remote: &FrameHash) -> Option<DesyncReport> {
+        self.get(remote.step)?.compare(remote)
+    }
+}
+
+impl<C> Default for FrameHasher<C> {
+    fn default() -> Self {
+        FrameHasher::new()
+    }
+}
+
+impl FrameHash {
+    // None if both hashes match.
+    pub fn compare(&self, remote: &FrameHash) -> Option<DesyncReport> {
+        if self.total == remote.total && self.sources == remote.sources {
+            return None;
+        }
+
+        let find = |hash: &FrameHash, name: &str| {
+            hash.sources
+                .iter()
+                .find(|(n, _)| n == name)
+                .map(|(_, h)| *h)
+        };
+        let mut sources: Vec<SourceDiff> = self
+            .sources
+            .iter()
+            .map(|(name, local)| SourceDiff {
+                name: name.clone(),
+                local: Some(*local),
+                remote: find(remote, name),
+            })
+            .collect();
+        sources.extend(
+            remote
+                .sources
+                .iter()
+                .filter(|(name, _)| find(self, name).is_none())
+                .map(|(name, hash)| SourceDiff {
+                    name: name.clone(),
+                    local: None,
+                    remote: Some(*hash),
+                }),
+        );
+        sources.retain(|diff| diff.local != diff.remote);
+
+        Some(DesyncReport {
+            step: self.step,
+            sources,
+        })
+    }
+
+    // Reads a line written by FrameHasher::log_to.
+    pub fn parse(line: &str) -> Option<Self> {
+        let mut parts = line.split_whitespace();
+        let step = parts.next()?.parse().ok()?;
+        let total = u64::from_str_radix(parts.next()?, 16).ok()?;
+        let sources = parts
+            .map(|part| {
+                let (name, hash) = part.rsplit_once('=')?;
+                Some((name.to_string(), u64::from_str_radix(hash, 16).ok()?))
+            })
+            .collect::<Option<_>>()?;
+        Some(FrameHash {
+            step,
+            total,
+            sources,
+        })
+    }
+}
+
+impl Display for FrameHash {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {:016x}", self.step, self.total)?;
+        for (name, hash) in &self.sources {
+            write!(f, " {name}={hash:016x}")?;
+        }
+        Ok(())
+    }
+}
+
+impl Display for DesyncReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "desync at step {}", self.step)?;
+        let hash = |h: Option<u64>| h.map_or("missing".to_string(), |h| format!("{h:016x}"));
+        for diff in &self.sources {
+            write!(
+                f,
+                "\n  {}: {} locally, {} remotely",
+                diff.name,
+                hash(diff.local),
+                hash(diff.remote)
+            )?;
+        }
+        Ok(())
+    }
+}
+
+// Hashes logged by FrameHasher::log_to, in the order they were written.
+pub fn read_log<P: AsRef<Path>>(path: P) -> Result<Vec<FrameHash>, String> {
+    let path = path.as_ref();
+    let log = std::fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    log.lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(i, line)| {
+            FrameHash::parse(line)
+                .ok_or_else(|| format!("{}:{}: invalid hash", path.display(), i + 1))
+        })
+        .collect()
+}
+
+// First divergence between the hashes of two runs, e.g. read from the logs of two machines,
+// among the steps both hashed. The last hash of a step counts when it was hashed again.
+pub fn compare_logs(local: &[FrameHash], remote: &[FrameHash]) -> Option<DesyncReport> {
+    let mut steps: Vec<u64> = local.iter().map(|h| h.step).collect();
+    steps.sort_unstable();
+    steps.dedup();
+    let last =
+        |hashes: &[FrameHash], step: u64| hashes.iter().rev().find(|h| h.step == step).cloned();
+    steps.into_iter().find_map(|step| {
+        let remote = last(remote, step)?;
+        last(local, step)?.compare(&remote)
+    })
+}
+
+impl StateHash for u64 {
+    fn hash_state(&self, hasher: &mut StateHasher) {
+        hasher.write_bytes(&self.to_le_bytes());
+    }
+}
+
+impl StateHash for i64 {
+    fn hash_state(&self, hasher: &mut StateHasher) {
+        hasher.write_bytes(&self.to_le_bytes());
+    }
+}
+
+impl StateHash for u32 {
+    fn hash_state(&self, hasher: &mut StateHasher) {
+        hasher.write_bytes(&self.to_le_bytes());
+    }
+}
+
+impl StateHash for i32 {
+    fn hash_state(&self, hasher: &mut StateHasher) {
+        hasher.write_bytes(&self.to_le_bytes());
+    }
+}
+
+impl StateHash for usize {
+    fn hash_state(&self, hasher: &mut StateHasher) {
+        hasher.write(&(*self as u64));
+    }
+}
+
+impl StateHash for bool {
+    fn hash_state(&self, hasher: &mut StateHasher) {
+        hasher.write_bytes(&[*self as u8]);
+    }
+}
+
+impl StateHash for f64 {
+    fn hash_state(&self, hasher: &mut StateHasher) {
+        hasher.write(&self.to_bits());
+    }
+}
+
+impl StateHash for str {
+    // Prefixed by the length for "a" then "bc" not to hash like "ab" then "c".
+    fn hash_state(&self, hasher: &mut StateHasher) {
+        hasher.write(&self.len());
+        hasher.write_bytes(self.as_bytes());
+    }
+}
+
+impl StateHash for String {
+    fn hash_state(&self, hasher: &mut StateHasher) {
+        hasher.write(self.as_str());
+    }
+}
+
+impl StateHash for Vec2 {
+    fn hash_state(&self, hasher: &mut StateHasher) {
+        hasher.write(&self.x);
+        hasher.write(&self.y);
+    }
+}
+
+impl StateHash for Point {
+    fn hash_state(&self, hasher: &mut StateHasher) {
+        hasher.write(&self.x);
+        hasher.write(&self.y);
+    }
+}
+
+impl<T: StateHash> StateHash for Option<T> {
+    fn hash_state(&self, hasher: &mut StateHasher) {
+        hasher.write(&self.is_some());
+        if let Some(value) = self {
+            hasher.write(value);
+        }
+    }
+}
+
+impl<T: StateHash> StateHash for [T] {
+    fn hash_state(&self, hasher: &mut StateHasher) {
+        hasher.write(&self.len());
+        for value in self {
+            hasher.write(value);
+        }
+    }
+}
+
+impl<T: StateHash> StateHash for Vec<T> {
+    fn hash_state(&self, hasher: &mut StateHasher) {
+        hasher.write(self.as_slice());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frame(step: u64, sources: &[(&str, u64)]) -> FrameHash {
+        FrameHash {
+            step,
+            total: sources.iter().fold(0, |total, (_, h)| total ^ h),
+            sources: sources.iter().map(|(n, h)| (n.to_string(), *h)).collect(),
+        }
+    }
+
+    #[test]
+    fn hashes_are_fnv_1a() {
+        let hash = |bytes: &[u8]| {
+            let mut hasher = StateHasher::new();
+            hasher.write_bytes(bytes);
+            hasher.finish()
+        };
+        assert_eq!(hash(b""), 0xcbf29ce484222325);
+        assert_eq!(hash(b"a"), 0xaf63dc4c8601ec8c);
+        assert_eq!(hash(b"foobar"), 0x85944171f73967e8);
+
+        let strings = |a: &str, b: &str| {
+            let mut hasher = StateHasher::new();
+            hasher.write(a);
+            hasher.write(b);
+            hasher.finish()
+        };
+        assert_ne!(strings("a", "bc"), strings("ab", "c"));
+    }
+
+    #[test]
+    fn frame_hashes_are_read_back() {
+        let hash = frame(42, &[("physics", 0xdead), ("rng", u64::MAX)]);
+        let line = hash.to_string();
+        assert_eq!(
+            line,
+            "42 ffffffffffff2152 physics=000000000000dead rng=ffffffffffffffff"
+        );
+        assert_eq!(FrameHash::parse(&line), Some(hash));
+        let empty = FrameHash {
+            step: 7,
+            total: 0xff,
+            sources: Vec::new(),
+        };
+        assert_eq!(FrameHash::parse("7 ff"), Some(empty));
+
+        assert_eq!(FrameHash::parse(""), None);
+        assert_eq!(FrameHash::parse("x 00"), None);
+        assert_eq!(FrameHash::parse("1 zz"), None);
+        assert_eq!(FrameHash::parse("1 00 physics"), None);
+        assert_eq!(FrameHash::parse("1 00 physics=zz"), None);
+    }
+
+    #[test]
+    fn logs_are_compared_at_their_first_divergence() {
+        let local = [
+            frame(1, &[("a", 1), ("b", 2)]),
+            frame(2, &[("a", 1), ("b", 3)]),
+            // Resimulated after a rollback, matching the remote this time
+            frame(2, &[("a", 1), ("b", 2)]),
+            frame(3, &[("a", 5), ("b", 2)]),
+            frame(4, &[("a", 6)]),
+        ];
+        let remote = [
+            frame(4, &[("a", 6), ("c", 9)]),
+            frame(1, &[("a", 1), ("b", 2)]),
+            frame(2, &[("a", 1), ("b", 2)]),
+            frame(3, &[("a", 4), ("b", 2)]),
+        ];
+
+        let report = compare_logs(&local, &remote).unwrap();
+        let diff = SourceDiff {
+            name: "a".to_string(),
+            local: Some(5),
+            remote: Some(4),
+        };
+        assert_eq!(
+            report,
+            DesyncReport {
+                step: 3,
+                sources: vec![diff]
+            }
+        );
+        assert_eq!(
+            report.to_string(),
+            "desync at step 3\n  a: 0000000000000005 locally, 0000000000000004 remotely"
+        );
+
+        let report = compare_logs(&local[4..], &remote).unwrap();
+        let diff = SourceDiff {
+            name: "c".to_string(),
+            local: None,
+            remote: Some(9),
+        };
+        assert_eq!(
+            report,
+            DesyncReport {
+                step: 4,
+                sources: vec![diff]
+            }
+        );
+
+        assert_eq!(compare_logs(&local[..3], &remote), None);
+        // Steps only one side hashed are skipped
+        assert_eq!(compare_logs(&local[3..4], &remote[..3]), None);
+    }
+
+    #[test]
+    fn hasher_keeps_and_logs_the_last_hashes() {
+        let mut hasher = FrameHasher::<u64>::new();
+        hasher.history = 2;
+        hasher.register("the state", |state: &u64, h| h.write(state));
+        let path = std::env::temp_dir().join(format!("engine-{}-hashes.log", std::process::id()));
+        hasher.log_to(&path).unwrap();
+
+        let first = hasher.hash(1, &10).clone();
+        hasher.hash(2, &20);
+        hasher.hash(2, &21);
+        hasher.hash(3, &30);
+        hasher.stop_logging();
+
+        assert_eq!(first.sources[0].0, "the_state");
+        assert_eq!(hasher.get(1), None);
+        assert_eq!(hasher.latest().map(|h| h.step), Some(3));
+
+        let log = read_log(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(log.iter().map(|h| h.step).collect::<Vec<_>>(), [1, 2, 2, 3]);
+        assert_eq!(log[0], first);
+        assert_eq!(hasher.check(&log[2]), None);
+        assert_eq!(hasher.check(&log[1]).map(|r| r.step), Some(2));
+    }
+}
@@ -0,0 +1,383 @@
+use std::{collections::HashMap, fmt, path::Path};
+
+mod parser;
+
+const END: &str = "end";
+// Instructions executed without presenting anything before a conversation is deemed stuck in
+// a loop, e.g. nodes jumping to each other without any line.
+const MAX_STEPS: usize = 10_000;
+
+// Conversations written as nodes of lines, choices and commands:
+//
+// === start
+// Guard: Halt! Who goes there? #guard_halt
+// <<if $has_pass>>
+// Guard: Oh, it's you. Go ahead.
+// <<else>>
+// Guard: Come back with a pass.
+// <<endif>>
+// -> Bribe him => bribe <<if $gold >= 10>>
+// -> Leave => end
+//
+// A line may start with its speaker and end with a #key for localization. Lines show the
+// value of variables written as {$name}. Commands are <<set $v = value>>, <<set $v += n>>,
+// <<if>>, <<else>>, <<endif>> and <<stop>>. "=> node" jumps to another node and consecutive
+// choices are offered together. Reaching the end of a node or jumping to "end" ends the
+// conversation, as does looping through nodes without ever presenting a line or choice.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Dialogue {
+    nodes: HashMap<String, Vec<Instruction>>,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum Value {
+    Bool(bool),
+    Number(f64),
+    Text(String),
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Choice {
+    pub text: String,
+    pub key: Option<String>,
+}
+
+// What the UI has to present, DialogueRunner::current stays on it until the player moves on.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum DialogueEvent {
+    Line {
+        speaker: Option<String>,
+        text: String,
+        key: Option<String>,
+    },
+    Choices(Vec<Choice>),
+}
+
+// Runs a dialogue, keeping its variables from one conversation to the next.
+#[derive(Clone, Debug, Default)]
+pub struct DialogueRunner {
+    variables: HashMap<String, Value>,
+    node: Option<String>,
+    next: usize,
+    current: Option<DialogueEvent>,
+    // Targets of the choices being offered.
+    targets: Vec<String>,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum Comparison {
+    Equal,
+    NotEqual,
+    Less,
+    LessOrEqual,
+    Greater,
+    GreaterOrEqual,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+struct Condition {
+    variable: String,
+    comparison: Comparison,
+    value: Value,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+enum Instruction {
+    Line {
+        speaker: Option<String>,
+        text: String,
+        key: Option<String>,
+    },
+    Choice {
+        text: String,
+        key: Option<String>,
+        target: String,
+        condition: Option<Condition>,
+    },
+    Jump(String),
+    Set(String, Value),
+    Add(String, f64),
+    // Skips to the instruction if the condition is false.
+    If(Condition, usize),
+    Goto(usize),
+    Stop,
+}
+
+impl Dialogue {
+    pub fn parse(source: &str) -> Result<Self, String> {
+        parser::parse(source)
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, String> {
+        let source = std::fs::read_to_string(path).map_err(|e| e.to_string())?;
+        Dialogue::parse(&source)
+    }
+
+    pub fn has_node(&self, name: &str) -> bool {
+        self.nodes.contains_key(name)
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::Bool(b) => write!(f, "{b}"),
+            Value::Number(n) => write!(f, "{n}"),
+            Value::Text(t) => write!(f, "{t}"),
+        }
+    }
+}
+
+impl Condition {
+    fn is_met(&self, variables: &HashMap<String, Value>) -> bool {
+        let Some(value) = variables.get(&self.variable) else {
+            // Unset variables are only equal to false
+            return match self.comparison {
+                Comparison::Equal => self.value == Value::Bool(false),
+                Comparison::NotEqual => self.value != Value::Bool(false),
+                _ => false,
+            };
+        };
+
+        let ordering = match (value, &self.value) {
+            (Value::Number(a), Value::Number(b)) => a.partial_cmp(b),
+            (Value::Text(a), Value::Text(b)) => Some(a.cmp(b)),
+            (Value::Bool(a), Value::Bool(b)) => Some(a.cmp(b)),
+            _ => None,
+        };
+
+        match (self.comparison, ordering) {
+            (Comparison::Equal, Some(o)) => o.is_eq(),
+            (Comparison::NotEqual, Some(o)) => o.is_ne(),
+            (Comparison::NotEqual, None) => true,
+            (Comparison::Less, Some(o)) => o.is_lt(),
+            (Comparison::LessOrEqual, Some(o)) => o.is_le(),
+            (Comparison::Greater, Some(o)) => o.is_gt(),
+            (Comparison::GreaterOrEqual, Some(o)) => o.is_ge(),
+            (_, None) => false,
+        }
+    }
+}
+
+impl DialogueRunner {
+    pub fn new() -> Self {
+        DialogueRunner::default()
+    }
+
+    pub fn variable(&self, name: &str) -> Option<&Value> {
+        self.variables.get(name)
+    }
+
+    pub fn set_variable(&mut self, name: &str, value: Value) {
+        self.variables.insert(name.to_string(), value);
+    }
+
+    pub fn start(&mut self, dialogue: &Dialogue, node: &str) -> Result<(), String> {
+        if !dialogue.has_node(node) {
+            return Err(format!("unknown node \"{node}\""));
+        }
+
+        self.jump(node);
+        self.run(dialogue);
+        Ok(())
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.current.is_some()
+    }
+
+    // None once the conversation is over.
+    pub fn current(&self) -> Option<&DialogueEvent> {
+        self.current.as_ref()
+    }
+
+    // Moves past the current line, does nothing while choices are offered.
+    pub fn advance(&mut self, dialogue: &Dialogue) {
+        if matches!(self.current, Some(DialogueEvent::Line { .. })) {
+            self.run(dialogue);
+        }
+    }
+
+    // index is among the choices offered, returns false if there is no such choice.
+    pub fn choose(&mut self, dialogue: &Dialogue, index: usize) -> bool {
+        let Some(target) = self.targets.get(index).cloned() else {
+            return false;
+        };
+
+        self.jump(&target);
+        self.run(dialogue);
+        true
+    }
+
+    pub fn stop(&mut self) {
+        self.node = None;
+        self.current = None;
+        self.targets.clear();
+    }
+
+    fn jump(&mut self, node: &str) {
+        self.node = (node != END).then(|| node.to_string());
+        self.next = 0;
+    }
+
+    // Executes instructions until something has to be presented.
+    fn run(&mut self, dialogue: &Dialogue) {
+        self.current = None;
+        self.targets.clear();
+
+        let mut steps = 0;
+        while let Some(node) = &self.node {
+            steps += 1;
+            if steps > MAX_STEPS {
+                log::error!("the dialogue loops without presenting anything in node \"{node}\"");
+                break;
+            }
+
+            let instructions = &dialogue.nodes[node];
+            let Some(instruction) = instructions.get(self.next) else {
+                break;
+            };
+            self.next += 1;
+
+            match instruction {
+                Instruction::Line { speaker, text, key } => {
+                    self.current = Some(DialogueEvent::Line {
+                        speaker: speaker.clone(),
+                        text: self.interpolate(text),
+                        key: key.clone(),
+                    });
+                    return;
+                }
+                Instruction::Choice { .. } => {
+                    self.offer_choices(instructions);
+                    if self.current.is_some() {
+                        return;
+                    }
+                }
+                Instruction::Jump(target) => {
+                    let target = target.clone();
+                    self.jump(&target);
+                }
+                Instruction::Set(variable, value) => {
+                    self.variables.insert(variable.clone(), value.clone());
+                }
+                Instruction::Add(variable, amount) => {
+                    let value = match self.variables.get(variable) {
+                        Some(Value::Number(n)) => n + amount,
+                        _ => *amount,
+                    };
+                    self.variables
+                        .insert(variable.clone(), Value::Number(value));
+                }
+                Instruction::If(condition, skip_to) => {
+                    if !condition.is_met(&self.variables) {
+                        self.next = *skip_to;
+                    }
+                }
+                Instruction::Goto(target) => self.next = *target,
+                Instruction::Stop => break,
+            }
+        }
+
+        self.stop();
+    }
+
+    // Gathers the choice starting at next - 1 and the ones right after.
+    fn offer_choices(&mut self, instructions: &[Instruction]) {
+        let mut choices = Vec::new();
+        self.next -= 1;
+
+        while let Some(Instruction::Choice {
+            text,
+            key,
+            target,
+            condition,
+        }) = instructions.get(self.next)
+        {
+            self.next += 1;
+            if condition
+                .as_ref()
+                .is_some_and(|c| !c.is_met(&self.variables))
+            {
+                continue;
+            }
+
+            choices.push(Choice {
+                text: self.interpolate(text),
+                key: key.clone(),
+            });
+            self.targets.push(target.clone());
+        }
+
+        if !choices.is_empty() {
+            self.current = Some(DialogueEvent::Choices(choices));
+        }
+    }
+
+    fn interpolate(&self, text: &str) -> String {
+        let mut result = String::new();
+        let mut rest = text;
+
+        while let Some(start) = rest.find("{$") {
+            let Some(end) = rest[start..].find('}') else {
+                break;
+            };
+
+            result.push_str(&rest[..start]);
+            let name = &rest[start + 2..start + end];
+            match self.variables.get(name) {
+                Some(value) => result.push_str(&value.to_string()),
+                None => result.push_str(&rest[start..start + end + 1]),
+            }
+            rest = &rest[start + end + 1..];
+        }
+
+        result.push_str(rest);
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn loops_without_lines_end_the_dialogue() {
+        let source = "
+            === start
+            <<set $count += 1>>
+            => other
+
+            === other
+            => start
+        ";
+        let dialogue = Dialogue::parse(source).unwrap();
+        let mut runner = DialogueRunner::new();
+
+        runner.start(&dialogue, "start").unwrap();
+
+        assert!(!runner.is_running());
+        assert!(runner.variable("count").is_some());
+    }
+
+    #[test]
+    fn loops_with_lines_keep_going() {
+        let source = "
+            === start
+            Hello
+            => start
+        ";
+        let dialogue = Dialogue::parse(source).unwrap();
+        let mut runner = DialogueRunner::new();
+
+        runner.start(&dialogue, "start").unwrap();
+        for _ in 0..MAX_STEPS {
+            runner.advance(&dialogue);
+        }
+
+        assert!(matches!(
+            runner.current(),
+            Some(DialogueEvent::Line { text, .. }) if text == "Hello"
+        ));
+    }
+}
@@ -0,0 +1,391 @@
+use std::collections::HashMap;
+
+use super::{Comparison, Condition, Dialogue, Instruction, Value, END};
+
+// Lines being parsed within the current node, with the if blocks left open.
+struct NodeBuilder {
+    name: String,
+    instructions: Vec<Instruction>,
+    // Index of the If instruction, then of the jump over the else block if there is one.
+    open_ifs: Vec<(usize, Option<usize>)>,
+}
+
+pub(super) fn parse(source: &str) -> Result<Dialogue, String> {
+    let mut nodes = HashMap::new();
+    let mut current: Option<NodeBuilder> = None;
+
+    for (number, line) in source.lines().enumerate() {
+        let error = |message: &str| format!("line {}: {}", number + 1, message);
+        let line = line.trim();
+        if line.is_empty() || line.starts_with("//") {
+            continue;
+        }
+
+        if let Some(name) = line.strip_prefix("===") {
+            if let Some(node) = current.take() {
+                finish(node, &mut nodes).map_err(|e| error(&e))?;
+            }
+
+            current = Some(NodeBuilder {
+                name: name.trim().to_string(),
+                instructions: Vec::new(),
+                open_ifs: Vec::new(),
+            });
+            continue;
+        }
+
+        let node = current
+            .as_mut()
+            .ok_or_else(|| error("expected a node header \"=== name\""))?;
+        parse_line(node, line).map_err(|e| error(&e))?;
+    }
+
+    if let Some(node) = current {
+        finish(node, &mut nodes)?;
+    }
+
+    let dialogue = Dialogue { nodes };
+    for (name, instructions) in &dialogue.nodes {
+        for instruction in instructions {
+            let target = match instruction {
+                Instruction::Jump(target) => target,
+                Instruction::Choice { target, .. } => target,
+                _ => continue,
+            };
+
+            if target != END && !dialogue.nodes.contains_key(target) {
+                return Err(format!("node {name}: unknown node \"{target}\""));
+            }
+        }
+    }
+
+    Ok(dialogue)
+}
+
+fn finish(node: NodeBuilder, nodes: &mut HashMap<String, Vec<Instruction>>) -> Result<(), String> {
+    if !node.open_ifs.is_empty() {
+        return Err(format!("node {}: missing <<endif>>", node.name));
+    }
+
+    if nodes.insert(node.name.clone(), node.instructions).is_some() {
+        return Err(format!("node {} is defined twice", node.name));
+    }
+    Ok(())
+}
+
+fn parse_line(node: &mut NodeBuilder, line: &str) -> Result<(), String> {
+    if let Some(command) = line
+        .strip_prefix("<<")
+        .and_then(|l| l.strip_suffix(">>"))
+        .map(str::trim)
+    {
+        return parse_command(node, command);
+    }
+
+    if let Some(target) = line.strip_prefix("=>") {
+        node.instructions
+            .push(Instruction::Jump(target.trim().to_string()));
+        return Ok(());
+    }
+
+    if let Some(choice) = line.strip_prefix("->") {
+        let (choice, condition) = match choice.split_once("<<if") {
+            Some((choice, condition)) => {
+                let condition = condition
+                    .trim()
+                    .strip_suffix(">>")
+                    .ok_or("expected >> after the choice condition")?;
+                (choice, Some(parse_condition(condition)?))
+            }
+            None => (choice, None),
+        };
+
+        let (text, target) = choice
+            .split_once("=>")
+            .ok_or("expected \"-> text => node\"")?;
+        let (text, key) = split_key(text);
+        node.instructions.push(Instruction::Choice {
+            text,
+            key,
+            target: target.trim().to_string(),
+            condition,
+        });
+        return Ok(());
+    }
+
+    let (speaker, text) = match line.split_once(':') {
+        Some((speaker, text)) if is_identifier(speaker.trim()) => {
+            (Some(speaker.trim().to_string()), text)
+        }
+        _ => (None, line),
+    };
+    let (text, key) = split_key(text);
+    node.instructions
+        .push(Instruction::Line { speaker, text, key });
+
+    Ok(())
+}
+
+fn parse_command(node: &mut NodeBuilder, command: &str) -> Result<(), String> {
+    let (keyword, rest) = command.split_once(' ').unwrap_or((command, ""));
+    let rest = rest.trim();
+
+    match keyword {
+        "set" => {
+            let (variable, operator, value) = ["+=", "-=", "="]
+                .iter()
+                .find_map(|op| {
+                    rest.split_once(op)
+                        .map(|(variable, value)| (variable, *op, value))
+                })
+                .ok_or("expected \"<<set $variable = value>>\"")?;
+
+            let variable = parse_variable(variable)?;
+            let value = parse_value(value)?;
+            let instruction = match (operator, value) {
+                ("=", value) => Instruction::Set(variable, value),
+                ("+=", Value::Number(n)) => Instruction::Add(variable, n),
+                ("-=", Value::Number(n)) => Instruction::Add(variable, -n),
+                _ => return Err("only numbers can be added".to_string()),
+            };
+            node.instructions.push(instruction);
+        }
+        "if" => {
+            let condition = parse_condition(rest)?;
+            node.open_ifs.push((node.instructions.len(), None));
+            node.instructions.push(Instruction::If(condition, 0));
+        }
+        "else" => {
+            let open = node.open_ifs.last_mut().ok_or("<<else>> without <<if>>")?;
+            if open.1.is_some() {
+                return Err("<<if>> with several <<else>>".to_string());
+            }
+
+            open.1 = Some(node.instructions.len());
+            node.instructions.push(Instruction::Goto(0));
+        }
+        "endif" => {
+            let (if_index, else_index) = node.open_ifs.pop().ok_or("<<endif>> without <<if>>")?;
+            let end = node.instructions.len();
+
+            // A false condition skips to the else block, or to the end without one
+            let skip_to = else_index.map_or(end, |e| e + 1);
+            if let Instruction::If(_, skip) = &mut node.instructions[if_index] {
+                *skip = skip_to;
+            }
+            if let Some(Instruction::Goto(target)) = else_index.map(|e| &mut node.instructions[e]) {
+                *target = end;
+            }
+        }
+        "stop" => node.instructions.push(Instruction::Stop),
+        _ => return Err(format!("unknown command \"{keyword}\"")),
+    }
+
+    Ok(())
+}
+
+fn parse_condition(condition: &str) -> Result<Condition, String> {
+    let comparisons = [
+        ("==", Comparison::Equal),
+        ("!=", Comparison::NotEqual),
+        ("<=", Comparison::LessOrEqual),
+        (">=", Comparison::GreaterOrEqual),
+        ("<", Comparison::Less),
+        (">", Comparison::Greater),
+    ];
+
+    for (operator, comparison) in comparisons {
+        if let Some((variable, value)) = condition.split_once(operator) {
+            return Ok(Condition {
+                variable: parse_variable(variable)?,
+                comparison,
+                value: parse_value(value)?,
+            });
+        }
+    }
+
+    // A lone variable checks that it is true
+    Ok(Condition {
+        variable: parse_variable(condition)?,
+        comparison: Comparison::Equal,
+        value: Value::Bool(true),
+    })
+}
+
+fn parse_variable(variable: &str) -> Result<String, String> {
+    variable
+        .trim()
+        .strip_prefix('$')
+        .filter(|v| is_identifier(v))
+        .map(str::to_string)
+        .ok_or_else(|| format!("expected a variable, got \"{}\"", variable.trim()))
+}
+
+fn parse_value(value: &str) -> Result<Value, String> {
+    let value = value.trim();
+    match value {
+        "true" => return Ok(Value::Bool(true)),
+        "false" => return Ok(Value::Bool(false)),
+        _ => {}
+    }
+
+    if let Some(text) = value.strip_prefix('"').and_then(|v| v.strip_suffix('"')) {
+        return Ok(Value::Text(text.to_string()));
+    }
+
+    value
+        .parse()
+        .map(Value::Number)
+        .map_err(|_| format!("invalid value \"{value}\""))
+}
+
+// Splits the trailing "#key" localization key from the text.
+fn split_key(text: &str) -> (String, Option<String>) {
+    let text = text.trim();
+    match text.rsplit_once(" #") {
+        Some((text, key)) if is_identifier(key) => (text.trim().to_string(), Some(key.to_string())),
+        _ => (text.to_string(), None),
+    }
+}
+
+fn is_identifier(text: &str) -> bool {
+    !text.is_empty()
+        && text
+            .chars()
+            .all(|c| c.is_alphanumeric() || c == '_' || c == '.')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn line(speaker: Option<&str>, text: &str, key: Option<&str>) -> Instruction {
+        Instruction::Line {
+            speaker: speaker.map(str::to_string),
+            text: text.to_string(),
+            key: key.map(str::to_string),
+        }
+    }
+
+    fn condition(variable: &str, comparison: Comparison, value: Value) -> Condition {
+        Condition {
+            variable: variable.to_string(),
+            comparison,
+            value,
+        }
+    }
+
+    #[test]
+    fn nodes_are_compiled_to_instructions() {
+        let source = "
+            // The guard at the gate
+            === start
+            Guard: Halt! Who goes there? #guard_halt
+            <<if $has_pass>>
+                Guard: Oh, it's you. Go ahead.
+            <<else>>
+                Guard: Come back with a pass.
+            <<endif>>
+            -> Bribe him #bribe => bribe <<if $gold >= 10>>
+            -> Leave => end
+
+            === bribe
+            <<set $gold -= 10>>
+            <<set $bribed = true>>
+            Nice weather: isn't it?
+            <<stop>>
+        ";
+        let dialogue = parse(source).unwrap();
+
+        let start = vec![
+            line(Some("Guard"), "Halt! Who goes there?", Some("guard_halt")),
+            Instruction::If(
+                condition("has_pass", Comparison::Equal, Value::Bool(true)),
+                4,
+            ),
+            line(Some("Guard"), "Oh, it's you. Go ahead.", None),
+            Instruction::Goto(5),
+            line(Some("Guard"), "Come back with a pass.", None),
+            Instruction::Choice {
+                text: "Bribe him".to_string(),
+                key: Some("bribe".to_string()),
+                target: "bribe".to_string(),
+                condition: Some(condition(
+                    "gold",
+                    Comparison::GreaterOrEqual,
+                    Value::Number(10.),
+                )),
+            },
+            Instruction::Choice {
+                text: "Leave".to_string(),
+                key: None,
+                target: "end".to_string(),
+                condition: None,
+            },
+        ];
+        assert_eq!(dialogue.nodes["start"], start);
+
+        let bribe = vec![
+            Instruction::Add("gold".to_string(), -10.),
+            Instruction::Set("bribed".to_string(), Value::Bool(true)),
+            line(None, "Nice weather: isn't it?", None),
+            Instruction::Stop,
+        ];
+        assert_eq!(dialogue.nodes["bribe"], bribe);
+    }
+
+    #[test]
+    fn ifs_without_else_skip_to_their_end() {
+        let source = "
+            === start
+            <<if $name != \"Bob\">>
+            <<if $level < 3>>
+            Too weak.
+            <<endif>>
+            <<endif>>
+            Done.
+        ";
+        let dialogue = parse(source).unwrap();
+        let name = condition("name", Comparison::NotEqual, Value::Text("Bob".to_string()));
+        let level = condition("level", Comparison::Less, Value::Number(3.));
+        assert_eq!(dialogue.nodes["start"][0], Instruction::If(name, 3));
+        assert_eq!(dialogue.nodes["start"][1], Instruction::If(level, 3));
+    }
+
+    #[test]
+    fn errors_name_the_line_or_node() {
+        let error = |source: &str| parse(source).unwrap_err();
+        assert_eq!(
+            error("Hello"),
+            "line 1: expected a node header \"=== name\""
+        );
+        assert_eq!(error("=== a\n<<else>>"), "line 2: <<else>> without <<if>>");
+        assert_eq!(
+            error("=== a\n<<endif>>"),
+            "line 2: <<endif>> without <<if>>"
+        );
+        assert_eq!(error("=== a\n<<if $x>>"), "node a: missing <<endif>>");
+        assert_eq!(
+            error("=== a\n<<if $x>>\n=== b"),
+            "line 3: node a: missing <<endif>>"
+        );
+        assert_eq!(error("=== a\n=== a"), "node a is defined twice");
+        assert_eq!(error("=== a\n=> b"), "node a: unknown node \"b\"");
+        assert_eq!(
+            error("=== a\n<<set $x += \"a\">>"),
+            "line 2: only numbers can be added"
+        );
+        assert_eq!(
+            error("=== a\n<<if x>>\n<<endif>>"),
+            "line 2: expected a variable, got \"x\""
+        );
+        assert_eq!(
+            error("=== a\n<<jump b>>"),
+            "line 2: unknown command \"jump\""
+        );
+        assert_eq!(
+            error("=== a\n-> Go"),
+            "line 2: expected \"-> text => node\""
+        );
+    }
+}
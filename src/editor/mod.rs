@@ -0,0 +1,614 @@
+use std::{path::PathBuf, time::Instant};
+
+use parry2d_f64::shape::SharedShape;
+
+use crate::{
+    graphics::{BitmapFont, Color, DrawParams, GraphicsPipeline, PixelRect, TileId, Tilemap},
+    inputs::{ButtonControl, InputScheme, InputsPipeline, MouseButton, Scancode},
+    math::Rect,
+    physics::BodyId,
+    prefab::Prefabs,
+    scene::{Scene, SceneObject},
+    Engine, Point, Vec2,
+};
+
+// In pixels.
+const HANDLE_SIZE: u32 = 8;
+const SLOT_SIZE: u32 = 40;
+const SLOT_MARGIN: u32 = 4;
+// Smallest half extent objects and colliders can be scaled down to.
+const MIN_HALF_EXTENT: f64 = 0.01;
+
+const DIGITS: [Scancode; 9] = [
+    Scancode::Num1,
+    Scancode::Num2,
+    Scancode::Num3,
+    Scancode::Num4,
+    Scancode::Num5,
+    Scancode::Num6,
+    Scancode::Num7,
+    Scancode::Num8,
+    Scancode::Num9,
+];
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum Tool {
+    // Picks scene objects and bodies, to move them or scale them by their corner handle.
+    Select,
+    // Places an object of the prefab per click.
+    Place(String),
+    Paint(TileId),
+    Erase,
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum Selection {
+    Object(String),
+    Body(BodyId),
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum EditorEvent {
+    Toggled(bool),
+    Selected(Option<Selection>),
+    // Moved or scaled.
+    Changed(Selection),
+    Placed(String),
+    Removed(String),
+    TileChanged(u32, u32),
+    Saved,
+}
+
+// What the editor works on besides the engine's camera and physics.
+pub struct EditorContext<'a> {
+    pub scene: &'a mut Scene,
+    // Objects are picked by the size property of their instance, [1, 1] by default, scaled.
+    pub prefabs: &'a Prefabs,
+    // Drawn with its top left corner at the position, see Tilemap::draw.
+    pub tilemap: Option<(&'a mut Tilemap, Vec2)>,
+}
+
+enum Drag {
+    Move {
+        offset: Vec2,
+    },
+    Scale {
+        center: Vec2,
+        half: Vec2,
+        from: Scaled,
+    },
+    Pan {
+        last: Point,
+    },
+}
+
+// What's being scaled, as it was when the drag started.
+enum Scaled {
+    Object(Vec2),
+    Body(SharedShape),
+}
+
+// Level editor toggled at runtime with toggle_key, pausing the game meanwhile. The camera flies
+// with the arrow keys or WASD, or by dragging with the middle button, and is put back when the
+// editor is closed. The palette is drawn at the bottom of the screen, its tools picked by
+// clicking them or with the number keys, Escape going back to Select. Delete removes the
+// selected object and Ctrl+S saves the scene to path.
+pub struct Editor {
+    pub enabled: bool,
+    pub toggle_key: Scancode,
+    // Units per second.
+    pub camera_speed: f64,
+    pub tool: Tool,
+    pub palette: Vec<Tool>,
+    // Prefab names are written in the palette with it.
+    pub font: Option<BitmapFont>,
+    pub path: Option<PathBuf>,
+    selection: Option<Selection>,
+    drag: Option<Drag>,
+    // Controls held during the last update, to tell when they're pressed.
+    held: Vec<ButtonControl>,
+    game_camera: Vec2,
+    last_update: Option<Instant>,
+}
+
+impl Editor {
+    pub fn new(path: Option<PathBuf>) -> Self {
+        Editor {
+            enabled: false,
+            toggle_key: Scancode::F1,
+            camera_speed: 10.,
+            tool: Tool::Select,
+            palette: vec![Tool::Select],
+            font: None,
+            path,
+            selection: None,
+            drag: None,
+            held: Vec::new(),
+            game_camera: Vec2::ZERO,
+            last_update: None,
+        }
+    }
+
+    pub fn selection(&self) -> Option<&Selection> {
+        self.selection.as_ref()
+    }
+
+    // To be called every frame after Engine::update, the game updating the objects it spawned
+    // from the scene as told by the events.
+    pub fn update<T: InputScheme>(
+        &mut self,
+        engine: &mut Engine<T>,
+        context: &mut EditorContext,
+    ) -> Vec<EditorEvent> {
+        // Measured by the editor, the game's delta being 0 while paused
+        let now = Instant::now();
+        let dt = self
+            .last_update
+            .map_or(0., |last| now.duration_since(last).as_secs_f64());
+        self.last_update = Some(now);
+
+        let mut events = Vec::new();
+        let held = self.held_controls(&engine.inputs_ppl);
+        let just_pressed: Vec<ButtonControl> = held
+            .iter()
+            .filter(|control| !self.held.contains(control))
+            .copied()
+            .collect();
+        let pressed = |control: ButtonControl| just_pressed.contains(&control);
+
+        let keyboard = |key: Scancode| engine.inputs_ppl.is_held(&ButtonControl::Keyboard(key));
+        let axis = |negative: [Scancode; 2], positive: [Scancode; 2]| {
+            let held = |keys: [Scancode; 2]| keys.iter().any(|k| keyboard(*k));
+            held(positive) as i32 as f64 - held(negative) as i32 as f64
+        };
+        let ctrl = keyboard(Scancode::LCtrl) || keyboard(Scancode::RCtrl);
+        let horizontal = axis(
+            [Scancode::Left, Scancode::A],
+            [Scancode::Right, Scancode::D],
+        );
+        let vertical = axis([Scancode::Up, Scancode::W], [Scancode::Down, Scancode::S]);
+
+        if pressed(ButtonControl::Keyboard(self.toggle_key)) {
+            self.set_enabled(engine, !self.enabled);
+            events.push(EditorEvent::Toggled(self.enabled));
+        }
+        if !self.enabled {
+            self.held = held;
+            return events;
+        }
+
+        // Camera, Ctrl being kept for shortcuts
+        let graphics_ppl = &mut engine.graphics_ppl;
+        if !ctrl {
+            let direction = Vec2::new(horizontal, 0.) + graphics_ppl.options.down() * vertical;
+            graphics_ppl.camera.position += direction.normalize_or_zero() * self.camera_speed * dt;
+        }
+
+        let mouse = engine.inputs_ppl.mouse_position();
+        let middle = ButtonControl::Mouse(MouseButton::Middle);
+        if pressed(middle) {
+            self.drag = Some(Drag::Pan { last: mouse });
+        } else if let Some(Drag::Pan { last }) = &mut self.drag {
+            if held.contains(&middle) {
+                let delta = graphics_ppl.screen_to_world_position(&mouse)
+                    - graphics_ppl.screen_to_world_position(last);
+                graphics_ppl.camera.position -= delta;
+                *last = mouse;
+            } else {
+                self.drag = None;
+            }
+        }
+
+        // Tools
+        if let Some(index) = DIGITS
+            .iter()
+            .position(|key| pressed(ButtonControl::Keyboard(*key)))
+        {
+            if let Some(tool) = self.palette.get(index) {
+                self.tool = tool.clone();
+            }
+        }
+        if pressed(ButtonControl::Keyboard(Scancode::Escape)) {
+            self.tool = Tool::Select;
+        }
+
+        let world = graphics_ppl
+            .camera
+            .get_world_coordinate(graphics_ppl, &mouse);
+        let left = ButtonControl::Mouse(MouseButton::Left);
+        let slot = self.slot_at(graphics_ppl, mouse);
+        if pressed(left) && slot.is_some() {
+            if let Some(tool) = slot.and_then(|i| self.palette.get(i)) {
+                self.tool = tool.clone();
+            }
+        } else if pressed(left) {
+            self.press(engine, context, world, mouse, &mut events);
+        } else if held.contains(&left) {
+            self.hold(engine, context, world, &mut events);
+        } else if !matches!(self.drag, Some(Drag::Pan { .. })) {
+            self.drag = None;
+        }
+
+        if pressed(ButtonControl::Keyboard(Scancode::Delete)) {
+            if let Some(Selection::Object(name)) = self.selection.take() {
+                context.scene.objects.remove(&name);
+                events.push(EditorEvent::Removed(name));
+                events.push(EditorEvent::Selected(None));
+            }
+        }
+        if ctrl && pressed(ButtonControl::Keyboard(Scancode::S)) {
+            if let Some((tilemap, _)) = &context.tilemap {
+                context.scene.capture_tiles(tilemap);
+            }
+            match &self.path {
+                Some(path) => match context.scene.save(path) {
+                    Ok(()) => {
+                        log::info!("saved scene to {}", path.display());
+                        events.push(EditorEvent::Saved);
+                    }
+                    Err(e) => log::warn!("failed to save scene to {}: {e}", path.display()),
+                },
+                None => log::warn!("the editor has no path to save the scene to"),
+            }
+        }
+
+        self.held = held;
+        events
+    }
+
+    // To be called once the scene is drawn.
+    pub fn draw<T: InputScheme>(&self, engine: &mut Engine<T>, context: &EditorContext) {
+        if !self.enabled {
+            return;
+        }
+
+        let mouse = engine.inputs_ppl.mouse_position();
+        let graphics_ppl = &mut engine.graphics_ppl;
+        let params = DrawParams::default();
+        let outline = |graphics_ppl: &mut GraphicsPipeline, bounds: Rect, color: Color| {
+            graphics_ppl.draw_rect(&bounds.center(), &bounds.size, &color, false, &params);
+        };
+
+        for (name, object) in &context.scene.objects {
+            let selected = self.selection == Some(Selection::Object(name.clone()));
+            if !selected {
+                let bounds = object_bounds(context.prefabs, object);
+                outline(graphics_ppl, bounds, Color::RGBA(255, 255, 255, 96));
+            }
+        }
+        if let Some(bounds) = self.selection_bounds(engine.physics.as_ref(), context) {
+            outline(graphics_ppl, bounds, Color::YELLOW);
+            let handle = handle_rect(graphics_ppl, bounds);
+            graphics_ppl.draw_rect_screen(handle, &Color::YELLOW, true, &params);
+        }
+
+        // Cell painted by the tile tools
+        if let (Tool::Paint(_) | Tool::Erase, Some((tilemap, position))) =
+            (&self.tool, &context.tilemap)
+        {
+            let world = graphics_ppl
+                .camera
+                .get_world_coordinate(graphics_ppl, &mouse);
+            if let Some((x, y)) = cell_at(graphics_ppl, tilemap, *position, world) {
+                let size = tile_size(graphics_ppl, tilemap);
+                let center = *position
+                    + Vec2::new((x as f64 + 0.5) * size.x, 0.)
+                    + graphics_ppl.options.down() * ((y as f64 + 0.5) * size.y);
+                outline(graphics_ppl, Rect::from_center(center, size), Color::CYAN);
+            }
+        }
+
+        // Palette
+        for (index, tool) in self.palette.iter().enumerate() {
+            let rect = slot_rect(graphics_ppl, index);
+            graphics_ppl.draw_rect_screen(rect, &Color::RGBA(0, 0, 0, 160), true, &params);
+            let inner = PixelRect::new(
+                rect.x() + SLOT_MARGIN as i32,
+                rect.y() + SLOT_MARGIN as i32,
+                rect.width() - SLOT_MARGIN * 2,
+                rect.height() - SLOT_MARGIN * 2,
+            );
+            match tool {
+                Tool::Select => graphics_ppl.draw_rect_screen(inner, &Color::WHITE, false, &params),
+                Tool::Erase => graphics_ppl.draw_rect_screen(inner, &Color::RED, false, &params),
+                Tool::Paint(tile) => {
+                    if let Some((tilemap, _)) = &context.tilemap {
+                        let src = tilemap.tileset.tile_rect(*tile);
+                        let texture = tilemap.tileset.texture;
+                        graphics_ppl.draw_sprite_screen(texture, Some(src), inner, &params);
+                    }
+                }
+                Tool::Place(prefab) => {
+                    graphics_ppl.draw_rect_screen(inner, &Color::GREEN, false, &params);
+                    if let Some(font) = &self.font {
+                        let columns = (inner.width() / font.glyph_width.max(1)) as usize;
+                        let label: String = prefab.chars().take(columns).collect();
+                        let position = Point::new(inner.x(), inner.y());
+                        graphics_ppl.draw_text_screen(font, &label, position, 1., &params);
+                    }
+                }
+            }
+            if *tool == self.tool {
+                graphics_ppl.draw_rect_screen(rect, &Color::YELLOW, false, &params);
+            }
+        }
+    }
+
+    fn set_enabled<T: InputScheme>(&mut self, engine: &mut Engine<T>, enabled: bool) {
+        self.enabled = enabled;
+        self.drag = None;
+        if enabled {
+            self.game_camera = engine.graphics_ppl.camera.position;
+            engine.focus.pause();
+        } else {
+            engine.graphics_ppl.camera.position = self.game_camera;
+            engine.focus.resume();
+        }
+    }
+
+    fn held_controls<T: InputScheme>(&self, inputs_ppl: &InputsPipeline<T>) -> Vec<ButtonControl> {
+        let keys = [
+            self.toggle_key,
+            Scancode::Escape,
+            Scancode::Delete,
+            Scancode::S,
+        ];
+        keys.iter()
+            .chain(&DIGITS)
+            .map(|key| ButtonControl::Keyboard(*key))
+            .chain([MouseButton::Left, MouseButton::Middle].map(ButtonControl::Mouse))
+            .filter(|control| inputs_ppl.is_held(control))
+            .collect()
+    }
+
+    fn press<T: InputScheme>(
+        &mut self,
+        engine: &mut Engine<T>,
+        context: &mut EditorContext,
+        world: Vec2,
+        mouse: Point,
+        events: &mut Vec<EditorEvent>,
+    ) {
+        match self.tool.clone() {
+            Tool::Select => {
+                // The handle of the selection first, then what's under the mouse
+                if let Some(bounds) = self.selection_bounds(engine.physics.as_ref(), context) {
+                    if handle_rect(&engine.graphics_ppl, bounds).contains_point((mouse.x, mouse.y))
+                    {
+                        let from = match &self.selection {
+                            Some(Selection::Object(name)) => {
+                                Scaled::Object(context.scene.objects[name].scale)
+                            }
+                            Some(Selection::Body(id)) => {
+                                let body = engine.physics.as_ref().and_then(|p| p.body(*id));
+                                match body {
+                                    Some(body) => Scaled::Body(body.shape.clone()),
+                                    None => return,
+                                }
+                            }
+                            None => return,
+                        };
+                        self.drag = Some(Drag::Scale {
+                            center: bounds.center(),
+                            half: (bounds.size / 2.).max(Vec2::splat(MIN_HALF_EXTENT)),
+                            from,
+                        });
+                        return;
+                    }
+                }
+
+                let picked = self.pick(engine, context, world);
+                if picked != self.selection {
+                    self.selection = picked.clone();
+                    events.push(EditorEvent::Selected(picked.clone()));
+                }
+                let position = match &picked {
+                    Some(Selection::Object(name)) => context.scene.objects[name].position,
+                    Some(Selection::Body(id)) => {
+                        match engine.physics.as_ref().and_then(|p| p.body(*id)) {
+                            Some(body) => body.position,
+                            None => return,
+                        }
+                    }
+                    None => return,
+                };
+                self.drag = Some(Drag::Move {
+                    offset: position - world,
+                });
+            }
+            Tool::Place(prefab) => {
+                let name = context.scene.unused_name(&prefab);
+                context
+                    .scene
+                    .objects
+                    .insert(name.clone(), SceneObject::new(&prefab, world));
+                events.push(EditorEvent::Placed(name.clone()));
+                self.selection = Some(Selection::Object(name));
+                events.push(EditorEvent::Selected(self.selection.clone()));
+            }
+            Tool::Paint(_) | Tool::Erase => self.hold(engine, context, world, events),
+        }
+    }
+
+    fn hold<T: InputScheme>(
+        &mut self,
+        engine: &mut Engine<T>,
+        context: &mut EditorContext,
+        world: Vec2,
+        events: &mut Vec<EditorEvent>,
+    ) {
+        let tile = match self.tool {
+            Tool::Paint(tile) => Some(tile),
+            Tool::Erase => None,
+            _ => return self.drag_selection(engine, context, world, events),
+        };
+        let Some((tilemap, position)) = &mut context.tilemap else {
+            return;
+        };
+        if let Some((x, y)) = cell_at(&engine.graphics_ppl, tilemap, *position, world) {
+            if tilemap.tile(x, y) != tile {
+                tilemap.set_tile(x, y, tile);
+                events.push(EditorEvent::TileChanged(x, y));
+            }
+        }
+    }
+
+    fn drag_selection<T: InputScheme>(
+        &mut self,
+        engine: &mut Engine<T>,
+        context: &mut EditorContext,
+        world: Vec2,
+        events: &mut Vec<EditorEvent>,
+    ) {
+        let Some(selection) = self.selection.clone() else {
+            return;
+        };
+        let object = match &selection {
+            Selection::Object(name) => context.scene.objects.get_mut(name),
+            Selection::Body(_) => None,
+        };
+        let body = match &selection {
+            Selection::Body(id) => engine.physics.as_mut().and_then(|p| p.body_mut(*id)),
+            Selection::Object(_) => None,
+        };
+
+        match &self.drag {
+            Some(Drag::Move { offset }) => {
+                let position = world + *offset;
+                if let Some(object) = object {
+                    object.position = position;
+                } else if let Some(body) = body {
+                    body.teleport(position, body.rotation);
+                    body.velocity = Vec2::ZERO;
+                }
+            }
+            Some(Drag::Scale { center, half, from }) => {
+                let factor = (world - *center).abs().max(Vec2::splat(MIN_HALF_EXTENT)) / *half;
+                match (from, object, body) {
+                    (Scaled::Object(scale), Some(object), _) => object.scale = *scale * factor,
+                    (Scaled::Body(shape), _, Some(body)) => {
+                        let Some(scaled) = scale_shape(shape, factor) else {
+                            return;
+                        };
+                        // The density is kept
+                        let density = body.mass() / body.shape.mass_properties(1.).mass();
+                        body.shape = scaled;
+                        if density.is_finite() {
+                            body.set_density(density);
+                        }
+                    }
+                    _ => return,
+                }
+            }
+            _ => return,
+        }
+        events.push(EditorEvent::Changed(selection));
+    }
+
+    // Topmost scene object under the point, or else the first body.
+    fn pick<T: InputScheme>(
+        &self,
+        engine: &Engine<T>,
+        context: &EditorContext,
+        point: Vec2,
+    ) -> Option<Selection> {
+        context
+            .scene
+            .objects
+            .iter()
+            .rev()
+            .find(|(_, object)| object_bounds(context.prefabs, object).contains(&point))
+            .map(|(name, _)| Selection::Object(name.clone()))
+            .or_else(|| {
+                let physics = engine.physics.as_ref()?;
+                physics
+                    .bodies_at(point)
+                    .first()
+                    .map(|id| Selection::Body(*id))
+            })
+    }
+
+    fn selection_bounds(
+        &self,
+        physics: Option<&crate::physics::PhysicsWorld>,
+        context: &EditorContext,
+    ) -> Option<Rect> {
+        match self.selection.as_ref()? {
+            Selection::Object(name) => Some(object_bounds(
+                context.prefabs,
+                context.scene.objects.get(name)?,
+            )),
+            Selection::Body(id) => {
+                let body = physics?.body(*id)?;
+                let aabb = body.shape.compute_aabb(&body.pose());
+                Some(Rect::from_corners(aabb.mins, aabb.maxs))
+            }
+        }
+    }
+
+    fn slot_at(&self, graphics_ppl: &GraphicsPipeline, mouse: Point) -> Option<usize> {
+        (0..self.palette.len())
+            .find(|i| slot_rect(graphics_ppl, *i).contains_point((mouse.x, mouse.y)))
+    }
+}
+
+fn object_bounds(prefabs: &Prefabs, object: &SceneObject) -> Rect {
+    let size = prefabs
+        .instantiate(&object.prefab, &object.overrides)
+        .ok()
+        .and_then(|instance| instance.vec2("size"))
+        .unwrap_or(Vec2::ONE);
+    Rect::from_center(object.position, (size * object.scale).abs())
+}
+
+// Only cuboids and balls can be scaled, balls staying round.
+fn scale_shape(shape: &SharedShape, factor: Vec2) -> Option<SharedShape> {
+    if let Some(cuboid) = shape.as_cuboid() {
+        let half_extents = cuboid.half_extents * factor;
+        return Some(SharedShape::cuboid(half_extents.x, half_extents.y));
+    }
+    let ball = shape.as_ball()?;
+    Some(SharedShape::ball(ball.radius * factor.max_element()))
+}
+
+// At the bottom right corner of the bounds on screen.
+fn handle_rect(graphics_ppl: &GraphicsPipeline, bounds: Rect) -> PixelRect {
+    let camera = &graphics_ppl.camera;
+    let a = camera.get_screen_coordinate(graphics_ppl, &bounds.min());
+    let b = camera.get_screen_coordinate(graphics_ppl, &bounds.max());
+    let corner = a.max(b);
+    let half = HANDLE_SIZE as i32 / 2;
+    PixelRect::new(corner.x - half, corner.y - half, HANDLE_SIZE, HANDLE_SIZE)
+}
+
+fn slot_rect(graphics_ppl: &GraphicsPipeline, index: usize) -> PixelRect {
+    let (_, height) = graphics_ppl.viewport_size();
+    PixelRect::new(
+        (SLOT_MARGIN + index as u32 * (SLOT_SIZE + SLOT_MARGIN)) as i32,
+        height as i32 - (SLOT_SIZE + SLOT_MARGIN) as i32,
+        SLOT_SIZE,
+        SLOT_SIZE,
+    )
+}
+
+fn tile_size(graphics_ppl: &GraphicsPipeline, tilemap: &Tilemap) -> Vec2 {
+    let pixel_per_unit = graphics_ppl.options.pixel_per_unit as f64;
+    Vec2::new(
+        tilemap.tileset.tile_width as f64,
+        tilemap.tileset.tile_height as f64,
+    ) / pixel_per_unit
+}
+
+fn cell_at(
+    graphics_ppl: &GraphicsPipeline,
+    tilemap: &Tilemap,
+    position: Vec2,
+    world: Vec2,
+) -> Option<(u32, u32)> {
+    let size = tile_size(graphics_ppl, tilemap);
+    let offset = world - position;
+    let x = (offset.x / size.x).floor();
+    let y = (offset.dot(graphics_ppl.options.down()) / size.y).floor();
+    let (width, height) = tilemap.size();
+    (x >= 0. && y >= 0. && x < width as f64 && y < height as f64).then_some((x as u32, y as u32))
+}
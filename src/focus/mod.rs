@@ -0,0 +1,182 @@
+use std::time::Instant;
+
+use sdl2::event::{Event, WindowEvent};
+
+// What the engine does while none of its windows has focus or the main one is minimized.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct FocusOptions {
+    // Pauses the game, see Focus::delta.
+    pub pause: bool,
+    // Sets Focus::volume to 0.
+    pub mute: bool,
+    // Longest frame in seconds, longer ones being shortened to it, e.g. the first one after the
+    // window was dragged or the process suspended.
+    pub max_delta: f64,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum FocusEvent {
+    Lost,
+    Gained,
+    Minimized,
+    Restored,
+    // Whether by Focus::pause or because of the focus.
+    Paused,
+    Resumed,
+}
+
+// Focus of the game's windows and whether it's paused, updated by Engine::update.
+pub struct Focus {
+    pub options: FocusOptions,
+    focused: bool,
+    minimized: bool,
+    // Paused by the game, the focus aside.
+    paused: bool,
+    events: Vec<FocusEvent>,
+    last_frame: Option<Instant>,
+    delta: f64,
+}
+
+impl Default for FocusOptions {
+    fn default() -> Self {
+        FocusOptions {
+            pause: true,
+            mute: true,
+            max_delta: 0.25,
+        }
+    }
+}
+
+impl Focus {
+    pub fn new(options: FocusOptions) -> Self {
+        Focus {
+            options,
+            focused: true,
+            minimized: false,
+            paused: false,
+            events: Vec::new(),
+            last_frame: None,
+            delta: 0.,
+        }
+    }
+
+    pub fn has_focus(&self) -> bool {
+        self.focused
+    }
+
+    pub fn is_minimized(&self) -> bool {
+        self.minimized
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused || (self.options.pause && self.is_away())
+    }
+
+    pub fn pause(&mut self) {
+        self.set_paused(true);
+    }
+
+    pub fn resume(&mut self) {
+        self.set_paused(false);
+    }
+
+    pub fn is_muted(&self) -> bool {
+        self.options.mute && self.is_away()
+    }
+
+    // Factor of the game's volumes, the engine not playing audio itself.
+    pub fn volume(&self) -> f64 {
+        if self.is_muted() {
+            0.
+        } else {
+            1.
+        }
+    }
+
+    // Events since the last Engine::update, in order.
+    pub fn events(&self) -> &[FocusEvent] {
+        &self.events
+    }
+
+    // Seconds between the last two calls to Engine::update, 0 while paused, at most
+    // options.max_delta. Engine::run_schedule applies the same to the dt it's given.
+    pub fn delta(&self) -> f64 {
+        self.clamp(self.delta)
+    }
+
+    pub(crate) fn clamp(&self, dt: f64) -> f64 {
+        if self.is_paused() {
+            0.
+        } else {
+            dt.clamp(0., self.options.max_delta)
+        }
+    }
+
+    // main_window is the id of the window minimized with the game, the focus being the one of
+    // any of the engine's windows.
+    pub(crate) fn update(&mut self, events: &[Event], main_window: u32) {
+        self.events.clear();
+        let now = Instant::now();
+        self.delta = self
+            .last_frame
+            .map_or(0., |last| now.duration_since(last).as_secs_f64());
+        self.last_frame = Some(now);
+
+        let was_paused = self.is_paused();
+        let mut focused = self.focused;
+        for event in events {
+            let Event::Window {
+                window_id,
+                win_event,
+                ..
+            } = event
+            else {
+                continue;
+            };
+            match win_event {
+                // Focus moving between the engine's windows is lost then gained in the same frame
+                WindowEvent::FocusGained => focused = true,
+                WindowEvent::FocusLost => focused = false,
+                WindowEvent::Minimized if *window_id == main_window && !self.minimized => {
+                    self.minimized = true;
+                    self.events.push(FocusEvent::Minimized);
+                }
+                WindowEvent::Restored | WindowEvent::Maximized
+                    if *window_id == main_window && self.minimized =>
+                {
+                    self.minimized = false;
+                    self.events.push(FocusEvent::Restored);
+                }
+                _ => {}
+            }
+        }
+
+        if focused != self.focused {
+            self.focused = focused;
+            self.events.push(if focused {
+                FocusEvent::Gained
+            } else {
+                FocusEvent::Lost
+            });
+        }
+        self.push_pause_event(was_paused);
+    }
+
+    fn is_away(&self) -> bool {
+        !self.focused || self.minimized
+    }
+
+    fn set_paused(&mut self, paused: bool) {
+        let was_paused = self.is_paused();
+        self.paused = paused;
+        self.push_pause_event(was_paused);
+    }
+
+    fn push_pause_event(&mut self, was_paused: bool) {
+        match (was_paused, self.is_paused()) {
+            (false, true) => self.events.push(FocusEvent::Paused),
+            (true, false) => self.events.push(FocusEvent::Resumed),
+            _ => {}
+        }
+    }
+}
@@ -0,0 +1,113 @@
+use sdl2::{
+    rect::{FPoint, Rect},
+    render::Vertex,
+};
+
+use super::{BlendMode, Color, DrawParams, GraphicsPipeline, TextureId};
+use crate::{math::Vec2Ext, Vec2};
+
+// Consecutive textured quads sharing a texture and a blend mode, submitted in a single call.
+#[derive(Default)]
+pub(super) struct SpriteBatch {
+    texture: Option<TextureId>,
+    blend_mode: BlendMode,
+    vertices: Vec<Vertex>,
+    indices: Vec<u32>,
+}
+
+impl GraphicsPipeline {
+    // Queues a textured quad, the batch gets flushed once anything else has to be drawn.
+    pub(super) fn push_quad(
+        &mut self,
+        texture: TextureId,
+        src: Option<Rect>,
+        dest: Rect,
+        params: &DrawParams,
+    ) {
+        self.use_material(params.material);
+        if self.batch.texture != Some(texture) || self.batch.blend_mode != params.blend_mode {
+            self.flush();
+            self.batch.texture = Some(texture);
+            self.batch.blend_mode = params.blend_mode;
+        }
+
+        let (width, height) = self.texture_size(texture);
+        let src = src.unwrap_or(Rect::new(0, 0, width, height));
+        let (mut left, mut right) = (
+            src.left() as f32 / width as f32,
+            src.right() as f32 / width as f32,
+        );
+        let (mut top, mut bottom) = (
+            src.top() as f32 / height as f32,
+            src.bottom() as f32 / height as f32,
+        );
+        if params.scale.x < 0. {
+            std::mem::swap(&mut left, &mut right);
+        }
+        if params.scale.y < 0. {
+            std::mem::swap(&mut top, &mut bottom);
+        }
+
+        let tex_coords = [
+            FPoint::new(left, top),
+            FPoint::new(right, top),
+            FPoint::new(right, bottom),
+            FPoint::new(left, bottom),
+        ];
+        let color = Color::RGBA(params.tint.r, params.tint.g, params.tint.b, params.alpha);
+
+        let first = self.batch.vertices.len() as u32;
+        self.batch
+            .vertices
+            .extend(quad_corners(dest, params).into_iter().zip(tex_coords).map(
+                |(position, tex_coord)| Vertex {
+                    position,
+                    color,
+                    tex_coord,
+                },
+            ));
+        self.batch
+            .indices
+            .extend([0, 1, 2, 0, 2, 3].map(|i| first + i));
+    }
+
+    // Submits the queued quads, has to be called before anything else touches the renderer.
+    pub(super) fn flush(&mut self) {
+        let Some(texture) = self.batch.texture.take() else {
+            return;
+        };
+
+        self.renderer
+            .draw_geometry(
+                Some(texture),
+                &self.batch.vertices,
+                &self.batch.indices,
+                self.batch.blend_mode,
+            )
+            .unwrap();
+
+        self.batch.vertices.clear();
+        self.batch.indices.clear();
+        self.frame_stats.batches += 1;
+    }
+}
+
+// Corners of the rect once scaled and rotated around the pivot, clockwise from the top left.
+pub(super) fn quad_corners(rect: Rect, params: &DrawParams) -> [FPoint; 4] {
+    let (rect, center) = params.scale_rect(rect);
+    let pivot = Vec2::new(
+        (rect.x() + center.x()) as f64,
+        (rect.y() + center.y()) as f64,
+    );
+
+    [
+        (rect.left(), rect.top()),
+        (rect.right(), rect.top()),
+        (rect.right(), rect.bottom()),
+        (rect.left(), rect.bottom()),
+    ]
+    .map(|(x, y)| {
+        let corner = pivot + (Vec2::new(x as f64, y as f64) - pivot).rotated(params.rotation);
+        FPoint::new(corner.x as f32, corner.y as f32)
+    })
+}
@@ -0,0 +1,216 @@
+use std::{collections::HashMap, path::Path};
+
+use super::Color;
+
+// Helpers missing from Color. Hue is in degrees, saturation, value and lightness from 0 to 1.
+pub trait ColorExt: Sized {
+    // Accepts "#rgb", "#rrggbb" and "#rrggbbaa", the '#' being optional.
+    fn from_hex(hex: &str) -> Result<Self, String>;
+
+    fn from_hsv(hue: f64, saturation: f64, value: f64) -> Self;
+
+    fn from_hsl(hue: f64, saturation: f64, lightness: f64) -> Self;
+
+    fn to_hsv(&self) -> (f64, f64, f64);
+
+    fn to_hsl(&self) -> (f64, f64, f64);
+
+    fn to_hex(&self) -> String;
+
+    // Alpha is interpolated too.
+    fn lerp(&self, other: &Self, t: f64) -> Self;
+
+    fn with_alpha(&self, alpha: u8) -> Self;
+}
+
+// Named colors, kept in the order they were defined so that they can also be indexed.
+#[derive(Clone, PartialEq, Debug, Default)]
+pub struct Palette {
+    names: HashMap<String, usize>,
+    colors: Vec<(String, Color)>,
+}
+
+impl ColorExt for Color {
+    fn from_hex(hex: &str) -> Result<Self, String> {
+        let digits = hex.trim().trim_start_matches('#');
+        let invalid = || format!("invalid hex color \"{hex}\"");
+        if !digits.is_ascii() {
+            return Err(invalid());
+        }
+
+        let channel = |i: usize, len: usize| {
+            let value = u8::from_str_radix(&digits[i * len..(i + 1) * len], 16);
+            value.map(|v| if len == 1 { v * 17 } else { v })
+        };
+
+        let channels: Result<Vec<u8>, _> = match digits.len() {
+            3 => (0..3).map(|i| channel(i, 1)).collect(),
+            6 => (0..3).map(|i| channel(i, 2)).collect(),
+            8 => (0..4).map(|i| channel(i, 2)).collect(),
+            _ => return Err(invalid()),
+        };
+
+        match channels.map_err(|_| invalid())?.as_slice() {
+            [r, g, b] => Ok(Color::RGB(*r, *g, *b)),
+            [r, g, b, a] => Ok(Color::RGBA(*r, *g, *b, *a)),
+            _ => Err(invalid()),
+        }
+    }
+
+    fn from_hsv(hue: f64, saturation: f64, value: f64) -> Self {
+        let chroma = value * saturation;
+        let (r, g, b) = hue_to_rgb(hue, chroma);
+        let m = value - chroma;
+        Color::RGB(to_channel(r + m), to_channel(g + m), to_channel(b + m))
+    }
+
+    fn from_hsl(hue: f64, saturation: f64, lightness: f64) -> Self {
+        let chroma = (1. - (2. * lightness - 1.).abs()) * saturation;
+        let (r, g, b) = hue_to_rgb(hue, chroma);
+        let m = lightness - chroma / 2.;
+        Color::RGB(to_channel(r + m), to_channel(g + m), to_channel(b + m))
+    }
+
+    fn to_hsv(&self) -> (f64, f64, f64) {
+        let (hue, min, max) = hue(self);
+        let saturation = if max == 0. { 0. } else { (max - min) / max };
+        (hue, saturation, max)
+    }
+
+    fn to_hsl(&self) -> (f64, f64, f64) {
+        let (hue, min, max) = hue(self);
+        let lightness = (max + min) / 2.;
+        let saturation = if max == min {
+            0.
+        } else {
+            (max - min) / (1. - (2. * lightness - 1.).abs())
+        };
+        (hue, saturation, lightness)
+    }
+
+    fn to_hex(&self) -> String {
+        if self.a == u8::MAX {
+            format!("#{:02x}{:02x}{:02x}", self.r, self.g, self.b)
+        } else {
+            format!("#{:02x}{:02x}{:02x}{:02x}", self.r, self.g, self.b, self.a)
+        }
+    }
+
+    fn lerp(&self, other: &Self, t: f64) -> Self {
+        let channel = |a: u8, b: u8| (a as f64 + (b as f64 - a as f64) * t).round() as u8;
+        Color::RGBA(
+            channel(self.r, other.r),
+            channel(self.g, other.g),
+            channel(self.b, other.b),
+            channel(self.a, other.a),
+        )
+    }
+
+    fn with_alpha(&self, alpha: u8) -> Self {
+        Color::RGBA(self.r, self.g, self.b, alpha)
+    }
+}
+
+impl Palette {
+    pub fn new() -> Self {
+        Palette::default()
+    }
+
+    // One "name = #rrggbb" per line, empty lines and lines starting with "//" are skipped.
+    pub fn parse(source: &str) -> Result<Self, String> {
+        let mut palette = Palette::new();
+        for (number, line) in source.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with("//") {
+                continue;
+            }
+
+            let (name, hex) = line
+                .split_once('=')
+                .ok_or_else(|| format!("line {}: expected \"name = #rrggbb\"", number + 1))?;
+            let color = Color::from_hex(hex).map_err(|e| format!("line {}: {}", number + 1, e))?;
+            palette.insert(name.trim(), color);
+        }
+
+        Ok(palette)
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, String> {
+        let source = std::fs::read_to_string(path).map_err(|e| e.to_string())?;
+        Palette::parse(&source)
+    }
+
+    // Replaces the color if the name is already taken, keeping its index.
+    pub fn insert(&mut self, name: &str, color: Color) {
+        match self.names.get(name) {
+            Some(i) => self.colors[*i].1 = color,
+            None => {
+                self.names.insert(name.to_string(), self.colors.len());
+                self.colors.push((name.to_string(), color));
+            }
+        }
+    }
+
+    pub fn get(&self, name: &str) -> Option<Color> {
+        self.names.get(name).map(|i| self.colors[*i].1)
+    }
+
+    pub fn index(&self, index: usize) -> Option<Color> {
+        self.colors.get(index).map(|(_, c)| *c)
+    }
+
+    pub fn len(&self) -> usize {
+        self.colors.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.colors.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, Color)> {
+        self.colors.iter().map(|(n, c)| (n.as_str(), *c))
+    }
+}
+
+// RGB of the hue at the given chroma, before adding the lightness offset.
+fn hue_to_rgb(hue: f64, chroma: f64) -> (f64, f64, f64) {
+    let sector = hue.rem_euclid(360.) / 60.;
+    let x = chroma * (1. - (sector % 2. - 1.).abs());
+
+    match sector as u32 {
+        0 => (chroma, x, 0.),
+        1 => (x, chroma, 0.),
+        2 => (0., chroma, x),
+        3 => (0., x, chroma),
+        4 => (x, 0., chroma),
+        _ => (chroma, 0., x),
+    }
+}
+
+// Hue of the color, along with its min and max channels from 0 to 1.
+fn hue(color: &Color) -> (f64, f64, f64) {
+    let (r, g, b) = (
+        color.r as f64 / 255.,
+        color.g as f64 / 255.,
+        color.b as f64 / 255.,
+    );
+    let max = r.max(g).max(b);
+    let min = r.min(g).min(b);
+    let delta = max - min;
+
+    let hue = if delta == 0. {
+        0.
+    } else if max == r {
+        60. * ((g - b) / delta).rem_euclid(6.)
+    } else if max == g {
+        60. * ((b - r) / delta + 2.)
+    } else {
+        60. * ((r - g) / delta + 4.)
+    };
+
+    (hue, min, max)
+}
+
+fn to_channel(value: f64) -> u8 {
+    (value.clamp(0., 1.) * 255.).round() as u8
+}
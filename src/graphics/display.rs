@@ -0,0 +1,108 @@
+use sdl2::{
+    video::{WindowPos, WindowPos::Positioned},
+    VideoSubsystem,
+};
+
+use super::{GraphicsPipeline, PixelRect};
+
+// SDL_WINDOWPOS_CENTERED_DISPLAY without the display index.
+const CENTERED_ON_DISPLAY: i32 = 0x2fff_0000;
+
+// Monitor, its bounds being in desktop coordinates. Usable bounds leave out task bars and docks.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Display {
+    pub index: i32,
+    pub name: String,
+    pub bounds: PixelRect,
+    pub usable_bounds: PixelRect,
+    pub desktop_mode: DisplayMode,
+    // Resolutions supported in fullscreen, largest first.
+    pub modes: Vec<DisplayMode>,
+    // Diagonal dots per inch, None when the platform doesn't tell.
+    pub dpi: Option<f32>,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct DisplayMode {
+    pub width: u32,
+    pub height: u32,
+    // In Hz, 0 when unknown.
+    pub refresh_rate: u32,
+}
+
+impl From<sdl2::video::DisplayMode> for DisplayMode {
+    fn from(mode: sdl2::video::DisplayMode) -> Self {
+        DisplayMode {
+            width: mode.w.max(0) as u32,
+            height: mode.h.max(0) as u32,
+            refresh_rate: mode.refresh_rate.max(0) as u32,
+        }
+    }
+}
+
+pub fn displays(video: &VideoSubsystem) -> Result<Vec<Display>, String> {
+    (0..video.num_video_displays()?)
+        .map(|index| {
+            let mut modes = Vec::new();
+            for mode in 0..video.num_display_modes(index)? {
+                let mode = DisplayMode::from(video.display_mode(index, mode)?);
+                // Modes differing only by pixel format are listed once
+                if !modes.contains(&mode) {
+                    modes.push(mode);
+                }
+            }
+
+            Ok(Display {
+                index,
+                name: video.display_name(index)?,
+                bounds: video.display_bounds(index)?,
+                usable_bounds: video.display_usable_bounds(index)?,
+                desktop_mode: video.desktop_display_mode(index)?.into(),
+                modes,
+                dpi: video
+                    .display_dpi(index)
+                    .ok()
+                    .map(|(diagonal, _, _)| diagonal),
+            })
+        })
+        .collect()
+}
+
+// Window position centering it on the display.
+pub(crate) fn centered_on(display: i32) -> i32 {
+    CENTERED_ON_DISPLAY | display
+}
+
+impl GraphicsPipeline {
+    // Display the window is mostly on.
+    pub fn display_index(&self) -> Result<i32, String> {
+        self.renderer.window().display_index()
+    }
+
+    // Centers the window on the display, fullscreen windows then filling it.
+    pub fn move_to_display(&mut self, display: i32) -> Result<(), String> {
+        let position: WindowPos = Positioned(centered_on(display));
+        self.renderer.window_mut().set_position(position, position);
+        self.resized()
+    }
+
+    // Pixels drawn per window coordinate, above 1 on high DPI screens when the window was created
+    // with GraphicsOptions::high_dpi. Rendering is scaled by it so that everything keeps its size
+    // in window coordinates while using every pixel of the screen.
+    // Render targets are drawn at their own size, without it.
+    pub fn dpi_scale(&self) -> f64 {
+        self.dpi_scale
+    }
+
+    // The scale is the window's own, the current target being unbound meanwhile.
+    pub(super) fn update_dpi_scale(&mut self) -> Result<(), String> {
+        self.bind_target(None)?;
+        let (width, height) = self.renderer.window().size();
+        let (output_width, _) = self.renderer.output_size()?;
+        if width > 0 && height > 0 {
+            self.dpi_scale = output_width as f64 / width as f64;
+            self.renderer.set_scale(self.dpi_scale)?;
+        }
+        self.bind_target(self.render_target.or(self.frame_target))
+    }
+}
@@ -0,0 +1,120 @@
+use std::collections::BTreeMap;
+
+use super::{GraphicsPipeline, Material, MaterialId, TextureId, Uniform};
+
+// Post effects shipped with the engine, for renderers running GLSL such as GlRenderer, see
+// GraphicsPipeline::create_effect and post_effects.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Effect {
+    // Curved screen with scanlines, u_curvature and u_scanlines setting their strength.
+    Crt,
+    // Glow around the pixels brighter than u_threshold, u_radius pixels wide.
+    Bloom,
+    // Replaces each color by the one of the palette, a row of colors, at the index in its red
+    // channel, e.g. to recolor sprites drawn with indices.
+    PaletteSwap(TextureId),
+    // Red and blue shifted apart towards the edges, by up to u_offset pixels.
+    ChromaticAberration,
+}
+
+const CRT: &str = "
+uniform float u_curvature;
+uniform float u_scanlines;
+
+void main() {
+    vec2 centered = v_tex_coord * 2.0 - 1.0;
+    centered *= 1.0 + u_curvature * dot(centered.yx, centered.yx);
+    vec2 uv = centered * 0.5 + 0.5;
+    if (uv.x < 0.0 || uv.x > 1.0 || uv.y < 0.0 || uv.y > 1.0) {
+        frag_color = vec4(0.0, 0.0, 0.0, 1.0);
+        return;
+    }
+    vec4 color = texture(u_texture, uv) * v_color;
+    float scanline = 1.0 - u_scanlines * (0.5 + 0.5 * sin(uv.y * u_resolution.y * 3.14159));
+    frag_color = vec4(color.rgb * scanline, color.a);
+}
+";
+
+const BLOOM: &str = "
+uniform float u_threshold;
+uniform float u_intensity;
+uniform float u_radius;
+
+void main() {
+    vec4 color = texture(u_texture, v_tex_coord);
+    vec2 spread = u_radius / 2.0 / u_resolution;
+    vec3 glow = vec3(0.0);
+    for (int x = -2; x <= 2; x++) {
+        for (int y = -2; y <= 2; y++) {
+            vec3 near = texture(u_texture, v_tex_coord + vec2(x, y) * spread).rgb;
+            glow += max(near - u_threshold, 0.0);
+        }
+    }
+    frag_color = vec4(color.rgb + glow / 25.0 * u_intensity, color.a) * v_color;
+}
+";
+
+const PALETTE_SWAP: &str = "
+uniform sampler2D u_palette;
+
+void main() {
+    vec4 color = texture(u_texture, v_tex_coord);
+    float size = float(textureSize(u_palette, 0).x);
+    vec3 swapped = texture(u_palette, vec2((color.r * 255.0 + 0.5) / size, 0.5)).rgb;
+    frag_color = vec4(swapped, color.a) * v_color;
+}
+";
+
+const CHROMATIC_ABERRATION: &str = "
+uniform float u_offset;
+
+void main() {
+    vec2 shift = (v_tex_coord - 0.5) * 2.0 * u_offset / u_resolution;
+    vec4 color = texture(u_texture, v_tex_coord);
+    float red = texture(u_texture, v_tex_coord + shift).r;
+    float blue = texture(u_texture, v_tex_coord - shift).b;
+    frag_color = vec4(red, color.g, blue, color.a) * v_color;
+}
+";
+
+impl Effect {
+    pub fn source(&self) -> &'static str {
+        match self {
+            Effect::Crt => CRT,
+            Effect::Bloom => BLOOM,
+            Effect::PaletteSwap(_) => PALETTE_SWAP,
+            Effect::ChromaticAberration => CHROMATIC_ABERRATION,
+        }
+    }
+
+    pub fn uniforms(&self) -> BTreeMap<String, Uniform> {
+        let uniforms = match self {
+            Effect::Crt => vec![
+                ("u_curvature", Uniform::Float(0.1)),
+                ("u_scanlines", Uniform::Float(0.25)),
+            ],
+            Effect::Bloom => vec![
+                ("u_threshold", Uniform::Float(0.7)),
+                ("u_intensity", Uniform::Float(1.)),
+                ("u_radius", Uniform::Float(4.)),
+            ],
+            Effect::PaletteSwap(palette) => vec![("u_palette", Uniform::Texture(*palette))],
+            Effect::ChromaticAberration => vec![("u_offset", Uniform::Float(2.))],
+        };
+        uniforms
+            .into_iter()
+            .map(|(name, value)| (name.to_string(), value))
+            .collect()
+    }
+}
+
+impl GraphicsPipeline {
+    // Material drawing with the effect, its uniforms starting at their defaults.
+    pub fn create_effect(&mut self, effect: Effect) -> Result<MaterialId, String> {
+        let shader = self.create_shader(effect.source())?;
+        Ok(self.create_material(Material {
+            shader,
+            uniforms: effect.uniforms(),
+        }))
+    }
+}
@@ -0,0 +1,252 @@
+use super::{Color, DrawParams, GraphicsPipeline, TextureId};
+use crate::{config::Value, Point, Vec2};
+
+// Pixels of the fog's texture per cell side, the fog being interpolated between the centers of
+// the cells for its edges to be soft.
+const CELL_PIXELS: u32 = 4;
+
+// Hides the cells of a grid the player hasn't explored and dims the ones explored but out of
+// sight. The visible cells typically come from nav::field_of_view around the player.
+pub struct FogOfWar {
+    pub unexplored: Color,
+    pub explored: Color,
+    pub enabled: bool,
+    width: u32,
+    height: u32,
+    explored_cells: Vec<bool>,
+    visible_cells: Vec<bool>,
+    texture: Option<TextureId>,
+    // Whether the texture has to be drawn again.
+    changed: bool,
+}
+
+impl FogOfWar {
+    pub fn new(width: u32, height: u32) -> Self {
+        let count = (width * height) as usize;
+        FogOfWar {
+            unexplored: Color::RGB(0, 0, 0),
+            explored: Color::RGBA(0, 0, 0, 160),
+            enabled: true,
+            width,
+            height,
+            explored_cells: vec![false; count],
+            visible_cells: vec![false; count],
+            texture: None,
+            changed: true,
+        }
+    }
+
+    pub fn size(&self) -> (u32, u32) {
+        (self.width, self.height)
+    }
+
+    // Cells outside of the grid are never visible nor explored.
+    pub fn is_visible(&self, cell: Point) -> bool {
+        self.index(cell).is_some_and(|i| self.visible_cells[i])
+    }
+
+    pub fn is_explored(&self, cell: Point) -> bool {
+        self.index(cell).is_some_and(|i| self.explored_cells[i])
+    }
+
+    // Replaces the visible cells, which become explored.
+    pub fn set_visible(&mut self, cells: &[Point]) {
+        // Often the same cells as the last frame's, which don't need the fog to be drawn again
+        let count = self.visible_cells.len();
+        let previous = std::mem::replace(&mut self.visible_cells, vec![false; count]);
+        self.add_visible(cells);
+        self.changed |= self.visible_cells != previous;
+    }
+
+    // Adds to the visible cells, e.g. those of another unit of the player.
+    pub fn add_visible(&mut self, cells: &[Point]) {
+        for &cell in cells {
+            if let Some(i) = self.index(cell) {
+                self.changed |= !self.visible_cells[i] || !self.explored_cells[i];
+                self.visible_cells[i] = true;
+                self.explored_cells[i] = true;
+            }
+        }
+    }
+
+    // Explores cells without them being visible, e.g. from a map found by the player.
+    pub fn explore(&mut self, cells: &[Point]) {
+        for &cell in cells {
+            if let Some(i) = self.index(cell) {
+                self.changed |= !self.explored_cells[i];
+                self.explored_cells[i] = true;
+            }
+        }
+    }
+
+    // Hides every cell again.
+    pub fn reset(&mut self) {
+        self.explored_cells.fill(false);
+        self.visible_cells.fill(false);
+        self.changed = true;
+    }
+
+    // Explored cells as rows of hexadecimal digits, a bit per cell, to be written with saves.
+    pub fn to_value(&self) -> Value {
+        let rows = self
+            .explored_cells
+            .chunks(self.width.max(1) as usize)
+            .map(|row| {
+                let digits = row.chunks(4).map(|bits| {
+                    let digit = bits
+                        .iter()
+                        .enumerate()
+                        .fold(0, |digit, (i, bit)| digit | (*bit as u32) << (3 - i));
+                    char::from_digit(digit, 16).unwrap_or('0')
+                });
+                Value::Text(digits.collect())
+            });
+        Value::Array(rows.collect())
+    }
+
+    // Restores the cells explored from to_value, the visible ones being cleared. Fails if the
+    // value wasn't made by a fog of the same size.
+    pub fn load_value(&mut self, value: &Value) -> Result<(), String> {
+        let rows = value.as_array().ok_or("expected rows of explored cells")?;
+        if rows.len() != self.height as usize {
+            return Err(format!("expected {} rows of explored cells", self.height));
+        }
+
+        let mut explored = Vec::with_capacity(self.explored_cells.len());
+        for row in rows {
+            let digits = row.as_str().ok_or("expected rows of hexadecimal digits")?;
+            if digits.len() != self.width.div_ceil(4) as usize {
+                return Err(format!("expected rows of {} cells", self.width));
+            }
+            let mut cells = Vec::with_capacity(digits.len() * 4);
+            for digit in digits.chars() {
+                let digit = digit.to_digit(16).ok_or("expected hexadecimal digits")?;
+                cells.extend((0..4).map(|i| digit & (1 << (3 - i)) != 0));
+            }
+            explored.extend(&cells[..self.width as usize]);
+        }
+
+        self.explored_cells = explored;
+        self.visible_cells.fill(false);
+        self.changed = true;
+        Ok(())
+    }
+
+    // Has to be called once the world is drawn, before the UI, position being the world
+    // position of the top left corner of the grid and cell_size the size of its cells in world
+    // units, like Tilemap::draw.
+    pub fn draw(
+        &mut self,
+        graphics_ppl: &mut GraphicsPipeline,
+        position: &Vec2,
+        cell_size: &Vec2,
+    ) -> Result<(), String> {
+        if !self.enabled || self.width == 0 || self.height == 0 {
+            return Ok(());
+        }
+
+        let texture = match self.texture {
+            Some(texture) if !self.changed => texture,
+            _ => self.update_texture(graphics_ppl)?,
+        };
+        let size = Vec2::new(
+            self.width as f64 * cell_size.x,
+            self.height as f64 * cell_size.y,
+        );
+        let center =
+            position + Vec2::new(size.x / 2., 0.) + graphics_ppl.options.down() * (size.y / 2.);
+        graphics_ppl.draw_sprite(texture, None, &center, &size, &DrawParams::default());
+        Ok(())
+    }
+
+    fn update_texture(&mut self, graphics_ppl: &mut GraphicsPipeline) -> Result<TextureId, String> {
+        let (width, height) = (self.width * CELL_PIXELS, self.height * CELL_PIXELS);
+        let cells: Vec<[f64; 4]> = (0..self.explored_cells.len())
+            .map(|i| {
+                let color = if self.visible_cells[i] {
+                    Color::RGBA(self.explored.r, self.explored.g, self.explored.b, 0)
+                } else if self.explored_cells[i] {
+                    self.explored
+                } else {
+                    self.unexplored
+                };
+                [color.r, color.g, color.b, color.a].map(|c| c as f64)
+            })
+            .collect();
+        let cell = |x: i64, y: i64| {
+            let x = x.clamp(0, self.width as i64 - 1) as u32;
+            let y = y.clamp(0, self.height as i64 - 1) as u32;
+            cells[(y * self.width + x) as usize]
+        };
+
+        // Bilinear interpolation between the centers of the cells around each pixel
+        let mut pixels = Vec::with_capacity((width * height * 4) as usize);
+        for py in 0..height {
+            let v = (py as f64 + 0.5) / CELL_PIXELS as f64 - 0.5;
+            let (y, ty) = (v.floor() as i64, v - v.floor());
+            for px in 0..width {
+                let u = (px as f64 + 0.5) / CELL_PIXELS as f64 - 0.5;
+                let (x, tx) = (u.floor() as i64, u - u.floor());
+                let (a, b) = (cell(x, y), cell(x + 1, y));
+                let (c, d) = (cell(x, y + 1), cell(x + 1, y + 1));
+                for channel in 0..4 {
+                    let top = a[channel] + (b[channel] - a[channel]) * tx;
+                    let bottom = c[channel] + (d[channel] - c[channel]) * tx;
+                    pixels.push((top + (bottom - top) * ty).round() as u8);
+                }
+            }
+        }
+
+        let texture = match self.texture {
+            Some(texture) => {
+                graphics_ppl.update_texture(texture, &pixels)?;
+                texture
+            }
+            None => *self
+                .texture
+                .insert(graphics_ppl.create_texture(width, height, &pixels)?),
+        };
+        self.changed = false;
+        Ok(texture)
+    }
+
+    fn index(&self, cell: Point) -> Option<usize> {
+        let inside = cell.x >= 0
+            && cell.y >= 0
+            && (cell.x as u32) < self.width
+            && (cell.y as u32) < self.height;
+        inside.then(|| (cell.y as u32 * self.width + cell.x as u32) as usize)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cells_outside_the_fog_are_ignored() {
+        let mut fog = FogOfWar::new(3, 2);
+        fog.set_visible(&[Point::new(-1, 0), Point::new(0, -1), Point::new(1, 1)]);
+        fog.explore(&[Point::new(3, 0), Point::new(2, 0)]);
+        assert!(fog.is_visible(Point::new(1, 1)) && fog.is_explored(Point::new(1, 1)));
+        assert!(!fog.is_visible(Point::new(2, 0)) && fog.is_explored(Point::new(2, 0)));
+        assert!(!fog.is_explored(Point::new(-1, 0)));
+
+        fog.set_visible(&[Point::new(0, 0)]);
+        assert!(!fog.is_visible(Point::new(1, 1)) && fog.is_explored(Point::new(1, 1)));
+    }
+
+    #[test]
+    fn explored_cells_are_saved_and_loaded() {
+        let mut fog = FogOfWar::new(5, 2);
+        fog.explore(&[Point::new(0, 0), Point::new(4, 0), Point::new(2, 1)]);
+        let value = fog.to_value();
+        assert_eq!(value, Value::Array(vec!["88".into(), "20".into()]));
+
+        let mut loaded = FogOfWar::new(5, 2);
+        loaded.load_value(&value).unwrap();
+        assert_eq!(loaded.explored_cells, fog.explored_cells);
+        assert!(FogOfWar::new(4, 2).load_value(&value).is_err());
+        assert!(FogOfWar::new(5, 3).load_value(&value).is_err());
+    }
+}
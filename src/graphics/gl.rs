@@ -0,0 +1,852 @@
+use std::{
+    ffi::{c_void, CString},
+    ptr,
+};
+
+use sdl2::{
+    rect::{FPoint, Rect},
+    render::Vertex,
+    video::{GLContext, GLProfile, SwapInterval, Window},
+    VideoSubsystem,
+};
+
+use super::{BlendMode, Color, GraphicsOptions, Material, Renderer, ShaderId, TextureId, Uniform};
+
+const TEXTURE_2D: u32 = 0x0DE1;
+const RGBA: u32 = 0x1908;
+const UNSIGNED_BYTE: u32 = 0x1401;
+const UNSIGNED_INT: u32 = 0x1405;
+const FLOAT: u32 = 0x1406;
+const TEXTURE_MAG_FILTER: u32 = 0x2800;
+const TEXTURE_MIN_FILTER: u32 = 0x2801;
+const TEXTURE_WRAP_S: u32 = 0x2802;
+const TEXTURE_WRAP_T: u32 = 0x2803;
+const NEAREST: i32 = 0x2600;
+const CLAMP_TO_EDGE: i32 = 0x812F;
+const TEXTURE0: u32 = 0x84C0;
+const FRAMEBUFFER: u32 = 0x8D40;
+const COLOR_ATTACHMENT0: u32 = 0x8CE0;
+const FRAMEBUFFER_COMPLETE: u32 = 0x8CD5;
+const COLOR_BUFFER_BIT: u32 = 0x4000;
+const BLEND: u32 = 0x0BE2;
+const ZERO: u32 = 0;
+const ONE: u32 = 1;
+const SRC_ALPHA: u32 = 0x0302;
+const ONE_MINUS_SRC_ALPHA: u32 = 0x0303;
+const DST_COLOR: u32 = 0x0306;
+const ARRAY_BUFFER: u32 = 0x8892;
+const ELEMENT_ARRAY_BUFFER: u32 = 0x8893;
+const STREAM_DRAW: u32 = 0x88E0;
+const LINE_LOOP: u32 = 0x0002;
+const LINE_STRIP: u32 = 0x0003;
+const TRIANGLES: u32 = 0x0004;
+const FRAGMENT_SHADER: u32 = 0x8B30;
+const VERTEX_SHADER: u32 = 0x8B31;
+const COMPILE_STATUS: u32 = 0x8B81;
+const LINK_STATUS: u32 = 0x8B82;
+const INFO_LOG_LENGTH: u32 = 0x8B84;
+const PACK_ALIGNMENT: u32 = 0x0D05;
+const UNPACK_ALIGNMENT: u32 = 0x0CF5;
+
+#[cfg(not(target_os = "emscripten"))]
+const GLSL_VERSION: &str = "#version 330 core\n";
+#[cfg(target_os = "emscripten")]
+const GLSL_VERSION: &str = "#version 300 es\nprecision mediump float;\n";
+
+// Coordinates are in pixels of the bound target, flip turning them upside down on the window
+// whose rows start from the bottom.
+const VERTEX_SHADER_SOURCE: &str = "
+layout(location = 0) in vec2 a_position;
+layout(location = 1) in vec2 a_tex_coord;
+layout(location = 2) in vec4 a_color;
+uniform vec2 u_resolution;
+uniform float u_scale;
+uniform float u_flip;
+out vec2 v_tex_coord;
+out vec4 v_color;
+
+void main() {
+    vec2 position = a_position * u_scale / u_resolution * 2.0 - 1.0;
+    gl_Position = vec4(position.x, position.y * u_flip, 0.0, 1.0);
+    v_tex_coord = a_tex_coord;
+    v_color = a_color;
+}
+";
+
+// Declarations prepended to every fragment shader, see GlRenderer.
+const FRAGMENT_PRELUDE: &str = "
+in vec2 v_tex_coord;
+in vec4 v_color;
+uniform sampler2D u_texture;
+uniform vec2 u_resolution;
+out vec4 frag_color;
+";
+
+const DEFAULT_FRAGMENT_SHADER: &str = "
+void main() {
+    frag_color = texture(u_texture, v_tex_coord) * v_color;
+}
+";
+
+// Declares the GL functions used, loaded from the context when the renderer is created.
+macro_rules! gl_functions {
+    ($($name:ident: fn($($arg:ty),*) $(-> $ret:ty)?;)*) => {
+        #[allow(non_snake_case)]
+        struct Gl {
+            $($name: unsafe extern "system" fn($($arg),*) $(-> $ret)?,)*
+        }
+
+        impl Gl {
+            fn load(video: &VideoSubsystem) -> Result<Self, String> {
+                Ok(Gl {
+                    $($name: {
+                        let address = video.gl_get_proc_address(stringify!($name));
+                        if address.is_null() {
+                            return Err(format!("missing {}", stringify!($name)));
+                        }
+                        // The address is the function's, which has this signature in GL 3.3
+                        unsafe {
+                            std::mem::transmute::<
+                                *const (),
+                                unsafe extern "system" fn($($arg),*) $(-> $ret)?,
+                            >(address)
+                        }
+                    },)*
+                })
+            }
+        }
+    };
+}
+
+gl_functions! {
+    glViewport: fn(i32, i32, i32, i32);
+    glClearColor: fn(f32, f32, f32, f32);
+    glClear: fn(u32);
+    glEnable: fn(u32);
+    glDisable: fn(u32);
+    glBlendFuncSeparate: fn(u32, u32, u32, u32);
+    glPixelStorei: fn(u32, i32);
+    glReadPixels: fn(i32, i32, i32, i32, u32, u32, *mut c_void);
+    glGenTextures: fn(i32, *mut u32);
+    glDeleteTextures: fn(i32, *const u32);
+    glBindTexture: fn(u32, u32);
+    glActiveTexture: fn(u32);
+    glTexImage2D: fn(u32, i32, i32, i32, i32, i32, u32, u32, *const c_void);
+    glTexSubImage2D: fn(u32, i32, i32, i32, i32, i32, u32, u32, *const c_void);
+    glTexParameteri: fn(u32, u32, i32);
+    glGenFramebuffers: fn(i32, *mut u32);
+    glDeleteFramebuffers: fn(i32, *const u32);
+    glBindFramebuffer: fn(u32, u32);
+    glFramebufferTexture2D: fn(u32, u32, u32, u32, i32);
+    glCheckFramebufferStatus: fn(u32) -> u32;
+    glGenVertexArrays: fn(i32, *mut u32);
+    glBindVertexArray: fn(u32);
+    glGenBuffers: fn(i32, *mut u32);
+    glBindBuffer: fn(u32, u32);
+    glBufferData: fn(u32, isize, *const c_void, u32);
+    glVertexAttribPointer: fn(u32, i32, u32, u8, i32, *const c_void);
+    glEnableVertexAttribArray: fn(u32);
+    glDrawArrays: fn(u32, i32, i32);
+    glDrawElements: fn(u32, i32, u32, *const c_void);
+    glCreateShader: fn(u32) -> u32;
+    glShaderSource: fn(u32, i32, *const *const i8, *const i32);
+    glCompileShader: fn(u32);
+    glGetShaderiv: fn(u32, u32, *mut i32);
+    glGetShaderInfoLog: fn(u32, i32, *mut i32, *mut i8);
+    glDeleteShader: fn(u32);
+    glCreateProgram: fn() -> u32;
+    glAttachShader: fn(u32, u32);
+    glLinkProgram: fn(u32);
+    glGetProgramiv: fn(u32, u32, *mut i32);
+    glGetProgramInfoLog: fn(u32, i32, *mut i32, *mut i8);
+    glDeleteProgram: fn(u32);
+    glUseProgram: fn(u32);
+    glGetUniformLocation: fn(u32, *const i8) -> i32;
+    glUniform1i: fn(i32, i32);
+    glUniform1f: fn(i32, f32);
+    glUniform2f: fn(i32, f32, f32);
+    glUniform4f: fn(i32, f32, f32, f32, f32);
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct GlVertex {
+    position: [f32; 2],
+    tex_coord: [f32; 2],
+    color: [u8; 4],
+}
+
+struct GlTexture {
+    id: u32,
+    // Render targets only.
+    framebuffer: Option<u32>,
+    width: u32,
+    height: u32,
+}
+
+struct Program {
+    id: u32,
+    resolution: i32,
+    scale: i32,
+    flip: i32,
+    texture: i32,
+}
+
+// OpenGL 3.3 backend, WebGL 2 on the web, drawing every call through a shader so that
+// materials can replace it. Windows are created with OpenGL support when the opengl feature is
+// enabled.
+pub struct GlRenderer {
+    // Dropped after the context's objects, which it owns.
+    gl: Gl,
+    window: Window,
+    context: GLContext,
+    // None once destroyed.
+    textures: Vec<Option<GlTexture>>,
+    // Bound when drawing without texture.
+    white: u32,
+    default_program: Program,
+    shaders: Vec<Program>,
+    // Program of the bound material.
+    material: Option<usize>,
+    vertex_array: u32,
+    vertex_buffer: u32,
+    index_buffer: u32,
+    target: Option<TextureId>,
+    scale: f32,
+}
+
+impl GlRenderer {
+    // See CreateRenderer.
+    pub fn create(window: Window, options: &GraphicsOptions) -> Result<Box<dyn Renderer>, String> {
+        let video = window.subsystem().clone();
+        let attributes = video.gl_attr();
+        if cfg!(target_os = "emscripten") {
+            attributes.set_context_profile(GLProfile::GLES);
+            attributes.set_context_version(3, 0);
+        } else {
+            attributes.set_context_profile(GLProfile::Core);
+            attributes.set_context_version(3, 3);
+        }
+
+        let context = window
+            .gl_create_context()
+            .map_err(|e| format!("renderer: {e}"))?;
+        window
+            .gl_make_current(&context)
+            .map_err(|e| format!("renderer: {e}"))?;
+        let gl = Gl::load(&video).map_err(|e| format!("renderer: {e}"))?;
+
+        let default_program = compile_program(&gl, DEFAULT_FRAGMENT_SHADER)?;
+        let mut renderer = GlRenderer {
+            gl,
+            window,
+            context,
+            textures: Vec::new(),
+            white: 0,
+            default_program,
+            shaders: Vec::new(),
+            material: None,
+            vertex_array: 0,
+            vertex_buffer: 0,
+            index_buffer: 0,
+            target: None,
+            scale: 1.,
+        };
+        renderer.init_buffers();
+        renderer.white = renderer.upload_texture(1, 1, &[u8::MAX; 4]);
+        renderer.set_vsync(options.vsync)?;
+        Ok(Box::new(renderer))
+    }
+
+    fn init_buffers(&mut self) {
+        let gl = &self.gl;
+        let stride = std::mem::size_of::<GlVertex>() as i32;
+        unsafe {
+            (gl.glGenVertexArrays)(1, &mut self.vertex_array);
+            (gl.glBindVertexArray)(self.vertex_array);
+            (gl.glGenBuffers)(1, &mut self.vertex_buffer);
+            (gl.glGenBuffers)(1, &mut self.index_buffer);
+            (gl.glBindBuffer)(ARRAY_BUFFER, self.vertex_buffer);
+            (gl.glBindBuffer)(ELEMENT_ARRAY_BUFFER, self.index_buffer);
+            (gl.glVertexAttribPointer)(0, 2, FLOAT, 0, stride, ptr::null());
+            (gl.glVertexAttribPointer)(1, 2, FLOAT, 0, stride, 8 as *const c_void);
+            (gl.glVertexAttribPointer)(2, 4, UNSIGNED_BYTE, 1, stride, 16 as *const c_void);
+            for attribute in 0..3 {
+                (gl.glEnableVertexAttribArray)(attribute);
+            }
+        }
+    }
+
+    // Operations apply to the current context, which differs with several windows.
+    fn make_current(&self) {
+        if let Err(e) = self.window.gl_make_current(&self.context) {
+            log::warn!("failed to bind the OpenGL context: {e}");
+        }
+    }
+
+    fn upload_texture(&mut self, width: u32, height: u32, pixels: &[u8]) -> u32 {
+        let gl = &self.gl;
+        let mut id = 0;
+        let data = if pixels.is_empty() {
+            ptr::null()
+        } else {
+            pixels.as_ptr() as *const c_void
+        };
+        unsafe {
+            (gl.glGenTextures)(1, &mut id);
+            (gl.glActiveTexture)(TEXTURE0);
+            (gl.glBindTexture)(TEXTURE_2D, id);
+            (gl.glPixelStorei)(UNPACK_ALIGNMENT, 1);
+            (gl.glTexImage2D)(
+                TEXTURE_2D,
+                0,
+                RGBA as i32,
+                width as i32,
+                height as i32,
+                0,
+                RGBA,
+                UNSIGNED_BYTE,
+                data,
+            );
+            // Nearest like SDL's renderer, for pixel art to stay sharp
+            (gl.glTexParameteri)(TEXTURE_2D, TEXTURE_MIN_FILTER, NEAREST);
+            (gl.glTexParameteri)(TEXTURE_2D, TEXTURE_MAG_FILTER, NEAREST);
+            (gl.glTexParameteri)(TEXTURE_2D, TEXTURE_WRAP_S, CLAMP_TO_EDGE);
+            (gl.glTexParameteri)(TEXTURE_2D, TEXTURE_WRAP_T, CLAMP_TO_EDGE);
+        }
+        id
+    }
+
+    fn add(&mut self, texture: GlTexture) -> TextureId {
+        self.textures.push(Some(texture));
+        TextureId::new(self.textures.len() - 1)
+    }
+
+    fn texture(&self, texture: TextureId) -> Result<&GlTexture, String> {
+        self.textures
+            .get(texture.index())
+            .and_then(Option::as_ref)
+            .ok_or_else(|| "the texture was destroyed".to_string())
+    }
+
+    fn set_blend_mode(&self, blend_mode: BlendMode) {
+        let gl = &self.gl;
+        // Same equations as SDL's renderer
+        let factors = match blend_mode {
+            BlendMode::None => None,
+            BlendMode::Alpha => Some((SRC_ALPHA, ONE_MINUS_SRC_ALPHA, ONE, ONE_MINUS_SRC_ALPHA)),
+            BlendMode::Additive => Some((SRC_ALPHA, ONE, ZERO, ONE)),
+            BlendMode::Multiply => Some((DST_COLOR, ZERO, ZERO, ONE)),
+        };
+        unsafe {
+            match factors {
+                Some((src, dst, src_alpha, dst_alpha)) => {
+                    (gl.glEnable)(BLEND);
+                    (gl.glBlendFuncSeparate)(src, dst, src_alpha, dst_alpha);
+                }
+                None => (gl.glDisable)(BLEND),
+            }
+        }
+    }
+
+    fn target_size(&self) -> (u32, u32) {
+        match self.target {
+            Some(target) => self.texture_size(target),
+            None => self.window.drawable_size(),
+        }
+    }
+
+    // Binds the program, buffers and texture of a draw.
+    fn prepare(&self, texture: u32, vertices: &[GlVertex], indices: &[u32]) {
+        let gl = &self.gl;
+        let program = match self.material {
+            Some(shader) => &self.shaders[shader],
+            None => &self.default_program,
+        };
+        let (width, height) = self.target_size();
+        let (scale, flip) = match self.target {
+            Some(_) => (1., 1.),
+            None => (self.scale, -1.),
+        };
+        unsafe {
+            (gl.glUseProgram)(program.id);
+            (gl.glUniform2f)(program.resolution, width as f32, height as f32);
+            (gl.glUniform1f)(program.scale, scale);
+            (gl.glUniform1f)(program.flip, flip);
+            (gl.glUniform1i)(program.texture, 0);
+            (gl.glActiveTexture)(TEXTURE0);
+            (gl.glBindTexture)(TEXTURE_2D, texture);
+
+            (gl.glBindVertexArray)(self.vertex_array);
+            (gl.glBufferData)(
+                ARRAY_BUFFER,
+                std::mem::size_of_val(vertices) as isize,
+                vertices.as_ptr() as *const c_void,
+                STREAM_DRAW,
+            );
+            (gl.glBufferData)(
+                ELEMENT_ARRAY_BUFFER,
+                std::mem::size_of_val(indices) as isize,
+                indices.as_ptr() as *const c_void,
+                STREAM_DRAW,
+            );
+        }
+    }
+
+    // Lines through the centers of the pixels, untextured.
+    fn draw_points(&mut self, mode: u32, points: &[FPoint], color: Color, blend_mode: BlendMode) {
+        if points.is_empty() {
+            return;
+        }
+        self.make_current();
+        let vertices: Vec<GlVertex> = points
+            .iter()
+            .map(|p| GlVertex {
+                position: [p.x + 0.5, p.y + 0.5],
+                tex_coord: [0., 0.],
+                color: [color.r, color.g, color.b, color.a],
+            })
+            .collect();
+        self.set_blend_mode(blend_mode);
+        self.prepare(self.white, &vertices, &[]);
+        unsafe { (self.gl.glDrawArrays)(mode, 0, vertices.len() as i32) };
+    }
+}
+
+impl Renderer for GlRenderer {
+    fn window(&self) -> &Window {
+        &self.window
+    }
+
+    fn window_mut(&mut self) -> &mut Window {
+        &mut self.window
+    }
+
+    fn output_size(&self) -> Result<(u32, u32), String> {
+        Ok(self.window.drawable_size())
+    }
+
+    fn set_scale(&mut self, scale: f64) -> Result<(), String> {
+        self.scale = scale as f32;
+        Ok(())
+    }
+
+    fn set_vsync(&mut self, vsync: bool) -> Result<(), String> {
+        self.make_current();
+        let interval = if vsync {
+            SwapInterval::VSync
+        } else {
+            SwapInterval::Immediate
+        };
+        self.window.subsystem().gl_set_swap_interval(interval)
+    }
+
+    fn create_texture(
+        &mut self,
+        width: u32,
+        height: u32,
+        pixels: &[u8],
+    ) -> Result<TextureId, String> {
+        if pixels.len() != (width * height * 4) as usize {
+            return Err(format!("expected {} bytes of pixels", width * height * 4));
+        }
+        self.make_current();
+        let id = self.upload_texture(width, height, pixels);
+        Ok(self.add(GlTexture {
+            id,
+            framebuffer: None,
+            width,
+            height,
+        }))
+    }
+
+    fn update_texture(&mut self, texture: TextureId, pixels: &[u8]) -> Result<(), String> {
+        let &GlTexture {
+            id, width, height, ..
+        } = self.texture(texture)?;
+        if pixels.len() != (width * height * 4) as usize {
+            return Err(format!("expected {} bytes of pixels", width * height * 4));
+        }
+        self.make_current();
+        let gl = &self.gl;
+        unsafe {
+            (gl.glActiveTexture)(TEXTURE0);
+            (gl.glBindTexture)(TEXTURE_2D, id);
+            (gl.glPixelStorei)(UNPACK_ALIGNMENT, 1);
+            (gl.glTexSubImage2D)(
+                TEXTURE_2D,
+                0,
+                0,
+                0,
+                width as i32,
+                height as i32,
+                RGBA,
+                UNSIGNED_BYTE,
+                pixels.as_ptr() as *const c_void,
+            );
+        }
+        Ok(())
+    }
+
+    fn create_render_target(&mut self, width: u32, height: u32) -> Result<TextureId, String> {
+        self.make_current();
+        let id = self.upload_texture(width, height, &[]);
+        let gl = &self.gl;
+        let mut framebuffer = 0;
+        let status = unsafe {
+            (gl.glGenFramebuffers)(1, &mut framebuffer);
+            (gl.glBindFramebuffer)(FRAMEBUFFER, framebuffer);
+            (gl.glFramebufferTexture2D)(FRAMEBUFFER, COLOR_ATTACHMENT0, TEXTURE_2D, id, 0);
+            let status = (gl.glCheckFramebufferStatus)(FRAMEBUFFER);
+            let bound = self
+                .target
+                .and_then(|t| self.texture(t).ok()?.framebuffer)
+                .unwrap_or(0);
+            (gl.glBindFramebuffer)(FRAMEBUFFER, bound);
+            status
+        };
+        if status != FRAMEBUFFER_COMPLETE {
+            unsafe {
+                (gl.glDeleteFramebuffers)(1, &framebuffer);
+                (gl.glDeleteTextures)(1, &id);
+            }
+            return Err(format!("incomplete framebuffer {status:#x}"));
+        }
+
+        Ok(self.add(GlTexture {
+            id,
+            framebuffer: Some(framebuffer),
+            width,
+            height,
+        }))
+    }
+
+    fn destroy_texture(&mut self, texture: TextureId) {
+        if self.target == Some(texture) {
+            // Can't fail with the window
+            let _ = self.set_target(None);
+        }
+        if let Some(texture) = self
+            .textures
+            .get_mut(texture.index())
+            .and_then(Option::take)
+        {
+            self.make_current();
+            delete_texture(&self.gl, &texture);
+        }
+    }
+
+    fn texture_size(&self, texture: TextureId) -> (u32, u32) {
+        self.texture(texture)
+            .map_or((0, 0), |texture| (texture.width, texture.height))
+    }
+
+    fn set_target(&mut self, target: Option<TextureId>) -> Result<(), String> {
+        let framebuffer = match target {
+            Some(t) => self
+                .texture(t)?
+                .framebuffer
+                .ok_or("the texture isn't a render target")?,
+            None => 0,
+        };
+        self.make_current();
+        self.target = target;
+        let (width, height) = self.target_size();
+        unsafe {
+            (self.gl.glBindFramebuffer)(FRAMEBUFFER, framebuffer);
+            (self.gl.glViewport)(0, 0, width as i32, height as i32);
+        }
+        Ok(())
+    }
+
+    fn clear(&mut self, color: Color) {
+        self.make_current();
+        let [r, g, b, a] = [color.r, color.g, color.b, color.a].map(|c| c as f32 / 255.);
+        unsafe {
+            (self.gl.glClearColor)(r, g, b, a);
+            (self.gl.glClear)(COLOR_BUFFER_BIT);
+        }
+    }
+
+    fn fill_rect(
+        &mut self,
+        rect: Option<Rect>,
+        color: Color,
+        blend_mode: BlendMode,
+    ) -> Result<(), String> {
+        let rect = match rect {
+            Some(rect) => rect,
+            None => {
+                let (width, height) = self.target_size();
+                Rect::new(0, 0, width, height)
+            }
+        };
+        let (left, top) = (rect.left() as f32, rect.top() as f32);
+        let (right, bottom) = (rect.right() as f32, rect.bottom() as f32);
+        let corner = |x: f32, y: f32| Vertex {
+            position: FPoint::new(x, y),
+            color,
+            tex_coord: FPoint::new(0., 0.),
+        };
+        let vertices = [
+            corner(left, top),
+            corner(right, top),
+            corner(right, bottom),
+            corner(left, bottom),
+        ];
+        self.draw_geometry(None, &vertices, &[0, 1, 2, 0, 2, 3], blend_mode)
+    }
+
+    fn draw_rect(&mut self, rect: Rect, color: Color, blend_mode: BlendMode) -> Result<(), String> {
+        let (left, top) = (rect.left() as f32, rect.top() as f32);
+        let (right, bottom) = (rect.right() as f32 - 1., rect.bottom() as f32 - 1.);
+        let corners = [
+            FPoint::new(left, top),
+            FPoint::new(right, top),
+            FPoint::new(right, bottom),
+            FPoint::new(left, bottom),
+        ];
+        self.draw_points(LINE_LOOP, &corners, color, blend_mode);
+        Ok(())
+    }
+
+    fn draw_lines(
+        &mut self,
+        points: &[FPoint],
+        color: Color,
+        blend_mode: BlendMode,
+    ) -> Result<(), String> {
+        self.draw_points(LINE_STRIP, points, color, blend_mode);
+        Ok(())
+    }
+
+    fn draw_geometry(
+        &mut self,
+        texture: Option<TextureId>,
+        vertices: &[Vertex],
+        indices: &[u32],
+        blend_mode: BlendMode,
+    ) -> Result<(), String> {
+        if indices.is_empty() {
+            return Ok(());
+        }
+        self.make_current();
+        // Texture coordinates are normalized like SDL's, rows starting from the top in both
+        // textures and render targets
+        let vertices: Vec<GlVertex> = vertices
+            .iter()
+            .map(|v| GlVertex {
+                position: [v.position.x, v.position.y],
+                tex_coord: [v.tex_coord.x, v.tex_coord.y],
+                color: [v.color.r, v.color.g, v.color.b, v.color.a],
+            })
+            .collect();
+        let texture = match texture.map(|t| self.texture(t)) {
+            Some(Ok(texture)) => texture.id,
+            Some(Err(_)) => {
+                log::warn!("skipped a draw with a destroyed texture");
+                return Ok(());
+            }
+            None => self.white,
+        };
+        self.set_blend_mode(blend_mode);
+        self.prepare(texture, &vertices, indices);
+        unsafe {
+            (self.gl.glDrawElements)(TRIANGLES, indices.len() as i32, UNSIGNED_INT, ptr::null())
+        };
+        Ok(())
+    }
+
+    fn present(&mut self) {
+        self.make_current();
+        self.window.gl_swap_window();
+    }
+
+    fn read_pixels(&mut self) -> Result<(u32, u32, Vec<u8>), String> {
+        self.make_current();
+        let (width, height) = self.window.drawable_size();
+        let row = width as usize * 4;
+        let mut pixels = vec![0; row * height as usize];
+        unsafe {
+            (self.gl.glPixelStorei)(PACK_ALIGNMENT, 1);
+            (self.gl.glReadPixels)(
+                0,
+                0,
+                width as i32,
+                height as i32,
+                RGBA,
+                UNSIGNED_BYTE,
+                pixels.as_mut_ptr() as *mut c_void,
+            );
+        }
+        // The window's rows start from the bottom
+        let flipped = pixels.chunks(row.max(1)).rev().flatten().copied().collect();
+        Ok((width, height, flipped))
+    }
+
+    // GLSL fragment shaders without the version line, which get the drawn texture as
+    // u_texture, the size of the target as u_resolution and the interpolated v_tex_coord and
+    // v_color, and write frag_color.
+    fn create_shader(&mut self, source: &str) -> Result<ShaderId, String> {
+        self.make_current();
+        let program = compile_program(&self.gl, source)?;
+        self.shaders.push(program);
+        Ok(ShaderId::new(self.shaders.len() - 1))
+    }
+
+    fn reload_shader(&mut self, shader: ShaderId, source: &str) -> Result<(), String> {
+        self.make_current();
+        let program = compile_program(&self.gl, source)?;
+        let previous = std::mem::replace(&mut self.shaders[shader.index()], program);
+        unsafe { (self.gl.glDeleteProgram)(previous.id) };
+        Ok(())
+    }
+
+    // Uniforms are set once here, textures being bound from unit 1 in the order of their names.
+    fn set_material(&mut self, material: Option<&Material>) -> Result<(), String> {
+        let Some(material) = material else {
+            self.material = None;
+            return Ok(());
+        };
+        let shader = material.shader.index();
+        let program = self.shaders.get(shader).ok_or("unknown shader")?.id;
+        self.make_current();
+        self.material = Some(shader);
+
+        let gl = &self.gl;
+        let mut unit = 1;
+        unsafe {
+            (gl.glUseProgram)(program);
+            for (name, value) in &material.uniforms {
+                let location = uniform_location(gl, program, name);
+                match value {
+                    Uniform::Float(v) => (gl.glUniform1f)(location, *v),
+                    Uniform::Vec2(v) => (gl.glUniform2f)(location, v.x as f32, v.y as f32),
+                    Uniform::Color(c) => {
+                        let [r, g, b, a] = [c.r, c.g, c.b, c.a].map(|c| c as f32 / 255.);
+                        (gl.glUniform4f)(location, r, g, b, a);
+                    }
+                    Uniform::Texture(texture) => {
+                        let id = self.texture(*texture)?.id;
+                        (gl.glActiveTexture)(TEXTURE0 + unit);
+                        (gl.glBindTexture)(TEXTURE_2D, id);
+                        (gl.glUniform1i)(location, unit as i32);
+                        unit += 1;
+                    }
+                }
+            }
+            (gl.glActiveTexture)(TEXTURE0);
+        }
+        Ok(())
+    }
+}
+
+impl Drop for GlRenderer {
+    fn drop(&mut self) {
+        self.make_current();
+        let gl = &self.gl;
+        unsafe {
+            for texture in self.textures.iter().flatten() {
+                delete_texture(gl, texture);
+            }
+            (gl.glDeleteTextures)(1, &self.white);
+            for program in self.shaders.iter().chain([&self.default_program]) {
+                (gl.glDeleteProgram)(program.id);
+            }
+        }
+    }
+}
+
+fn delete_texture(gl: &Gl, texture: &GlTexture) {
+    unsafe {
+        if let Some(framebuffer) = texture.framebuffer {
+            (gl.glDeleteFramebuffers)(1, &framebuffer);
+        }
+        (gl.glDeleteTextures)(1, &texture.id);
+    }
+}
+
+fn compile_program(gl: &Gl, fragment_source: &str) -> Result<Program, String> {
+    let vertex = compile_shader(gl, VERTEX_SHADER, &[GLSL_VERSION, VERTEX_SHADER_SOURCE])?;
+    let fragment = compile_shader(
+        gl,
+        FRAGMENT_SHADER,
+        &[GLSL_VERSION, FRAGMENT_PRELUDE, fragment_source],
+    );
+    let fragment = match fragment {
+        Ok(fragment) => fragment,
+        Err(e) => {
+            unsafe { (gl.glDeleteShader)(vertex) };
+            return Err(e);
+        }
+    };
+
+    unsafe {
+        let id = (gl.glCreateProgram)();
+        (gl.glAttachShader)(id, vertex);
+        (gl.glAttachShader)(id, fragment);
+        (gl.glLinkProgram)(id);
+        // Kept by the program until it's deleted
+        (gl.glDeleteShader)(vertex);
+        (gl.glDeleteShader)(fragment);
+
+        let mut linked = 0;
+        (gl.glGetProgramiv)(id, LINK_STATUS, &mut linked);
+        if linked == 0 {
+            let mut length = 0;
+            (gl.glGetProgramiv)(id, INFO_LOG_LENGTH, &mut length);
+            let mut log = vec![0u8; length.max(1) as usize];
+            (gl.glGetProgramInfoLog)(id, length, ptr::null_mut(), log.as_mut_ptr() as *mut i8);
+            (gl.glDeleteProgram)(id);
+            return Err(info_log(&log));
+        }
+
+        Ok(Program {
+            id,
+            resolution: uniform_location(gl, id, "u_resolution"),
+            scale: uniform_location(gl, id, "u_scale"),
+            flip: uniform_location(gl, id, "u_flip"),
+            texture: uniform_location(gl, id, "u_texture"),
+        })
+    }
+}
+
+fn compile_shader(gl: &Gl, kind: u32, sources: &[&str]) -> Result<u32, String> {
+    let pointers: Vec<*const i8> = sources.iter().map(|s| s.as_ptr() as *const i8).collect();
+    let lengths: Vec<i32> = sources.iter().map(|s| s.len() as i32).collect();
+    unsafe {
+        let id = (gl.glCreateShader)(kind);
+        (gl.glShaderSource)(
+            id,
+            sources.len() as i32,
+            pointers.as_ptr(),
+            lengths.as_ptr(),
+        );
+        (gl.glCompileShader)(id);
+
+        let mut compiled = 0;
+        (gl.glGetShaderiv)(id, COMPILE_STATUS, &mut compiled);
+        if compiled == 0 {
+            let mut length = 0;
+            (gl.glGetShaderiv)(id, INFO_LOG_LENGTH, &mut length);
+            let mut log = vec![0u8; length.max(1) as usize];
+            (gl.glGetShaderInfoLog)(id, length, ptr::null_mut(), log.as_mut_ptr() as *mut i8);
+            (gl.glDeleteShader)(id);
+            return Err(info_log(&log));
+        }
+        Ok(id)
+    }
+}
+
+// -1 for unknown names, which GL ignores.
+fn uniform_location(gl: &Gl, program: u32, name: &str) -> i32 {
+    let Ok(name) = CString::new(name) else {
+        return -1;
+    };
+    unsafe { (gl.glGetUniformLocation)(program, name.as_ptr()) }
+}
+
+fn info_log(log: &[u8]) -> String {
+    let end = log.iter().position(|b| *b == 0).unwrap_or(log.len());
+    String::from_utf8_lossy(&log[..end]).trim().to_string()
+}
@@ -0,0 +1,289 @@
+use std::time::Duration;
+
+use super::{BlendMode, Color, ColorExt, DrawParams, GraphicsPipeline};
+
+// Applied to the whole window when the frame is presented, after the post process: the frame
+// is multiplied by tint, the flash is added to it, then the filter is applied.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct ColorGrade {
+    pub tint: Color,
+    pub filter: ColorFilter,
+    flash: Option<Flash>,
+}
+
+// Color blindness corrections, shifting the colors a player can't tell apart towards ones they
+// can. SDL's renderer applies them to the frame read back, which is slow on large windows.
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
+pub enum ColorFilter {
+    #[default]
+    None,
+    Protanopia,
+    Deuteranopia,
+    Tritanopia,
+    Grayscale,
+    // Rows of the matrix multiplying the RGB channels from 0 to 1.
+    Matrix([[f32; 3]; 3]),
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+struct Flash {
+    color: Color,
+    duration: Duration,
+    elapsed: Duration,
+}
+
+// Colors keyed by position, e.g. by hour for day and night cycles.
+#[derive(Clone, PartialEq, Debug, Default)]
+pub struct Gradient {
+    keys: Vec<(f64, Color)>,
+}
+
+impl Default for ColorGrade {
+    fn default() -> Self {
+        ColorGrade {
+            tint: Color::WHITE,
+            filter: ColorFilter::None,
+            flash: None,
+        }
+    }
+}
+
+impl ColorGrade {
+    // Adds color to the frame, fading out over duration.
+    pub fn flash(&mut self, color: Color, duration: Duration) {
+        self.flash = Some(Flash {
+            color,
+            duration,
+            elapsed: Duration::ZERO,
+        });
+    }
+
+    pub fn update(&mut self, dt: Duration) {
+        if let Some(flash) = &mut self.flash {
+            flash.elapsed += dt;
+            if flash.elapsed >= flash.duration {
+                self.flash = None;
+            }
+        }
+    }
+
+    // Color currently added by the flash, black without one.
+    pub fn flash_color(&self) -> Color {
+        let Some(flash) = self.flash else {
+            return Color::BLACK;
+        };
+        let t = flash.elapsed.as_secs_f64() / flash.duration.as_secs_f64();
+        flash.color.lerp(&Color::BLACK, t.min(1.))
+    }
+}
+
+impl ColorFilter {
+    pub const NAMED: [ColorFilter; 5] = [
+        ColorFilter::None,
+        ColorFilter::Protanopia,
+        ColorFilter::Deuteranopia,
+        ColorFilter::Tritanopia,
+        ColorFilter::Grayscale,
+    ];
+
+    // None for matrices.
+    pub fn name(&self) -> Option<&'static str> {
+        Some(match self {
+            ColorFilter::None => "none",
+            ColorFilter::Protanopia => "protanopia",
+            ColorFilter::Deuteranopia => "deuteranopia",
+            ColorFilter::Tritanopia => "tritanopia",
+            ColorFilter::Grayscale => "grayscale",
+            ColorFilter::Matrix(_) => return None,
+        })
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        ColorFilter::NAMED
+            .into_iter()
+            .find(|f| f.name() == Some(name))
+    }
+
+    // None when the filter leaves colors as they are.
+    pub fn matrix(&self) -> Option<[[f32; 3]; 3]> {
+        // Daltonization: the error between the colors and how they're seen is moved to the
+        // channels that are still seen
+        let daltonize = |simulation: [[f32; 3]; 3]| {
+            const SHIFT: [[f32; 3]; 3] = [[0., 0., 0.], [0.7, 1., 0.], [0.7, 0., 1.]];
+            let mut matrix = [[0.; 3]; 3];
+            for (row, values) in matrix.iter_mut().enumerate() {
+                for (column, value) in values.iter_mut().enumerate() {
+                    let error = |k: usize| {
+                        let identity = if k == column { 1. } else { 0. };
+                        identity - simulation[k][column]
+                    };
+                    let identity = if row == column { 1. } else { 0. };
+                    *value = identity + (0..3).map(|k| SHIFT[row][k] * error(k)).sum::<f32>();
+                }
+            }
+            matrix
+        };
+
+        match self {
+            ColorFilter::None => None,
+            ColorFilter::Protanopia => Some(daltonize([
+                [0.567, 0.433, 0.],
+                [0.558, 0.442, 0.],
+                [0., 0.242, 0.758],
+            ])),
+            ColorFilter::Deuteranopia => Some(daltonize([
+                [0.625, 0.375, 0.],
+                [0.7, 0.3, 0.],
+                [0., 0.3, 0.7],
+            ])),
+            ColorFilter::Tritanopia => Some(daltonize([
+                [0.95, 0.05, 0.],
+                [0., 0.433, 0.567],
+                [0., 0.475, 0.525],
+            ])),
+            ColorFilter::Grayscale => Some([[0.299, 0.587, 0.114]; 3]),
+            ColorFilter::Matrix(matrix) => Some(*matrix),
+        }
+    }
+
+    // Alpha is kept.
+    pub fn apply(&self, color: Color) -> Color {
+        let Some(matrix) = self.matrix() else {
+            return color;
+        };
+        let [r, g, b] = transform(&matrix, [color.r, color.g, color.b]);
+        Color::RGBA(r, g, b, color.a)
+    }
+
+    // RGBA pixels, 4 bytes per pixel.
+    pub fn apply_to_pixels(&self, pixels: &mut [u8]) {
+        let Some(matrix) = self.matrix() else {
+            return;
+        };
+        for pixel in pixels.chunks_exact_mut(4) {
+            let rgb = transform(&matrix, [pixel[0], pixel[1], pixel[2]]);
+            pixel[..3].copy_from_slice(&rgb);
+        }
+    }
+}
+
+impl Gradient {
+    pub fn new(keys: &[(f64, Color)]) -> Self {
+        let mut gradient = Gradient::default();
+        for (key, color) in keys {
+            gradient.insert(*key, *color);
+        }
+        gradient
+    }
+
+    // Tints of a day, keyed by hour from 0 to 24.
+    pub fn day_night() -> Self {
+        Gradient::new(&[
+            (0., Color::RGB(60, 70, 120)),
+            (5., Color::RGB(90, 90, 140)),
+            (7., Color::RGB(255, 190, 160)),
+            (10., Color::WHITE),
+            (17., Color::WHITE),
+            (19., Color::RGB(255, 160, 110)),
+            (21., Color::RGB(100, 90, 150)),
+            (23., Color::RGB(60, 70, 120)),
+        ])
+    }
+
+    pub fn insert(&mut self, key: f64, color: Color) {
+        let index = self.keys.partition_point(|(k, _)| *k <= key);
+        self.keys.insert(index, (key, color));
+    }
+
+    // Keys outside of the gradient take the color of the closest end, white without keys.
+    pub fn sample(&self, key: f64) -> Color {
+        let index = self.keys.partition_point(|(k, _)| *k <= key);
+        match (
+            index.checked_sub(1).map(|i| self.keys[i]),
+            self.keys.get(index).copied(),
+        ) {
+            (Some((start, from)), Some((end, to))) => from.lerp(&to, (key - start) / (end - start)),
+            (Some((_, color)), None) | (None, Some((_, color))) => color,
+            (None, None) => Color::WHITE,
+        }
+    }
+
+    // Sample of a gradient repeating every period, going from its last key back to its first.
+    pub fn sample_wrapped(&self, key: f64, period: f64) -> Color {
+        let (Some(first), Some(last)) = (self.keys.first(), self.keys.last()) else {
+            return Color::WHITE;
+        };
+        let key = key.rem_euclid(period);
+        if key >= first.0 && key <= last.0 {
+            return self.sample(key);
+        }
+
+        let span = first.0 + period - last.0;
+        let offset = (key - last.0).rem_euclid(period);
+        if span <= 0. {
+            return last.1;
+        }
+        last.1.lerp(&first.1, offset / span)
+    }
+}
+
+fn transform(matrix: &[[f32; 3]; 3], rgb: [u8; 3]) -> [u8; 3] {
+    matrix.map(|row| {
+        let value: f32 = row.iter().zip(rgb).map(|(m, c)| m * c as f32).sum();
+        value.round().clamp(0., 255.) as u8
+    })
+}
+
+impl GraphicsPipeline {
+    pub(super) fn apply_color_grade(&mut self) {
+        self.use_material(None);
+        let flash = self.color_grade.flash_color();
+        // Skipped when they wouldn't change the frame
+        for (color, blend_mode, identity) in [
+            (self.color_grade.tint, BlendMode::Multiply, Color::WHITE),
+            (flash, BlendMode::Additive, Color::BLACK),
+        ] {
+            let color = Color::RGB(color.r, color.g, color.b);
+            if color == identity {
+                continue;
+            }
+
+            self.renderer.fill_rect(None, color, blend_mode).unwrap();
+        }
+
+        if let Err(e) = self.apply_color_filter() {
+            log::warn!("failed to apply the color filter: {e}");
+        }
+    }
+
+    // The frame is read back, filtered and drawn over itself from a texture kept between frames.
+    fn apply_color_filter(&mut self) -> Result<(), String> {
+        if self.color_grade.filter.matrix().is_none() {
+            return Ok(());
+        }
+
+        let (width, height, mut pixels) = self.renderer.read_pixels()?;
+        self.color_grade.filter.apply_to_pixels(&mut pixels);
+        let texture = match self.filter_texture {
+            Some(texture) if self.renderer.texture_size(texture) == (width, height) => {
+                self.renderer.update_texture(texture, &pixels)?;
+                texture
+            }
+            _ => {
+                if let Some(previous) = self.filter_texture.take() {
+                    self.renderer.destroy_texture(previous);
+                }
+                let texture = self.renderer.create_texture(width, height, &pixels)?;
+                *self.filter_texture.insert(texture)
+            }
+        };
+
+        let params = DrawParams {
+            blend_mode: BlendMode::None,
+            ..Default::default()
+        };
+        self.copy_texture(texture, None, None, &params);
+        self.flush();
+        Ok(())
+    }
+}
@@ -0,0 +1,347 @@
+use std::f64::consts::{PI, TAU};
+
+use parry2d_f64::{
+    bounding_volume::{Aabb, BoundingVolume},
+    math::Pose,
+    query::Ray,
+    shape::SharedShape,
+};
+use sdl2::{rect::FPoint, render::Vertex};
+
+use super::{BlendMode, Color, DrawParams, GraphicsPipeline, TextureId};
+use crate::Vec2;
+
+// Rays spread evenly over a light on top of the ones aimed at the occluders' corners.
+const RAYS: usize = 64;
+// Angle on each side of a corner where rays are cast, for them to reach past it.
+const CORNER_OFFSET: f64 = 1e-4;
+const GRADIENT_SIZE: u32 = 128;
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Debug)]
+pub struct LightId(usize);
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Debug)]
+pub struct OccluderId(usize);
+
+// Light fading from its color at position to nothing at radius, the intensity at distance d
+// being (1 - d / radius) ^ falloff. Cone lights shine spread radians on each side of direction,
+// point lights having a spread of PI. Angles are in world space, from +X towards +Y.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Light {
+    pub position: Vec2,
+    pub radius: f64,
+    pub color: Color,
+    pub falloff: f64,
+    pub direction: f64,
+    pub spread: f64,
+    pub shadows: bool,
+}
+
+// Shape blocking the light of the lights casting shadows.
+#[derive(Clone)]
+pub struct Occluder {
+    pub shape: SharedShape,
+    pub position: Vec2,
+    pub rotation: f64,
+}
+
+// Darkens the scene to the ambient color except where it's lit. Lights are added together into
+// a light map the size of the current render target, drawn over the scene with blend_mode:
+// Multiply tints the scene by the light, Additive makes lights glow over it.
+pub struct Lighting {
+    pub ambient: Color,
+    pub blend_mode: BlendMode,
+    pub enabled: bool,
+    lights: Vec<Option<Light>>,
+    occluders: Vec<Option<Occluder>>,
+    light_map: Option<TextureId>,
+    // Radial gradients by falloff.
+    gradients: Vec<(f64, TextureId)>,
+}
+
+crate::reflect!(Light {
+    position,
+    radius,
+    color,
+    falloff,
+    direction,
+    spread,
+    shadows,
+});
+
+impl Light {
+    pub fn point(position: Vec2, radius: f64, color: Color) -> Self {
+        Light {
+            position,
+            radius,
+            color,
+            falloff: 1.,
+            direction: 0.,
+            spread: PI,
+            shadows: true,
+        }
+    }
+
+    pub fn cone(position: Vec2, radius: f64, color: Color, direction: f64, spread: f64) -> Self {
+        Light {
+            direction,
+            spread: spread.clamp(0., PI),
+            ..Light::point(position, radius, color)
+        }
+    }
+
+    fn aabb(&self) -> Aabb {
+        let extent = Vec2::splat(self.radius);
+        Aabb::new(self.position - extent, self.position + extent)
+    }
+}
+
+impl Occluder {
+    pub fn new(shape: SharedShape, position: Vec2) -> Self {
+        Occluder {
+            shape,
+            position,
+            rotation: 0.,
+        }
+    }
+
+    pub fn pose(&self) -> Pose {
+        Pose::new(self.position, self.rotation)
+    }
+
+    // Angles from origin at which the occluder's outline turns, where shadow edges start.
+    fn corner_angles(&self, origin: Vec2) -> Vec<f64> {
+        let pose = self.pose();
+        let angle = |point: Vec2| (point - origin).to_angle();
+
+        if let Some(ball) = self.shape.as_ball() {
+            // Tangents from the origin
+            let offset = self.position - origin;
+            let distance = offset.length();
+            if distance <= ball.radius {
+                return Vec::new();
+            }
+            let half_width = (ball.radius / distance).asin();
+            return vec![
+                offset.to_angle() - half_width,
+                offset.to_angle() + half_width,
+            ];
+        }
+
+        let points: Vec<Vec2> = if let Some(cuboid) = self.shape.as_cuboid() {
+            let extents = cuboid.half_extents;
+            [(-1., -1.), (1., -1.), (1., 1.), (-1., 1.)]
+                .map(|(x, y)| Vec2::new(extents.x * x, extents.y * y))
+                .to_vec()
+        } else if let Some(polygon) = self.shape.as_convex_polygon() {
+            polygon.points().to_vec()
+        } else {
+            let aabb = self.shape.compute_local_aabb();
+            let (min, max) = (aabb.mins, aabb.maxs);
+            vec![min, Vec2::new(max.x, min.y), max, Vec2::new(min.x, max.y)]
+        };
+        points
+            .into_iter()
+            .map(|p| angle(pose.transform_point(p)))
+            .collect()
+    }
+}
+
+impl Lighting {
+    pub fn new(ambient: Color) -> Self {
+        Lighting {
+            ambient,
+            blend_mode: BlendMode::Multiply,
+            enabled: true,
+            lights: Vec::new(),
+            occluders: Vec::new(),
+            light_map: None,
+            gradients: Vec::new(),
+        }
+    }
+
+    pub fn add_light(&mut self, light: Light) -> LightId {
+        self.lights.push(Some(light));
+        LightId(self.lights.len() - 1)
+    }
+
+    pub fn remove_light(&mut self, id: LightId) -> Option<Light> {
+        self.lights.get_mut(id.0)?.take()
+    }
+
+    pub fn light(&self, id: LightId) -> Option<&Light> {
+        self.lights.get(id.0)?.as_ref()
+    }
+
+    pub fn light_mut(&mut self, id: LightId) -> Option<&mut Light> {
+        self.lights.get_mut(id.0)?.as_mut()
+    }
+
+    pub fn add_occluder(&mut self, occluder: Occluder) -> OccluderId {
+        self.occluders.push(Some(occluder));
+        OccluderId(self.occluders.len() - 1)
+    }
+
+    pub fn remove_occluder(&mut self, id: OccluderId) -> Option<Occluder> {
+        self.occluders.get_mut(id.0)?.take()
+    }
+
+    pub fn occluder(&self, id: OccluderId) -> Option<&Occluder> {
+        self.occluders.get(id.0)?.as_ref()
+    }
+
+    pub fn occluder_mut(&mut self, id: OccluderId) -> Option<&mut Occluder> {
+        self.occluders.get_mut(id.0)?.as_mut()
+    }
+
+    // Area lit by the light in world space, as a fan of points around its position ordered by
+    // angle. Cone lights start and end at their position.
+    pub fn lit_area(&self, light: &Light) -> Vec<Vec2> {
+        let full = light.spread >= PI;
+        let start = if full {
+            0.
+        } else {
+            light.direction - light.spread
+        };
+        let width = if full { TAU } else { light.spread * 2. };
+
+        // Occluders reached by the light
+        let aabb = light.aabb();
+        let occluders: Vec<(&Occluder, Pose)> = if light.shadows {
+            self.occluders
+                .iter()
+                .flatten()
+                .map(|o| (o, o.pose()))
+                .filter(|(o, pose)| o.shape.compute_aabb(pose).intersects(&aabb))
+                .collect()
+        } else {
+            Vec::new()
+        };
+
+        // Angles relative to start, within the light's width
+        let mut angles: Vec<f64> = (0..=RAYS).map(|i| width * i as f64 / RAYS as f64).collect();
+        for (occluder, _) in &occluders {
+            for corner in occluder.corner_angles(light.position) {
+                for angle in [corner - CORNER_OFFSET, corner, corner + CORNER_OFFSET] {
+                    let angle = (angle - start).rem_euclid(TAU);
+                    if angle <= width {
+                        angles.push(angle);
+                    }
+                }
+            }
+        }
+        angles.sort_by(f64::total_cmp);
+        angles.dedup();
+        if full {
+            angles.pop();
+        }
+
+        let mut points: Vec<Vec2> = angles
+            .into_iter()
+            .map(|angle| {
+                let direction = Vec2::from_angle(start + angle);
+                let ray = Ray::new(light.position, direction);
+                let distance = occluders
+                    .iter()
+                    .filter_map(|(o, pose)| o.shape.cast_ray(pose, &ray, light.radius, true))
+                    .fold(light.radius, f64::min);
+                light.position + direction * distance
+            })
+            .collect();
+        if !full {
+            points.insert(0, light.position);
+        }
+        points
+    }
+
+    // Has to be called once the scene is drawn, before anything that shouldn't be lit like HUD.
+    pub fn draw(&mut self, graphics_ppl: &mut GraphicsPipeline) -> Result<(), String> {
+        if !self.enabled {
+            return Ok(());
+        }
+
+        let target = graphics_ppl.render_target();
+        let (width, height) = graphics_ppl.viewport_size();
+        let light_map = match self.light_map {
+            Some(map) if graphics_ppl.texture_size(map) == (width, height) => map,
+            _ => {
+                if let Some(previous) = self.light_map.take() {
+                    graphics_ppl.destroy_texture(previous);
+                }
+                *self
+                    .light_map
+                    .insert(graphics_ppl.create_render_target(width, height)?)
+            }
+        };
+
+        graphics_ppl.set_render_target(Some(light_map))?;
+        graphics_ppl.clear(&self.ambient);
+
+        let visible = graphics_ppl.visible_area();
+        let visible = Aabb::new(visible.min(), visible.max());
+        let lights: Vec<Light> = self.lights.iter().flatten().copied().collect();
+        for light in lights
+            .iter()
+            .filter(|l| l.radius > 0. && l.aabb().intersects(&visible))
+        {
+            let gradient = self.gradient(graphics_ppl, light.falloff)?;
+            let points = self.lit_area(light);
+
+            // The gradient is stretched over the light's bounds and clipped to the lit area
+            let vertex = |point: Vec2| {
+                let screen = graphics_ppl
+                    .camera
+                    .get_screen_coordinate(graphics_ppl, &point);
+                let uv = (point - light.position) / (light.radius * 2.) + 0.5;
+                Vertex {
+                    position: FPoint::new(screen.x as f32, screen.y as f32),
+                    color: light.color,
+                    tex_coord: FPoint::new(uv.x as f32, uv.y as f32),
+                }
+            };
+            let mut vertices = vec![vertex(light.position)];
+            vertices.extend(points.iter().map(|p| vertex(*p)));
+
+            let count = points.len() as u32;
+            let mut indices: Vec<u32> = (1..count).flat_map(|i| [0, i, i + 1]).collect();
+            if light.spread >= PI {
+                indices.extend([0, count, 1]);
+            }
+            graphics_ppl.draw_geometry(Some(gradient), &vertices, &indices, BlendMode::Additive);
+        }
+
+        graphics_ppl.set_render_target(target)?;
+        let params = DrawParams {
+            blend_mode: self.blend_mode,
+            ..Default::default()
+        };
+        graphics_ppl.draw_render_target(light_map, None, None, &params);
+        Ok(())
+    }
+
+    // White disc fading to black at its edge, created the first time a falloff is used.
+    fn gradient(
+        &mut self,
+        graphics_ppl: &mut GraphicsPipeline,
+        falloff: f64,
+    ) -> Result<TextureId, String> {
+        if let Some((_, texture)) = self.gradients.iter().find(|(f, _)| *f == falloff) {
+            return Ok(*texture);
+        }
+
+        let center = GRADIENT_SIZE as f64 / 2.;
+        let mut pixels = Vec::with_capacity((GRADIENT_SIZE * GRADIENT_SIZE * 4) as usize);
+        for y in 0..GRADIENT_SIZE {
+            for x in 0..GRADIENT_SIZE {
+                let offset = Vec2::new(x as f64 + 0.5, y as f64 + 0.5) - center;
+                let intensity = (1. - offset.length() / center).max(0.).powf(falloff);
+                let value = (intensity * 255.).round() as u8;
+                pixels.extend([value, value, value, u8::MAX]);
+            }
+        }
+
+        let texture = graphics_ppl.create_texture(GRADIENT_SIZE, GRADIENT_SIZE, &pixels)?;
+        self.gradients.push((falloff, texture));
+        Ok(texture)
+    }
+}
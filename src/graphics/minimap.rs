@@ -0,0 +1,141 @@
+use super::{Camera, Color, DrawParams, GraphicsPipeline, PixelRect, TextureId};
+use crate::{Point, Vec2};
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum MinimapMode {
+    // Centered on the position given to Minimap::draw, e.g. the player's.
+    Follow,
+    // Always showing the area around a world position, e.g. a whole small level.
+    Fixed(Vec2),
+}
+
+// Point of interest drawn over the minimap, the same size whatever the zoom.
+#[derive(Clone, Copy, PartialEq, Debug)]
+struct Marker {
+    position: Vec2,
+    color: Color,
+    pinned: bool,
+}
+
+// World drawn at low resolution to an offscreen target, then stretched over a rect of the
+// screen. The game draws what the minimap shows, e.g. its tilemap but not its particles,
+// through the camera the minimap sets up.
+pub struct Minimap {
+    // Where the minimap is drawn on screen.
+    pub dest: PixelRect,
+    // Size of the offscreen target, usually smaller than dest.
+    pub resolution: (u32, u32),
+    // Pixels of the target per world unit, replacing GraphicsOptions::pixel_per_unit while the
+    // minimap is drawn.
+    pub zoom: u32,
+    pub mode: MinimapMode,
+    pub background: Color,
+    pub border: Option<Color>,
+    // Side of the markers, in pixels of the target.
+    pub marker_size: u32,
+    markers: Vec<Marker>,
+    target: Option<TextureId>,
+}
+
+impl Minimap {
+    pub fn new(dest: PixelRect, resolution: (u32, u32), zoom: u32) -> Self {
+        Minimap {
+            dest,
+            resolution,
+            zoom,
+            mode: MinimapMode::Follow,
+            background: Color::RGBA(0, 0, 0, 160),
+            border: Some(Color::RGB(255, 255, 255)),
+            marker_size: 3,
+            markers: Vec::new(),
+            target: None,
+        }
+    }
+
+    // Marks a position until the next draw. Pinned markers out of the minimap's view are kept
+    // on its edge, pointing towards e.g. objectives.
+    pub fn mark(&mut self, position: Vec2, color: Color, pinned: bool) {
+        self.markers.push(Marker {
+            position,
+            color,
+            pinned,
+        });
+    }
+
+    // Draws the minimap in screen space once the world is drawn, draw_world drawing the world
+    // seen from the minimap as it's drawn normally. focus is the center in Follow mode.
+    pub fn draw<F: FnOnce(&mut GraphicsPipeline)>(
+        &mut self,
+        graphics_ppl: &mut GraphicsPipeline,
+        focus: Vec2,
+        draw_world: F,
+    ) -> Result<(), String> {
+        let (width, height) = (self.resolution.0.max(1), self.resolution.1.max(1));
+        let target = match self.target {
+            Some(target) if graphics_ppl.texture_size(target) == (width, height) => target,
+            _ => {
+                if let Some(previous) = self.target.take() {
+                    graphics_ppl.destroy_texture(previous);
+                }
+                *self
+                    .target
+                    .insert(graphics_ppl.create_render_target(width, height)?)
+            }
+        };
+
+        let previous_target = graphics_ppl.render_target();
+        graphics_ppl.set_render_target(Some(target))?;
+        graphics_ppl.clear(&self.background);
+
+        let center = match self.mode {
+            MinimapMode::Follow => focus,
+            MinimapMode::Fixed(center) => center,
+        };
+        // Shakes would move the minimap along with the view
+        let camera = std::mem::replace(
+            &mut graphics_ppl.camera,
+            Camera {
+                position: center,
+                shake_scale: 0.,
+                shake: None,
+            },
+        );
+        let pixel_per_unit = graphics_ppl.options.pixel_per_unit;
+        graphics_ppl.options.pixel_per_unit = self.zoom.max(1);
+
+        draw_world(graphics_ppl);
+        for marker in std::mem::take(&mut self.markers) {
+            let mut point = graphics_ppl
+                .camera
+                .get_screen_coordinate(graphics_ppl, &marker.position);
+            let half = self.marker_size as i32 / 2;
+            let inside = point.cmpge(Point::ZERO).all()
+                && point.cmplt(Point::new(width as i32, height as i32)).all();
+            if !inside && !marker.pinned {
+                continue;
+            }
+            point = point.clamp(
+                Point::splat(half),
+                Point::new(width as i32 - 1 - half, height as i32 - 1 - half)
+                    .max(Point::splat(half)),
+            );
+            let rect = PixelRect::new(
+                point.x - half,
+                point.y - half,
+                self.marker_size.max(1),
+                self.marker_size.max(1),
+            );
+            graphics_ppl.draw_rect_screen(rect, &marker.color, true, &DrawParams::default());
+        }
+
+        graphics_ppl.options.pixel_per_unit = pixel_per_unit;
+        graphics_ppl.camera = camera;
+        graphics_ppl.set_render_target(previous_target)?;
+
+        graphics_ppl.draw_render_target(target, None, Some(self.dest), &DrawParams::default());
+        if let Some(border) = self.border {
+            graphics_ppl.draw_rect_screen(self.dest, &border, false, &DrawParams::default());
+        }
+        Ok(())
+    }
+}
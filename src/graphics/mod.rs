@@ -1,66 +1,840 @@
-use sdl2::{pixels, rect::Rect, render::WindowCanvas};
+use std::{path::Path, time::Duration};
 
-use crate::{Point, ToPoint, Vec2};
+use sdl2::{
+    pixels::{self, PixelFormatEnum},
+    rect::{FPoint, Rect},
+    render::{self, Vertex, WindowCanvas},
+    rwops::RWops,
+    surface::Surface,
+    video::FullscreenType,
+};
+
+use crate::{
+    math,
+    physics::{BodyId, PhysicsWorld},
+    profile_scope, Point, Vec2,
+};
+
+pub use color::{ColorExt, Palette};
+pub(crate) use display::centered_on;
+pub use display::{displays, Display, DisplayMode};
+pub use effects::Effect;
+pub use fog::FogOfWar;
+#[cfg(feature = "opengl")]
+pub use gl::GlRenderer;
+pub use grading::{ColorFilter, ColorGrade, Gradient};
+pub use lighting::{Light, LightId, Lighting, Occluder, OccluderId};
+pub use minimap::{Minimap, MinimapMode};
+pub use renderer::{CanvasRenderer, CreateRenderer, Renderer};
+pub use shaders::{post_effects, Material, MaterialId, ShaderId, Uniform};
+pub use text::BitmapFont;
+pub use tilemap::{Terrain, TerrainId, TileId, Tilemap, Tileset};
+pub use transitions::Transitions;
+
+use batch::{quad_corners, SpriteBatch};
+use shaders::Shaders;
+
+mod batch;
+mod color;
+mod display;
+mod effects;
+mod fog;
+#[cfg(feature = "opengl")]
+mod gl;
+mod grading;
+mod lighting;
+mod minimap;
+mod renderer;
+mod shaders;
+mod text;
+mod tilemap;
+mod transitions;
 
 pub type Color = pixels::Color;
+pub type PixelRect = Rect;
+
+// Receives the frame rendered offscreen and is responsible for drawing it to the window.
+pub type PostProcess = Box<dyn FnMut(&mut GraphicsPipeline, TextureId)>;
 
 pub struct GraphicsPipeline {
     pub options: GraphicsOptions,
     pub camera: Camera,
-    canvas: WindowCanvas,
+    pub color_grade: ColorGrade,
+    renderer: Box<dyn Renderer>,
+    clear_color: Color,
+    render_target: Option<TextureId>,
+    frame_target: Option<TextureId>,
+    post_process: Option<PostProcess>,
+    frame_stats: RenderStats,
+    last_stats: RenderStats,
+    batch: SpriteBatch,
+    shaders: Shaders,
+    screenshot_requested: bool,
+    screenshot: Option<Screenshot>,
+    // Where the color filter draws the filtered frame from.
+    filter_texture: Option<TextureId>,
+    dpi_scale: f64,
 }
 
+// The world origin is at the center of the screen. +Y goes down the screen unless y_up is set,
+// in which case world rotations also become counter-clockwise.
 pub struct GraphicsOptions {
     pub pixel_per_unit: u32,
     pub window_size: (u32, u32),
+    pub y_up: bool,
+    // Draws further than this many pixels outside of the viewport are skipped.
+    pub cull_margin: u32,
+    // Borderless fullscreen at the desktop resolution.
+    pub fullscreen: bool,
+    pub vsync: bool,
+    // Renders at the screen's full resolution on high DPI displays, window_size and every
+    // coordinate staying in window coordinates.
+    pub high_dpi: bool,
+    // Multiplies the scale of the UI's text, for players who need it larger. Developer overlays
+    // keep their own scale.
+    pub text_scale: f64,
+}
+
+// Number of draw calls of a frame, culled ones being skipped because they were off-screen.
+// Consecutive sprites sharing a texture and a blend mode are submitted together as one batch.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub struct RenderStats {
+    pub submitted: u32,
+    pub culled: u32,
+    pub batches: u32,
 }
 
-#[derive(Default)]
+// Pixels of a presented frame, see GraphicsPipeline::request_screenshot.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Screenshot {
+    pub width: u32,
+    pub height: u32,
+    // RGBA, 4 bytes per pixel row by row.
+    pub pixels: Vec<u8>,
+}
+
+// Shakes offset what the camera sees without moving its position, and are updated by
+// Camera::update.
 pub struct Camera {
     pub position: Vec2,
+    // Multiplies the strength of shakes, 0 disabling them for players sensitive to motion.
+    pub shake_scale: f64,
+    shake: Option<Shake>,
+}
+
+struct Shake {
+    // Largest offset, in world units.
+    strength: f64,
+    duration: Duration,
+    elapsed: Duration,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct TextureId(usize);
+
+// SDL's id of a window, which events refer to.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Debug)]
+pub struct WindowId(pub(crate) u32);
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum BlendMode {
+    None,
+    #[default]
+    Alpha,
+    Additive,
+    Multiply,
+}
+
+// rotation is in radians, clockwise on screen, around the pivot. The pivot is relative to the
+// drawn rect, (0, 0) being its top left corner and (1, 1) its bottom right one. Negative scales
+// flip sprites. Text and nine slices ignore rotation and scale. Materials need a renderer
+// supporting shaders.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct DrawParams {
+    pub tint: Color,
+    pub alpha: u8,
+    pub blend_mode: BlendMode,
+    pub rotation: f64,
+    pub pivot: Vec2,
+    pub scale: Vec2,
+    pub material: Option<MaterialId>,
+}
+
+// Borders of a nine slice texture, in pixels.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub struct Margins {
+    pub left: u32,
+    pub right: u32,
+    pub top: u32,
+    pub bottom: u32,
+}
+
+impl Default for GraphicsOptions {
+    fn default() -> Self {
+        GraphicsOptions {
+            pixel_per_unit: 32,
+            window_size: (800, 600),
+            y_up: false,
+            cull_margin: 64,
+            fullscreen: false,
+            vsync: false,
+            high_dpi: true,
+            text_scale: 1.,
+        }
+    }
+}
+
+impl GraphicsOptions {
+    // World direction pointing down the screen, used for default gravity.
+    pub fn down(&self) -> Vec2 {
+        if self.y_up {
+            Vec2::new(0., -1.)
+        } else {
+            Vec2::new(0., 1.)
+        }
+    }
+}
+
+impl Default for DrawParams {
+    fn default() -> Self {
+        DrawParams {
+            tint: Color::WHITE,
+            alpha: u8::MAX,
+            blend_mode: BlendMode::default(),
+            rotation: 0.,
+            pivot: Vec2::new(0.5, 0.5),
+            scale: Vec2::ONE,
+            material: None,
+        }
+    }
+}
+
+impl DrawParams {
+    fn is_transformed(&self) -> bool {
+        self.rotation != 0. || self.scale != Vec2::ONE
+    }
+
+    // Applies the scale around the pivot, returns the new rect and the pivot position within it.
+    fn scale_rect(&self, rect: Rect) -> (Rect, sdl2::rect::Point) {
+        let width = rect.width() as f64 * self.scale.x.abs();
+        let height = rect.height() as f64 * self.scale.y.abs();
+        let pivot = Vec2::new(
+            rect.x() as f64 + self.pivot.x * rect.width() as f64,
+            rect.y() as f64 + self.pivot.y * rect.height() as f64,
+        );
+
+        let scaled = Rect::new(
+            (pivot.x - self.pivot.x * width).round() as i32,
+            (pivot.y - self.pivot.y * height).round() as i32,
+            width.round() as u32,
+            height.round() as u32,
+        );
+        let center = sdl2::rect::Point::new(
+            (self.pivot.x * width).round() as i32,
+            (self.pivot.y * height).round() as i32,
+        );
+
+        (scaled, center)
+    }
+}
+
+impl From<BlendMode> for render::BlendMode {
+    fn from(value: BlendMode) -> Self {
+        match value {
+            BlendMode::None => render::BlendMode::None,
+            BlendMode::Alpha => render::BlendMode::Blend,
+            BlendMode::Additive => render::BlendMode::Add,
+            BlendMode::Multiply => render::BlendMode::Mod,
+        }
+    }
 }
 
 impl GraphicsPipeline {
     pub fn new(options: GraphicsOptions, canvas: WindowCanvas) -> Self {
-        GraphicsPipeline {
+        GraphicsPipeline::with_renderer(options, Box::new(CanvasRenderer::new(canvas)))
+    }
+
+    // Draws through another backend than SDL's renderer, the drawing API staying the same.
+    pub fn with_renderer(mut options: GraphicsOptions, renderer: Box<dyn Renderer>) -> Self {
+        // Fullscreen windows take the size of the display
+        options.window_size = renderer.window().size();
+
+        let mut graphics_ppl = GraphicsPipeline {
             options,
-            canvas,
+            renderer,
+            clear_color: Color::BLACK,
+            render_target: None,
+            frame_target: None,
+            post_process: None,
+            frame_stats: RenderStats::default(),
+            last_stats: RenderStats::default(),
+            batch: SpriteBatch::default(),
+            shaders: Shaders::new(),
+            screenshot_requested: false,
+            screenshot: None,
+            filter_texture: None,
             camera: Camera::default(),
+            color_grade: ColorGrade::default(),
+            dpi_scale: 1.,
+        };
+        if let Err(e) = graphics_ppl.update_dpi_scale() {
+            log::warn!("failed to scale rendering to the display: {e}");
         }
+        graphics_ppl
     }
 
-    pub fn draw_rect(&mut self, position: &Vec2, size: &Vec2, color: &Color, filled: bool) {
-        self.canvas.set_draw_color(*color);
+    pub fn window_id(&self) -> WindowId {
+        WindowId(self.renderer.window().id())
+    }
+
+    pub fn set_window_size(&mut self, width: u32, height: u32) -> Result<(), String> {
+        self.renderer
+            .window_mut()
+            .set_size(width, height)
+            .map_err(|e| e.to_string())?;
+        self.resized()
+    }
+
+    pub fn set_fullscreen(&mut self, fullscreen: bool) -> Result<(), String> {
+        let fullscreen_type = if fullscreen {
+            FullscreenType::Desktop
+        } else {
+            FullscreenType::Off
+        };
+        self.renderer.window_mut().set_fullscreen(fullscreen_type)?;
+        self.options.fullscreen = fullscreen;
+        self.resized()
+    }
+
+    pub fn set_vsync(&mut self, vsync: bool) -> Result<(), String> {
+        self.renderer.set_vsync(vsync)?;
+        self.options.vsync = vsync;
+        Ok(())
+    }
+
+    pub fn create_render_target(&mut self, width: u32, height: u32) -> Result<TextureId, String> {
+        self.renderer.create_render_target(width, height)
+    }
+
+    // Frees a texture or render target, which can't be drawn anymore.
+    pub fn destroy_texture(&mut self, texture: TextureId) {
+        // Pending draws may use it
+        self.flush();
+        self.renderer.destroy_texture(texture);
+    }
+
+    // Redirects drawing to an offscreen target, None goes back to the screen.
+    pub fn set_render_target(&mut self, target: Option<TextureId>) -> Result<(), String> {
+        self.bind_target(target.or(self.frame_target))?;
+        self.render_target = target;
+        Ok(())
+    }
+
+    pub fn render_target(&self) -> Option<TextureId> {
+        self.render_target
+    }
+
+    // While a post process is set, frames are rendered offscreen and handed to it on run.
+    pub fn set_post_process(&mut self, post_process: Option<PostProcess>) -> Result<(), String> {
+        if post_process.is_some() && self.frame_target.is_none() {
+            let (width, height) = self.options.window_size;
+            self.frame_target = Some(self.create_render_target(width, height)?);
+        }
+
+        if post_process.is_none() {
+            if let Some(frame) = self.frame_target.take() {
+                self.destroy_texture(frame);
+            }
+        }
+
+        self.post_process = post_process;
+        self.set_render_target(self.render_target)
+    }
+
+    // Draws a render target to the current one, src and dest being in pixels.
+    // None respectively means the whole texture and the whole current target.
+    pub fn draw_render_target(
+        &mut self,
+        target: TextureId,
+        src: Option<PixelRect>,
+        dest: Option<PixelRect>,
+        params: &DrawParams,
+    ) {
+        self.copy_texture(target, src, dest, params);
+    }
 
-        let center = Vec2::new(position.x - (size.x / 2.), position.y - (size.y / 2.));
+    pub fn load_texture<P: AsRef<Path>>(&mut self, path: P) -> Result<TextureId, String> {
+        let path = path.as_ref();
+        let surface = Surface::load_bmp(path)?;
+        let texture = self.surface_texture(surface)?;
 
-        let pos = self.camera.get_screen_coordinate(self, &center);
-        let rect = Rect::new(
-            pos.x,
-            pos.y,
-            (size.x * self.options.pixel_per_unit as f64) as u32,
-            (size.y * self.options.pixel_per_unit as f64) as u32,
+        log::debug!("loaded texture {}", path.display());
+        Ok(texture)
+    }
+
+    // BMP file already read, e.g. from a Vfs.
+    pub fn load_texture_from_memory(&mut self, data: &[u8]) -> Result<TextureId, String> {
+        let surface = Surface::load_bmp_rw(&mut RWops::from_bytes(data)?)?;
+        self.surface_texture(surface)
+    }
+
+    fn surface_texture(&mut self, surface: Surface) -> Result<TextureId, String> {
+        let surface = surface.convert_format(PixelFormatEnum::RGBA32)?;
+        let (width, height) = surface.size();
+        let pitch = surface.pitch() as usize;
+        // Rows may be padded
+        let pixels = surface.with_lock(|p| {
+            p.chunks(pitch)
+                .flat_map(|row| &row[..width as usize * 4])
+                .copied()
+                .collect::<Vec<_>>()
+        });
+        self.renderer.create_texture(width, height, &pixels)
+    }
+
+    // Texture from RGBA pixels, 4 bytes per pixel row by row.
+    pub fn create_texture(
+        &mut self,
+        width: u32,
+        height: u32,
+        pixels: &[u8],
+    ) -> Result<TextureId, String> {
+        if pixels.len() != (width * height * 4) as usize {
+            return Err(format!("expected {width}x{height} RGBA pixels"));
+        }
+        self.renderer.create_texture(width, height, pixels)
+    }
+
+    // Replaces the pixels of a texture, which keeps its size.
+    pub fn update_texture(&mut self, texture: TextureId, pixels: &[u8]) -> Result<(), String> {
+        let (width, height) = self.texture_size(texture);
+        if pixels.len() != (width * height * 4) as usize {
+            return Err(format!("expected {width}x{height} RGBA pixels"));
+        }
+        // Sprites already batched would be drawn with the new pixels
+        self.flush();
+        self.renderer.update_texture(texture, pixels)
+    }
+
+    pub fn texture_size(&self, texture: TextureId) -> (u32, u32) {
+        self.renderer.texture_size(texture)
+    }
+
+    pub fn draw_rect(
+        &mut self,
+        position: &Vec2,
+        size: &Vec2,
+        color: &Color,
+        filled: bool,
+        params: &DrawParams,
+    ) {
+        let rect = self.world_rect(position, size);
+        self.draw_rect_screen(rect, color, filled, &self.world_params(params));
+    }
+
+    // Screen space variants take pixel coordinates and ignore the camera, for HUD elements.
+    pub fn draw_rect_screen(
+        &mut self,
+        rect: PixelRect,
+        color: &Color,
+        filled: bool,
+        params: &DrawParams,
+    ) {
+        if self.cull(rect, params) {
+            return;
+        }
+        self.use_material(params.material);
+        self.flush();
+
+        let color = Color::RGBA(
+            modulate(color.r, params.tint.r),
+            modulate(color.g, params.tint.g),
+            modulate(color.b, params.tint.b),
+            modulate(color.a, params.alpha),
         );
 
+        let blend_mode = params.blend_mode;
+
+        if !params.is_transformed() {
+            if filled {
+                self.renderer
+                    .fill_rect(Some(rect), color, blend_mode)
+                    .unwrap();
+            } else {
+                self.renderer.draw_rect(rect, color, blend_mode).unwrap();
+            }
+            return;
+        }
+
+        let corners = quad_corners(rect, params);
+
         if filled {
-            self.canvas.fill_rect(rect).unwrap();
+            let vertices = corners.map(|position| Vertex {
+                position,
+                color,
+                tex_coord: FPoint::new(0., 0.),
+            });
+            self.renderer
+                .draw_geometry(None, &vertices, &[0, 1, 2, 0, 2, 3], blend_mode)
+                .unwrap();
         } else {
-            self.canvas.draw_rect(rect).unwrap();
+            let mut outline = corners.to_vec();
+            outline.push(corners[0]);
+            self.renderer
+                .draw_lines(outline.as_slice(), color, blend_mode)
+                .unwrap();
         }
     }
 
+    // src is the area of the texture to draw, in pixels. None draws the whole texture.
+    pub fn draw_sprite(
+        &mut self,
+        texture: TextureId,
+        src: Option<PixelRect>,
+        position: &Vec2,
+        size: &Vec2,
+        params: &DrawParams,
+    ) {
+        let rect = self.world_rect(position, size);
+        self.draw_sprite_screen(texture, src, rect, &self.world_params(params));
+    }
+
+    // Sprite following a body, drawn at its transform interpolated between the last two physics
+    // steps so that it moves smoothly when frames and steps don't line up. The body's rotation
+    // is added to params'.
+    pub fn draw_body(
+        &mut self,
+        physics: &PhysicsWorld,
+        body: BodyId,
+        texture: TextureId,
+        src: Option<PixelRect>,
+        size: &Vec2,
+        params: &DrawParams,
+    ) {
+        let Some((position, rotation)) = physics.interpolated(body) else {
+            return;
+        };
+        let params = DrawParams {
+            rotation: params.rotation + rotation,
+            ..*params
+        };
+        self.draw_sprite(texture, src, &position, size, &params);
+    }
+
+    pub fn draw_sprite_screen(
+        &mut self,
+        texture: TextureId,
+        src: Option<PixelRect>,
+        dest: PixelRect,
+        params: &DrawParams,
+    ) {
+        self.copy_texture(texture, src, Some(dest), params);
+    }
+
+    pub fn clear(&mut self, color: &Color) {
+        self.flush();
+        self.clear_color = *color;
+        self.renderer.clear(*color);
+    }
+
+    // Corners keep their size in pixels, edges are stretched along one axis and the center
+    // along both, so the texture can be drawn at any size without distortion of the borders.
+    pub fn draw_nine_slice(
+        &mut self,
+        texture: TextureId,
+        margins: &Margins,
+        position: &Vec2,
+        size: &Vec2,
+        params: &DrawParams,
+    ) {
+        let rect = self.world_rect(position, size);
+        self.draw_nine_slice_screen(texture, margins, rect, &self.world_params(params));
+    }
+
     pub fn world_to_screen_position(&self, position: &Vec2) -> Point {
+        let viewport_size = self.viewport_size();
+        let y = if self.options.y_up {
+            -position.y
+        } else {
+            position.y
+        };
+
         Point::new(
             (position.x * self.options.pixel_per_unit as f64).round() as i32
-                + self.options.window_size.0 as i32 / 2,
-            (position.y * self.options.pixel_per_unit as f64).round() as i32
-                + self.options.window_size.1 as i32 / 2,
+                + viewport_size.0 as i32 / 2,
+            (y * self.options.pixel_per_unit as f64).round() as i32 + viewport_size.1 as i32 / 2,
         )
     }
 
+    pub fn screen_to_world_position(&self, position: &Point) -> Vec2 {
+        let viewport_size = self.viewport_size();
+        let pixel_per_unit = self.options.pixel_per_unit as f64;
+        let x = (position.x - viewport_size.0 as i32 / 2) as f64 / pixel_per_unit;
+        let y = (position.y - viewport_size.1 as i32 / 2) as f64 / pixel_per_unit;
+
+        Vec2::new(x, if self.options.y_up { -y } else { y })
+    }
+
+    // World area covered by the current render target, seen through the camera.
+    pub fn visible_area(&self) -> math::Rect {
+        let (width, height) = self.viewport_size();
+        math::Rect::from_corners(
+            self.camera.get_world_coordinate(self, &Point::ZERO),
+            self.camera
+                .get_world_coordinate(self, &Point::new(width as i32, height as i32)),
+        )
+    }
+
+    // Size of the current render target in pixels.
+    pub fn viewport_size(&self) -> (u32, u32) {
+        match self.render_target {
+            Some(target) => self.texture_size(target),
+            None => self.options.window_size,
+        }
+    }
+
+    // Stats of the last presented frame.
+    pub fn stats(&self) -> RenderStats {
+        self.last_stats
+    }
+
+    // Reads the next presented frame back, as shown on the window after post processing and
+    // color grading, at the resolution of the screen on high DPI displays.
+    pub fn request_screenshot(&mut self) {
+        self.screenshot_requested = true;
+    }
+
+    // The requested frame once it's presented, taken by the first call.
+    pub fn take_screenshot(&mut self) -> Option<Screenshot> {
+        self.screenshot.take()
+    }
+
     pub fn run(&mut self) {
-        self.canvas.present();
-        self.canvas.clear();
+        profile_scope!("render");
+        self.reload_shaders();
+        self.flush();
+        self.last_stats = std::mem::take(&mut self.frame_stats);
+
+        if let (Some(mut post_process), Some(frame)) = (self.post_process.take(), self.frame_target)
+        {
+            let render_target = self.render_target.take();
+
+            self.frame_target = None;
+            self.bind_target(None).unwrap();
+            self.renderer.clear(self.clear_color);
+            post_process(self, frame);
+            self.flush();
+            self.apply_color_grade();
+            self.present();
+
+            self.frame_target = Some(frame);
+            self.post_process.get_or_insert(post_process);
+            self.set_render_target(render_target).unwrap();
+            self.renderer.clear(self.clear_color);
+            return;
+        }
+
+        self.apply_color_grade();
+        self.present();
+        self.renderer.clear(self.clear_color);
+    }
+
+    fn present(&mut self) {
+        self.flush();
+        if std::mem::take(&mut self.screenshot_requested) {
+            match self.renderer.read_pixels() {
+                Ok((width, height, pixels)) => {
+                    self.screenshot = Some(Screenshot {
+                        width,
+                        height,
+                        pixels,
+                    })
+                }
+                Err(e) => log::warn!("failed to read the frame back: {e}"),
+            }
+        }
+        self.renderer.present();
+    }
+
+    pub fn draw_nine_slice_screen(
+        &mut self,
+        texture: TextureId,
+        margins: &Margins,
+        dest: Rect,
+        params: &DrawParams,
+    ) {
+        if self.cull(dest, &DrawParams::default()) {
+            return;
+        }
+
+        let (width, height) = self.texture_size(texture);
+
+        // Offsets and sizes of the three columns and rows, in the texture then in dest
+        let slices = |length: u32, start: u32, end: u32| {
+            let middle = length.saturating_sub(start + end);
+            [(0, start), (start, middle), (start + middle, end)]
+        };
+        let src_columns = slices(width, margins.left, margins.right);
+        let src_rows = slices(height, margins.top, margins.bottom);
+        let dest_columns = slices(dest.width(), margins.left, margins.right);
+        let dest_rows = slices(dest.height(), margins.top, margins.bottom);
+        let params = DrawParams {
+            rotation: 0.,
+            scale: Vec2::ONE,
+            ..*params
+        };
+
+        for (row, dest_row) in src_rows.iter().zip(dest_rows) {
+            for (column, dest_column) in src_columns.iter().zip(dest_columns) {
+                if column.1 == 0 || row.1 == 0 || dest_column.1 == 0 || dest_row.1 == 0 {
+                    continue;
+                }
+
+                let src = Rect::new(column.0 as i32, row.0 as i32, column.1, row.1);
+                let dst = Rect::new(
+                    dest.x() + dest_column.0 as i32,
+                    dest.y() + dest_row.0 as i32,
+                    dest_column.1,
+                    dest_row.1,
+                );
+                self.push_quad(texture, Some(src), dst, &params);
+            }
+        }
+    }
+
+    // Triangles in screen space, tex_coord being ignored without a texture.
+    pub(super) fn draw_geometry(
+        &mut self,
+        texture: Option<TextureId>,
+        vertices: &[Vertex],
+        indices: &[u32],
+        blend_mode: BlendMode,
+    ) {
+        self.frame_stats.submitted += 1;
+        self.use_material(None);
+        self.flush();
+
+        self.renderer
+            .draw_geometry(texture, vertices, indices, blend_mode)
+            .unwrap();
+        self.frame_stats.batches += 1;
+    }
+
+    fn copy_texture(
+        &mut self,
+        texture: TextureId,
+        src: Option<PixelRect>,
+        dest: Option<PixelRect>,
+        params: &DrawParams,
+    ) {
+        if dest.is_some_and(|d| self.cull(d, params)) {
+            return;
+        }
+
+        let (width, height) = self.viewport_size();
+        let dest = dest.unwrap_or(Rect::new(0, 0, width, height));
+        self.push_quad(texture, src, dest, params);
+    }
+
+    // Counts the draw, returns true if it lies off-screen and should be skipped.
+    fn cull(&mut self, rect: Rect, params: &DrawParams) -> bool {
+        self.frame_stats.submitted += 1;
+
+        let bounds = if params.is_transformed() {
+            // Rotations are accounted for with the circle the rect sweeps around its pivot
+            let (rect, center) = params.scale_rect(rect);
+            let radius = [
+                (0, 0),
+                (rect.width() as i32, 0),
+                (0, rect.height() as i32),
+                (rect.width() as i32, rect.height() as i32),
+            ]
+            .iter()
+            .map(|(x, y)| {
+                Point::new(x - center.x(), y - center.y())
+                    .as_dvec2()
+                    .length()
+            })
+            .fold(0., f64::max)
+            .ceil() as i32;
+
+            Rect::new(
+                rect.x() + center.x() - radius,
+                rect.y() + center.y() - radius,
+                radius as u32 * 2,
+                radius as u32 * 2,
+            )
+        } else {
+            rect
+        };
+
+        let (width, height) = self.viewport_size();
+        let margin = self.options.cull_margin as i32;
+        let visible = bounds.right() >= -margin
+            && bounds.bottom() >= -margin
+            && bounds.left() <= width as i32 + margin
+            && bounds.top() <= height as i32 + margin;
+
+        if !visible {
+            self.frame_stats.culled += 1;
+        }
+        !visible
+    }
+
+    // Keeps window_size and the offscreen frame in sync with the actual window size.
+    pub(crate) fn resized(&mut self) -> Result<(), String> {
+        // The window may also have moved to a display with another DPI
+        self.update_dpi_scale()?;
+        let size = self.renderer.window().size();
+        if size == self.options.window_size {
+            return Ok(());
+        }
+
+        self.options.window_size = size;
+        if let Some(previous) = self.frame_target {
+            self.frame_target = Some(self.create_render_target(size.0, size.1)?);
+            self.destroy_texture(previous);
+            self.set_render_target(self.render_target)?;
+        }
+        Ok(())
+    }
+
+    fn bind_target(&mut self, target: Option<TextureId>) -> Result<(), String> {
+        self.flush();
+        self.renderer.set_target(target)
+    }
+
+    // Screen rect of a world space area centered on position.
+    fn world_rect(&self, position: &Vec2, size: &Vec2) -> Rect {
+        let center = self.camera.get_screen_coordinate(self, position);
+        let width = size.x * self.options.pixel_per_unit as f64;
+        let height = size.y * self.options.pixel_per_unit as f64;
+
+        Rect::new(
+            center.x - (width / 2.).round() as i32,
+            center.y - (height / 2.).round() as i32,
+            width as u32,
+            height as u32,
+        )
+    }
+
+    // Rotations are counter-clockwise in the world when +Y goes up.
+    fn world_params(&self, params: &DrawParams) -> DrawParams {
+        let mut params = *params;
+        if self.options.y_up {
+            params.rotation = -params.rotation;
+        }
+        params
+    }
+}
+
+impl Default for Camera {
+    fn default() -> Self {
+        Camera {
+            position: Vec2::ZERO,
+            shake_scale: 1.,
+            shake: None,
+        }
     }
 }
 
@@ -70,7 +844,84 @@ impl Camera {
         graphics_ppl: &GraphicsPipeline,
         world_coordinate: &Vec2,
     ) -> Point {
-        let relative_pos = world_coordinate - self.position;
+        let relative_pos = world_coordinate - self.position - self.shake_offset();
         graphics_ppl.world_to_screen_position(&relative_pos)
     }
+
+    pub fn get_world_coordinate(
+        &self,
+        graphics_ppl: &GraphicsPipeline,
+        screen_coordinate: &Point,
+    ) -> Vec2 {
+        graphics_ppl.screen_to_world_position(screen_coordinate)
+            + self.position
+            + self.shake_offset()
+    }
+
+    // Fades out over duration, replacing the current shake unless it's stronger.
+    pub fn shake(&mut self, strength: f64, duration: Duration) {
+        if self
+            .shake
+            .as_ref()
+            .is_some_and(|s| s.current_strength() > strength)
+        {
+            return;
+        }
+        self.shake = Some(Shake {
+            strength,
+            duration,
+            elapsed: Duration::ZERO,
+        });
+    }
+
+    pub fn update(&mut self, dt: Duration) {
+        if let Some(shake) = &mut self.shake {
+            shake.elapsed += dt;
+            if shake.elapsed >= shake.duration {
+                self.shake = None;
+            }
+        }
+    }
+
+    // Current offset of the view, zero without a shake.
+    pub fn shake_offset(&self) -> Vec2 {
+        let Some(shake) = &self.shake else {
+            return Vec2::ZERO;
+        };
+        // Sums of sines of unrelated frequencies, which look random but are smooth
+        let t = shake.elapsed.as_secs_f64();
+        let wave = |a: f64, b: f64, phase: f64| ((t * a + phase).sin() + (t * b).sin() * 0.5) / 1.5;
+        let offset = Vec2::new(wave(47., 83., 0.), wave(59., 71., 1.3));
+        offset * shake.current_strength() * self.shake_scale.max(0.)
+    }
+}
+
+impl Shake {
+    fn current_strength(&self) -> f64 {
+        // Also keeps shakes without a duration from dividing by zero
+        if self.elapsed >= self.duration {
+            return 0.;
+        }
+        let t = self.elapsed.as_secs_f64() / self.duration.as_secs_f64();
+        self.strength * (1. - t).max(0.)
+    }
+}
+
+fn modulate(value: u8, factor: u8) -> u8 {
+    (value as u16 * factor as u16 / u8::MAX as u16) as u8
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shake_without_duration_has_no_offset() {
+        let mut camera = Camera::default();
+        camera.shake(2., Duration::ZERO);
+        assert_eq!(camera.shake_offset(), Vec2::ZERO);
+
+        camera.shake(1., Duration::from_secs(1));
+        assert!(camera.shake_offset().is_finite());
+    }
 }
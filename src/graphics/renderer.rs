@@ -0,0 +1,321 @@
+use sdl2::{
+    pixels::PixelFormatEnum,
+    rect::{FPoint, Rect},
+    render::{Texture, TextureCreator, Vertex, WindowCanvas},
+    surface::Surface,
+    video::{Window, WindowContext},
+};
+
+use super::{BlendMode, Color, GraphicsOptions, Material, ShaderId, TextureId};
+
+// Creates the backend of a window's pipeline, see EngineBuilder::renderer.
+pub type CreateRenderer = fn(Window, &GraphicsOptions) -> Result<Box<dyn Renderer>, String>;
+
+// What GraphicsPipeline needs to draw, the pipeline doing the coordinates, culling, batching
+// and post processing itself so that every backend draws the same. Coordinates are in pixels
+// of the bound target, textures are allocated by the backend and render targets are textures
+// that can be bound.
+pub trait Renderer {
+    fn window(&self) -> &Window;
+    fn window_mut(&mut self) -> &mut Window;
+
+    // Size of the window in pixels, larger than the window's on high DPI displays.
+    fn output_size(&self) -> Result<(u32, u32), String>;
+    // Scales drawing on the window, not on render targets.
+    fn set_scale(&mut self, scale: f64) -> Result<(), String>;
+    fn set_vsync(&mut self, vsync: bool) -> Result<(), String>;
+
+    // RGBA pixels, 4 bytes per pixel row by row.
+    fn create_texture(
+        &mut self,
+        width: u32,
+        height: u32,
+        pixels: &[u8],
+    ) -> Result<TextureId, String>;
+    // Replaces the pixels of a texture of the same size.
+    fn update_texture(&mut self, texture: TextureId, pixels: &[u8]) -> Result<(), String>;
+    fn create_render_target(&mut self, width: u32, height: u32) -> Result<TextureId, String>;
+    // Frees a texture or render target, its id not being reused. Destroyed textures make the
+    // methods returning a Result fail and are skipped by draws.
+    fn destroy_texture(&mut self, texture: TextureId);
+    // (0, 0) for a destroyed texture.
+    fn texture_size(&self, texture: TextureId) -> (u32, u32);
+    // None binds the window.
+    fn set_target(&mut self, target: Option<TextureId>) -> Result<(), String>;
+
+    fn clear(&mut self, color: Color);
+    // None fills the whole target.
+    fn fill_rect(
+        &mut self,
+        rect: Option<Rect>,
+        color: Color,
+        blend_mode: BlendMode,
+    ) -> Result<(), String>;
+    fn draw_rect(&mut self, rect: Rect, color: Color, blend_mode: BlendMode) -> Result<(), String>;
+    fn draw_lines(
+        &mut self,
+        points: &[FPoint],
+        color: Color,
+        blend_mode: BlendMode,
+    ) -> Result<(), String>;
+    // Triangles, the vertex colors modulating the texture. tex_coord is ignored without one.
+    fn draw_geometry(
+        &mut self,
+        texture: Option<TextureId>,
+        vertices: &[Vertex],
+        indices: &[u32],
+        blend_mode: BlendMode,
+    ) -> Result<(), String>;
+    fn present(&mut self);
+    // RGBA pixels drawn on the window so far, 4 bytes per pixel row by row, with their size.
+    // Read while the window is bound.
+    fn read_pixels(&mut self) -> Result<(u32, u32, Vec<u8>), String>;
+
+    // Shaders are in the backend's own language, the default being to support none.
+    fn create_shader(&mut self, _source: &str) -> Result<ShaderId, String> {
+        Err("the renderer doesn't support shaders".to_string())
+    }
+
+    // Keeps the previous version of the shader when the source doesn't compile.
+    fn reload_shader(&mut self, _shader: ShaderId, _source: &str) -> Result<(), String> {
+        Err("the renderer doesn't support shaders".to_string())
+    }
+
+    // Applies to the following draws, None going back to the default shading.
+    fn set_material(&mut self, material: Option<&Material>) -> Result<(), String> {
+        match material {
+            Some(_) => Err("the renderer doesn't support shaders".to_string()),
+            None => Ok(()),
+        }
+    }
+}
+
+// SDL_Renderer backend, the default one.
+pub struct CanvasRenderer {
+    canvas: WindowCanvas,
+    texture_creator: TextureCreator<WindowContext>,
+    // None once destroyed.
+    textures: Vec<Option<Texture>>,
+}
+
+impl TextureId {
+    // Backends number their textures in creation order.
+    pub fn new(index: usize) -> Self {
+        TextureId(index)
+    }
+
+    pub fn index(&self) -> usize {
+        self.0
+    }
+}
+
+impl CanvasRenderer {
+    pub fn new(canvas: WindowCanvas) -> Self {
+        CanvasRenderer {
+            texture_creator: canvas.texture_creator(),
+            canvas,
+            textures: Vec::new(),
+        }
+    }
+
+    // See CreateRenderer.
+    pub fn create(window: Window, options: &GraphicsOptions) -> Result<Box<dyn Renderer>, String> {
+        let mut canvas_builder = window.into_canvas();
+        if options.vsync {
+            canvas_builder = canvas_builder.present_vsync();
+        }
+        let canvas = canvas_builder
+            .build()
+            .map_err(|e| format!("renderer: {e}"))?;
+        Ok(Box::new(CanvasRenderer::new(canvas)))
+    }
+
+    fn add(&mut self, texture: Texture) -> TextureId {
+        self.textures.push(Some(texture));
+        TextureId(self.textures.len() - 1)
+    }
+
+    fn texture(&mut self, texture: TextureId) -> Result<&mut Texture, String> {
+        self.textures
+            .get_mut(texture.0)
+            .and_then(Option::as_mut)
+            .ok_or_else(|| "the texture was destroyed".to_string())
+    }
+}
+
+impl Renderer for CanvasRenderer {
+    fn window(&self) -> &Window {
+        self.canvas.window()
+    }
+
+    fn window_mut(&mut self) -> &mut Window {
+        self.canvas.window_mut()
+    }
+
+    fn output_size(&self) -> Result<(u32, u32), String> {
+        self.canvas.output_size()
+    }
+
+    fn set_scale(&mut self, scale: f64) -> Result<(), String> {
+        self.canvas.set_scale(scale as f32, scale as f32)
+    }
+
+    fn set_vsync(&mut self, vsync: bool) -> Result<(), String> {
+        let result = unsafe { sdl2::sys::SDL_RenderSetVSync(self.canvas.raw(), vsync as i32) };
+        if result != 0 {
+            return Err(sdl2::get_error());
+        }
+        Ok(())
+    }
+
+    fn create_texture(
+        &mut self,
+        width: u32,
+        height: u32,
+        pixels: &[u8],
+    ) -> Result<TextureId, String> {
+        let mut pixels = pixels.to_vec();
+        let surface = Surface::from_data(
+            &mut pixels,
+            width,
+            height,
+            width * 4,
+            PixelFormatEnum::RGBA32,
+        )?;
+        let texture = self
+            .texture_creator
+            .create_texture_from_surface(surface)
+            .map_err(|e| e.to_string())?;
+        Ok(self.add(texture))
+    }
+
+    fn update_texture(&mut self, texture: TextureId, pixels: &[u8]) -> Result<(), String> {
+        // Textures made from surfaces can be in another format than RGBA, they're made again
+        let query = self.texture(texture)?.query();
+        let (width, height) = (query.width, query.height);
+        let mut pixels = pixels.to_vec();
+        let surface = Surface::from_data(
+            &mut pixels,
+            width,
+            height,
+            width * 4,
+            PixelFormatEnum::RGBA32,
+        )?;
+        let new = self
+            .texture_creator
+            .create_texture_from_surface(surface)
+            .map_err(|e| e.to_string())?;
+        let old = std::mem::replace(self.texture(texture)?, new);
+        unsafe { old.destroy() };
+        Ok(())
+    }
+
+    fn create_render_target(&mut self, width: u32, height: u32) -> Result<TextureId, String> {
+        let texture = self
+            .texture_creator
+            .create_texture_target(None, width, height)
+            .map_err(|e| e.to_string())?;
+        Ok(self.add(texture))
+    }
+
+    fn destroy_texture(&mut self, texture: TextureId) {
+        if let Some(texture) = self.textures.get_mut(texture.0).and_then(Option::take) {
+            unsafe { texture.destroy() };
+        }
+    }
+
+    fn texture_size(&self, texture: TextureId) -> (u32, u32) {
+        match self.textures.get(texture.0).and_then(Option::as_ref) {
+            Some(texture) => {
+                let query = texture.query();
+                (query.width, query.height)
+            }
+            None => (0, 0),
+        }
+    }
+
+    fn set_target(&mut self, target: Option<TextureId>) -> Result<(), String> {
+        let raw = match target {
+            Some(t) => self.texture(t)?.raw(),
+            None => std::ptr::null_mut(),
+        };
+
+        let result = unsafe { sdl2::sys::SDL_SetRenderTarget(self.canvas.raw(), raw) };
+        if result != 0 {
+            return Err(sdl2::get_error());
+        }
+        Ok(())
+    }
+
+    fn clear(&mut self, color: Color) {
+        self.canvas.set_draw_color(color);
+        self.canvas.clear();
+    }
+
+    fn fill_rect(
+        &mut self,
+        rect: Option<Rect>,
+        color: Color,
+        blend_mode: BlendMode,
+    ) -> Result<(), String> {
+        self.canvas.set_blend_mode(blend_mode.into());
+        self.canvas.set_draw_color(color);
+        self.canvas.fill_rect(rect)
+    }
+
+    fn draw_rect(&mut self, rect: Rect, color: Color, blend_mode: BlendMode) -> Result<(), String> {
+        self.canvas.set_blend_mode(blend_mode.into());
+        self.canvas.set_draw_color(color);
+        self.canvas.draw_rect(rect)
+    }
+
+    fn draw_lines(
+        &mut self,
+        points: &[FPoint],
+        color: Color,
+        blend_mode: BlendMode,
+    ) -> Result<(), String> {
+        self.canvas.set_blend_mode(blend_mode.into());
+        self.canvas.set_draw_color(color);
+        self.canvas.draw_flines(points)
+    }
+
+    fn draw_geometry(
+        &mut self,
+        texture: Option<TextureId>,
+        vertices: &[Vertex],
+        indices: &[u32],
+        blend_mode: BlendMode,
+    ) -> Result<(), String> {
+        let texture = match texture {
+            Some(t) => match self.textures.get_mut(t.0).and_then(Option::as_mut) {
+                Some(texture) => {
+                    // The tint is carried by the vertex colors
+                    texture.set_color_mod(u8::MAX, u8::MAX, u8::MAX);
+                    texture.set_alpha_mod(u8::MAX);
+                    texture.set_blend_mode(blend_mode.into());
+                    Some(&*texture)
+                }
+                None => {
+                    log::warn!("skipped a draw with a destroyed texture");
+                    return Ok(());
+                }
+            },
+            None => None,
+        };
+        self.canvas.set_blend_mode(blend_mode.into());
+        self.canvas
+            .render_geometry(vertices, texture, indices)
+            .map_err(|e| e.to_string())
+    }
+
+    fn present(&mut self) {
+        self.canvas.present();
+    }
+
+    fn read_pixels(&mut self) -> Result<(u32, u32, Vec<u8>), String> {
+        let (width, height) = self.canvas.output_size()?;
+        let pixels = self.canvas.read_pixels(None, PixelFormatEnum::RGBA32)?;
+        Ok((width, height, pixels))
+    }
+}
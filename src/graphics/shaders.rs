@@ -0,0 +1,227 @@
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+    time::{Duration, Instant, SystemTime},
+};
+
+use super::{BlendMode, Color, DrawParams, GraphicsPipeline, PostProcess, TextureId};
+use crate::Vec2;
+
+// How often shader files are checked for changes, in debug builds.
+const RELOAD_INTERVAL: Duration = Duration::from_millis(500);
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct ShaderId(usize);
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct MaterialId(usize);
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Uniform {
+    Float(f32),
+    Vec2(Vec2),
+    Color(Color),
+    Texture(TextureId),
+}
+
+// Shader and the values of its parameters, set on sprites with DrawParams::material. The drawn
+// texture is bound before the uniforms' ones.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Material {
+    pub shader: ShaderId,
+    pub uniforms: BTreeMap<String, Uniform>,
+}
+
+pub(super) struct Shaders {
+    materials: Vec<Material>,
+    // Material the renderer draws with, None being its default shading.
+    bound: Option<MaterialId>,
+    // The bound material's uniforms were changed since it was given to the renderer.
+    dirty: bool,
+    // Files of the loaded shaders with their last modification time.
+    files: Vec<(ShaderId, PathBuf, Option<SystemTime>)>,
+    last_check: Instant,
+}
+
+impl ShaderId {
+    // Backends number their shaders in creation order.
+    pub fn new(index: usize) -> Self {
+        ShaderId(index)
+    }
+
+    pub fn index(&self) -> usize {
+        self.0
+    }
+}
+
+impl Material {
+    pub fn new(shader: ShaderId) -> Self {
+        Material {
+            shader,
+            uniforms: BTreeMap::new(),
+        }
+    }
+}
+
+impl Shaders {
+    pub(super) fn new() -> Self {
+        Shaders {
+            materials: Vec::new(),
+            bound: None,
+            dirty: false,
+            files: Vec::new(),
+            last_check: Instant::now(),
+        }
+    }
+}
+
+impl GraphicsPipeline {
+    // The source is in the renderer's shading language, e.g. GLSL for GlRenderer, SDL's
+    // renderer not supporting any. In debug builds the file is watched and the shader reloaded
+    // when it changes.
+    pub fn load_shader<P: AsRef<Path>>(&mut self, path: P) -> Result<ShaderId, String> {
+        let path = path.as_ref();
+        let source =
+            std::fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        let shader = self
+            .renderer
+            .create_shader(&source)
+            .map_err(|e| format!("{}: {}", path.display(), e))?;
+
+        log::debug!("loaded shader {}", path.display());
+        self.shaders
+            .files
+            .push((shader, path.to_path_buf(), modified(path)));
+        Ok(shader)
+    }
+
+    pub fn create_shader(&mut self, source: &str) -> Result<ShaderId, String> {
+        self.renderer.create_shader(source)
+    }
+
+    pub fn create_material(&mut self, material: Material) -> MaterialId {
+        self.shaders.materials.push(material);
+        MaterialId(self.shaders.materials.len() - 1)
+    }
+
+    pub fn material(&self, material: MaterialId) -> &Material {
+        &self.shaders.materials[material.0]
+    }
+
+    // Changes apply to the following draws.
+    pub fn material_mut(&mut self, material: MaterialId) -> &mut Material {
+        if self.shaders.bound == Some(material) {
+            self.flush();
+            self.shaders.dirty = true;
+        }
+        &mut self.shaders.materials[material.0]
+    }
+
+    pub fn set_uniform(&mut self, material: MaterialId, name: &str, value: Uniform) {
+        self.material_mut(material)
+            .uniforms
+            .insert(name.to_string(), value);
+    }
+
+    // Has to be called before drawing, pending draws being submitted with the previous material.
+    pub(super) fn use_material(&mut self, material: Option<MaterialId>) {
+        if self.shaders.bound == material && !self.shaders.dirty {
+            return;
+        }
+        self.flush();
+
+        let result = self
+            .renderer
+            .set_material(material.map(|m| &self.shaders.materials[m.0]));
+        if let Err(e) = result {
+            log::warn!("failed to use material: {e}");
+        }
+        self.shaders.bound = material;
+        self.shaders.dirty = false;
+    }
+
+    // Only in debug builds, a shader that fails to compile keeping its previous version.
+    pub(super) fn reload_shaders(&mut self) {
+        if !cfg!(debug_assertions)
+            || self.shaders.files.is_empty()
+            || self.shaders.last_check.elapsed() < RELOAD_INTERVAL
+        {
+            return;
+        }
+        self.shaders.last_check = Instant::now();
+
+        for (shader, path, last_modified) in &mut self.shaders.files {
+            let modified = modified(path);
+            if modified == *last_modified {
+                continue;
+            }
+            *last_modified = modified;
+
+            let result = std::fs::read_to_string(&*path)
+                .map_err(|e| e.to_string())
+                .and_then(|source| self.renderer.reload_shader(*shader, &source));
+            match result {
+                Ok(()) => log::info!("reloaded shader {}", path.display()),
+                Err(e) => log::warn!("failed to reload shader {}: {}", path.display(), e),
+            }
+        }
+        // The renderer may have dropped the previous program
+        self.shaders.dirty = true;
+    }
+}
+
+// Post process drawing the frame through each material in turn, e.g. bloom then a CRT effect,
+// see GraphicsPipeline::set_post_process. Each pass reads the previous one's output.
+pub fn post_effects(materials: Vec<MaterialId>) -> PostProcess {
+    let mut targets: Vec<TextureId> = Vec::new();
+
+    Box::new(move |graphics_ppl, frame| {
+        // Passes alternate between two offscreen targets, the last one drawing to the window
+        let size = graphics_ppl.texture_size(frame);
+        let count = materials.len().saturating_sub(1).min(2);
+        if targets.len() != count
+            || targets
+                .first()
+                .is_some_and(|t| graphics_ppl.texture_size(*t) != size)
+        {
+            for target in targets.drain(..) {
+                graphics_ppl.destroy_texture(target);
+            }
+            for _ in 0..count {
+                match graphics_ppl.create_render_target(size.0, size.1) {
+                    Ok(target) => targets.push(target),
+                    Err(e) => {
+                        log::warn!("failed to create a post effect target: {e}");
+                        graphics_ppl.draw_render_target(frame, None, None, &DrawParams::default());
+                        return;
+                    }
+                }
+            }
+        }
+
+        let mut source = frame;
+        for (index, material) in materials.iter().enumerate() {
+            let target = (index + 1 < materials.len()).then(|| targets[index % 2]);
+            if let Err(e) = graphics_ppl.set_render_target(target) {
+                log::warn!("failed to apply a post effect: {e}");
+                return;
+            }
+            // Replacing the target's pixels, which still hold the previous frame
+            let params = DrawParams {
+                material: Some(*material),
+                blend_mode: BlendMode::None,
+                ..Default::default()
+            };
+            graphics_ppl.draw_render_target(source, None, None, &params);
+            source = target.unwrap_or(source);
+        }
+
+        if materials.is_empty() {
+            graphics_ppl.draw_render_target(frame, None, None, &DrawParams::default());
+        }
+    })
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|m| m.modified()).ok()
+}
@@ -0,0 +1,99 @@
+use sdl2::rect::Rect;
+
+use super::{DrawParams, GraphicsPipeline, TextureId};
+use crate::{Point, Vec2};
+
+// Monospace font stored as a grid of glyphs in a texture, in char order starting from first_char.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct BitmapFont {
+    pub texture: TextureId,
+    pub glyph_width: u32,
+    pub glyph_height: u32,
+    pub columns: u32,
+    pub first_char: char,
+}
+
+impl BitmapFont {
+    // Size of the text in pixels, lines being separated by '\n'.
+    pub fn text_size(&self, text: &str, scale: f64) -> (u32, u32) {
+        let columns = text.lines().map(|l| l.chars().count()).max().unwrap_or(0) as u32;
+        let lines = text.lines().count() as u32;
+
+        (
+            (columns as f64 * self.glyph_width as f64 * scale).round() as u32,
+            (lines as f64 * self.glyph_height as f64 * scale).round() as u32,
+        )
+    }
+
+    fn glyph_rect(&self, c: char) -> Option<Rect> {
+        let index = (c as u32).checked_sub(self.first_char as u32)?;
+        Some(Rect::new(
+            ((index % self.columns) * self.glyph_width) as i32,
+            ((index / self.columns) * self.glyph_height) as i32,
+            self.glyph_width,
+            self.glyph_height,
+        ))
+    }
+}
+
+impl GraphicsPipeline {
+    // position is the world position of the top left corner of the text.
+    pub fn draw_text(
+        &mut self,
+        font: &BitmapFont,
+        text: &str,
+        position: &Vec2,
+        scale: f64,
+        params: &DrawParams,
+    ) {
+        let pos = self.camera.get_screen_coordinate(self, position);
+        self.draw_text_screen(font, text, pos, scale, params);
+    }
+
+    pub fn draw_text_screen(
+        &mut self,
+        font: &BitmapFont,
+        text: &str,
+        position: Point,
+        scale: f64,
+        params: &DrawParams,
+    ) {
+        let (width, height) = font.text_size(text, scale);
+        if self.cull(
+            Rect::new(position.x, position.y, width, height),
+            &DrawParams::default(),
+        ) {
+            return;
+        }
+
+        let (texture_width, texture_height) = self.texture_size(font.texture);
+        let glyph_width = (font.glyph_width as f64 * scale).round() as u32;
+        let glyph_height = (font.glyph_height as f64 * scale).round() as u32;
+
+        let params = DrawParams {
+            rotation: 0.,
+            scale: Vec2::ONE,
+            ..*params
+        };
+
+        for (line_index, line) in text.lines().enumerate() {
+            for (column, c) in line.chars().enumerate() {
+                let Some(src) = font.glyph_rect(c) else {
+                    continue;
+                };
+
+                if src.right() as u32 > texture_width || src.bottom() as u32 > texture_height {
+                    continue;
+                }
+
+                let dst = Rect::new(
+                    position.x + (column as u32 * glyph_width) as i32,
+                    position.y + (line_index as u32 * glyph_height) as i32,
+                    glyph_width,
+                    glyph_height,
+                );
+                self.push_quad(font.texture, Some(src), dst, &params);
+            }
+        }
+    }
+}
@@ -0,0 +1,262 @@
+use std::{collections::HashMap, time::Duration};
+
+use super::{DrawParams, GraphicsPipeline, PixelRect, TextureId};
+use crate::Vec2;
+
+// Index of a tile in the tileset texture, left to right then top to bottom.
+pub type TileId = u32;
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct TerrainId(usize);
+
+pub struct Tileset {
+    pub texture: TextureId,
+    pub tile_width: u32,
+    pub tile_height: u32,
+    pub columns: u32,
+    animations: HashMap<TileId, Vec<(TileId, Duration)>>,
+}
+
+// Picks the tile of a cell from which of its 8 neighbors are painted with the same terrain.
+// Corners only count when both adjacent edges are set, so a 47 tiles set covers every case.
+// Masks missing from the rules fall back to the edges only, then to the fallback tile.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Terrain {
+    pub rules: HashMap<u8, TileId>,
+    pub fallback: TileId,
+}
+
+pub struct Tilemap {
+    pub tileset: Tileset,
+    width: u32,
+    height: u32,
+    tiles: Vec<Option<TileId>>,
+    painted: Vec<Option<TerrainId>>,
+    terrains: Vec<Terrain>,
+    elapsed: Duration,
+}
+
+impl Tileset {
+    pub fn new(texture: TextureId, tile_width: u32, tile_height: u32, columns: u32) -> Self {
+        Tileset {
+            texture,
+            tile_width,
+            tile_height,
+            columns,
+            animations: HashMap::new(),
+        }
+    }
+
+    // The tile gets drawn as each frame in turn, for the given durations.
+    pub fn set_animation(&mut self, tile: TileId, frames: &[(TileId, Duration)]) {
+        if frames.is_empty() {
+            self.animations.remove(&tile);
+        } else {
+            self.animations.insert(tile, frames.to_vec());
+        }
+    }
+
+    // Tile to draw for the given one once elapsed time went by.
+    pub fn animated_tile(&self, tile: TileId, elapsed: Duration) -> TileId {
+        let Some(frames) = self.animations.get(&tile) else {
+            return tile;
+        };
+
+        let total: Duration = frames.iter().map(|(_, d)| *d).sum();
+        if total.is_zero() {
+            return frames[0].0;
+        }
+
+        let mut time = Duration::from_nanos((elapsed.as_nanos() % total.as_nanos()) as u64);
+        for (frame, duration) in frames {
+            if time < *duration {
+                return *frame;
+            }
+            time -= *duration;
+        }
+
+        frames[frames.len() - 1].0
+    }
+
+    // Area of the tile in the texture.
+    pub fn tile_rect(&self, tile: TileId) -> PixelRect {
+        PixelRect::new(
+            ((tile % self.columns) * self.tile_width) as i32,
+            ((tile / self.columns) * self.tile_height) as i32,
+            self.tile_width,
+            self.tile_height,
+        )
+    }
+}
+
+impl Terrain {
+    pub const NORTH: u8 = 1;
+    pub const NORTH_EAST: u8 = 1 << 1;
+    pub const EAST: u8 = 1 << 2;
+    pub const SOUTH_EAST: u8 = 1 << 3;
+    pub const SOUTH: u8 = 1 << 4;
+    pub const SOUTH_WEST: u8 = 1 << 5;
+    pub const WEST: u8 = 1 << 6;
+    pub const NORTH_WEST: u8 = 1 << 7;
+
+    const EDGES: u8 = Self::NORTH | Self::EAST | Self::SOUTH | Self::WEST;
+
+    pub fn new(fallback: TileId) -> Self {
+        Terrain {
+            rules: HashMap::new(),
+            fallback,
+        }
+    }
+
+    pub fn rule(mut self, mask: u8, tile: TileId) -> Self {
+        self.rules.insert(mask, tile);
+        self
+    }
+
+    pub fn tile(&self, mask: u8) -> TileId {
+        self.rules
+            .get(&mask)
+            .or_else(|| self.rules.get(&(mask & Self::EDGES)))
+            .copied()
+            .unwrap_or(self.fallback)
+    }
+}
+
+impl Tilemap {
+    pub fn new(tileset: Tileset, width: u32, height: u32) -> Self {
+        let cells = (width * height) as usize;
+
+        Tilemap {
+            tileset,
+            width,
+            height,
+            tiles: vec![None; cells],
+            painted: vec![None; cells],
+            terrains: Vec::new(),
+            elapsed: Duration::ZERO,
+        }
+    }
+
+    pub fn size(&self) -> (u32, u32) {
+        (self.width, self.height)
+    }
+
+    pub fn tile(&self, x: u32, y: u32) -> Option<TileId> {
+        self.index(x, y).and_then(|i| self.tiles[i])
+    }
+
+    // Sets the tile directly, the cell stops being part of a terrain.
+    pub fn set_tile(&mut self, x: u32, y: u32, tile: Option<TileId>) {
+        if let Some(i) = self.index(x, y) {
+            self.tiles[i] = tile;
+            self.painted[i] = None;
+        }
+    }
+
+    pub fn add_terrain(&mut self, terrain: Terrain) -> TerrainId {
+        self.terrains.push(terrain);
+        TerrainId(self.terrains.len() - 1)
+    }
+
+    pub fn terrain(&self, x: u32, y: u32) -> Option<TerrainId> {
+        self.index(x, y).and_then(|i| self.painted[i])
+    }
+
+    // Paints the cell with a terrain, it and its neighbors get their tiles picked again.
+    pub fn paint(&mut self, x: u32, y: u32, terrain: Option<TerrainId>) {
+        let Some(i) = self.index(x, y) else {
+            return;
+        };
+
+        self.painted[i] = terrain;
+        if terrain.is_none() {
+            self.tiles[i] = None;
+        }
+
+        for ny in y.saturating_sub(1)..=(y + 1).min(self.height - 1) {
+            for nx in x.saturating_sub(1)..=(x + 1).min(self.width - 1) {
+                self.autotile_cell(nx, ny);
+            }
+        }
+    }
+
+    // Picks the tiles of every painted cell, to be called once the terrains are loaded.
+    pub fn autotile(&mut self) {
+        for y in 0..self.height {
+            for x in 0..self.width {
+                self.autotile_cell(x, y);
+            }
+        }
+    }
+
+    // Advances the tile animations.
+    pub fn update(&mut self, dt: Duration) {
+        self.elapsed += dt;
+    }
+
+    // position is the world position of the top left corner of the map.
+    pub fn draw(&self, graphics_ppl: &mut GraphicsPipeline, position: &Vec2, params: &DrawParams) {
+        let pixel_per_unit = graphics_ppl.options.pixel_per_unit as f64;
+        let size = Vec2::new(
+            self.tileset.tile_width as f64 / pixel_per_unit,
+            self.tileset.tile_height as f64 / pixel_per_unit,
+        );
+        let down = graphics_ppl.options.down();
+
+        for y in 0..self.height {
+            for x in 0..self.width {
+                let Some(tile) = self.tile(x, y) else {
+                    continue;
+                };
+
+                let tile = self.tileset.animated_tile(tile, self.elapsed);
+                let center = position
+                    + Vec2::new((x as f64 + 0.5) * size.x, 0.)
+                    + down * ((y as f64 + 0.5) * size.y);
+                graphics_ppl.draw_sprite(
+                    self.tileset.texture,
+                    Some(self.tileset.tile_rect(tile)),
+                    &center,
+                    &size,
+                    params,
+                );
+            }
+        }
+    }
+
+    fn index(&self, x: u32, y: u32) -> Option<usize> {
+        (x < self.width && y < self.height).then_some((y * self.width + x) as usize)
+    }
+
+    fn autotile_cell(&mut self, x: u32, y: u32) {
+        let Some(i) = self.index(x, y) else {
+            return;
+        };
+        let Some(terrain) = self.painted[i] else {
+            return;
+        };
+
+        let same = |dx: i32, dy: i32| {
+            let (nx, ny) = (x as i32 + dx, y as i32 + dy);
+            nx >= 0 && ny >= 0 && self.terrain(nx as u32, ny as u32) == Some(terrain)
+        };
+
+        let (north, east, south, west) = (same(0, -1), same(1, 0), same(0, 1), same(-1, 0));
+        let bits = [
+            (north, Terrain::NORTH),
+            (north && east && same(1, -1), Terrain::NORTH_EAST),
+            (east, Terrain::EAST),
+            (south && east && same(1, 1), Terrain::SOUTH_EAST),
+            (south, Terrain::SOUTH),
+            (south && west && same(-1, 1), Terrain::SOUTH_WEST),
+            (west, Terrain::WEST),
+            (north && west && same(-1, -1), Terrain::NORTH_WEST),
+        ];
+        let mask = bits
+            .iter()
+            .filter(|(set, _)| *set)
+            .fold(0, |mask, (_, bit)| mask | bit);
+
+        self.tiles[i] = Some(self.terrains[terrain.0].tile(mask));
+    }
+}
@@ -0,0 +1,238 @@
+use std::time::Duration;
+
+use super::{Color, DrawParams, GraphicsPipeline, PixelRect, TextureId};
+
+const MAX_PIXEL_SIZE: f64 = 32.;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum Effect {
+    Fade(Color),
+    Wipe(Color),
+    Pixelate,
+    Crossfade,
+}
+
+struct Transition {
+    effect: Effect,
+    // Transitions "in" reveal the scene, transitions "out" hide it.
+    reveal: bool,
+    duration: Duration,
+    elapsed: Duration,
+    on_complete: Option<Box<dyn FnOnce()>>,
+}
+
+// Draws transitions over the scene. The scene has to be drawn between begin_frame and end_frame,
+// it goes through an offscreen target so that it can be pixelated or crossfaded.
+#[derive(Default)]
+pub struct Transitions {
+    current: Option<Transition>,
+    // Effect left covering the screen once a transition out is over.
+    covered_by: Option<Effect>,
+    scene: Option<TextureId>,
+    previous_scene: Option<TextureId>,
+    pixelated: Option<TextureId>,
+}
+
+impl Transition {
+    fn progress(&self) -> f64 {
+        if self.duration.is_zero() {
+            return 1.;
+        }
+
+        (self.elapsed.as_secs_f64() / self.duration.as_secs_f64()).min(1.)
+    }
+
+    // How much of the screen the effect covers, from 0 to 1.
+    fn coverage(&self) -> f64 {
+        if self.reveal {
+            1. - self.progress()
+        } else {
+            self.progress()
+        }
+    }
+}
+
+impl Transitions {
+    pub fn new() -> Self {
+        Transitions::default()
+    }
+
+    pub fn fade_out(&mut self, duration: Duration, color: Color) -> &mut Self {
+        self.start(Effect::Fade(color), false, duration)
+    }
+
+    pub fn fade_in(&mut self, duration: Duration, color: Color) -> &mut Self {
+        self.start(Effect::Fade(color), true, duration)
+    }
+
+    pub fn wipe_out(&mut self, duration: Duration, color: Color) -> &mut Self {
+        self.start(Effect::Wipe(color), false, duration)
+    }
+
+    pub fn wipe_in(&mut self, duration: Duration, color: Color) -> &mut Self {
+        self.start(Effect::Wipe(color), true, duration)
+    }
+
+    pub fn pixelate_out(&mut self, duration: Duration) -> &mut Self {
+        self.start(Effect::Pixelate, false, duration)
+    }
+
+    pub fn pixelate_in(&mut self, duration: Duration) -> &mut Self {
+        self.start(Effect::Pixelate, true, duration)
+    }
+
+    // Fades from the last drawn frame to whatever gets drawn next.
+    pub fn crossfade(
+        &mut self,
+        graphics_ppl: &mut GraphicsPipeline,
+        duration: Duration,
+    ) -> Result<&mut Self, String> {
+        let (width, height) = graphics_ppl.options.window_size;
+        let previous_scene = match self.previous_scene {
+            Some(t) => t,
+            None => *self
+                .previous_scene
+                .insert(graphics_ppl.create_render_target(width, height)?),
+        };
+
+        if let Some(scene) = self.scene {
+            let target = graphics_ppl.render_target();
+            graphics_ppl.set_render_target(Some(previous_scene))?;
+            graphics_ppl.draw_render_target(scene, None, None, &DrawParams::default());
+            graphics_ppl.set_render_target(target)?;
+        }
+
+        Ok(self.start(Effect::Crossfade, true, duration))
+    }
+
+    // Called once the transition started last is over.
+    pub fn on_complete<F: FnOnce() + 'static>(&mut self, callback: F) -> &mut Self {
+        if let Some(t) = &mut self.current {
+            t.on_complete = Some(Box::new(callback));
+        }
+        self
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.current.is_some()
+    }
+
+    pub fn update(&mut self, dt: Duration) {
+        let Some(t) = &mut self.current else {
+            return;
+        };
+
+        t.elapsed += dt;
+        if t.elapsed < t.duration {
+            return;
+        }
+
+        let mut t = self.current.take().unwrap();
+        self.covered_by = (!t.reveal).then_some(t.effect);
+        if let Some(callback) = t.on_complete.take() {
+            callback();
+        }
+    }
+
+    pub fn begin_frame(&mut self, graphics_ppl: &mut GraphicsPipeline) -> Result<(), String> {
+        let (width, height) = graphics_ppl.options.window_size;
+        let scene = match self.scene {
+            Some(t) => t,
+            None => *self
+                .scene
+                .insert(graphics_ppl.create_render_target(width, height)?),
+        };
+
+        graphics_ppl.set_render_target(Some(scene))?;
+        graphics_ppl.clear(&Color::BLACK);
+        Ok(())
+    }
+
+    pub fn end_frame(&mut self, graphics_ppl: &mut GraphicsPipeline) -> Result<(), String> {
+        graphics_ppl.set_render_target(None)?;
+
+        let Some(scene) = self.scene else {
+            return Ok(());
+        };
+
+        let (effect, coverage) = match (&self.current, self.covered_by) {
+            (Some(t), _) => (Some(t.effect), t.coverage()),
+            (None, Some(effect)) => (Some(effect), 1.),
+            (None, None) => (None, 0.),
+        };
+
+        let (width, height) = graphics_ppl.options.window_size;
+        match effect {
+            Some(Effect::Pixelate) => {
+                let pixelated = match self.pixelated {
+                    Some(t) => t,
+                    None => *self
+                        .pixelated
+                        .insert(graphics_ppl.create_render_target(width, height)?),
+                };
+
+                let pixel_size = 1. + coverage * (MAX_PIXEL_SIZE - 1.);
+                let small = PixelRect::new(
+                    0,
+                    0,
+                    ((width as f64 / pixel_size) as u32).max(1),
+                    ((height as f64 / pixel_size) as u32).max(1),
+                );
+
+                graphics_ppl.set_render_target(Some(pixelated))?;
+                graphics_ppl.draw_render_target(scene, None, Some(small), &DrawParams::default());
+                graphics_ppl.set_render_target(None)?;
+                graphics_ppl.draw_render_target(
+                    pixelated,
+                    Some(small),
+                    None,
+                    &DrawParams::default(),
+                );
+            }
+            _ => graphics_ppl.draw_render_target(scene, None, None, &DrawParams::default()),
+        }
+
+        match effect {
+            Some(Effect::Fade(color)) => {
+                let color = Color::RGBA(color.r, color.g, color.b, (coverage * 255.) as u8);
+                graphics_ppl.draw_rect_screen(
+                    PixelRect::new(0, 0, width, height),
+                    &color,
+                    true,
+                    &DrawParams::default(),
+                );
+            }
+            Some(Effect::Wipe(color)) => {
+                let covered_width = (coverage * width as f64).round() as u32;
+                if covered_width > 0 {
+                    let rect = PixelRect::new(0, 0, covered_width, height);
+                    graphics_ppl.draw_rect_screen(rect, &color, true, &DrawParams::default());
+                }
+            }
+            Some(Effect::Crossfade) => {
+                if let Some(previous_scene) = self.previous_scene {
+                    let params = DrawParams {
+                        alpha: (coverage * 255.) as u8,
+                        ..Default::default()
+                    };
+                    graphics_ppl.draw_render_target(previous_scene, None, None, &params);
+                }
+            }
+            Some(Effect::Pixelate) | None => {}
+        }
+
+        Ok(())
+    }
+
+    fn start(&mut self, effect: Effect, reveal: bool, duration: Duration) -> &mut Self {
+        self.covered_by = None;
+        self.current = Some(Transition {
+            effect,
+            reveal,
+            duration,
+            elapsed: Duration::ZERO,
+            on_complete: None,
+        });
+        self
+    }
+}
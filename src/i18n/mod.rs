@@ -0,0 +1,186 @@
+use std::{collections::HashMap, fmt, path::Path};
+
+pub use plural::PluralCategory;
+
+mod plural;
+
+// Strings of one language, parsed from "key = value" lines. Plural forms are defined with
+// the plural category as key suffix, e.g. "apples.one = {count} apple" and
+// "apples.other = {count} apples".
+#[derive(Clone, PartialEq, Eq, Debug, Default)]
+pub struct StringTable {
+    strings: HashMap<String, String>,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum Arg {
+    Text(String),
+    Number(f64),
+}
+
+// Translates keys in the current language, falling back to the fallback language then to
+// the key itself so that missing strings stay visible.
+#[derive(Clone, PartialEq, Eq, Debug, Default)]
+pub struct Localization {
+    tables: HashMap<String, StringTable>,
+    language: String,
+    fallback: Option<String>,
+}
+
+impl StringTable {
+    pub fn new() -> Self {
+        StringTable::default()
+    }
+
+    // Empty lines and lines starting with "//" are skipped, "\n" in values is a line break.
+    pub fn parse(source: &str) -> Result<Self, String> {
+        let mut table = StringTable::new();
+        for (number, line) in source.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with("//") {
+                continue;
+            }
+
+            let (key, value) = line
+                .split_once('=')
+                .ok_or_else(|| format!("line {}: expected \"key = value\"", number + 1))?;
+            table.insert(key.trim(), &value.trim().replace("\\n", "\n"));
+        }
+
+        Ok(table)
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, String> {
+        let source = std::fs::read_to_string(path).map_err(|e| e.to_string())?;
+        StringTable::parse(&source)
+    }
+
+    pub fn insert(&mut self, key: &str, value: &str) {
+        self.strings.insert(key.to_string(), value.to_string());
+    }
+
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.strings.get(key).map(String::as_str)
+    }
+}
+
+impl Localization {
+    pub fn new(language: &str) -> Self {
+        Localization {
+            language: language.to_string(),
+            ..Default::default()
+        }
+    }
+
+    pub fn add_language(&mut self, language: &str, table: StringTable) {
+        self.tables.insert(language.to_string(), table);
+    }
+
+    pub fn load_language<P: AsRef<Path>>(&mut self, language: &str, path: P) -> Result<(), String> {
+        self.add_language(language, StringTable::load(path)?);
+        Ok(())
+    }
+
+    pub fn languages(&self) -> impl Iterator<Item = &str> {
+        self.tables.keys().map(String::as_str)
+    }
+
+    pub fn language(&self) -> &str {
+        &self.language
+    }
+
+    pub fn set_language(&mut self, language: &str) -> Result<(), String> {
+        if !self.tables.contains_key(language) {
+            return Err(format!("no strings for language \"{language}\""));
+        }
+
+        self.language = language.to_string();
+        Ok(())
+    }
+
+    pub fn set_fallback(&mut self, language: Option<&str>) {
+        self.fallback = language.map(str::to_string);
+    }
+
+    pub fn has(&self, key: &str) -> bool {
+        self.lookup(key, None).is_some()
+    }
+
+    // Replaces the {name} placeholders with the args. A numeric "count" arg picks the plural
+    // form of the string.
+    pub fn tr(&self, key: &str, args: &[(&str, Arg)]) -> String {
+        let count = args.iter().find_map(|(name, arg)| match arg {
+            Arg::Number(n) if *name == "count" => Some(*n),
+            _ => None,
+        });
+
+        let Some(text) = self.lookup(key, count) else {
+            return key.to_string();
+        };
+
+        args.iter().fold(text.to_string(), |text, (name, arg)| {
+            text.replace(&format!("{{{name}}}"), &arg.to_string())
+        })
+    }
+
+    fn lookup(&self, key: &str, count: Option<f64>) -> Option<&str> {
+        [Some(&self.language), self.fallback.as_ref()]
+            .into_iter()
+            .flatten()
+            .find_map(|language| {
+                let table = self.tables.get(language)?;
+                let plural = count.and_then(|count| {
+                    let category = PluralCategory::of(language, count);
+                    table
+                        .get(&format!("{key}.{}", category.suffix()))
+                        .or_else(|| table.get(&format!("{key}.other")))
+                });
+                plural.or_else(|| table.get(key))
+            })
+    }
+}
+
+impl fmt::Display for Arg {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Arg::Text(t) => write!(f, "{t}"),
+            Arg::Number(n) => write!(f, "{n}"),
+        }
+    }
+}
+
+impl From<&str> for Arg {
+    fn from(value: &str) -> Self {
+        Arg::Text(value.to_string())
+    }
+}
+
+impl From<String> for Arg {
+    fn from(value: String) -> Self {
+        Arg::Text(value)
+    }
+}
+
+impl From<f64> for Arg {
+    fn from(value: f64) -> Self {
+        Arg::Number(value)
+    }
+}
+
+impl From<i32> for Arg {
+    fn from(value: i32) -> Self {
+        Arg::Number(value as f64)
+    }
+}
+
+impl From<u32> for Arg {
+    fn from(value: u32) -> Self {
+        Arg::Number(value as f64)
+    }
+}
+
+impl From<usize> for Arg {
+    fn from(value: usize) -> Self {
+        Arg::Number(value as f64)
+    }
+}
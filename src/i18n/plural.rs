@@ -0,0 +1,171 @@
+// CLDR plural categories, string tables pick the form with their name as key suffix.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum PluralCategory {
+    Zero,
+    One,
+    Two,
+    Few,
+    Many,
+    Other,
+}
+
+// CLDR operands of a number as it's displayed: its integer part, its number of fraction digits
+// and those digits, e.g. i = 1, v = 2 and f = 5 for 1.05. Numbers are displayed with as few
+// digits as they need, so that 1.50 is 1.5 and 2.0 is 2.
+struct Operands {
+    i: u64,
+    v: usize,
+    f: u64,
+}
+
+impl Operands {
+    fn of(count: f64) -> Self {
+        let text = count.abs().to_string();
+        let fraction = text.split_once('.').map_or("", |(_, fraction)| fraction);
+        Operands {
+            i: count.abs() as u64,
+            v: fraction.len(),
+            f: fraction.parse().unwrap_or(0),
+        }
+    }
+}
+
+impl PluralCategory {
+    pub fn suffix(&self) -> &'static str {
+        match self {
+            PluralCategory::Zero => "zero",
+            PluralCategory::One => "one",
+            PluralCategory::Two => "two",
+            PluralCategory::Few => "few",
+            PluralCategory::Many => "many",
+            PluralCategory::Other => "other",
+        }
+    }
+
+    // Rules of the most common languages, by their ISO 639-1 code. Unknown languages use the
+    // english rule.
+    pub fn of(language: &str, count: f64) -> Self {
+        let language = language.split(['-', '_']).next().unwrap_or(language);
+        let Operands { i, v, f } = Operands::of(count);
+        let integer = v == 0;
+
+        match language {
+            "ja" | "zh" | "ko" | "th" | "vi" | "id" | "ms" => PluralCategory::Other,
+            // Millions take "de" in french, e.g. "1 000 000 de pommes"
+            "fr" | "pt" => {
+                if i < 2 {
+                    PluralCategory::One
+                } else if integer && i % 1_000_000 == 0 {
+                    PluralCategory::Many
+                } else {
+                    PluralCategory::Other
+                }
+            }
+            // Decimals also follow the rule of their fraction digits, e.g. 0.1 is one
+            "sr" | "hr" | "bs" => {
+                let one = |x: u64| x % 10 == 1 && x % 100 != 11;
+                let few = |x: u64| (2..=4).contains(&(x % 10)) && !(12..=14).contains(&(x % 100));
+                if (integer && one(i)) || one(f) {
+                    PluralCategory::One
+                } else if (integer && few(i)) || few(f) {
+                    PluralCategory::Few
+                } else {
+                    PluralCategory::Other
+                }
+            }
+            "ru" | "uk" | "be" => {
+                if !integer {
+                    PluralCategory::Other
+                } else if i % 10 == 1 && i % 100 != 11 {
+                    PluralCategory::One
+                } else if (2..=4).contains(&(i % 10)) && !(12..=14).contains(&(i % 100)) {
+                    PluralCategory::Few
+                } else {
+                    PluralCategory::Many
+                }
+            }
+            "pl" => {
+                if !integer {
+                    PluralCategory::Other
+                } else if i == 1 {
+                    PluralCategory::One
+                } else if (2..=4).contains(&(i % 10)) && !(12..=14).contains(&(i % 100)) {
+                    PluralCategory::Few
+                } else {
+                    PluralCategory::Many
+                }
+            }
+            "cs" | "sk" => match (integer, i) {
+                (false, _) => PluralCategory::Many,
+                (true, 1) => PluralCategory::One,
+                (true, 2..=4) => PluralCategory::Few,
+                _ => PluralCategory::Other,
+            },
+            "ar" => match (integer, i % 100) {
+                (false, _) => PluralCategory::Other,
+                (true, _) if i == 0 => PluralCategory::Zero,
+                (true, _) if i == 1 => PluralCategory::One,
+                (true, _) if i == 2 => PluralCategory::Two,
+                (true, 3..=10) => PluralCategory::Few,
+                (true, 11..=99) => PluralCategory::Many,
+                _ => PluralCategory::Other,
+            },
+            _ => {
+                if integer && i == 1 {
+                    PluralCategory::One
+                } else {
+                    PluralCategory::Other
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn french_one_covers_zero_and_one_with_decimals() {
+        for count in [0., 0.5, 1., 1.5, 1.99] {
+            assert_eq!(
+                PluralCategory::of("fr", count),
+                PluralCategory::One,
+                "{count}"
+            );
+        }
+        for count in [2., 2.5, 10., 999_999.] {
+            assert_eq!(
+                PluralCategory::of("fr-CA", count),
+                PluralCategory::Other,
+                "{count}"
+            );
+        }
+        assert_eq!(PluralCategory::of("fr", 2e6), PluralCategory::Many);
+        assert_eq!(PluralCategory::of("fr", 2_000_000.5), PluralCategory::Other);
+    }
+
+    #[test]
+    fn english_one_has_no_decimals() {
+        assert_eq!(PluralCategory::of("en", 1.), PluralCategory::One);
+        assert_eq!(PluralCategory::of("en", 1.5), PluralCategory::Other);
+        assert_eq!(PluralCategory::of("en", 0.), PluralCategory::Other);
+    }
+
+    #[test]
+    fn croatian_decimals_follow_their_fraction_digits() {
+        assert_eq!(PluralCategory::of("hr", 21.), PluralCategory::One);
+        assert_eq!(PluralCategory::of("hr", 0.1), PluralCategory::One);
+        assert_eq!(PluralCategory::of("hr", 1.2), PluralCategory::Few);
+        assert_eq!(PluralCategory::of("hr", 5.), PluralCategory::Other);
+        assert_eq!(PluralCategory::of("hr", 0.11), PluralCategory::Other);
+    }
+
+    #[test]
+    fn operands_are_those_of_the_displayed_number() {
+        let Operands { i, v, f } = Operands::of(-1.05);
+        assert_eq!((i, v, f), (1, 2, 5));
+        let Operands { i, v, f } = Operands::of(2.);
+        assert_eq!((i, v, f), (2, 0, 0));
+    }
+}
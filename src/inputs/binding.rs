@@ -0,0 +1,134 @@
+use super::{
+    AxisControl, ButtonControl, Control, GamepadAxis, GamepadButton, InputRegistrationError,
+    InputScheme, InputsPipeline, MouseButton, Scancode,
+};
+
+pub struct ButtonBinding<'a, T>
+where
+    T: InputScheme,
+{
+    pipeline: &'a mut InputsPipeline<T>,
+    input_id: T,
+    controls: Vec<Control>,
+    context: Option<String>,
+}
+
+pub struct AxisBinding<'a, T>
+where
+    T: InputScheme,
+{
+    pipeline: &'a mut InputsPipeline<T>,
+    input_id: T,
+    controls: Vec<Control>,
+    context: Option<String>,
+}
+
+impl<'a, T> ButtonBinding<'a, T>
+where
+    T: InputScheme,
+{
+    pub(crate) fn new(pipeline: &'a mut InputsPipeline<T>, input_id: T) -> Self {
+        ButtonBinding {
+            pipeline,
+            input_id,
+            controls: Vec::new(),
+            context: None,
+        }
+    }
+
+    pub fn keyboard(mut self, scancode: Scancode) -> Self {
+        self.controls
+            .push(Control::Button(ButtonControl::Keyboard(scancode)));
+        self
+    }
+
+    pub fn gamepad(mut self, button: GamepadButton) -> Self {
+        self.controls
+            .push(Control::Button(ButtonControl::Gamepad(button)));
+        self
+    }
+
+    pub fn mouse(mut self, button: MouseButton) -> Self {
+        self.controls
+            .push(Control::Button(ButtonControl::Mouse(button)));
+        self
+    }
+
+    pub fn keyboard_chord(mut self, first: Scancode, second: Scancode) -> Self {
+        self.controls
+            .push(Control::Button(ButtonControl::KeyboardChord(first, second)));
+        self
+    }
+
+    pub fn gamepad_chord(mut self, first: GamepadButton, second: GamepadButton) -> Self {
+        self.controls
+            .push(Control::Button(ButtonControl::GamepadChord(first, second)));
+        self
+    }
+
+    // Pressed past threshold percent of the axis, e.g. to shoot with a trigger.
+    pub fn gamepad_axis(mut self, axis: GamepadAxis, threshold: i8) -> Self {
+        self.controls
+            .push(Control::Button(ButtonControl::GamepadAxis(axis, threshold)));
+        self
+    }
+
+    // See InputsPipeline::set_context.
+    pub fn context(mut self, context: &str) -> Self {
+        self.context = Some(context.to_string());
+        self
+    }
+
+    pub fn register(self) -> Result<(), InputRegistrationError<T>> {
+        if let Some(context) = &self.context {
+            self.pipeline.set_context(self.input_id, Some(context))?;
+        }
+        self.pipeline.register(self.input_id, &self.controls)
+    }
+}
+
+impl<'a, T> AxisBinding<'a, T>
+where
+    T: InputScheme,
+{
+    pub(crate) fn new(pipeline: &'a mut InputsPipeline<T>, input_id: T) -> Self {
+        AxisBinding {
+            pipeline,
+            input_id,
+            controls: Vec::new(),
+            context: None,
+        }
+    }
+
+    pub fn keyboard(mut self, min: Scancode, max: Scancode) -> Self {
+        self.controls
+            .push(Control::Axis(AxisControl::Keyboard(min, max)));
+        self
+    }
+
+    pub fn gamepad(mut self, axis: GamepadAxis) -> Self {
+        self.controls
+            .push(Control::Axis(AxisControl::Gamepad(axis)));
+        self
+    }
+
+    // Moves the axis to value percent while the button is held.
+    pub fn gamepad_button(mut self, button: GamepadButton, value: i8) -> Self {
+        self.controls
+            .push(Control::Axis(AxisControl::GamepadButton(button, value)));
+        self
+    }
+
+    // See InputsPipeline::set_context.
+    pub fn context(mut self, context: &str) -> Self {
+        self.context = Some(context.to_string());
+        self
+    }
+
+    pub fn register(self) -> Result<(), InputRegistrationError<T>> {
+        if let Some(context) = &self.context {
+            self.pipeline.set_context(self.input_id, Some(context))?;
+        }
+        self.pipeline.register(self.input_id, &self.controls)
+    }
+}
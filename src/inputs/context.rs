@@ -0,0 +1,42 @@
+// Contexts pushed by the game, e.g. "gameplay" then "menu" when a menu opens, the last pushed
+// having priority over the ones below: a control bound in several active contexts only reaches
+// the topmost one's input, and a consuming context deactivates every context below it.
+#[derive(Default)]
+pub(super) struct ContextStack {
+    // Names and whether they consume, bottom first.
+    contexts: Vec<(String, bool)>,
+}
+
+impl ContextStack {
+    // A context already on the stack is moved to the top.
+    pub(super) fn push(&mut self, name: &str, consume: bool) {
+        self.remove(name);
+        self.contexts.push((name.to_string(), consume));
+    }
+
+    pub(super) fn pop(&mut self) -> Option<String> {
+        self.contexts.pop().map(|(name, _)| name)
+    }
+
+    pub(super) fn remove(&mut self, name: &str) -> bool {
+        let count = self.contexts.len();
+        self.contexts.retain(|(n, _)| n != name);
+        self.contexts.len() != count
+    }
+
+    pub(super) fn top(&self) -> Option<&str> {
+        self.contexts.last().map(|(name, _)| name.as_str())
+    }
+
+    // Active contexts, top first, down to the first consuming one included.
+    pub(super) fn active(&self) -> Vec<&str> {
+        let mut active = Vec::new();
+        for (name, consume) in self.contexts.iter().rev() {
+            active.push(name.as_str());
+            if *consume {
+                break;
+            }
+        }
+        active
+    }
+}
@@ -0,0 +1,175 @@
+use sdl2::controller::{Button, GameController};
+
+use super::{AxisControl, ButtonControl, Control, GamepadAxis, GamepadButton, MouseButton};
+
+const MICROSOFT: u16 = 0x045e;
+const SONY: u16 = 0x054c;
+const NINTENDO: u16 = 0x057e;
+
+// What a player plays with, gamepads being told apart by their labels.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum Device {
+    KeyboardMouse,
+    Xbox,
+    PlayStation,
+    Nintendo,
+    // Labelled like Xbox controllers, as most of them are.
+    Gamepad,
+}
+
+impl Device {
+    pub(super) fn of(gamepad: &GameController) -> Self {
+        let name = gamepad.name().to_lowercase();
+        match gamepad.vendor_id() {
+            Some(MICROSOFT) => Device::Xbox,
+            Some(SONY) => Device::PlayStation,
+            Some(NINTENDO) => Device::Nintendo,
+            _ if name.contains("xbox") => Device::Xbox,
+            _ if ["playstation", "dualshock", "dualsense", "ps3", "ps4", "ps5"]
+                .iter()
+                .any(|n| name.contains(n)) =>
+            {
+                Device::PlayStation
+            }
+            _ if name.contains("nintendo") || name.contains("switch") => Device::Nintendo,
+            _ => Device::Gamepad,
+        }
+    }
+
+    pub fn is_gamepad(&self) -> bool {
+        *self != Device::KeyboardMouse
+    }
+
+    // Whether the control is on this device.
+    pub fn has(&self, control: &Control) -> bool {
+        let on_gamepad = matches!(
+            control,
+            Control::Button(
+                ButtonControl::Gamepad(_)
+                    | ButtonControl::GamepadChord(..)
+                    | ButtonControl::GamepadAxis(..)
+            ) | Control::Axis(AxisControl::Gamepad(_) | AxisControl::GamepadButton(..))
+        );
+        on_gamepad == self.is_gamepad()
+    }
+}
+
+// Short name of a control as printed on the device, e.g. "A" or "Cross" for the bottom face
+// button, for text prompts like "press [A]".
+pub fn control_label(control: &Control, device: Device) -> String {
+    match control {
+        Control::Button(ButtonControl::Keyboard(key)) => key_label(key.name()),
+        Control::Button(ButtonControl::Mouse(button)) => mouse_label(*button).to_string(),
+        Control::Button(ButtonControl::Gamepad(button)) => {
+            button_label(*button, device).to_string()
+        }
+        Control::Button(ButtonControl::KeyboardChord(first, second)) => {
+            format!("{}+{}", key_label(first.name()), key_label(second.name()))
+        }
+        Control::Button(ButtonControl::GamepadChord(first, second)) => format!(
+            "{}+{}",
+            button_label(*first, device),
+            button_label(*second, device)
+        ),
+        Control::Axis(AxisControl::Keyboard(min, max)) => {
+            format!("{}/{}", key_label(min.name()), key_label(max.name()))
+        }
+        Control::Button(ButtonControl::GamepadAxis(axis, _))
+        | Control::Axis(AxisControl::Gamepad(axis)) => axis_label(*axis, device).to_string(),
+        Control::Axis(AxisControl::GamepadButton(button, _)) => {
+            button_label(*button, device).to_string()
+        }
+    }
+}
+
+fn key_label(name: &str) -> String {
+    match name {
+        "Up" | "Down" | "Left" | "Right" => return name.to_string(),
+        "Escape" => return "Esc".to_string(),
+        "Return" => return "Enter".to_string(),
+        _ => {}
+    }
+    for (prefix, short) in [("Left ", "L"), ("Right ", "R"), ("Keypad ", "Num ")] {
+        if let Some(rest) = name.strip_prefix(prefix) {
+            return format!("{short}{rest}");
+        }
+    }
+    name.to_string()
+}
+
+fn mouse_label(button: MouseButton) -> &'static str {
+    match button {
+        MouseButton::Left => "LMB",
+        MouseButton::Right => "RMB",
+        MouseButton::Middle => "MMB",
+        MouseButton::X1 => "M4",
+        MouseButton::X2 => "M5",
+        MouseButton::Unknown => "Mouse",
+    }
+}
+
+// SDL names buttons by their position on an Xbox controller, e.g. A is always the bottom one.
+fn button_label(button: GamepadButton, device: Device) -> &'static str {
+    use Button::*;
+
+    match (device, button) {
+        (Device::PlayStation, A) => "Cross",
+        (Device::PlayStation, B) => "Circle",
+        (Device::PlayStation, X) => "Square",
+        (Device::PlayStation, Y) => "Triangle",
+        (Device::PlayStation, LeftShoulder) => "L1",
+        (Device::PlayStation, RightShoulder) => "R1",
+        (Device::PlayStation, LeftStick) => "L3",
+        (Device::PlayStation, RightStick) => "R3",
+        (Device::PlayStation, Back) => "Share",
+        (Device::PlayStation, Start) => "Options",
+        (Device::PlayStation, Guide) => "PS",
+        (Device::PlayStation, Touchpad) => "Touchpad",
+        (Device::Nintendo, A) => "B",
+        (Device::Nintendo, B) => "A",
+        (Device::Nintendo, X) => "Y",
+        (Device::Nintendo, Y) => "X",
+        (Device::Nintendo, LeftShoulder) => "L",
+        (Device::Nintendo, RightShoulder) => "R",
+        (Device::Nintendo, Back) => "-",
+        (Device::Nintendo, Start) => "+",
+        (Device::Nintendo, Guide) => "Home",
+        (Device::Nintendo, Misc1) => "Capture",
+        (_, A) => "A",
+        (_, B) => "B",
+        (_, X) => "X",
+        (_, Y) => "Y",
+        (_, LeftShoulder) => "LB",
+        (_, RightShoulder) => "RB",
+        (_, Back) => "View",
+        (_, Start) => "Menu",
+        (_, Guide) => "Guide",
+        (_, Misc1) => "Share",
+        (_, Touchpad) => "Touchpad",
+        (_, LeftStick) => "LS",
+        (_, RightStick) => "RS",
+        (_, DPadUp) => "Up",
+        (_, DPadDown) => "Down",
+        (_, DPadLeft) => "Left",
+        (_, DPadRight) => "Right",
+        (_, Paddle1) => "P1",
+        (_, Paddle2) => "P2",
+        (_, Paddle3) => "P3",
+        (_, Paddle4) => "P4",
+    }
+}
+
+fn axis_label(axis: GamepadAxis, device: Device) -> &'static str {
+    match (device, axis) {
+        (Device::PlayStation, GamepadAxis::LeftX | GamepadAxis::LeftY) => "L",
+        (Device::PlayStation, GamepadAxis::RightX | GamepadAxis::RightY) => "R",
+        (Device::PlayStation, GamepadAxis::TriggerLeft) => "L2",
+        (Device::PlayStation, GamepadAxis::TriggerRight) => "R2",
+        (Device::Nintendo, GamepadAxis::TriggerLeft) => "ZL",
+        (Device::Nintendo, GamepadAxis::TriggerRight) => "ZR",
+        (_, GamepadAxis::LeftX | GamepadAxis::LeftY) => "LS",
+        (_, GamepadAxis::RightX | GamepadAxis::RightY) => "RS",
+        (_, GamepadAxis::TriggerLeft) => "LT",
+        (_, GamepadAxis::TriggerRight) => "RT",
+    }
+}
@@ -1,10 +1,40 @@
-use std::{collections::HashMap, error::Error, fmt::Display, hash::Hash};
+use std::{
+    collections::{HashMap, HashSet},
+    error::Error,
+    fmt::Display,
+    hash::Hash,
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
 
-use sdl2::{event::Event, EventPump};
+use sdl2::{
+    controller::GameController, event::Event, keyboard::TextInputUtil, EventPump,
+    GameControllerSubsystem,
+};
+
+use crate::{graphics::WindowId, Point};
+
+use context::ContextStack;
+use recording::InputRecorder;
+
+pub use binding::{AxisBinding, ButtonBinding};
+pub use device::{control_label, Device};
+pub use text::TextInputState;
+pub use touch::{Finger, FingerId, Gesture, TouchState};
+
+mod binding;
+mod context;
+mod device;
+mod names;
+mod recording;
+mod text;
+mod touch;
 
 pub type Scancode = sdl2::keyboard::Scancode;
 pub type GamepadButton = sdl2::controller::Button;
 pub type GamepadAxis = sdl2::controller::Axis;
+pub type MouseButton = sdl2::mouse::MouseButton;
+pub type WindowEvent = sdl2::event::WindowEvent;
 
 pub trait InputScheme: Hash + Eq + std::fmt::Debug + Display + Copy {}
 
@@ -18,12 +48,21 @@ pub enum Control {
 pub enum ButtonControl {
     Keyboard(Scancode),
     Gamepad(GamepadButton),
+    Mouse(MouseButton),
+    // Pressed while both buttons are held.
+    KeyboardChord(Scancode, Scancode),
+    GamepadChord(GamepadButton, GamepadButton),
+    // Pressed while the axis is past a threshold in percent, the negative side's for negative
+    // thresholds, e.g. (TriggerRight, 50) for a trigger pulled halfway.
+    GamepadAxis(GamepadAxis, i8),
 }
 
 #[derive(PartialEq, Eq, Hash, Clone, Copy)]
 pub enum AxisControl {
     Keyboard(Scancode, Scancode),
     Gamepad(GamepadAxis),
+    // Value in percent while the button is held, e.g. 100 for a button accelerating fully.
+    GamepadButton(GamepadButton, i8),
 }
 
 #[derive(Clone, Copy, PartialEq, Eq)]
@@ -32,6 +71,12 @@ pub enum ButtonState {
     Up,
 }
 
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum BufferWindow {
+    Time(Duration),
+    Frames(u64),
+}
+
 pub enum Input {
     Button(ButtonInputData),
     Axis(AxisInputData),
@@ -40,11 +85,16 @@ pub enum Input {
 pub struct ButtonInputData {
     pub value: ButtonState,
     pub changed_this_frame: bool,
+    pub double_tapped: bool,
+    last_pressed: Option<Instant>,
+    buffered_press: Option<(Instant, u64)>,
+    // Whether the controls are held, which differs from value for toggled inputs.
+    held: bool,
     controls: Vec<ButtonControl>,
 }
 
 pub struct AxisInputData {
-    value: f64,
+    pub value: f64,
     controls: Vec<AxisControl>,
 }
 
@@ -54,15 +104,81 @@ where
     T: InputScheme,
 {
     ControlBusy(T),
+    InputAlreadyRegistered(T),
+    MixedControls(T),
+    // A gamepad axis used as a button has a threshold of 0 or past 100 percent.
+    InvalidThreshold(T),
 }
 
 pub struct InputsPipeline<T>
 where
     T: InputScheme,
 {
-    event_pump: EventPump,
-    controls_input: HashMap<Control, T>,
+    // None without SDL, see detached.
+    event_pump: Option<EventPump>,
+    // None when controllers are disabled.
+    controller_subsystem: Option<GameControllerSubsystem>,
+    gamepads: Vec<Option<GameController>>,
+    touch: TouchState,
+    text_input_util: Option<TextInputUtil>,
+    text_input: TextInputState,
+    clipboard_updated: bool,
+    held_buttons: HashSet<ButtonControl>,
+    // Most deflected value of each axis among the gamepads.
+    gamepad_axes: HashMap<GamepadAxis, f64>,
+    // Buttons and axes of each gamepad by instance id, merged into held_buttons and
+    // gamepad_axes.
+    pads: HashMap<u32, PadState>,
+    mouse_position: Point,
+    mouse_wheel: Point,
+    mouse_window: Option<WindowId>,
+    focused_window: Option<WindowId>,
+    // Events of the last frame by window.
+    window_events: Vec<(WindowId, WindowEvent)>,
+    // Files dropped on a window during the last frame.
+    dropped_files: Vec<(WindowId, PathBuf)>,
+    double_tap_window: Duration,
+    frame: u64,
+    // Inputs by control, several inputs sharing a control when they're in different contexts.
+    controls_input: HashMap<Control, Vec<T>>,
     inputs: HashMap<T, Input>,
+    // Context of the inputs in one, the others always being active.
+    input_contexts: HashMap<T, String>,
+    contexts: ContextStack,
+    // Device each player last used, by player. The keyboard and mouse belong to player 0.
+    last_devices: HashMap<usize, Device>,
+    // Buttons that stay down from one press to the next, for players who can't hold them.
+    toggled: HashSet<T>,
+    recorder: Option<InputRecorder>,
+}
+
+#[derive(Default)]
+struct PadState {
+    buttons: HashSet<GamepadButton>,
+    axes: HashMap<GamepadAxis, f64>,
+}
+
+impl ButtonInputData {
+    fn new(controls: Vec<ButtonControl>) -> Self {
+        ButtonInputData {
+            value: ButtonState::Up,
+            changed_this_frame: false,
+            double_tapped: false,
+            last_pressed: None,
+            buffered_press: None,
+            held: false,
+            controls,
+        }
+    }
+}
+
+impl AxisInputData {
+    fn new(controls: Vec<AxisControl>) -> Self {
+        AxisInputData {
+            value: 0.,
+            controls,
+        }
+    }
 }
 
 impl<T> Error for InputRegistrationError<T> where T: InputScheme {}
@@ -76,6 +192,19 @@ where
             InputRegistrationError::ControlBusy(id) => {
                 write!(f, "Control already assigned to {}", id)
             }
+            InputRegistrationError::InputAlreadyRegistered(id) => {
+                write!(f, "Input {} is already registered", id)
+            }
+            InputRegistrationError::MixedControls(id) => {
+                write!(f, "Input {} mixes button and axis controls", id)
+            }
+            InputRegistrationError::InvalidThreshold(id) => {
+                write!(
+                    f,
+                    "Input {} has a gamepad axis threshold of 0 or past 100 percent",
+                    id
+                )
+            }
         }
     }
 }
@@ -84,134 +213,893 @@ impl<T> InputsPipeline<T>
 where
     T: InputScheme,
 {
-    pub(crate) fn new(event_pump: EventPump) -> Self {
-        let controller_inputs = HashMap::new();
-        let inputs = HashMap::new();
+    pub(crate) fn new(
+        event_pump: EventPump,
+        controller_subsystem: Option<GameControllerSubsystem>,
+        text_input_util: TextInputUtil,
+    ) -> Self {
+        InputsPipeline {
+            event_pump: Some(event_pump),
+            controller_subsystem,
+            text_input_util: Some(text_input_util),
+            ..InputsPipeline::detached()
+        }
+    }
 
+    // Without SDL, the events being passed to handle_events, e.g. in tests.
+    fn detached() -> Self {
         InputsPipeline {
-            event_pump,
-            controls_input: controller_inputs,
-            inputs,
+            event_pump: None,
+            controller_subsystem: None,
+            gamepads: Vec::new(),
+            touch: TouchState::default(),
+            text_input_util: None,
+            text_input: TextInputState::default(),
+            clipboard_updated: false,
+            held_buttons: HashSet::new(),
+            gamepad_axes: HashMap::new(),
+            pads: HashMap::new(),
+            mouse_position: Point::ZERO,
+            mouse_window: None,
+            focused_window: None,
+            window_events: Vec::new(),
+            dropped_files: Vec::new(),
+            mouse_wheel: Point::ZERO,
+            double_tap_window: Duration::from_millis(250),
+            frame: 0,
+            controls_input: HashMap::new(),
+            inputs: HashMap::new(),
+            input_contexts: HashMap::new(),
+            contexts: ContextStack::default(),
+            last_devices: HashMap::new(),
+            toggled: HashSet::new(),
+            recorder: None,
         }
     }
 
+    pub fn bind_button(&mut self, input_id: T) -> ButtonBinding<'_, T> {
+        ButtonBinding::new(self, input_id)
+    }
+
+    pub fn bind_axis(&mut self, input_id: T) -> AxisBinding<'_, T> {
+        AxisBinding::new(self, input_id)
+    }
+
+    // The input kind is deduced from the controls, which must all be buttons or all be axes.
+    // Controls can only be bound to one input of each context, see set_context.
     pub fn register(
         &mut self,
         input_id: T,
-        input: Input,
-        controls: &Vec<Control>,
+        controls: &[Control],
     ) -> Result<(), InputRegistrationError<T>> {
-        for c in controls {
-            if let Some(i) = self.controls_input.get(c) {
-                return Err(InputRegistrationError::ControlBusy(*i));
+        if self.inputs.contains_key(&input_id) {
+            return Err(InputRegistrationError::InputAlreadyRegistered(input_id));
+        }
+
+        // The axis never goes past 100 percent and a threshold of 0 is never held, see is_held
+        let invalid_threshold = controls.iter().any(|c| match c {
+            Control::Button(ButtonControl::GamepadAxis(_, threshold)) => {
+                *threshold == 0 || threshold.unsigned_abs() > 100
             }
+            _ => false,
+        });
+        if invalid_threshold {
+            return Err(InputRegistrationError::InvalidThreshold(input_id));
         }
 
+        let context = self.input_contexts.get(&input_id).cloned();
+        if let Some(i) = self.busy_control(input_id, context.as_deref(), controls) {
+            return Err(InputRegistrationError::ControlBusy(i));
+        }
+
+        let buttons: Vec<ButtonControl> = controls
+            .iter()
+            .filter_map(|c| match c {
+                Control::Button(b) => Some(*b),
+                _ => None,
+            })
+            .collect();
+
+        let axes: Vec<AxisControl> = controls
+            .iter()
+            .filter_map(|c| match c {
+                Control::Axis(a) => Some(*a),
+                _ => None,
+            })
+            .collect();
+
+        let input = match (buttons.is_empty(), axes.is_empty()) {
+            (_, true) => Input::Button(ButtonInputData::new(buttons)),
+            (true, false) => Input::Axis(AxisInputData::new(axes)),
+            (false, false) => return Err(InputRegistrationError::MixedControls(input_id)),
+        };
+
+        for c in controls {
+            self.controls_input.entry(*c).or_default().push(input_id);
+        }
+        self.inputs.insert(input_id, input);
+
+        Ok(())
+    }
+
+    // Returns false if the input wasn't registered.
+    pub fn deregister(&mut self, input_id: &T) -> bool {
+        if self.inputs.remove(input_id).is_none() {
+            return false;
+        }
+
+        for inputs in self.controls_input.values_mut() {
+            inputs.retain(|i| i != input_id);
+        }
+        self.controls_input.retain(|_, inputs| !inputs.is_empty());
+        true
+    }
+
+    // Puts an input in a context, e.g. "menu", or back in none. Inputs in a context only react
+    // while it's active, see push_context, and can share their controls with the inputs of other
+    // contexts. Kept when the input is registered again.
+    pub fn set_context(
+        &mut self,
+        input_id: T,
+        context: Option<&str>,
+    ) -> Result<(), InputRegistrationError<T>> {
+        if let Some(i) = self.busy_control(input_id, context, &self.controls(&input_id)) {
+            return Err(InputRegistrationError::ControlBusy(i));
+        }
+
+        match context {
+            Some(context) => self.input_contexts.insert(input_id, context.to_string()),
+            None => self.input_contexts.remove(&input_id),
+        };
         Ok(())
     }
 
+    pub fn context(&self, input_id: &T) -> Option<&str> {
+        self.input_contexts.get(input_id).map(String::as_str)
+    }
+
+    // Activates a context over the others, e.g. "menu" when a menu is opened over "gameplay".
+    // Controls bound in several active contexts only reach the input of the topmost, and a
+    // consuming context deactivates all those below it, e.g. "text" while typing in a text box
+    // for gameplay not to react to the letters. A context already pushed is moved to the top.
+    pub fn push_context(&mut self, context: &str, consume: bool) {
+        self.contexts.push(context, consume);
+    }
+
+    pub fn pop_context(&mut self) -> Option<String> {
+        self.contexts.pop()
+    }
+
+    // Removes a context wherever it is, e.g. when a scene under the top one is closed. Returns
+    // false if it wasn't pushed.
+    pub fn remove_context(&mut self, context: &str) -> bool {
+        self.contexts.remove(context)
+    }
+
+    pub fn top_context(&self) -> Option<&str> {
+        self.contexts.top()
+    }
+
+    pub fn is_context_active(&self, context: &str) -> bool {
+        self.contexts.active().contains(&context)
+    }
+
+    // Other input of the same context already bound to one of the controls.
+    fn busy_control(&self, input_id: T, context: Option<&str>, controls: &[Control]) -> Option<T> {
+        controls
+            .iter()
+            .filter_map(|c| self.controls_input.get(c))
+            .flatten()
+            .find(|i| **i != input_id && self.context(i) == context)
+            .copied()
+    }
+
+    // Ids of the registered inputs, in no particular order.
+    pub fn inputs(&self) -> impl Iterator<Item = T> + '_ {
+        self.inputs.keys().copied()
+    }
+
+    // Controls bound to an input, empty if it isn't registered.
+    pub fn controls(&self, input_id: &T) -> Vec<Control> {
+        match self.inputs.get(input_id) {
+            Some(Input::Button(data)) => {
+                data.controls.iter().map(|c| Control::Button(*c)).collect()
+            }
+            Some(Input::Axis(data)) => data.controls.iter().map(|c| Control::Axis(*c)).collect(),
+            None => Vec::new(),
+        }
+    }
+
+    // Hold to press conversion: a toggled button goes down when pressed and back up when pressed
+    // again. Kept when the input is registered again.
+    pub fn set_toggled(&mut self, input_id: T, toggled: bool) {
+        if toggled {
+            self.toggled.insert(input_id);
+        } else {
+            self.toggled.remove(&input_id);
+        }
+    }
+
+    pub fn is_toggled(&self, input_id: &T) -> bool {
+        self.toggled.contains(input_id)
+    }
+
+    // Device the player last pressed something on, for prompts to show its controls. Defaults
+    // to the player's gamepad, or to the keyboard and mouse for player 0.
+    pub fn last_device(&self, player: usize) -> Device {
+        if let Some(device) = self.last_devices.get(&player) {
+            return *device;
+        }
+        match self.gamepads.get(player) {
+            Some(Some(gamepad)) => Device::of(gamepad),
+            _ if player == 0 => Device::KeyboardMouse,
+            _ => Device::Gamepad,
+        }
+    }
+
+    // Controls of an input on a device, e.g. to show the ones of the device last used.
+    pub fn device_controls(&self, input_id: &T, device: Device) -> Vec<Control> {
+        let mut controls = self.controls(input_id);
+        controls.retain(|c| device.has(c));
+        controls
+    }
+
     pub fn read(&self, key: &T) -> Option<&Input> {
         self.inputs.get(key)
     }
 
-    pub(crate) fn process_events(&mut self) {
-        let inputs: HashMap<Control, Event> = self
+    // Whether a control is currently held, regardless of what it is bound to.
+    pub fn is_held(&self, control: &ButtonControl) -> bool {
+        is_held(&self.held_buttons, &self.gamepad_axes, control)
+    }
+
+    // Last known position of a gamepad axis, in the [-1, 1] range.
+    pub fn gamepad_axis(&self, axis: GamepadAxis) -> f64 {
+        self.gamepad_axes.get(&axis).copied().unwrap_or(0.)
+    }
+
+    // Position of the cursor in the window it's over, in pixels.
+    pub fn mouse_position(&self) -> Point {
+        self.mouse_position
+    }
+
+    // Window the cursor is over.
+    pub fn mouse_window(&self) -> Option<WindowId> {
+        self.mouse_window
+    }
+
+    // Window receiving keyboard input.
+    pub fn focused_window(&self) -> Option<WindowId> {
+        self.focused_window
+    }
+
+    // Events of the window during the last frame.
+    pub fn window_events(&self, window: WindowId) -> impl Iterator<Item = &WindowEvent> {
+        self.window_events
+            .iter()
+            .filter(move |(id, _)| *id == window)
+            .map(|(_, e)| e)
+    }
+
+    // Files dropped on the window during the last frame, in the order they were dropped.
+    pub fn dropped_files(&self, window: WindowId) -> impl Iterator<Item = &Path> {
+        self.dropped_files
+            .iter()
+            .filter(move |(id, _)| *id == window)
+            .map(|(_, path)| path.as_path())
+    }
+
+    // Whether the window's close button was clicked during the last frame.
+    pub fn close_requested(&self, window: WindowId) -> bool {
+        self.window_events(window)
+            .any(|e| matches!(e, WindowEvent::Close))
+    }
+
+    // Wheel scrolling during the last frame.
+    pub fn mouse_wheel(&self) -> Point {
+        self.mouse_wheel
+    }
+
+    pub fn just_pressed(&self, key: &T) -> bool {
+        match self.inputs.get(key) {
+            Some(Input::Button(b)) => b.changed_this_frame && b.value == ButtonState::Down,
+            _ => false,
+        }
+    }
+
+    // Whether the button was pressed within the window and the press hasn't been consumed yet.
+    pub fn buffered_press(&self, key: &T, window: BufferWindow) -> bool {
+        match self.inputs.get(key) {
+            Some(Input::Button(b)) => b
+                .buffered_press
+                .is_some_and(|press| self.in_buffer_window(press, window)),
+            _ => false,
+        }
+    }
+
+    // Same as buffered_press, but the press won't be reported again.
+    pub fn consume_press(&mut self, key: &T, window: BufferWindow) -> bool {
+        let buffered = self.buffered_press(key, window);
+        if let Some(Input::Button(b)) = self.inputs.get_mut(key) {
+            b.buffered_press = None;
+        }
+
+        buffered
+    }
+
+    fn in_buffer_window(&self, (time, frame): (Instant, u64), window: BufferWindow) -> bool {
+        match window {
+            BufferWindow::Time(duration) => time.elapsed() <= duration,
+            BufferWindow::Frames(frames) => self.frame - frame <= frames,
+        }
+    }
+
+    pub fn touch(&self) -> &TouchState {
+        &self.touch
+    }
+
+    pub fn start_text_input(&mut self) {
+        self.text_input.reset();
+        if let Some(util) = &self.text_input_util {
+            util.start();
+        }
+    }
+
+    pub fn stop_text_input(&mut self) {
+        if let Some(util) = &self.text_input_util {
+            util.stop();
+        }
+        self.text_input.reset();
+    }
+
+    pub fn is_text_input_active(&self) -> bool {
+        self.text_input_util
+            .as_ref()
+            .is_some_and(TextInputUtil::is_active)
+    }
+
+    pub fn text_input(&self) -> &TextInputState {
+        &self.text_input
+    }
+
+    // Whether the clipboard content changed during the last frame.
+    pub fn clipboard_updated(&self) -> bool {
+        self.clipboard_updated
+    }
+
+    // Maximum delay between two presses of a button for them to count as a double tap.
+    pub fn set_double_tap_window(&mut self, window: Duration) {
+        self.double_tap_window = window;
+    }
+
+    // Replaces the file being recorded to, if any, see EngineBuilder::record_input.
+    pub fn record_to<P: AsRef<std::path::Path>>(&mut self, path: P) -> Result<(), String> {
+        self.recorder = Some(InputRecorder::create(path.as_ref())?);
+        Ok(())
+    }
+
+    pub fn stop_recording(&mut self) {
+        self.recorder = None;
+    }
+
+    // Intensities are in the [0, 1] range. Does nothing if the player has no gamepad
+    // or if the gamepad doesn't support rumble.
+    pub fn rumble(&mut self, player: usize, low_freq: f64, high_freq: f64, duration: Duration) {
+        let Some(Some(gamepad)) = self.gamepads.get_mut(player) else {
+            return;
+        };
+
+        if !gamepad.has_rumble() {
+            return;
+        }
+
+        let to_intensity = |v: f64| (v.clamp(0., 1.) * u16::MAX as f64) as u16;
+        let _ = gamepad.set_rumble(
+            to_intensity(low_freq),
+            to_intensity(high_freq),
+            duration.as_millis().min(u32::MAX as u128) as u32,
+        );
+    }
+
+    fn add_gamepad(&mut self, joystick_index: u32) {
+        let Some(Ok(mut gamepad)) = self
+            .controller_subsystem
+            .as_ref()
+            .map(|c| c.open(joystick_index))
+        else {
+            return;
+        };
+
+        let player = match self.gamepads.iter().position(|g| g.is_none()) {
+            Some(p) => p,
+            None => {
+                self.gamepads.push(None);
+                self.gamepads.len() - 1
+            }
+        };
+
+        let _ = gamepad.set_player_index(Some(player as u32));
+        self.gamepads[player] = Some(gamepad);
+    }
+
+    // Its buttons and axes are released, for the inputs it was holding not to stay held.
+    fn remove_gamepad(&mut self, instance_id: u32) {
+        for (player, g) in self.gamepads.iter_mut().enumerate() {
+            if g.as_ref().is_some_and(|g| g.instance_id() == instance_id) {
+                *g = None;
+                self.last_devices.remove(&player);
+            }
+        }
+
+        if let Some(pad) = self.pads.remove(&instance_id) {
+            for button in pad.buttons {
+                self.merge_pad_button(button);
+            }
+            for axis in pad.axes.into_keys() {
+                self.merge_pad_axis(axis);
+            }
+        }
+    }
+
+    // A button is held while any gamepad holds it.
+    fn merge_pad_button(&mut self, button: GamepadButton) {
+        let control = ButtonControl::Gamepad(button);
+        if self.pads.values().any(|p| p.buttons.contains(&button)) {
+            self.held_buttons.insert(control);
+        } else {
+            self.held_buttons.remove(&control);
+        }
+    }
+
+    // The most deflected gamepad wins, as with the controls of axis inputs.
+    fn merge_pad_axis(&mut self, axis: GamepadAxis) {
+        let value = self
+            .pads
+            .values()
+            .filter_map(|p| p.axes.get(&axis).copied())
+            .fold(0., |v: f64, a| if a.abs() > v.abs() { a } else { v });
+        if value == 0. {
+            self.gamepad_axes.remove(&axis);
+        } else {
+            self.gamepad_axes.insert(axis, value);
+        }
+    }
+
+    fn gamepad_used(&mut self, instance_id: u32) {
+        let player = self
+            .gamepads
+            .iter()
+            .position(|g| g.as_ref().is_some_and(|g| g.instance_id() == instance_id));
+        if let Some(player) = player {
+            let device = self.gamepads[player]
+                .as_ref()
+                .map_or(Device::Gamepad, Device::of);
+            self.last_devices.insert(player, device);
+        }
+    }
+
+    // Returns the events polled, for the engine's event handlers.
+    pub(crate) fn process_events(&mut self) -> Vec<Event> {
+        let events: Vec<Event> = self
             .event_pump
-            .poll_iter()
-            .filter_map(|e| match e {
+            .as_mut()
+            .map_or_else(Vec::new, |pump| pump.poll_iter().collect());
+        self.handle_events(&events);
+        events
+    }
+
+    // Updates the inputs with the events of a frame.
+    fn handle_events(&mut self, events: &[Event]) {
+        self.frame += 1;
+
+        self.touch.begin_frame();
+        self.text_input.begin_frame();
+        self.clipboard_updated = false;
+        self.mouse_wheel = Point::ZERO;
+        self.window_events.clear();
+        self.dropped_files.clear();
+        let text_input_active = self.is_text_input_active();
+
+        for e in events {
+            if text_input_active {
+                self.text_input.handle_event(e);
+            }
+
+            match e {
+                Event::ControllerDeviceAdded { which, .. } => self.add_gamepad(*which),
+                Event::ControllerDeviceRemoved { which, .. } => self.remove_gamepad(*which),
+                Event::FingerDown { .. } | Event::FingerMotion { .. } | Event::FingerUp { .. } => {
+                    self.touch.handle_event(e)
+                }
+                Event::ClipboardUpdate { .. } => self.clipboard_updated = true,
+                Event::DropFile {
+                    window_id,
+                    filename,
+                    ..
+                } => {
+                    let window = WindowId(*window_id);
+                    self.dropped_files.push((window, PathBuf::from(filename)));
+                }
                 Event::KeyDown {
-                    scancode, repeat, ..
-                } => Some((Control::Button(ButtonControl::Keyboard(scancode?)), e)),
+                    scancode: Some(s), ..
+                } => {
+                    self.held_buttons.insert(ButtonControl::Keyboard(*s));
+                    self.last_devices.insert(0, Device::KeyboardMouse);
+                }
                 Event::KeyUp {
-                    scancode, repeat, ..
-                } => Some((Control::Button(ButtonControl::Keyboard(scancode?)), e)),
-                _ => None,
+                    scancode: Some(s), ..
+                } => {
+                    self.held_buttons.remove(&ButtonControl::Keyboard(*s));
+                }
+                Event::ControllerButtonDown { button, which, .. } => {
+                    let pad = self.pads.entry(*which).or_default();
+                    pad.buttons.insert(*button);
+                    self.merge_pad_button(*button);
+                    self.gamepad_used(*which);
+                }
+                Event::ControllerButtonUp { button, which, .. } => {
+                    if let Some(pad) = self.pads.get_mut(which) {
+                        pad.buttons.remove(button);
+                    }
+                    self.merge_pad_button(*button);
+                }
+                Event::MouseButtonDown { mouse_btn, .. } => {
+                    self.held_buttons.insert(ButtonControl::Mouse(*mouse_btn));
+                    self.last_devices.insert(0, Device::KeyboardMouse);
+                }
+                Event::MouseButtonUp { mouse_btn, .. } => {
+                    self.held_buttons.remove(&ButtonControl::Mouse(*mouse_btn));
+                }
+                Event::MouseMotion {
+                    window_id, x, y, ..
+                } => {
+                    self.mouse_window = Some(WindowId(*window_id));
+                    self.mouse_position = Point::new(*x, *y);
+                }
+                Event::Window {
+                    window_id,
+                    win_event,
+                    ..
+                } => {
+                    let window = WindowId(*window_id);
+                    match win_event {
+                        WindowEvent::Enter => self.mouse_window = Some(window),
+                        WindowEvent::Leave if self.mouse_window == Some(window) => {
+                            self.mouse_window = None
+                        }
+                        WindowEvent::FocusGained => self.focused_window = Some(window),
+                        WindowEvent::FocusLost if self.focused_window == Some(window) => {
+                            self.focused_window = None
+                        }
+                        _ => {}
+                    }
+                    self.window_events.push((window, *win_event));
+                }
+                Event::MouseWheel { x, y, .. } => self.mouse_wheel += Point::new(*x, *y),
+                Event::ControllerAxisMotion {
+                    axis, value, which, ..
+                } => {
+                    let value = (*value as f64 / i16::MAX as f64).max(-1.);
+                    self.pads
+                        .entry(*which)
+                        .or_default()
+                        .axes
+                        .insert(*axis, value);
+                    self.merge_pad_axis(*axis);
+                    // Sticks at rest drift a little
+                    if value.abs() > 0.5 {
+                        self.gamepad_used(*which);
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        let active_contexts = self.contexts.active();
+        // Whether a control reaches an input, which it doesn't when its context is inactive
+        // or when the control is bound in an active context above it
+        let reaches = |id: &T, control: Control| {
+            let Some(context) = self.input_contexts.get(id) else {
+                return true;
+            };
+            let Some(rank) = active_contexts.iter().position(|c| c == context) else {
+                return false;
+            };
+            let above = &active_contexts[..rank];
+            !self.controls_input.get(&control).is_some_and(|inputs| {
+                inputs.iter().any(|i| {
+                    self.input_contexts
+                        .get(i)
+                        .is_some_and(|c| above.contains(&c.as_str()))
+                })
             })
-            .collect();
+        };
 
-        for i in self.inputs.values_mut() {
+        for (id, i) in self.inputs.iter_mut() {
             match i {
                 Input::Axis(a) => {
-                    for control in &a.controls {
-                        match control {
-                            AxisControl::Gamepad(_) => {
-                                if let Some(event) = inputs.get(&Control::Axis(*control)) {
-                                    a.value = match event {
-                                        Event::ControllerAxisMotion { value, .. } => {
-                                            *value as f64 / i16::MAX as f64
-                                        }
-                                        _ => 0.,
-                                    }
-                                }
-                            },
-                            AxisControl::Keyboard(min, max) => {
-                                let mut v = 0.;
-                                if let Some(event) =
-                                    inputs.get(&Control::Button(ButtonControl::Keyboard(*min)))
-                                {
-                                    match event {
-                                        Event::KeyDown { .. } => v -= 1.,
-                                        _ => {}
-                                    }
-                                }
-
-                                if let Some(event) =
-                                    inputs.get(&Control::Button(ButtonControl::Keyboard(*max)))
-                                {
-                                    match event {
-                                        Event::KeyDown { .. } => v += 1.,
-                                        _ => {}
-                                    }
-                                }
-
-                                a.value = v;
-                            }
-                        }
-                    }
+                    // The most deflected control wins
+                    a.value = a
+                        .controls
+                        .iter()
+                        .filter(|c| reaches(id, Control::Axis(**c)))
+                        .map(|c| axis_value(&self.held_buttons, &self.gamepad_axes, c))
+                        .fold(0., |v: f64, c| if c.abs() > v.abs() { c } else { v });
                 }
                 Input::Button(b) => {
-                    for control in &b.controls {
-                        match control {
-                            ButtonControl::Gamepad(_) => {
-                                if let Some(event) = inputs.get(&Control::Button(*control)) {
-                                    match event {
-                                        Event::ControllerButtonDown { .. } => {
-                                            b.changed_this_frame = b.value != ButtonState::Down;
-                                            b.value = ButtonState::Down;
-                                        }
-                                        Event::ControllerButtonUp { .. } => {
-                                            b.changed_this_frame = b.value != ButtonState::Up;
-                                            b.value = ButtonState::Up;
-                                        }
-                                        _ => {
-                                            b.changed_this_frame = false;
-                                            b.value = ButtonState::Up;
-                                        }
-                                    };
-                                }
-                            }
-                            ButtonControl::Keyboard(_) => {
-                                if let Some(event) = inputs.get(&Control::Button(*control)) {
-                                    match event {
-                                        Event::KeyDown { .. } => {
-                                            b.changed_this_frame = b.value != ButtonState::Down;
-                                            b.value = ButtonState::Down;
-                                        }
-                                        Event::KeyUp { .. } => {
-                                            b.changed_this_frame = b.value != ButtonState::Up;
-                                            b.value = ButtonState::Up;
-                                        }
-                                        _ => {
-                                            b.changed_this_frame = false;
-                                            b.value = ButtonState::Up;
-                                        }
-                                    };
-                                }
+                    let mut live = false;
+                    let mut held = false;
+                    for c in &b.controls {
+                        let reached = reaches(id, Control::Button(*c));
+                        live |= reached;
+                        held |= reached && is_held(&self.held_buttons, &self.gamepad_axes, c);
+                    }
+                    // Controls still held from before the input got active, e.g. from closing a
+                    // menu, don't press it
+                    let still_held = b
+                        .controls
+                        .iter()
+                        .any(|c| is_held(&self.held_buttons, &self.gamepad_axes, c));
+                    let pressed = held && !b.held;
+                    b.held = if live { held } else { still_held };
+                    if !live {
+                        b.buffered_press = None;
+                    }
+                    let down = if self.toggled.contains(id) {
+                        (b.value == ButtonState::Down) != pressed
+                    } else {
+                        pressed || (held && b.value == ButtonState::Down)
+                    };
+                    let value = if down {
+                        ButtonState::Down
+                    } else {
+                        ButtonState::Up
+                    };
+
+                    b.changed_this_frame = b.value != value;
+                    b.value = value;
+
+                    b.double_tapped = false;
+                    if b.changed_this_frame && b.value == ButtonState::Down {
+                        let now = Instant::now();
+                        b.buffered_press = Some((now, self.frame));
+                        match b.last_pressed {
+                            Some(t) if now - t <= self.double_tap_window => {
+                                b.double_tapped = true;
+                                b.last_pressed = None;
                             }
+                            _ => b.last_pressed = Some(now),
                         }
                     }
                 }
             }
         }
+
+        if let Some(mut recorder) = self.recorder.take() {
+            recorder.record(self);
+            self.recorder = Some(recorder);
+        }
+    }
+}
+
+fn is_held(
+    held_buttons: &HashSet<ButtonControl>,
+    gamepad_axes: &HashMap<GamepadAxis, f64>,
+    control: &ButtonControl,
+) -> bool {
+    match control {
+        ButtonControl::Keyboard(_) | ButtonControl::Gamepad(_) | ButtonControl::Mouse(_) => {
+            held_buttons.contains(control)
+        }
+        ButtonControl::KeyboardChord(first, second) => {
+            held_buttons.contains(&ButtonControl::Keyboard(*first))
+                && held_buttons.contains(&ButtonControl::Keyboard(*second))
+        }
+        ButtonControl::GamepadChord(first, second) => {
+            held_buttons.contains(&ButtonControl::Gamepad(*first))
+                && held_buttons.contains(&ButtonControl::Gamepad(*second))
+        }
+        ButtonControl::GamepadAxis(axis, threshold) => {
+            let value = gamepad_axes.get(axis).copied().unwrap_or(0.);
+            let threshold = *threshold as f64 / 100.;
+            (threshold > 0. && value >= threshold) || (threshold < 0. && value <= threshold)
+        }
+    }
+}
+
+fn axis_value(
+    held_buttons: &HashSet<ButtonControl>,
+    gamepad_axes: &HashMap<GamepadAxis, f64>,
+    control: &AxisControl,
+) -> f64 {
+    match control {
+        AxisControl::Gamepad(axis) => gamepad_axes.get(axis).copied().unwrap_or(0.),
+        AxisControl::GamepadButton(button, value) => {
+            if held_buttons.contains(&ButtonControl::Gamepad(*button)) {
+                (*value as f64 / 100.).clamp(-1., 1.)
+            } else {
+                0.
+            }
+        }
+        AxisControl::Keyboard(min, max) => {
+            let mut v = 0.;
+            if held_buttons.contains(&ButtonControl::Keyboard(*min)) {
+                v -= 1.;
+            }
+
+            if held_buttons.contains(&ButtonControl::Keyboard(*max)) {
+                v += 1.;
+            }
+
+            v
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sdl2::keyboard::Mod;
+
+    use super::*;
+
+    #[derive(PartialEq, Eq, Hash, Clone, Copy, Debug)]
+    enum Action {
+        Jump,
+        Confirm,
+        Crouch,
+        Dash,
+    }
+
+    impl Display for Action {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            write!(f, "{self:?}")
+        }
+    }
+
+    impl InputScheme for Action {}
+
+    fn key(scancode: Scancode, down: bool) -> Event {
+        if down {
+            Event::KeyDown {
+                timestamp: 0,
+                window_id: 0,
+                keycode: None,
+                scancode: Some(scancode),
+                keymod: Mod::empty(),
+                repeat: false,
+            }
+        } else {
+            Event::KeyUp {
+                timestamp: 0,
+                window_id: 0,
+                keycode: None,
+                scancode: Some(scancode),
+                keymod: Mod::empty(),
+                repeat: false,
+            }
+        }
+    }
+
+    fn keyboard(scancode: Scancode) -> Control {
+        Control::Button(ButtonControl::Keyboard(scancode))
+    }
+
+    fn is_down(inputs: &InputsPipeline<Action>, action: Action) -> bool {
+        matches!(
+            inputs.read(&action),
+            Some(Input::Button(b)) if b.value == ButtonState::Down
+        )
+    }
+
+    #[test]
+    fn shared_controls_reach_the_top_context() {
+        let mut inputs = InputsPipeline::detached();
+        inputs.set_context(Action::Jump, Some("gameplay")).unwrap();
+        inputs.set_context(Action::Confirm, Some("menu")).unwrap();
+        inputs
+            .register(Action::Jump, &[keyboard(Scancode::Space)])
+            .unwrap();
+        inputs
+            .register(Action::Confirm, &[keyboard(Scancode::Space)])
+            .unwrap();
+        inputs.push_context("gameplay", false);
+        inputs.push_context("menu", false);
+
+        inputs.handle_events(&[key(Scancode::Space, true)]);
+        assert!(is_down(&inputs, Action::Confirm));
+        assert!(!is_down(&inputs, Action::Jump));
+
+        // Still held from the menu, which doesn't press gameplay's input
+        inputs.pop_context();
+        inputs.handle_events(&[]);
+        assert!(!is_down(&inputs, Action::Confirm));
+        assert!(!is_down(&inputs, Action::Jump));
+
+        inputs.handle_events(&[key(Scancode::Space, false)]);
+        inputs.handle_events(&[key(Scancode::Space, true)]);
+        assert!(is_down(&inputs, Action::Jump));
+    }
+
+    #[test]
+    fn consuming_contexts_deactivate_those_below() {
+        let mut inputs = InputsPipeline::detached();
+        inputs
+            .set_context(Action::Crouch, Some("gameplay"))
+            .unwrap();
+        inputs
+            .register(Action::Crouch, &[keyboard(Scancode::C)])
+            .unwrap();
+        inputs
+            .register(Action::Confirm, &[keyboard(Scancode::Return)])
+            .unwrap();
+        inputs.push_context("gameplay", false);
+        inputs.push_context("text", true);
+
+        inputs.handle_events(&[key(Scancode::C, true), key(Scancode::Return, true)]);
+        assert!(!inputs.is_context_active("gameplay"));
+        assert!(!is_down(&inputs, Action::Crouch));
+        // Inputs without a context are always active
+        assert!(is_down(&inputs, Action::Confirm));
+
+        inputs.pop_context();
+        inputs.handle_events(&[key(Scancode::C, false)]);
+        inputs.handle_events(&[key(Scancode::C, true)]);
+        assert!(is_down(&inputs, Action::Crouch));
+    }
+
+    #[test]
+    fn toggled_buttons_flip_on_each_press() {
+        let mut inputs = InputsPipeline::detached();
+        inputs
+            .register(Action::Crouch, &[keyboard(Scancode::C)])
+            .unwrap();
+        inputs.set_toggled(Action::Crouch, true);
+
+        inputs.handle_events(&[key(Scancode::C, true)]);
+        assert!(is_down(&inputs, Action::Crouch));
+        inputs.handle_events(&[key(Scancode::C, false)]);
+        assert!(is_down(&inputs, Action::Crouch));
+        inputs.handle_events(&[key(Scancode::C, true)]);
+        assert!(!is_down(&inputs, Action::Crouch));
+        inputs.handle_events(&[key(Scancode::C, false)]);
+        assert!(!is_down(&inputs, Action::Crouch));
+    }
+
+    #[test]
+    fn buffered_presses_expire() {
+        let mut inputs = InputsPipeline::detached();
+        inputs
+            .register(Action::Jump, &[keyboard(Scancode::Space)])
+            .unwrap();
+        let window = BufferWindow::Frames(2);
+
+        inputs.handle_events(&[key(Scancode::Space, true)]);
+        inputs.handle_events(&[key(Scancode::Space, false)]);
+        inputs.handle_events(&[]);
+        assert!(inputs.buffered_press(&Action::Jump, window));
+        assert!(inputs.buffered_press(&Action::Jump, BufferWindow::Time(Duration::MAX)));
+
+        inputs.handle_events(&[]);
+        assert!(!inputs.buffered_press(&Action::Jump, window));
+
+        inputs.handle_events(&[key(Scancode::Space, true)]);
+        assert!(inputs.consume_press(&Action::Jump, window));
+        assert!(!inputs.consume_press(&Action::Jump, window));
+    }
+
+    #[test]
+    fn chords_need_both_buttons() {
+        let mut inputs = InputsPipeline::detached();
+        let chord = ButtonControl::KeyboardChord(Scancode::LShift, Scancode::D);
+        inputs
+            .register(Action::Dash, &[Control::Button(chord)])
+            .unwrap();
+
+        inputs.handle_events(&[key(Scancode::D, true)]);
+        assert!(!is_down(&inputs, Action::Dash));
+        inputs.handle_events(&[key(Scancode::LShift, true)]);
+        assert!(is_down(&inputs, Action::Dash));
+        inputs.handle_events(&[key(Scancode::D, false)]);
+        assert!(!is_down(&inputs, Action::Dash));
     }
 }
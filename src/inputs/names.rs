@@ -0,0 +1,120 @@
+use std::{fmt, str::FromStr};
+
+use super::{
+    AxisControl, ButtonControl, Control, GamepadAxis, GamepadButton, MouseButton, Scancode,
+};
+
+// Controls are named "key:<scancode>", "pad:<button or axis>" and "mouse:<button>" using SDL's
+// names, chords join two of them with "+" and keyboard axes with "|", e.g. "key:Left|key:Right".
+// Axes used as buttons and buttons used as axes are followed by their percent after "@", e.g.
+// "pad:righttrigger@50" and "pad:a@100".
+impl fmt::Display for Control {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Control::Button(ButtonControl::Keyboard(key)) => write!(f, "key:{}", key.name()),
+            Control::Button(ButtonControl::Gamepad(button)) => write!(f, "pad:{}", button.string()),
+            Control::Button(ButtonControl::Mouse(button)) => {
+                write!(f, "mouse:{}", mouse_button_name(*button))
+            }
+            Control::Button(ButtonControl::KeyboardChord(first, second)) => {
+                write!(f, "key:{}+key:{}", first.name(), second.name())
+            }
+            Control::Button(ButtonControl::GamepadChord(first, second)) => {
+                write!(f, "pad:{}+pad:{}", first.string(), second.string())
+            }
+            Control::Axis(AxisControl::Keyboard(min, max)) => {
+                write!(f, "key:{}|key:{}", min.name(), max.name())
+            }
+            Control::Button(ButtonControl::GamepadAxis(axis, threshold)) => {
+                write!(f, "pad:{}@{}", axis.string(), threshold)
+            }
+            Control::Axis(AxisControl::Gamepad(axis)) => write!(f, "pad:{}", axis.string()),
+            Control::Axis(AxisControl::GamepadButton(button, value)) => {
+                write!(f, "pad:{}@{}", button.string(), value)
+            }
+        }
+    }
+}
+
+impl FromStr for Control {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("invalid control \"{s}\"");
+
+        if let Some((min, max)) = s.split_once("|key:") {
+            return Ok(Control::Axis(AxisControl::Keyboard(
+                key(min).ok_or_else(invalid)?,
+                key(&format!("key:{max}")).ok_or_else(invalid)?,
+            )));
+        }
+        if let Some((first, second)) = s.split_once("+key:") {
+            return Ok(Control::Button(ButtonControl::KeyboardChord(
+                key(first).ok_or_else(invalid)?,
+                key(&format!("key:{second}")).ok_or_else(invalid)?,
+            )));
+        }
+        if let Some((first, second)) = s.split_once("+pad:") {
+            return Ok(Control::Button(ButtonControl::GamepadChord(
+                pad_button(first).ok_or_else(invalid)?,
+                pad_button(&format!("pad:{second}")).ok_or_else(invalid)?,
+            )));
+        }
+
+        if let Some((name, percent)) = s.split_once('@') {
+            let percent: i8 = percent.parse().map_err(|_| invalid())?;
+            let name = name.strip_prefix("pad:").ok_or_else(invalid)?;
+            if let Some(axis) = GamepadAxis::from_string(name) {
+                return Ok(Control::Button(ButtonControl::GamepadAxis(axis, percent)));
+            }
+            let button = GamepadButton::from_string(name).ok_or_else(invalid)?;
+            return Ok(Control::Axis(AxisControl::GamepadButton(button, percent)));
+        }
+
+        if let Some(key) = key(s) {
+            return Ok(Control::Button(ButtonControl::Keyboard(key)));
+        }
+        if let Some(button) = pad_button(s) {
+            return Ok(Control::Button(ButtonControl::Gamepad(button)));
+        }
+        if let Some(axis) = s.strip_prefix("pad:").and_then(GamepadAxis::from_string) {
+            return Ok(Control::Axis(AxisControl::Gamepad(axis)));
+        }
+        if let Some(button) = s.strip_prefix("mouse:").and_then(mouse_button) {
+            return Ok(Control::Button(ButtonControl::Mouse(button)));
+        }
+
+        Err(invalid())
+    }
+}
+
+fn key(name: &str) -> Option<Scancode> {
+    Scancode::from_name(name.strip_prefix("key:")?)
+}
+
+fn pad_button(name: &str) -> Option<GamepadButton> {
+    GamepadButton::from_string(name.strip_prefix("pad:")?)
+}
+
+fn mouse_button_name(button: MouseButton) -> &'static str {
+    match button {
+        MouseButton::Left => "left",
+        MouseButton::Middle => "middle",
+        MouseButton::Right => "right",
+        MouseButton::X1 => "x1",
+        MouseButton::X2 => "x2",
+        MouseButton::Unknown => "unknown",
+    }
+}
+
+fn mouse_button(name: &str) -> Option<MouseButton> {
+    [
+        MouseButton::Left,
+        MouseButton::Middle,
+        MouseButton::Right,
+        MouseButton::X1,
+        MouseButton::X2,
+    ]
+    .into_iter()
+    .find(|b| mouse_button_name(*b) == name)
+}
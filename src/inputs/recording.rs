@@ -0,0 +1,54 @@
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{BufWriter, Write},
+    path::Path,
+};
+
+use super::{ButtonState, Input, InputScheme, InputsPipeline};
+
+// Writes the value of the registered inputs whenever it changes, one line per change:
+// "<frame> <input> <value>", buttons being down or up and inputs named by their Display. Inputs
+// start up or at 0.
+pub(super) struct InputRecorder {
+    writer: BufWriter<File>,
+    last: HashMap<String, String>,
+}
+
+impl InputRecorder {
+    pub(super) fn create(path: &Path) -> Result<Self, String> {
+        let file = File::create(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        Ok(InputRecorder {
+            writer: BufWriter::new(file),
+            last: HashMap::new(),
+        })
+    }
+
+    pub(super) fn record<T: InputScheme>(&mut self, inputs: &InputsPipeline<T>) {
+        let mut changed = false;
+        for (id, input) in &inputs.inputs {
+            let (value, initial) = match input {
+                Input::Button(b) if b.value == ButtonState::Down => ("down".to_string(), "up"),
+                Input::Button(_) => ("up".to_string(), "up"),
+                Input::Axis(a) => (a.value.to_string(), "0"),
+            };
+            let id = id.to_string();
+            if self.last.get(&id).map_or(initial, String::as_str) == value {
+                continue;
+            }
+
+            if let Err(e) = writeln!(self.writer, "{} {} {}", inputs.frame, id, value) {
+                log::warn!("failed to record inputs: {e}");
+            }
+            self.last.insert(id, value);
+            changed = true;
+        }
+
+        // Kept on disk as the game goes, in case it crashes
+        if changed {
+            if let Err(e) = self.writer.flush() {
+                log::warn!("failed to record inputs: {e}");
+            }
+        }
+    }
+}
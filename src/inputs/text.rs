@@ -0,0 +1,84 @@
+use sdl2::{event::Event, keyboard::Scancode};
+
+#[derive(Default)]
+pub struct TextInputState {
+    text: String,
+    composition: String,
+    composition_cursor: usize,
+    backspaces: u32,
+    submitted: bool,
+}
+
+impl TextInputState {
+    // Text committed during the last frame.
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    // Text being composed by the IME, not yet committed.
+    pub fn composition(&self) -> &str {
+        &self.composition
+    }
+
+    // Cursor position within the composition, in chars.
+    pub fn composition_cursor(&self) -> usize {
+        self.composition_cursor
+    }
+
+    // Number of backspace presses (key repeats included) during the last frame.
+    pub fn backspaces(&self) -> u32 {
+        self.backspaces
+    }
+
+    pub fn submitted(&self) -> bool {
+        self.submitted
+    }
+
+    // Applies the last frame's text input to a text field's buffer.
+    // Returns true if the user pressed enter.
+    pub fn edit(&self, buffer: &mut String) -> bool {
+        for _ in 0..self.backspaces {
+            buffer.pop();
+        }
+
+        buffer.push_str(&self.text);
+        self.submitted
+    }
+
+    pub(crate) fn begin_frame(&mut self) {
+        self.text.clear();
+        self.backspaces = 0;
+        self.submitted = false;
+    }
+
+    pub(crate) fn reset(&mut self) {
+        self.begin_frame();
+        self.composition.clear();
+        self.composition_cursor = 0;
+    }
+
+    pub(crate) fn handle_event(&mut self, event: &Event) {
+        match event {
+            Event::TextInput { text, .. } => {
+                self.text.push_str(text);
+                self.composition.clear();
+                self.composition_cursor = 0;
+            }
+            Event::TextEditing { text, start, .. } => {
+                self.composition = text.clone();
+                self.composition_cursor = (*start).max(0) as usize;
+            }
+            // Backspaces are only applied to committed text, the IME handles its own.
+            Event::KeyDown {
+                scancode: Some(Scancode::Backspace),
+                ..
+            } if self.composition.is_empty() => self.backspaces += 1,
+            Event::KeyDown {
+                scancode: Some(Scancode::Return | Scancode::KpEnter),
+                repeat: false,
+                ..
+            } if self.composition.is_empty() => self.submitted = true,
+            _ => {}
+        }
+    }
+}
@@ -0,0 +1,165 @@
+use std::collections::HashMap;
+
+use sdl2::event::Event;
+
+use crate::Vec2;
+
+// Touch positions are normalized to the window, (0, 0) being the top left corner
+// and (1, 1) the bottom right one.
+const TAP_MAX_DURATION_MS: u32 = 250;
+const TAP_MAX_DISTANCE: f64 = 0.02;
+
+pub type FingerId = i64;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Finger {
+    pub id: FingerId,
+    pub position: Vec2,
+    pub delta: Vec2,
+    pub pressure: f64,
+    start_position: Vec2,
+    start_time: u32,
+    dragging: bool,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Gesture {
+    Tap { position: Vec2 },
+    Drag { position: Vec2, delta: Vec2 },
+    Pinch { center: Vec2, scale: f64 },
+}
+
+#[derive(Default)]
+pub struct TouchState {
+    fingers: HashMap<FingerId, Finger>,
+    gestures: Vec<Gesture>,
+}
+
+impl TouchState {
+    pub fn fingers(&self) -> impl Iterator<Item = &Finger> {
+        self.fingers.values()
+    }
+
+    pub fn finger(&self, id: FingerId) -> Option<&Finger> {
+        self.fingers.get(&id)
+    }
+
+    // Gestures recognized during the last frame.
+    pub fn gestures(&self) -> &[Gesture] {
+        &self.gestures
+    }
+
+    pub(crate) fn begin_frame(&mut self) {
+        self.gestures.clear();
+        for f in self.fingers.values_mut() {
+            f.delta = Vec2::ZERO;
+        }
+    }
+
+    pub(crate) fn handle_event(&mut self, event: &Event) {
+        match *event {
+            Event::FingerDown {
+                timestamp,
+                finger_id,
+                x,
+                y,
+                pressure,
+                ..
+            } => {
+                let position = Vec2::new(x as f64, y as f64);
+                self.fingers.insert(
+                    finger_id,
+                    Finger {
+                        id: finger_id,
+                        position,
+                        delta: Vec2::ZERO,
+                        pressure: pressure as f64,
+                        start_position: position,
+                        start_time: timestamp,
+                        dragging: false,
+                    },
+                );
+            }
+            Event::FingerMotion {
+                finger_id,
+                x,
+                y,
+                dx,
+                dy,
+                pressure,
+                ..
+            } => {
+                let pinch_before = self.pinch_span();
+
+                let Some(f) = self.fingers.get_mut(&finger_id) else {
+                    return;
+                };
+
+                let delta = Vec2::new(dx as f64, dy as f64);
+                f.position = Vec2::new(x as f64, y as f64);
+                f.delta += delta;
+                f.pressure = pressure as f64;
+
+                if self.fingers.len() == 2 {
+                    if let (Some((_, before)), Some((center, after))) =
+                        (pinch_before, self.pinch_span())
+                    {
+                        if before > 0. {
+                            self.gestures.push(Gesture::Pinch {
+                                center,
+                                scale: after / before,
+                            });
+                        }
+                    }
+                    return;
+                }
+
+                let single = self.fingers.len() == 1;
+                let f = self.fingers.get_mut(&finger_id).unwrap();
+                if !f.dragging && (f.position - f.start_position).length() > TAP_MAX_DISTANCE {
+                    f.dragging = true;
+                }
+
+                if f.dragging && single {
+                    self.gestures.push(Gesture::Drag {
+                        position: f.position,
+                        delta,
+                    });
+                }
+            }
+            Event::FingerUp {
+                timestamp,
+                finger_id,
+                x,
+                y,
+                ..
+            } => {
+                let Some(f) = self.fingers.remove(&finger_id) else {
+                    return;
+                };
+
+                let position = Vec2::new(x as f64, y as f64);
+                let duration = timestamp.saturating_sub(f.start_time);
+                if !f.dragging
+                    && duration <= TAP_MAX_DURATION_MS
+                    && (position - f.start_position).length() <= TAP_MAX_DISTANCE
+                {
+                    self.gestures.push(Gesture::Tap { position });
+                }
+            }
+            _ => {}
+        }
+    }
+
+    // Center and distance between the two fingers of a pinch.
+    fn pinch_span(&self) -> Option<(Vec2, f64)> {
+        if self.fingers.len() != 2 {
+            return None;
+        }
+
+        let mut fingers = self.fingers.values();
+        let a = fingers.next()?.position;
+        let b = fingers.next()?.position;
+        Some(((a + b) / 2., (a - b).length()))
+    }
+}
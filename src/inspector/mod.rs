@@ -0,0 +1,351 @@
+use crate::{
+    config::{parse_toml, Value},
+    graphics::{BitmapFont, Color, DrawParams, GraphicsPipeline, PixelRect},
+    inputs::{ButtonControl, InputScheme, InputsPipeline, MouseButton},
+    reflect::Reflect,
+    Point,
+};
+
+// In pixels.
+const PADDING: i32 = 4;
+// Width of the entity list, in glyphs.
+const LIST_COLUMNS: u32 = 20;
+
+// Live object shown by the inspector, made of the components the game lends it for the frame.
+pub struct InspectedEntity<'a> {
+    pub name: String,
+    pub components: Vec<(&'static str, &'a mut dyn Reflect)>,
+}
+
+// Field edited through the inspector, applied to the component by the time it's returned.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Edit {
+    pub entity: String,
+    pub component: &'static str,
+    pub field: &'static str,
+    pub value: Value,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum Row {
+    Component(usize),
+    Field(usize, &'static str),
+}
+
+// Overlay listing the entities on the left and the fields of the selected one's components on
+// the right. Clicking a field toggles booleans and edits other values as text, Enter applying
+// them. The mouse wheel over a number nudges it by step, integers by 1.
+pub struct Inspector {
+    pub open: bool,
+    pub scale: f64,
+    pub step: f64,
+    selected: Option<String>,
+    // Component and field.
+    editing: Option<(usize, &'static str)>,
+    input: String,
+    mouse_held: bool,
+}
+
+impl<'a> InspectedEntity<'a> {
+    pub fn new(name: &str) -> Self {
+        InspectedEntity {
+            name: name.to_string(),
+            components: Vec::new(),
+        }
+    }
+
+    pub fn with(mut self, name: &'static str, component: &'a mut dyn Reflect) -> Self {
+        self.components.push((name, component));
+        self
+    }
+
+    fn rows(&self) -> Vec<Row> {
+        let mut rows = Vec::new();
+        for (index, (_, component)) in self.components.iter().enumerate() {
+            rows.push(Row::Component(index));
+            rows.extend(component.fields().iter().map(|f| Row::Field(index, f)));
+        }
+        rows
+    }
+}
+
+impl Inspector {
+    pub fn new() -> Self {
+        Inspector {
+            open: false,
+            scale: 1.,
+            step: 0.1,
+            selected: None,
+            editing: None,
+            input: String::new(),
+            mouse_held: false,
+        }
+    }
+
+    pub fn selected(&self) -> Option<&str> {
+        self.selected.as_deref()
+    }
+
+    pub fn toggle<T: InputScheme>(&mut self, inputs: &mut InputsPipeline<T>) {
+        self.open = !self.open;
+        if !self.open {
+            self.stop_editing(inputs);
+        }
+    }
+
+    // Has to be called once per frame, after InputsPipeline::process_events, with the same font
+    // as draw. Returns the fields changed.
+    pub fn update<T: InputScheme>(
+        &mut self,
+        inputs: &mut InputsPipeline<T>,
+        font: &BitmapFont,
+        entities: &mut [InspectedEntity],
+    ) -> Vec<Edit> {
+        let held = inputs.is_held(&ButtonControl::Mouse(MouseButton::Left));
+        let clicked = held && !self.mouse_held;
+        self.mouse_held = held;
+        if !self.open {
+            return Vec::new();
+        }
+
+        let mut edits = Vec::new();
+        let Some(index) = self.selected_index(entities) else {
+            self.editing = None;
+            if clicked {
+                self.click_list(inputs, font, entities);
+            }
+            return edits;
+        };
+
+        // Text being typed
+        if let Some((component, field)) = self.editing {
+            if inputs.text_input().edit(&mut self.input) {
+                let entity = &mut entities[index];
+                match parse_value(&self.input) {
+                    Ok(value) => edits.extend(set(entity, component, field, value)),
+                    Err(e) => log::warn!("invalid value for {field}: {e}"),
+                }
+                self.stop_editing(inputs);
+            }
+        }
+
+        let mouse = inputs.mouse_position();
+        let row = self.field_row(font, &entities[index], mouse);
+        let wheel = inputs.mouse_wheel().y;
+        if let (Some(Row::Field(component, field)), true) = (row, wheel != 0) {
+            let entity = &mut entities[index];
+            let nudged = match entity.components[component].1.get(field) {
+                Some(Value::Integer(i)) => Some(Value::Integer(i + wheel as i64)),
+                Some(Value::Float(x)) => Some(Value::Float(x + self.step * wheel as f64)),
+                _ => None,
+            };
+            if let Some(value) = nudged {
+                edits.extend(set(entity, component, field, value));
+            }
+        }
+
+        if clicked {
+            match row {
+                Some(Row::Field(component, field)) => {
+                    let entity = &mut entities[index];
+                    match entity.components[component].1.get(field) {
+                        Some(Value::Bool(b)) => {
+                            self.stop_editing(inputs);
+                            edits.extend(set(entity, component, field, Value::Bool(!b)));
+                        }
+                        Some(value) => {
+                            self.editing = Some((component, field));
+                            self.input = value.to_string();
+                            inputs.start_text_input();
+                        }
+                        None => {}
+                    }
+                }
+                Some(Row::Component(_)) => {}
+                None => self.click_list(inputs, font, entities),
+            }
+        }
+        edits
+    }
+
+    pub fn draw(
+        &self,
+        graphics_ppl: &mut GraphicsPipeline,
+        font: &BitmapFont,
+        entities: &[InspectedEntity],
+    ) {
+        if !self.open {
+            return;
+        }
+
+        let line_height = self.line_height(font);
+        let list_width = self.list_width(font);
+        let (_, height) = graphics_ppl.options.window_size;
+        let background = Color::RGBA(10, 10, 15, 220);
+        let params = DrawParams::default();
+        let text = |graphics_ppl: &mut GraphicsPipeline, text: &str, x: i32, y: i32, color| {
+            let params = DrawParams {
+                tint: color,
+                ..Default::default()
+            };
+            graphics_ppl.draw_text_screen(font, text, Point::new(x, y), self.scale, &params);
+        };
+
+        // Entities
+        graphics_ppl.draw_rect_screen(
+            PixelRect::new(0, 0, list_width, height),
+            &background,
+            true,
+            &params,
+        );
+        for (i, entity) in entities.iter().enumerate() {
+            let color = if self.selected.as_ref() == Some(&entity.name) {
+                Color::YELLOW
+            } else {
+                Color::WHITE
+            };
+            let name: String = entity.name.chars().take(LIST_COLUMNS as usize).collect();
+            text(
+                graphics_ppl,
+                &name,
+                PADDING,
+                PADDING + i as i32 * line_height,
+                color,
+            );
+        }
+
+        // Fields of the selected entity
+        let Some(index) = self.selected_index(entities) else {
+            return;
+        };
+        let entity = &entities[index];
+        let rows = entity.rows();
+        let x = list_width as i32 + PADDING;
+        let lines: Vec<(String, Color)> = rows
+            .iter()
+            .map(|row| match *row {
+                Row::Component(component) => {
+                    (format!("[{}]", entity.components[component].0), Color::CYAN)
+                }
+                Row::Field(component, field) if self.editing == Some((component, field)) => {
+                    (format!("{field} = {}_", self.input), Color::YELLOW)
+                }
+                Row::Field(component, field) => {
+                    let value = entity.components[component].1.get(field);
+                    let value = value.map_or(String::new(), |v| v.to_string());
+                    (format!("{field} = {value}"), Color::WHITE)
+                }
+            })
+            .collect();
+        let width = lines
+            .iter()
+            .map(|(line, _)| font.text_size(line, self.scale).0)
+            .max()
+            .unwrap_or(0);
+        graphics_ppl.draw_rect_screen(
+            PixelRect::new(
+                list_width as i32,
+                0,
+                width + PADDING as u32 * 2,
+                (rows.len() as i32 * line_height + PADDING * 2) as u32,
+            ),
+            &background,
+            true,
+            &params,
+        );
+        for (i, (line, color)) in lines.iter().enumerate() {
+            text(
+                graphics_ppl,
+                line,
+                x,
+                PADDING + i as i32 * line_height,
+                *color,
+            );
+        }
+    }
+
+    fn selected_index(&self, entities: &[InspectedEntity]) -> Option<usize> {
+        let selected = self.selected.as_ref()?;
+        entities.iter().position(|e| e.name == *selected)
+    }
+
+    fn click_list<T: InputScheme>(
+        &mut self,
+        inputs: &mut InputsPipeline<T>,
+        font: &BitmapFont,
+        entities: &[InspectedEntity],
+    ) {
+        let mouse = inputs.mouse_position();
+        if mouse.x >= self.list_width(font) as i32 {
+            return;
+        }
+
+        self.stop_editing(inputs);
+        let line = (mouse.y - PADDING).div_euclid(self.line_height(font));
+        if let Some(entity) = usize::try_from(line).ok().and_then(|i| entities.get(i)) {
+            self.selected = Some(entity.name.clone());
+        }
+    }
+
+    // Row of the selected entity's fields under the mouse.
+    fn field_row(&self, font: &BitmapFont, entity: &InspectedEntity, mouse: Point) -> Option<Row> {
+        let list_width = self.list_width(font) as i32;
+        if mouse.x < list_width {
+            return None;
+        }
+        let line = (mouse.y - PADDING).div_euclid(self.line_height(font));
+        entity.rows().get(usize::try_from(line).ok()?).copied()
+    }
+
+    fn stop_editing<T: InputScheme>(&mut self, inputs: &mut InputsPipeline<T>) {
+        if self.editing.take().is_some() {
+            inputs.stop_text_input();
+        }
+        self.input.clear();
+    }
+
+    fn line_height(&self, font: &BitmapFont) -> i32 {
+        ((font.glyph_height as f64 * self.scale).round() as i32).max(1)
+    }
+
+    fn list_width(&self, font: &BitmapFont) -> u32 {
+        (LIST_COLUMNS as f64 * font.glyph_width as f64 * self.scale).round() as u32
+            + PADDING as u32 * 2
+    }
+}
+
+impl Default for Inspector {
+    fn default() -> Self {
+        Inspector::new()
+    }
+}
+
+fn parse_value(text: &str) -> Result<Value, String> {
+    let document = parse_toml(&format!("value = {text}"))?;
+    document
+        .get("")
+        .and_then(|values| values.get("value"))
+        .cloned()
+        .ok_or_else(|| "expected a value".to_string())
+}
+
+fn set(
+    entity: &mut InspectedEntity,
+    component: usize,
+    field: &'static str,
+    value: Value,
+) -> Option<Edit> {
+    let (name, reflect) = &mut entity.components[component];
+    match reflect.set(field, &value) {
+        Ok(()) => Some(Edit {
+            entity: entity.name.clone(),
+            component: name,
+            field,
+            value,
+        }),
+        Err(e) => {
+            log::warn!("failed to set {field} of {}: {e}", entity.name);
+            None
+        }
+    }
+}
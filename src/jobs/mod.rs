@@ -0,0 +1,227 @@
+use std::{
+    cell::RefCell,
+    panic::{self, AssertUnwindSafe},
+    sync::{Arc, Mutex},
+};
+
+use crate::schedule::Resources;
+
+#[cfg(not(target_os = "emscripten"))]
+use std::sync::{
+    mpsc::{self, Receiver, Sender},
+    OnceLock,
+};
+
+type Work = Box<dyn FnOnce() + Send>;
+type Completion = Box<dyn FnMut(&mut Resources) -> bool>;
+
+// Work running in the background, see spawn.
+pub struct Job<R> {
+    state: Arc<Mutex<JobState<R>>>,
+}
+
+enum JobState<R> {
+    Running,
+    Done(R),
+    Panicked,
+    Taken,
+}
+
+// Jobs borrowing from the stack, see scope.
+pub struct Scope<'env> {
+    jobs: RefCell<Vec<Box<dyn FnOnce() + Send + 'env>>>,
+}
+
+// Threads shared by the whole engine, started with the first job.
+#[cfg(not(target_os = "emscripten"))]
+struct ThreadPool {
+    sender: Mutex<Sender<Work>>,
+}
+
+#[cfg(not(target_os = "emscripten"))]
+static POOL: OnceLock<ThreadPool> = OnceLock::new();
+
+thread_local! {
+    // Completions of the jobs spawned with spawn_then on the thread, the main one's being run
+    // by Engine::update.
+    static COMPLETIONS: RefCell<Vec<Completion>> = const { RefCell::new(Vec::new()) };
+}
+
+// Runs work on one of the engine's threads, e.g. loading a file or generating a level, its
+// result being taken from the job once done. On the web, which has no threads, work runs right
+// away. A panicking job is reported by the crash handler but doesn't stop the others.
+pub fn spawn<R, F>(work: F) -> Job<R>
+where
+    R: Send + 'static,
+    F: FnOnce() -> R + Send + 'static,
+{
+    let state = Arc::new(Mutex::new(JobState::Running));
+    let result = state.clone();
+    run(Box::new(move || {
+        let done = match panic::catch_unwind(AssertUnwindSafe(work)) {
+            Ok(r) => JobState::Done(r),
+            Err(_) => JobState::Panicked,
+        };
+        *result.lock().unwrap() = done;
+    }));
+    Job { state }
+}
+
+// Same as spawn, then is called with the result on the thread which spawned the job once it's
+// done, at the start of a frame for the main thread, so that it can safely change the game's
+// resources, e.g. to insert a path found for an enemy.
+pub fn spawn_then<R, F, C>(work: F, then: C)
+where
+    R: Send + 'static,
+    F: FnOnce() -> R + Send + 'static,
+    C: FnOnce(R, &mut Resources) + 'static,
+{
+    let job = spawn(work);
+    let mut then = Some(then);
+    let completion: Completion = Box::new(move |resources| {
+        if job.has_panicked() {
+            return true;
+        }
+        let Some(result) = job.take() else {
+            return false;
+        };
+        if let Some(then) = then.take() {
+            then(result, resources);
+        }
+        true
+    });
+    COMPLETIONS.with(|c| c.borrow_mut().push(completion));
+}
+
+// Calls the completions of the jobs done, in the order the jobs were spawned.
+pub fn complete(resources: &mut Resources) {
+    let completions = COMPLETIONS.with(|c| std::mem::take(&mut *c.borrow_mut()));
+    let mut pending = Vec::new();
+    for mut completion in completions {
+        if !completion(resources) {
+            pending.push(completion);
+        }
+    }
+    // Completions can spawn jobs of their own
+    COMPLETIONS.with(|c| {
+        let mut completions = c.borrow_mut();
+        pending.append(&mut completions);
+        *completions = pending;
+    });
+}
+
+// Number of jobs spawned with spawn_then whose completion hasn't been called yet.
+pub fn pending() -> usize {
+    COMPLETIONS.with(|c| c.borrow().len())
+}
+
+// Runs the jobs spawned in f on threads started for the scope, a thread per core at most, and
+// the calling one, returning once they are all done. Unlike spawn, jobs can borrow what outlives
+// the scope, e.g. slices to fill, which is why they don't run on the engine's threads, and a
+// panicking job panics the caller once the others are done.
+pub fn scope<'env, F: FnOnce(&Scope<'env>)>(f: F) {
+    let scope = Scope {
+        jobs: RefCell::new(Vec::new()),
+    };
+    f(&scope);
+    let jobs = scope.jobs.into_inner();
+
+    #[cfg(target_os = "emscripten")]
+    for job in jobs {
+        job();
+    }
+
+    #[cfg(not(target_os = "emscripten"))]
+    {
+        let threads = std::thread::available_parallelism().map_or(1, |n| n.get());
+        let queue = Mutex::new(jobs);
+        let next = || queue.lock().unwrap().pop();
+        std::thread::scope(|s| {
+            for _ in 1..threads.min(queue.lock().unwrap().len()) {
+                s.spawn(|| {
+                    while let Some(job) = next() {
+                        job();
+                    }
+                });
+            }
+            while let Some(job) = next() {
+                job();
+            }
+        });
+    }
+}
+
+impl<R> Job<R> {
+    pub fn is_done(&self) -> bool {
+        matches!(*self.state.lock().unwrap(), JobState::Done(_))
+    }
+
+    pub fn has_panicked(&self) -> bool {
+        matches!(*self.state.lock().unwrap(), JobState::Panicked)
+    }
+
+    // The result once the job is done, taken by the first call.
+    pub fn take(&self) -> Option<R> {
+        let mut state = self.state.lock().unwrap();
+        match std::mem::replace(&mut *state, JobState::Taken) {
+            JobState::Done(result) => Some(result),
+            previous => {
+                *state = previous;
+                None
+            }
+        }
+    }
+}
+
+impl<'env> Scope<'env> {
+    pub fn spawn<F: FnOnce() + Send + 'env>(&self, job: F) {
+        self.jobs.borrow_mut().push(Box::new(job));
+    }
+}
+
+#[cfg(target_os = "emscripten")]
+fn run(work: Work) {
+    work();
+}
+
+#[cfg(not(target_os = "emscripten"))]
+fn run(work: Work) {
+    let pool = POOL.get_or_init(ThreadPool::new);
+    if pool.sender.lock().unwrap().send(work).is_err() {
+        log::error!("job threads stopped");
+    }
+}
+
+#[cfg(not(target_os = "emscripten"))]
+impl ThreadPool {
+    // A thread per core but the main thread's.
+    fn new() -> Self {
+        let threads = std::thread::available_parallelism().map_or(2, |n| n.get().max(2)) - 1;
+        let (sender, receiver) = mpsc::channel::<Work>();
+        let receiver = Arc::new(Mutex::new(receiver));
+        for i in 0..threads {
+            let receiver = receiver.clone();
+            let spawned = std::thread::Builder::new()
+                .name(format!("job {i}"))
+                .spawn(move || work(&receiver));
+            if let Err(e) = spawned {
+                log::error!("failed to start job thread: {e}");
+            }
+        }
+        ThreadPool {
+            sender: Mutex::new(sender),
+        }
+    }
+}
+
+#[cfg(not(target_os = "emscripten"))]
+fn work(receiver: &Mutex<Receiver<Work>>) {
+    loop {
+        // The lock is released before the job runs
+        let work = receiver.lock().unwrap().recv();
+        match work {
+            Ok(work) => work(),
+            Err(_) => return,
+        }
+    }
+}
@@ -1,12 +1,51 @@
-use graphics::GraphicsOptions;
+use graphics::{CreateRenderer, GraphicsOptions, GraphicsPipeline, WindowId};
 use inputs::InputScheme;
+use plugin::{create_window, EngineBuilder, EventHandler};
+use schedule::{Resources, Schedule};
+use sdl2::{clipboard::ClipboardUtil, event::WindowEvent, AudioSubsystem, VideoSubsystem};
 
+pub mod ai;
+pub mod animation;
+pub mod args;
+pub mod capture;
+pub mod config;
+pub mod console;
+pub mod crash;
+pub mod crowd;
+pub mod desync;
+pub mod dialogue;
+#[cfg(feature = "editor")]
+pub mod editor;
+pub mod focus;
 pub mod graphics;
+pub mod i18n;
 pub mod inputs;
+pub mod inspector;
+pub mod jobs;
+pub mod math;
+pub mod nav;
+pub mod net;
 pub mod physics;
+pub mod platform;
+pub mod plugin;
+pub mod pool;
+pub mod prefab;
+pub mod presence;
+pub mod profiler;
+pub mod prompts;
+pub mod random;
+pub mod reflect;
+pub mod scene;
+pub mod schedule;
+#[cfg(feature = "scripting")]
+pub mod scripting;
+pub mod stats;
+pub mod timeline;
+pub mod ui;
+pub mod vfs;
 
-pub type Vec2 = parry2d_f64::math::Vector<f64>;
-pub type Point = parry2d_f64::math::Vector<i32>;
+pub type Vec2 = parry2d_f64::math::Vector;
+pub type Point = glam::IVec2;
 
 pub trait ToPoint {
     fn to_point(&self) -> Point;
@@ -22,6 +61,19 @@ where
 {
     pub graphics_ppl: graphics::GraphicsPipeline,
     pub inputs_ppl: inputs::InputsPipeline<T>,
+    pub random: random::Random,
+    // Only created when enabled with EngineBuilder::physics.
+    pub physics: Option<physics::PhysicsWorld>,
+    pub focus: focus::Focus,
+    pub resources: Resources,
+    schedule: Schedule<Engine<T>>,
+    event_handlers: Vec<EventHandler>,
+    audio: Option<AudioSubsystem>,
+    video: VideoSubsystem,
+    // Other than the main one.
+    windows: Vec<GraphicsPipeline>,
+    create_renderer: CreateRenderer,
+    clipboard: ClipboardUtil,
 }
 
 impl ToPoint for Vec2 {
@@ -35,30 +87,132 @@ where
     T: InputScheme,
 {
     pub fn new(game_title: &str, graphics_options: GraphicsOptions) -> Self {
-        let ctx = sdl2::init().unwrap();
-
-        // Setup GrahicsPipeline
-        let video_subsystem = ctx.video().unwrap();
-        let window = video_subsystem
-            .window(
-                game_title,
-                graphics_options.window_size.0,
-                graphics_options.window_size.1,
-            )
-            .position_centered()
+        let mut builder = EngineBuilder::new(game_title);
+        builder.graphics_options(graphics_options);
+        builder
             .build()
-            .unwrap();
+            .unwrap_or_else(|e| panic!("failed to create the engine: {e}"))
+    }
+
+    // Must be called once per frame, before reading inputs. Ends the profiler frame and calls
+    // the completions of the jobs done, see jobs::spawn_then.
+    pub fn update(&mut self) {
+        profiler::frame();
+        jobs::complete(&mut self.resources);
+        profile_scope!("inputs");
+        let events = self.inputs_ppl.process_events();
+        self.focus.update(&events, self.graphics_ppl.window_id().0);
+        crash::record_frame(&events, self.windows());
+        for event in &events {
+            if let sdl2::event::Event::Window {
+                window_id,
+                win_event: WindowEvent::SizeChanged(..) | WindowEvent::DisplayChanged(_),
+                ..
+            } = event
+            {
+                if let Some(window) = self.window_mut(WindowId(*window_id)) {
+                    if let Err(e) = window.resized() {
+                        log::warn!("can't resize window: {e}");
+                    }
+                }
+            }
+        }
+        for handler in &mut self.event_handlers {
+            for event in &events {
+                handler(event, &mut self.resources);
+            }
+        }
+    }
+
+    // Game loop working on every platform, see platform::run. Every frame the engine is updated,
+    // then frame is called with Focus::delta and the main window is presented, until it returns
+    // false.
+    pub fn run<F: FnMut(&mut Engine<T>, f64) -> bool + 'static>(mut self, mut frame: F)
+    where
+        T: 'static,
+    {
+        platform::run(move |_| {
+            self.update();
+            let delta = self.focus.delta();
+            let running = frame(&mut self, delta);
+            self.graphics_ppl.run();
+            running
+        });
+    }
+
+    // Runs the systems added by plugins, dt being the duration of the frame in seconds. The
+    // resources are lent to the systems meanwhile, exclusive ones receiving them separately
+    // from the engine. dt is clamped by the focus, fixed updates not running while paused.
+    pub fn run_schedule(&mut self, dt: f64) {
+        let dt = self.focus.clamp(dt);
+        let mut schedule = std::mem::take(&mut self.schedule);
+        let mut resources = std::mem::take(&mut self.resources);
+        schedule.run(&mut resources, self, dt);
+        self.schedule = schedule;
+        self.resources = resources;
+    }
+
+    // Opens another resizable window with its own renderer, camera and textures, e.g. for tools.
+    // Its pipeline has to be run like the main one.
+    pub fn open_window(
+        &mut self,
+        title: &str,
+        options: GraphicsOptions,
+    ) -> Result<WindowId, String> {
+        let window = create_window(&self.video, title, &options, true, None)?;
+        let renderer = (self.create_renderer)(window, &options)?;
+        let window = GraphicsPipeline::with_renderer(options, renderer);
+        let id = window.window_id();
+        self.windows.push(window);
+        Ok(id)
+    }
+
+    // The main window can't be closed, returns whether the window was.
+    pub fn close_window(&mut self, id: WindowId) -> bool {
+        let count = self.windows.len();
+        self.windows.retain(|w| w.window_id() != id);
+        self.windows.len() != count
+    }
+
+    // Pipeline of any window, the main one being graphics_ppl.
+    pub fn window(&self, id: WindowId) -> Option<&GraphicsPipeline> {
+        std::iter::once(&self.graphics_ppl)
+            .chain(&self.windows)
+            .find(|w| w.window_id() == id)
+    }
+
+    pub fn window_mut(&mut self, id: WindowId) -> Option<&mut GraphicsPipeline> {
+        std::iter::once(&mut self.graphics_ppl)
+            .chain(&mut self.windows)
+            .find(|w| w.window_id() == id)
+    }
+
+    // Main window first.
+    pub fn windows(&self) -> Vec<WindowId> {
+        std::iter::once(&self.graphics_ppl)
+            .chain(&self.windows)
+            .map(|w| w.window_id())
+            .collect()
+    }
 
-        let canvas = window.into_canvas().build().unwrap();
-        let graphics_ppl = graphics::GraphicsPipeline::new(graphics_options, canvas);
+    pub fn displays(&self) -> Result<Vec<graphics::Display>, String> {
+        graphics::displays(&self.video)
+    }
 
-        // Setup InputsPipeline
-        let event_pump = ctx.event_pump().unwrap();
-        let inputs_ppl = inputs::InputsPipeline::new(event_pump);
+    // SDL's audio subsystem, to open devices with, if enabled with EngineBuilder::audio.
+    pub fn audio(&self) -> Option<&AudioSubsystem> {
+        self.audio.as_ref()
+    }
 
-        Engine {
-            graphics_ppl,
-            inputs_ppl,
+    pub fn clipboard_get(&self) -> Option<String> {
+        if !self.clipboard.has_clipboard_text() {
+            return None;
         }
+
+        self.clipboard.clipboard_text().ok()
+    }
+
+    pub fn clipboard_set(&self, text: &str) -> Result<(), String> {
+        self.clipboard.set_clipboard_text(text)
     }
 }
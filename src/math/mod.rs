@@ -0,0 +1,59 @@
+use std::f64::consts::{PI, TAU};
+
+use crate::Vec2;
+
+pub use noise::{Fbm, Noise, NoiseKind};
+pub use rect::Rect;
+
+mod noise;
+mod rect;
+
+// Helpers missing from Vec2. Angles are in radians.
+pub trait Vec2Ext {
+    fn rotated(&self, angle: f64) -> Vec2;
+
+    // Moves toward target without going further than max_distance, nor overshooting it.
+    fn move_toward(&self, target: &Vec2, max_distance: f64) -> Vec2;
+
+    // Unsigned, between 0 and PI.
+    fn angle_between(&self, other: &Vec2) -> f64;
+}
+
+impl Vec2Ext for Vec2 {
+    fn rotated(&self, angle: f64) -> Vec2 {
+        Vec2::from_angle(angle).rotate(*self)
+    }
+
+    fn move_toward(&self, target: &Vec2, max_distance: f64) -> Vec2 {
+        let offset = target - self;
+        let distance = offset.length();
+        if distance <= max_distance || distance == 0. {
+            return *target;
+        }
+        self + offset / distance * max_distance
+    }
+
+    fn angle_between(&self, other: &Vec2) -> f64 {
+        self.angle_to(*other).abs()
+    }
+}
+
+// Wraps the angle between -PI excluded and PI included.
+pub fn wrap_angle(angle: f64) -> f64 {
+    let wrapped = (angle + PI).rem_euclid(TAU) - PI;
+    if wrapped == -PI {
+        PI
+    } else {
+        wrapped
+    }
+}
+
+// Shortest signed rotation going from one angle to the other.
+pub fn angle_difference(from: f64, to: f64) -> f64 {
+    wrap_angle(to - from)
+}
+
+// Interpolates along the shortest rotation.
+pub fn lerp_angle(from: f64, to: f64, t: f64) -> f64 {
+    from + angle_difference(from, to) * t
+}
@@ -0,0 +1,215 @@
+use std::f64::consts::FRAC_1_SQRT_2;
+
+use crate::{random::Rng, Vec2};
+
+const GRADIENTS: [Vec2; 8] = [
+    Vec2::new(1., 0.),
+    Vec2::new(-1., 0.),
+    Vec2::new(0., 1.),
+    Vec2::new(0., -1.),
+    Vec2::new(FRAC_1_SQRT_2, FRAC_1_SQRT_2),
+    Vec2::new(-FRAC_1_SQRT_2, FRAC_1_SQRT_2),
+    Vec2::new(FRAC_1_SQRT_2, -FRAC_1_SQRT_2),
+    Vec2::new(-FRAC_1_SQRT_2, -FRAC_1_SQRT_2),
+];
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum NoiseKind {
+    Value,
+    #[default]
+    Perlin,
+    // Perlin is used in 1D, simplex noise only differs from it in higher dimensions.
+    Simplex,
+}
+
+// Sums octaves of noise, each one lacunarity times the frequency and gain times the amplitude
+// of the previous one.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Fbm {
+    pub octaves: u32,
+    pub lacunarity: f64,
+    pub gain: f64,
+}
+
+// Coherent noise, samples are between -1 and 1 and vary smoothly with their position.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Noise {
+    permutation: Vec<u8>,
+}
+
+impl Default for Fbm {
+    fn default() -> Self {
+        Fbm {
+            octaves: 4,
+            lacunarity: 2.,
+            gain: 0.5,
+        }
+    }
+}
+
+impl Noise {
+    // Typically given a stream of Engine::random, so that the noise is reproduced by the seed.
+    pub fn new(rng: &mut Rng) -> Self {
+        let mut permutation: Vec<u8> = (0..=u8::MAX).collect();
+        rng.shuffle(&mut permutation);
+        permutation.extend_from_within(..);
+
+        Noise { permutation }
+    }
+
+    pub fn sample_1d(&self, kind: NoiseKind, x: f64) -> f64 {
+        match kind {
+            NoiseKind::Value => self.value_1d(x),
+            NoiseKind::Perlin | NoiseKind::Simplex => self.perlin_1d(x),
+        }
+    }
+
+    pub fn sample_2d(&self, kind: NoiseKind, position: &Vec2) -> f64 {
+        match kind {
+            NoiseKind::Value => self.value_2d(position),
+            NoiseKind::Perlin => self.perlin_2d(position),
+            NoiseKind::Simplex => self.simplex_2d(position),
+        }
+    }
+
+    // Normalized back between -1 and 1.
+    pub fn fbm_1d(&self, kind: NoiseKind, x: f64, fbm: &Fbm) -> f64 {
+        self.fbm(fbm, |frequency| self.sample_1d(kind, x * frequency))
+    }
+
+    pub fn fbm_2d(&self, kind: NoiseKind, position: &Vec2, fbm: &Fbm) -> f64 {
+        self.fbm(fbm, |frequency| {
+            self.sample_2d(kind, &(position * frequency))
+        })
+    }
+
+    pub fn value_1d(&self, x: f64) -> f64 {
+        let x0 = x.floor();
+        let t = fade(x - x0);
+        let i = x0 as i64;
+
+        lerp(self.random(i, 0), self.random(i + 1, 0), t)
+    }
+
+    pub fn value_2d(&self, position: &Vec2) -> f64 {
+        let cell = position.floor();
+        let (x, y) = (cell.x as i64, cell.y as i64);
+        let (tx, ty) = (fade(position.x - cell.x), fade(position.y - cell.y));
+
+        lerp(
+            lerp(self.random(x, y), self.random(x + 1, y), tx),
+            lerp(self.random(x, y + 1), self.random(x + 1, y + 1), tx),
+            ty,
+        )
+    }
+
+    pub fn perlin_1d(&self, x: f64) -> f64 {
+        let x0 = x.floor();
+        let i = x0 as i64;
+        let d = x - x0;
+
+        // Gradients are slopes between -1 and 1, the result peaks at 0.5
+        let slope = |i: i64| self.random(i, 0);
+        lerp(slope(i) * d, slope(i + 1) * (d - 1.), fade(d)) * 2.
+    }
+
+    pub fn perlin_2d(&self, position: &Vec2) -> f64 {
+        let cell = position.floor();
+        let (x, y) = (cell.x as i64, cell.y as i64);
+        let d = position - cell;
+
+        let corner = |dx: i64, dy: i64| {
+            self.gradient(x + dx, y + dy)
+                .dot(d - Vec2::new(dx as f64, dy as f64))
+        };
+        let (tx, ty) = (fade(d.x), fade(d.y));
+
+        let value = lerp(
+            lerp(corner(0, 0), corner(1, 0), tx),
+            lerp(corner(0, 1), corner(1, 1), tx),
+            ty,
+        );
+        (value * std::f64::consts::SQRT_2).clamp(-1., 1.)
+    }
+
+    pub fn simplex_2d(&self, position: &Vec2) -> f64 {
+        let skew = (3f64.sqrt() - 1.) / 2.;
+        let unskew = (3. - 3f64.sqrt()) / 6.;
+
+        // Cell of the skewed grid, made of two triangles
+        let s = (position.x + position.y) * skew;
+        let cell = (position + Vec2::splat(s)).floor();
+        let t = (cell.x + cell.y) * unskew;
+        let d0 = position - (cell - Vec2::splat(t));
+        let middle = if d0.x > d0.y {
+            Vec2::new(1., 0.)
+        } else {
+            Vec2::new(0., 1.)
+        };
+
+        let corners = [
+            (Vec2::ZERO, d0),
+            (middle, d0 - middle + Vec2::splat(unskew)),
+            (Vec2::ONE, d0 - Vec2::ONE + Vec2::splat(2. * unskew)),
+        ];
+
+        let value: f64 = corners
+            .iter()
+            .map(|(offset, d)| {
+                let falloff = 0.5 - d.length_squared();
+                if falloff <= 0. {
+                    return 0.;
+                }
+
+                let gradient = self.gradient(
+                    cell.x as i64 + offset.x as i64,
+                    cell.y as i64 + offset.y as i64,
+                );
+                falloff.powi(4) * gradient.dot(*d)
+            })
+            .sum();
+        (value * 70.).clamp(-1., 1.)
+    }
+
+    fn fbm<F: Fn(f64) -> f64>(&self, fbm: &Fbm, sample: F) -> f64 {
+        let mut frequency = 1.;
+        let mut amplitude = 1.;
+        let mut total = 0.;
+        let mut max = 0.;
+
+        for _ in 0..fbm.octaves {
+            total += sample(frequency) * amplitude;
+            max += amplitude;
+            frequency *= fbm.lacunarity;
+            amplitude *= fbm.gain;
+        }
+
+        if max == 0. {
+            return 0.;
+        }
+        total / max
+    }
+
+    fn hash(&self, x: i64, y: i64) -> u8 {
+        let x = (x & 0xff) as usize;
+        let y = (y & 0xff) as usize;
+        self.permutation[self.permutation[x] as usize + y]
+    }
+
+    // Between -1 and 1.
+    fn random(&self, x: i64, y: i64) -> f64 {
+        self.hash(x, y) as f64 / u8::MAX as f64 * 2. - 1.
+    }
+
+    fn gradient(&self, x: i64, y: i64) -> Vec2 {
+        GRADIENTS[self.hash(x, y) as usize % GRADIENTS.len()]
+    }
+}
+
+fn fade(t: f64) -> f64 {
+    t * t * t * (t * (t * 6. - 15.) + 10.)
+}
+
+fn lerp(a: f64, b: f64, t: f64) -> f64 {
+    a + (b - a) * t
+}
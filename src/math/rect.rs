@@ -0,0 +1,72 @@
+use crate::Vec2;
+
+// Axis aligned rect in world units, position being its min corner.
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
+pub struct Rect {
+    pub position: Vec2,
+    pub size: Vec2,
+}
+
+impl Rect {
+    pub fn new(position: Vec2, size: Vec2) -> Self {
+        Rect { position, size }
+    }
+
+    pub fn from_center(center: Vec2, size: Vec2) -> Self {
+        Rect::new(center - size / 2., size)
+    }
+
+    pub fn from_corners(a: Vec2, b: Vec2) -> Self {
+        let min = a.min(b);
+        Rect::new(min, a.max(b) - min)
+    }
+
+    pub fn min(&self) -> Vec2 {
+        self.position
+    }
+
+    pub fn max(&self) -> Vec2 {
+        self.position + self.size
+    }
+
+    pub fn center(&self) -> Vec2 {
+        self.position + self.size / 2.
+    }
+
+    // Points on the border are contained.
+    pub fn contains(&self, point: &Vec2) -> bool {
+        point.cmpge(self.min()).all() && point.cmple(self.max()).all()
+    }
+
+    pub fn contains_rect(&self, other: &Rect) -> bool {
+        self.contains(&other.min()) && self.contains(&other.max())
+    }
+
+    // Rects only touching by a border don't intersect.
+    pub fn intersects(&self, other: &Rect) -> bool {
+        self.min().cmplt(other.max()).all() && other.min().cmplt(self.max()).all()
+    }
+
+    pub fn intersection(&self, other: &Rect) -> Option<Rect> {
+        if !self.intersects(other) {
+            return None;
+        }
+        Some(Rect::from_corners(
+            self.min().max(other.min()),
+            self.max().min(other.max()),
+        ))
+    }
+
+    // Smallest rect containing both.
+    pub fn union(&self, other: &Rect) -> Rect {
+        Rect::from_corners(self.min().min(other.min()), self.max().max(other.max()))
+    }
+
+    // Grows the rect by margin on every side, negative margins shrink it.
+    pub fn expand(&self, margin: f64) -> Rect {
+        Rect::new(
+            self.position - Vec2::splat(margin),
+            (self.size + Vec2::splat(margin * 2.)).max(Vec2::ZERO),
+        )
+    }
+}
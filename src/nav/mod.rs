@@ -0,0 +1,385 @@
+use std::{
+    cmp::Reverse,
+    collections::{BinaryHeap, HashMap},
+};
+
+use crate::{
+    graphics::{TileId, Tilemap},
+    Point,
+};
+
+pub use requests::{PathRequestId, PathStatus, Pathfinder};
+pub use visibility::field_of_view;
+
+mod requests;
+mod visibility;
+
+const STRAIGHT_COST: u32 = 10;
+const DIAGONAL_COST: u32 = 14;
+
+// Grid of walkable cells, (0, 0) being the top left one.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct NavGrid {
+    width: u32,
+    height: u32,
+    walkable: Vec<bool>,
+    // Diagonal moves are never allowed to cut the corner of a blocked cell.
+    pub diagonal: bool,
+}
+
+// A* search that can be run a few nodes at a time.
+pub(crate) struct Search {
+    start: Point,
+    goal: Point,
+    open: BinaryHeap<Reverse<(u32, usize)>>,
+    came_from: HashMap<usize, usize>,
+    costs: HashMap<usize, u32>,
+}
+
+impl NavGrid {
+    pub fn new(width: u32, height: u32) -> Self {
+        NavGrid {
+            width,
+            height,
+            walkable: vec![true; (width * height) as usize],
+            diagonal: true,
+        }
+    }
+
+    // Cells are walkable when they have a tile for which is_walkable returns true.
+    pub fn from_tilemap<F: Fn(TileId) -> bool>(tilemap: &Tilemap, is_walkable: F) -> Self {
+        let (width, height) = tilemap.size();
+        let mut grid = NavGrid::new(width, height);
+        for y in 0..height {
+            for x in 0..width {
+                let walkable = tilemap.tile(x, y).is_some_and(&is_walkable);
+                grid.set_walkable(Point::new(x as i32, y as i32), walkable);
+            }
+        }
+        grid
+    }
+
+    pub fn size(&self) -> (u32, u32) {
+        (self.width, self.height)
+    }
+
+    // Cells outside of the grid are never walkable.
+    pub fn is_walkable(&self, cell: Point) -> bool {
+        self.index(cell).is_some_and(|i| self.walkable[i])
+    }
+
+    pub fn set_walkable(&mut self, cell: Point, walkable: bool) {
+        if let Some(i) = self.index(cell) {
+            self.walkable[i] = walkable;
+        }
+    }
+
+    // Cells from start to goal included, None if goal can't be reached.
+    pub fn find_path(&self, start: Point, goal: Point) -> Option<Vec<Point>> {
+        let mut search = Search::new(self, start, goal);
+        loop {
+            if let Some(result) = search.step(self, usize::MAX) {
+                return result;
+            }
+        }
+    }
+
+    // Removes the cells that can be skipped by walking in a straight line.
+    pub fn smooth_path(&self, path: &[Point]) -> Vec<Point> {
+        let Some(first) = path.first() else {
+            return Vec::new();
+        };
+
+        let mut smoothed = vec![*first];
+        let mut anchor = 0;
+        while anchor < path.len() - 1 {
+            let next = (anchor + 1..path.len())
+                .rev()
+                .find(|i| self.has_line_of_sight(path[anchor], path[*i]))
+                .unwrap_or(anchor + 1);
+
+            smoothed.push(path[next]);
+            anchor = next;
+        }
+
+        smoothed
+    }
+
+    // True if every cell touched by the segment between the centers of from and to is walkable.
+    pub fn has_line_of_sight(&self, from: Point, to: Point) -> bool {
+        let delta = to - from;
+        let step = delta.signum();
+        let (nx, ny) = (delta.x.abs(), delta.y.abs());
+        let (mut ix, mut iy) = (0, 0);
+        let mut cell = from;
+
+        if !self.is_walkable(cell) {
+            return false;
+        }
+
+        while ix < nx || iy < ny {
+            // Compares the distances to the next vertical and horizontal cell borders
+            let decision = (1 + 2 * ix) * ny - (1 + 2 * iy) * nx;
+            if decision == 0 {
+                // The segment goes exactly through a corner, both side cells are touched
+                if !self.is_walkable(cell + Point::new(step.x, 0))
+                    || !self.is_walkable(cell + Point::new(0, step.y))
+                {
+                    return false;
+                }
+                cell += step;
+                ix += 1;
+                iy += 1;
+            } else if decision < 0 {
+                cell.x += step.x;
+                ix += 1;
+            } else {
+                cell.y += step.y;
+                iy += 1;
+            }
+
+            if !self.is_walkable(cell) {
+                return false;
+            }
+        }
+
+        true
+    }
+
+    // Cells visible from origin, see field_of_view, unwalkable cells blocking the view.
+    pub fn field_of_view(&self, origin: Point, radius: u32) -> Vec<Point> {
+        field_of_view(origin, radius, |cell| !self.is_walkable(cell))
+    }
+
+    fn index(&self, cell: Point) -> Option<usize> {
+        let inside = cell.x >= 0
+            && cell.y >= 0
+            && (cell.x as u32) < self.width
+            && (cell.y as u32) < self.height;
+        inside.then(|| (cell.y as u32 * self.width + cell.x as u32) as usize)
+    }
+
+    fn cell(&self, index: usize) -> Point {
+        Point::new(
+            (index as u32 % self.width) as i32,
+            (index as u32 / self.width) as i32,
+        )
+    }
+
+    fn neighbors(&self, cell: Point) -> impl Iterator<Item = (Point, u32)> + '_ {
+        let straight = [
+            Point::new(0, -1),
+            Point::new(1, 0),
+            Point::new(0, 1),
+            Point::new(-1, 0),
+        ]
+        .map(|d| (d, STRAIGHT_COST));
+        let diagonal = [
+            Point::new(1, -1),
+            Point::new(1, 1),
+            Point::new(-1, 1),
+            Point::new(-1, -1),
+        ]
+        .map(|d| (d, DIAGONAL_COST));
+
+        straight
+            .into_iter()
+            .chain(diagonal.into_iter().filter(|_| self.diagonal))
+            .filter(move |(d, _)| {
+                self.is_walkable(cell + *d)
+                    && (d.x == 0
+                        || d.y == 0
+                        || self.is_walkable(cell + Point::new(d.x, 0))
+                            && self.is_walkable(cell + Point::new(0, d.y)))
+            })
+            .map(move |(d, cost)| (cell + d, cost))
+    }
+
+    fn heuristic(&self, from: Point, to: Point) -> u32 {
+        let d = (to - from).abs();
+        let (min, max) = (d.x.min(d.y) as u32, d.x.max(d.y) as u32);
+        if self.diagonal {
+            min * DIAGONAL_COST + (max - min) * STRAIGHT_COST
+        } else {
+            (min + max) * STRAIGHT_COST
+        }
+    }
+}
+
+impl Search {
+    pub(crate) fn new(grid: &NavGrid, start: Point, goal: Point) -> Self {
+        let mut search = Search {
+            start,
+            goal,
+            open: BinaryHeap::new(),
+            came_from: HashMap::new(),
+            costs: HashMap::new(),
+        };
+
+        if let Some(i) = grid.index(start).filter(|_| grid.is_walkable(goal)) {
+            search.costs.insert(i, 0);
+            search.open.push(Reverse((grid.heuristic(start, goal), i)));
+        }
+        search
+    }
+
+    // Expands up to budget nodes, returns the result once the search is over.
+    pub(crate) fn step(&mut self, grid: &NavGrid, budget: usize) -> Option<Option<Vec<Point>>> {
+        for _ in 0..budget {
+            let Some(Reverse((_, current))) = self.open.pop() else {
+                return Some(None);
+            };
+
+            let cell = grid.cell(current);
+            if cell == self.goal {
+                return Some(Some(self.path(grid, current)));
+            }
+
+            let cost = self.costs[&current];
+            for (neighbor, step_cost) in grid.neighbors(cell) {
+                let i = grid.index(neighbor).unwrap();
+                let new_cost = cost + step_cost;
+                if self.costs.get(&i).is_some_and(|c| *c <= new_cost) {
+                    continue;
+                }
+
+                self.costs.insert(i, new_cost);
+                self.came_from.insert(i, current);
+                self.open
+                    .push(Reverse((new_cost + grid.heuristic(neighbor, self.goal), i)));
+            }
+        }
+
+        None
+    }
+
+    fn path(&self, grid: &NavGrid, mut current: usize) -> Vec<Point> {
+        let mut path = vec![grid.cell(current)];
+        while let Some(previous) = self.came_from.get(&current) {
+            current = *previous;
+            path.push(grid.cell(current));
+        }
+
+        path.reverse();
+        debug_assert_eq!(path.first(), Some(&self.start));
+        path
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Grid drawn with '#' for blocked cells, returning it with the cells of 'S' and 'G'.
+    fn drawn(rows: &[&str]) -> (NavGrid, Point, Point) {
+        let mut grid = NavGrid::new(rows[0].len() as u32, rows.len() as u32);
+        let (mut start, mut goal) = (Point::ZERO, Point::ZERO);
+        for (y, row) in rows.iter().enumerate() {
+            for (x, c) in row.chars().enumerate() {
+                let cell = Point::new(x as i32, y as i32);
+                match c {
+                    '#' => grid.set_walkable(cell, false),
+                    'S' => start = cell,
+                    'G' => goal = cell,
+                    _ => {}
+                }
+            }
+        }
+        (grid, start, goal)
+    }
+
+    // Checks that the path only takes allowed moves, returning its cost.
+    fn cost(grid: &NavGrid, path: &[Point]) -> u32 {
+        path.windows(2)
+            .map(|step| {
+                let (from, to) = (step[0], step[1]);
+                let cost = grid
+                    .neighbors(from)
+                    .find(|(cell, _)| *cell == to)
+                    .map(|(_, cost)| cost);
+                cost.unwrap_or_else(|| panic!("invalid move from {from} to {to}"))
+            })
+            .sum()
+    }
+
+    #[test]
+    fn paths_are_the_shortest() {
+        let (mut grid, start, goal) = drawn(&[".....", "S....", ".....", "....G"]);
+        let path = grid.find_path(start, goal).unwrap();
+        assert_eq!((path.first(), path.last()), (Some(&start), Some(&goal)));
+        assert_eq!((path.len(), cost(&grid, &path)), (5, 2 * 14 + 2 * 10));
+
+        grid.diagonal = false;
+        let path = grid.find_path(start, goal).unwrap();
+        assert_eq!((path.len(), cost(&grid, &path)), (7, 6 * 10));
+
+        assert_eq!(grid.find_path(start, start), Some(vec![start]));
+    }
+
+    #[test]
+    fn paths_go_around_walls() {
+        let (grid, start, goal) = drawn(&[
+            "........", "...#....", "...#....", "S..#...G", "...#....", "...#....",
+        ]);
+        let path = grid.find_path(start, goal).unwrap();
+        assert_eq!(cost(&grid, &path), 100);
+        assert!(path.contains(&Point::new(3, 0)));
+    }
+
+    #[test]
+    fn corners_of_blocked_cells_are_not_cut() {
+        let (grid, start, goal) = drawn(&["S.#...", "#.#.#.", "#...#G"]);
+        let cells = [
+            (0, 0),
+            (1, 0),
+            (1, 1),
+            (1, 2),
+            (2, 2),
+            (3, 2),
+            (3, 1),
+            (3, 0),
+            (4, 0),
+            (5, 0),
+            (5, 1),
+            (5, 2),
+        ];
+        let expected: Vec<Point> = cells.iter().map(|(x, y)| Point::new(*x, *y)).collect();
+        assert_eq!(grid.find_path(start, goal), Some(expected));
+    }
+
+    #[test]
+    fn unreachable_goals_have_no_path() {
+        let (grid, start, goal) = drawn(&["S.#..", "..#.G", "..#.."]);
+        assert_eq!(grid.find_path(start, goal), None);
+        assert_eq!(grid.find_path(start, Point::new(2, 0)), None);
+        assert_eq!(grid.find_path(start, Point::new(-1, 0)), None);
+        // Diagonal gaps can't be squeezed through either
+        let (grid, start, goal) = drawn(&["S.#", ".#.", "#.G"]);
+        assert_eq!(grid.find_path(start, goal), None);
+    }
+
+    #[test]
+    fn searches_can_be_run_a_node_at_a_time() {
+        let (grid, start, goal) = drawn(&["S...#...", "..#.#.#.", "..#...#G"]);
+        let mut search = Search::new(&grid, start, goal);
+        let mut steps = 1;
+        let path = loop {
+            match search.step(&grid, 1) {
+                Some(path) => break path,
+                None => steps += 1,
+            }
+        };
+        assert!(steps > 1);
+        assert_eq!(path, grid.find_path(start, goal));
+    }
+
+    #[test]
+    fn smoothed_paths_keep_the_turns() {
+        let (grid, start, goal) = drawn(&["S....", "####.", "....G"]);
+        let path = grid.find_path(start, goal).unwrap();
+        let corners = [Point::new(0, 0), Point::new(4, 0), Point::new(4, 2)];
+        assert_eq!(grid.smooth_path(&path), corners);
+        assert!(!grid.has_line_of_sight(start, goal));
+        assert!(grid.has_line_of_sight(Point::new(0, 2), goal));
+    }
+}
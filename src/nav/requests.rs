@@ -0,0 +1,145 @@
+use std::collections::HashMap;
+
+use super::{NavGrid, Search};
+use crate::{
+    jobs::{self, Job},
+    profile_scope, Point,
+};
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct PathRequestId(u64);
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum PathStatus {
+    Pending,
+    Found(Vec<Point>),
+    NotFound,
+}
+
+// Runs path searches over several frames, update has to be called once per frame.
+pub struct Pathfinder {
+    // Maximum number of nodes expanded per update, shared by all pending searches.
+    pub budget: usize,
+    pub smooth: bool,
+    next_id: u64,
+    pending: Vec<(PathRequestId, Search)>,
+    // Searches running on a copy of the grid, see request_background.
+    background: Vec<(PathRequestId, Job<Option<Vec<Point>>>)>,
+    done: HashMap<PathRequestId, Option<Vec<Point>>>,
+}
+
+impl Default for Pathfinder {
+    fn default() -> Self {
+        Pathfinder {
+            budget: 1000,
+            smooth: false,
+            next_id: 0,
+            pending: Vec::new(),
+            background: Vec::new(),
+            done: HashMap::new(),
+        }
+    }
+}
+
+impl Pathfinder {
+    pub fn new() -> Self {
+        Pathfinder::default()
+    }
+
+    pub fn request(&mut self, grid: &NavGrid, start: Point, goal: Point) -> PathRequestId {
+        let id = PathRequestId(self.next_id);
+        self.next_id += 1;
+        self.pending.push((id, Search::new(grid, start, goal)));
+        id
+    }
+
+    // Searches on one of the engine's job threads instead of within the budget of update, for
+    // long paths on large grids. The grid is copied, later changes not being seen by the search.
+    pub fn request_background(
+        &mut self,
+        grid: &NavGrid,
+        start: Point,
+        goal: Point,
+    ) -> PathRequestId {
+        let id = PathRequestId(self.next_id);
+        self.next_id += 1;
+        let grid = grid.clone();
+        let smooth = self.smooth;
+        let job = jobs::spawn(move || {
+            let path = grid.find_path(start, goal)?;
+            Some(if smooth {
+                grid.smooth_path(&path)
+            } else {
+                path
+            })
+        });
+        self.background.push((id, job));
+        id
+    }
+
+    // Drops a search, or its result if it was over.
+    pub fn cancel(&mut self, id: PathRequestId) {
+        self.pending.retain(|(i, _)| *i != id);
+        self.background.retain(|(i, _)| *i != id);
+        self.done.remove(&id);
+    }
+
+    // Results are handed out once, later polls consider the request unknown and return None.
+    pub fn poll(&mut self, id: PathRequestId) -> Option<PathStatus> {
+        self.collect_background();
+        if let Some(result) = self.done.remove(&id) {
+            return Some(match result {
+                Some(path) => PathStatus::Found(path),
+                None => PathStatus::NotFound,
+            });
+        }
+
+        self.pending
+            .iter()
+            .map(|(i, _)| i)
+            .chain(self.background.iter().map(|(i, _)| i))
+            .any(|i| *i == id)
+            .then_some(PathStatus::Pending)
+    }
+
+    // Moves the results of the background searches done with the others. Panicked searches
+    // are reported as not found.
+    fn collect_background(&mut self) {
+        self.background.retain(|(id, job)| {
+            if job.has_panicked() {
+                self.done.insert(*id, None);
+                return false;
+            }
+            let Some(result) = job.take() else {
+                return true;
+            };
+            self.done.insert(*id, result);
+            false
+        });
+    }
+
+    // Searches are advanced in the order they were requested. The grid is expected not to
+    // change while they are pending.
+    pub fn update(&mut self, grid: &NavGrid) {
+        profile_scope!("pathfinding");
+        let mut budget = self.budget;
+        while budget > 0 && !self.pending.is_empty() {
+            let step = budget.min(self.budget / self.pending.len()).max(1);
+            budget -= step;
+
+            let (id, search) = &mut self.pending[0];
+            let Some(result) = search.step(grid, step) else {
+                // Moves on to the next search, the current one resumes on the next round
+                self.pending.rotate_left(1);
+                continue;
+            };
+
+            let result = match result {
+                Some(path) if self.smooth => Some(grid.smooth_path(&path)),
+                result => result,
+            };
+            self.done.insert(*id, result);
+            self.pending.remove(0);
+        }
+    }
+}
@@ -0,0 +1,104 @@
+use crate::Point;
+
+// Multipliers turning the coordinates of the first octant into each octant's.
+const OCTANTS: [[i32; 4]; 8] = [
+    [1, 0, 0, 1],
+    [0, 1, 1, 0],
+    [0, -1, 1, 0],
+    [-1, 0, 0, 1],
+    [-1, 0, 0, -1],
+    [0, -1, -1, 0],
+    [0, 1, -1, 0],
+    [1, 0, 0, -1],
+];
+
+// Cells visible from origin within radius cells, with recursive shadow casting: walls are
+// visible but hide what's behind them. The origin is always visible. Cells are sorted by row
+// then column, is_opaque having to return true outside of the map.
+pub fn field_of_view<F: Fn(Point) -> bool>(origin: Point, radius: u32, is_opaque: F) -> Vec<Point> {
+    let radius = radius as i32;
+    let side = 2 * radius + 1;
+    let mut visible = vec![false; (side * side) as usize];
+    let mut fov = Fov {
+        origin,
+        radius,
+        is_opaque: &is_opaque,
+        visible: &mut visible,
+    };
+    fov.mark(Point::ZERO);
+    for octant in &OCTANTS {
+        fov.cast(octant, 1, 1., 0.);
+    }
+
+    visible
+        .iter()
+        .enumerate()
+        .filter(|(_, visible)| **visible)
+        .map(|(i, _)| origin + Point::new(i as i32 % side - radius, i as i32 / side - radius))
+        .collect()
+}
+
+struct Fov<'a, F> {
+    origin: Point,
+    radius: i32,
+    is_opaque: &'a F,
+    // Square of side 2 * radius + 1 around the origin.
+    visible: &'a mut [bool],
+}
+
+impl<F: Fn(Point) -> bool> Fov<'_, F> {
+    // Scans the rows of an octant from row on, between two slopes of the lines from the origin,
+    // start being the steepest. Each wall met starts a scan of the next rows above it.
+    fn cast(&mut self, octant: &[i32; 4], row: i32, mut start: f64, end: f64) {
+        if start < end {
+            return;
+        }
+
+        for distance in row..=self.radius {
+            let dy = -distance;
+            let mut blocked = false;
+            let mut next_start = start;
+            for dx in -distance..=0 {
+                // Slopes of the cell's left and right corners
+                let left = (dx as f64 - 0.5) / (dy as f64 + 0.5);
+                let right = (dx as f64 + 0.5) / (dy as f64 - 0.5);
+                if start < right {
+                    continue;
+                } else if end > left {
+                    break;
+                }
+
+                let offset = Point::new(
+                    dx * octant[0] + dy * octant[1],
+                    dx * octant[2] + dy * octant[3],
+                );
+                if dx * dx + dy * dy <= self.radius * self.radius {
+                    self.mark(offset);
+                }
+
+                let opaque = (self.is_opaque)(self.origin + offset);
+                if blocked {
+                    if opaque {
+                        next_start = right;
+                    } else {
+                        blocked = false;
+                        start = next_start;
+                    }
+                } else if opaque && distance < self.radius {
+                    blocked = true;
+                    self.cast(octant, distance + 1, start, left);
+                    next_start = right;
+                }
+            }
+            if blocked {
+                break;
+            }
+        }
+    }
+
+    fn mark(&mut self, offset: Point) {
+        let side = 2 * self.radius + 1;
+        let (x, y) = (offset.x + self.radius, offset.y + self.radius);
+        self.visible[(y * side + x) as usize] = true;
+    }
+}
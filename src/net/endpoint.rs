@@ -0,0 +1,473 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    io::ErrorKind,
+    net::{SocketAddr, ToSocketAddrs, UdpSocket},
+    time::{Duration, Instant},
+};
+
+use super::{Channel, NetEvent};
+
+const PROTOCOL_ID: u32 = 0x454e_4731;
+const MAX_PACKET_SIZE: usize = 1200;
+// Protocol id and packet kind.
+const HEADER_SIZE: usize = 5;
+// Largest message sent on either channel, reliable ones being prefixed by their id.
+pub const MAX_MESSAGE_SIZE: usize = MAX_PACKET_SIZE - HEADER_SIZE - 4;
+const RESEND_INTERVAL: Duration = Duration::from_millis(100);
+const HEARTBEAT_INTERVAL: Duration = Duration::from_millis(250);
+const TIMEOUT: Duration = Duration::from_secs(5);
+// Reliable ids remembered from the oldest one not received yet, to drop duplicates. Messages
+// with later ids are dropped until it's received.
+const RECEIVED_WINDOW: usize = 1024;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[repr(u8)]
+enum PacketKind {
+    Connect,
+    Accept,
+    Disconnect,
+    Heartbeat,
+    Unreliable,
+    Reliable,
+    Ack,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum Received {
+    New,
+    Duplicate,
+    // Beyond the window, neither delivered nor acknowledged for the peer to send it again.
+    TooEarly,
+}
+
+struct Connection {
+    // Connections made with Endpoint::connect have to be accepted by the peer.
+    accepted: bool,
+    last_received: Instant,
+    last_sent: Instant,
+    next_reliable_id: u32,
+    // Reliable messages not acknowledged yet, with when they were last sent.
+    unacked: HashMap<u32, (Instant, Vec<u8>)>,
+    // Every reliable id before it was received.
+    received_until: u32,
+    // Whether each id of the window starting at received_until was received.
+    received: VecDeque<bool>,
+}
+
+// UDP socket exchanging messages with any number of peers. Reliable messages are sent again
+// until acknowledged but may arrive out of order, unreliable ones may be lost or duplicated.
+pub struct Endpoint {
+    socket: UdpSocket,
+    connections: HashMap<SocketAddr, Connection>,
+    // Peers are only accepted when listening, otherwise they have to be connected to.
+    listening: bool,
+}
+
+impl PacketKind {
+    fn from_u8(value: u8) -> Option<Self> {
+        [
+            PacketKind::Connect,
+            PacketKind::Accept,
+            PacketKind::Disconnect,
+            PacketKind::Heartbeat,
+            PacketKind::Unreliable,
+            PacketKind::Reliable,
+            PacketKind::Ack,
+        ]
+        .into_iter()
+        .find(|k| *k as u8 == value)
+    }
+}
+
+impl Connection {
+    fn new(accepted: bool) -> Self {
+        let now = Instant::now();
+        Connection {
+            accepted,
+            last_received: now,
+            last_sent: now,
+            next_reliable_id: 0,
+            unacked: HashMap::new(),
+            received_until: 0,
+            received: VecDeque::from(vec![false; RECEIVED_WINDOW]),
+        }
+    }
+
+    fn receive_reliable(&mut self, id: u32) -> Received {
+        // Ids before received_until wrap around to offsets in the upper half
+        let offset = id.wrapping_sub(self.received_until);
+        if offset > u32::MAX / 2 {
+            return Received::Duplicate;
+        }
+        let offset = offset as usize;
+        if offset >= RECEIVED_WINDOW {
+            return Received::TooEarly;
+        }
+        if self.received[offset] {
+            return Received::Duplicate;
+        }
+
+        self.received[offset] = true;
+        while self.received.front() == Some(&true) {
+            self.received.pop_front();
+            self.received.push_back(false);
+            self.received_until = self.received_until.wrapping_add(1);
+        }
+        Received::New
+    }
+}
+
+impl Endpoint {
+    pub fn bind<A: ToSocketAddrs>(address: A, listening: bool) -> Result<Self, String> {
+        let socket = UdpSocket::bind(address).map_err(|e| e.to_string())?;
+        socket.set_nonblocking(true).map_err(|e| e.to_string())?;
+
+        Ok(Endpoint {
+            socket,
+            connections: HashMap::new(),
+            listening,
+        })
+    }
+
+    pub fn local_address(&self) -> Result<SocketAddr, String> {
+        self.socket.local_addr().map_err(|e| e.to_string())
+    }
+
+    pub fn connect(&mut self, peer: SocketAddr) -> Result<(), String> {
+        self.connections.insert(peer, Connection::new(false));
+        self.send_packet(peer, PacketKind::Connect, &[])
+    }
+
+    pub fn disconnect(&mut self, peer: SocketAddr) -> Result<(), String> {
+        if self.connections.remove(&peer).is_some() {
+            self.send_packet(peer, PacketKind::Disconnect, &[])?;
+        }
+        Ok(())
+    }
+
+    pub fn peers(&self) -> impl Iterator<Item = SocketAddr> + '_ {
+        self.connections.keys().copied()
+    }
+
+    pub fn send(&mut self, peer: SocketAddr, channel: Channel, data: &[u8]) -> Result<(), String> {
+        let Some(connection) = self.connections.get_mut(&peer) else {
+            return Err(format!("{peer} is not connected"));
+        };
+        // Checked before reliable messages are kept, as they would fail every time they're sent
+        if data.len() > MAX_MESSAGE_SIZE {
+            return Err(format!(
+                "message of {} bytes is bigger than the {} bytes limit",
+                data.len(),
+                MAX_MESSAGE_SIZE
+            ));
+        }
+
+        match channel {
+            Channel::Unreliable => self.send_packet(peer, PacketKind::Unreliable, data),
+            Channel::Reliable => {
+                let id = connection.next_reliable_id;
+                connection.next_reliable_id = id.wrapping_add(1);
+
+                let mut payload = id.to_le_bytes().to_vec();
+                payload.extend_from_slice(data);
+                connection
+                    .unacked
+                    .insert(id, (Instant::now(), payload.clone()));
+                self.send_packet(peer, PacketKind::Reliable, &payload)
+            }
+        }
+    }
+
+    // Receives pending packets, resends unacknowledged messages and drops silent peers. Failures
+    // to receive or send are logged, the events received until then being returned.
+    pub fn poll(&mut self) -> Result<Vec<NetEvent>, String> {
+        let mut events = Vec::new();
+        let mut buffer = [0; MAX_PACKET_SIZE];
+
+        loop {
+            let (size, from) = match self.socket.recv_from(&mut buffer) {
+                Ok(received) => received,
+                Err(e) if e.kind() == ErrorKind::WouldBlock => break,
+                // Sending to a closed port is reported on the next receive on some platforms
+                Err(e) if e.kind() == ErrorKind::ConnectionReset => continue,
+                Err(e) => {
+                    log::warn!("failed to receive packets: {e}");
+                    break;
+                }
+            };
+
+            self.receive_packet(from, &buffer[..size], &mut events);
+        }
+
+        let now = Instant::now();
+        let timed_out: Vec<SocketAddr> = self
+            .connections
+            .iter()
+            .filter(|(_, c)| now - c.last_received > TIMEOUT)
+            .map(|(peer, _)| *peer)
+            .collect();
+        for peer in timed_out {
+            log::warn!("{peer} timed out");
+            self.connections.remove(&peer);
+            events.push(NetEvent::Disconnected(peer));
+        }
+
+        let mut resends = Vec::new();
+        for (peer, connection) in &mut self.connections {
+            for (sent, payload) in connection.unacked.values_mut() {
+                if now - *sent >= RESEND_INTERVAL {
+                    *sent = now;
+                    resends.push((*peer, PacketKind::Reliable, payload.clone()));
+                }
+            }
+
+            if !connection.accepted && now - connection.last_sent >= RESEND_INTERVAL {
+                resends.push((*peer, PacketKind::Connect, Vec::new()));
+            } else if now - connection.last_sent >= HEARTBEAT_INTERVAL {
+                resends.push((*peer, PacketKind::Heartbeat, Vec::new()));
+            }
+        }
+        for (peer, kind, payload) in resends {
+            if let Err(e) = self.send_packet(peer, kind, &payload) {
+                log::warn!("failed to send to {peer}: {e}");
+            }
+        }
+
+        Ok(events)
+    }
+
+    fn receive_packet(&mut self, from: SocketAddr, packet: &[u8], events: &mut Vec<NetEvent>) {
+        if packet.len() < HEADER_SIZE || packet[..4] != PROTOCOL_ID.to_le_bytes() {
+            return;
+        }
+        let Some(kind) = PacketKind::from_u8(packet[4]) else {
+            return;
+        };
+        let payload = &packet[HEADER_SIZE..];
+
+        if kind == PacketKind::Connect {
+            if !self.connections.contains_key(&from) {
+                if !self.listening {
+                    return;
+                }
+                log::info!("{from} connected");
+                self.connections.insert(from, Connection::new(true));
+                events.push(NetEvent::Connected(from));
+            }
+
+            // Answered every time in case the previous answer got lost
+            self.send_ack(from, PacketKind::Accept, &[]);
+            return;
+        }
+
+        let Some(connection) = self.connections.get_mut(&from) else {
+            return;
+        };
+        connection.last_received = Instant::now();
+
+        match kind {
+            PacketKind::Accept => {
+                if !connection.accepted {
+                    log::info!("connected to {from}");
+                    connection.accepted = true;
+                    events.push(NetEvent::Connected(from));
+                }
+            }
+            PacketKind::Connect | PacketKind::Heartbeat => {}
+            PacketKind::Disconnect => {
+                log::info!("{from} disconnected");
+                self.connections.remove(&from);
+                events.push(NetEvent::Disconnected(from));
+            }
+            PacketKind::Unreliable => events.push(NetEvent::Message {
+                from,
+                channel: Channel::Unreliable,
+                data: payload.to_vec(),
+            }),
+            PacketKind::Reliable => {
+                let Some(id) = read_u32(payload) else {
+                    return;
+                };
+
+                // Received even if the ack fails, the peer's resend being a duplicate then
+                match connection.receive_reliable(id) {
+                    Received::New => events.push(NetEvent::Message {
+                        from,
+                        channel: Channel::Reliable,
+                        data: payload[4..].to_vec(),
+                    }),
+                    Received::Duplicate => {}
+                    Received::TooEarly => return,
+                }
+                self.send_ack(from, PacketKind::Ack, &id.to_le_bytes());
+            }
+            PacketKind::Ack => {
+                if let Some(id) = read_u32(payload) {
+                    connection.unacked.remove(&id);
+                }
+            }
+        }
+    }
+
+    // Answers which are sent again when the peer repeats its packet, failures being logged.
+    fn send_ack(&mut self, peer: SocketAddr, kind: PacketKind, payload: &[u8]) {
+        if let Err(e) = self.send_packet(peer, kind, payload) {
+            log::warn!("failed to answer {peer}: {e}");
+        }
+    }
+
+    fn send_packet(
+        &mut self,
+        peer: SocketAddr,
+        kind: PacketKind,
+        payload: &[u8],
+    ) -> Result<(), String> {
+        if payload.len() + HEADER_SIZE > MAX_PACKET_SIZE {
+            return Err(format!(
+                "packet of {} bytes is bigger than the {} bytes limit",
+                payload.len() + HEADER_SIZE,
+                MAX_PACKET_SIZE
+            ));
+        }
+
+        let mut packet = PROTOCOL_ID.to_le_bytes().to_vec();
+        packet.push(kind as u8);
+        packet.extend_from_slice(payload);
+
+        if let Some(connection) = self.connections.get_mut(&peer) {
+            connection.last_sent = Instant::now();
+        }
+
+        match self.socket.send_to(&packet, peer) {
+            Ok(_) => Ok(()),
+            Err(e) if e.kind() == ErrorKind::WouldBlock => Ok(()),
+            Err(e) => Err(e.to_string()),
+        }
+    }
+}
+
+fn read_u32(bytes: &[u8]) -> Option<u32> {
+    Some(u32::from_le_bytes(bytes.get(..4)?.try_into().ok()?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Polls both endpoints until the client receives something matching, or a second passes.
+    fn exchange<F: Fn(&NetEvent) -> bool>(
+        server: &mut Endpoint,
+        client: &mut Endpoint,
+        expected: F,
+    ) -> Vec<NetEvent> {
+        let start = Instant::now();
+        let mut received = Vec::new();
+        while start.elapsed() < Duration::from_secs(1) {
+            received.extend(server.poll().unwrap());
+            let events = client.poll().unwrap();
+            let done = events.iter().any(&expected);
+            received.extend(events);
+            if done {
+                break;
+            }
+            std::thread::sleep(Duration::from_millis(5));
+        }
+        received
+    }
+
+    fn connected() -> (Endpoint, Endpoint, SocketAddr) {
+        let mut server = Endpoint::bind("127.0.0.1:0", true).unwrap();
+        let mut client = Endpoint::bind("127.0.0.1:0", false).unwrap();
+        let address = server.local_address().unwrap();
+        client.connect(address).unwrap();
+        let events = exchange(&mut server, &mut client, |e| {
+            matches!(e, NetEvent::Connected(_))
+        });
+        assert!(events.contains(&NetEvent::Connected(address)));
+        let client_address = client.local_address().unwrap();
+        (server, client, client_address)
+    }
+
+    #[test]
+    fn oversized_reliable_message_is_not_kept() {
+        let (mut server, mut client, client_address) = connected();
+
+        let big = vec![0; MAX_MESSAGE_SIZE + 1];
+        assert!(server
+            .send(client_address, Channel::Reliable, &big)
+            .is_err());
+        assert!(server.connections[&client_address].unacked.is_empty());
+
+        server
+            .send(client_address, Channel::Reliable, &[1, 2, 3])
+            .unwrap();
+        let events = exchange(&mut server, &mut client, |e| {
+            matches!(e, NetEvent::Message { .. })
+        });
+        assert!(events.iter().any(|e| matches!(
+            e,
+            NetEvent::Message { data, channel: Channel::Reliable, .. } if data == &[1, 2, 3]
+        )));
+    }
+
+    #[test]
+    fn largest_message_is_sent() {
+        let (mut server, mut client, client_address) = connected();
+
+        let largest = vec![7; MAX_MESSAGE_SIZE];
+        server
+            .send(client_address, Channel::Reliable, &largest)
+            .unwrap();
+        let events = exchange(&mut server, &mut client, |e| {
+            matches!(e, NetEvent::Message { .. })
+        });
+        assert!(events.iter().any(
+            |e| matches!(e, NetEvent::Message { data, .. } if data.len() == MAX_MESSAGE_SIZE)
+        ));
+    }
+
+    #[test]
+    fn reliable_message_is_received_once() {
+        let mut connection = Connection::new(true);
+        assert_eq!(connection.receive_reliable(3), Received::New);
+        assert_eq!(connection.receive_reliable(3), Received::Duplicate);
+        assert_eq!(connection.receive_reliable(4), Received::New);
+    }
+
+    #[test]
+    fn old_reliable_messages_stay_received() {
+        let mut connection = Connection::new(true);
+        for id in 0..RECEIVED_WINDOW as u32 * 3 {
+            assert_eq!(connection.receive_reliable(id), Received::New);
+        }
+
+        assert_eq!(connection.receive_reliable(0), Received::Duplicate);
+        assert_eq!(connection.receive_reliable(5), Received::Duplicate);
+    }
+
+    #[test]
+    fn reliable_messages_wait_for_the_oldest_missing_one() {
+        let mut connection = Connection::new(true);
+        let window = RECEIVED_WINDOW as u32;
+        assert_eq!(connection.receive_reliable(window - 1), Received::New);
+        assert_eq!(connection.receive_reliable(window), Received::TooEarly);
+
+        for id in 0..window - 1 {
+            assert_eq!(connection.receive_reliable(id), Received::New);
+        }
+        assert_eq!(connection.receive_reliable(window - 1), Received::Duplicate);
+        assert_eq!(connection.receive_reliable(window), Received::New);
+    }
+
+    #[test]
+    fn reliable_ids_wrap_around() {
+        let mut connection = Connection::new(true);
+        connection.received_until = u32::MAX - 1;
+        for id in [u32::MAX - 1, u32::MAX, 0, 1] {
+            assert_eq!(connection.receive_reliable(id), Received::New);
+        }
+
+        assert_eq!(connection.receive_reliable(u32::MAX), Received::Duplicate);
+        assert_eq!(connection.receive_reliable(2), Received::New);
+    }
+}
@@ -0,0 +1,499 @@
+use std::{
+    collections::{BTreeMap, VecDeque},
+    net::{SocketAddr, ToSocketAddrs},
+    time::{Duration, Instant},
+};
+
+use crate::Vec2;
+
+pub use endpoint::{Endpoint, MAX_MESSAGE_SIZE};
+
+mod endpoint;
+
+// Snapshots kept by clients to interpolate between them.
+const SNAPSHOT_BUFFER: usize = 32;
+// Tag, tick and number of states of each part of a snapshot.
+const SNAPSHOT_HEADER_SIZE: usize = 11;
+// Key and length of each state.
+const STATE_HEADER_SIZE: usize = 6;
+// Largest state replicated, as states aren't split between the parts of a snapshot.
+pub const MAX_STATE_SIZE: usize = MAX_MESSAGE_SIZE - SNAPSHOT_HEADER_SIZE - STATE_HEADER_SIZE;
+
+#[repr(u8)]
+enum MessageTag {
+    User,
+    Snapshot,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum Channel {
+    Unreliable,
+    Reliable,
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum NetEvent {
+    Connected(SocketAddr),
+    Disconnected(SocketAddr),
+    Message {
+        from: SocketAddr,
+        channel: Channel,
+        data: Vec<u8>,
+    },
+}
+
+// State the server sends to clients, interpolated on their side.
+pub trait Replicate: Sized {
+    fn encode(&self) -> Vec<u8>;
+
+    fn decode(data: &[u8]) -> Option<Self>;
+
+    fn interpolate(&self, other: &Self, t: f64) -> Self;
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Snapshot {
+    pub tick: u64,
+    pub received: Instant,
+    pub states: BTreeMap<u32, Vec<u8>>,
+}
+
+// Sends the registered states to every client tick_rate times per second, in an unreliable
+// snapshot split in as many packets as needed. Clients missing some of the packets of a
+// snapshot only miss the states they hold.
+pub struct Server {
+    pub endpoint: Endpoint,
+    pub tick_rate: u32,
+    tick: u64,
+    accumulator: Duration,
+    states: BTreeMap<u32, Vec<u8>>,
+}
+
+pub struct Client {
+    pub endpoint: Endpoint,
+    // How far behind the last snapshot states are shown, to always have two to interpolate.
+    pub interpolation_delay: Duration,
+    server: SocketAddr,
+    connected: bool,
+    snapshots: VecDeque<Snapshot>,
+}
+
+impl Server {
+    pub fn new<A: ToSocketAddrs>(address: A, tick_rate: u32) -> Result<Self, String> {
+        Ok(Server {
+            endpoint: Endpoint::bind(address, true)?,
+            tick_rate,
+            tick: 0,
+            accumulator: Duration::ZERO,
+            states: BTreeMap::new(),
+        })
+    }
+
+    pub fn tick(&self) -> u64 {
+        self.tick
+    }
+
+    // Fails if the encoded state is bigger than MAX_STATE_SIZE.
+    pub fn replicate<T: Replicate>(&mut self, key: u32, state: &T) -> Result<(), String> {
+        let state = state.encode();
+        if state.len() > MAX_STATE_SIZE {
+            return Err(format!(
+                "state {} of {} bytes is bigger than the {} bytes limit",
+                key,
+                state.len(),
+                MAX_STATE_SIZE
+            ));
+        }
+        self.states.insert(key, state);
+        Ok(())
+    }
+
+    pub fn stop_replicating(&mut self, key: u32) {
+        self.states.remove(&key);
+    }
+
+    pub fn send(&mut self, peer: SocketAddr, channel: Channel, data: &[u8]) -> Result<(), String> {
+        self.endpoint
+            .send(peer, channel, &tag(MessageTag::User, data))
+    }
+
+    pub fn broadcast(&mut self, channel: Channel, data: &[u8]) -> Result<(), String> {
+        let peers: Vec<SocketAddr> = self.endpoint.peers().collect();
+        for peer in peers {
+            self.send(peer, channel, data)?;
+        }
+        Ok(())
+    }
+
+    // Has to be called once per frame, sends at most one snapshot per call.
+    pub fn update(&mut self, dt: Duration) -> Result<Vec<NetEvent>, String> {
+        let events = untag(self.endpoint.poll()?);
+
+        let interval = Duration::from_secs_f64(1. / self.tick_rate.max(1) as f64);
+        self.accumulator += dt;
+        if self.accumulator < interval {
+            return Ok(events);
+        }
+
+        // Ticks missed because of a long frame are dropped instead of being sent in a burst
+        self.accumulator = (self.accumulator - interval).min(interval);
+        self.tick += 1;
+
+        let parts = encode_snapshot(self.tick, &self.states);
+        let peers: Vec<SocketAddr> = self.endpoint.peers().collect();
+        for peer in peers {
+            for part in &parts {
+                self.endpoint.send(peer, Channel::Unreliable, part)?;
+            }
+        }
+
+        Ok(events)
+    }
+}
+
+impl Client {
+    pub fn connect<A: ToSocketAddrs>(server: A) -> Result<Self, String> {
+        let server = server
+            .to_socket_addrs()
+            .map_err(|e| e.to_string())?
+            .next()
+            .ok_or("no address to connect to")?;
+        let local = if server.is_ipv4() {
+            "0.0.0.0:0"
+        } else {
+            "[::]:0"
+        };
+
+        let mut endpoint = Endpoint::bind(local, false)?;
+        endpoint.connect(server)?;
+
+        Ok(Client {
+            endpoint,
+            interpolation_delay: Duration::from_millis(100),
+            server,
+            connected: false,
+            snapshots: VecDeque::new(),
+        })
+    }
+
+    pub fn is_connected(&self) -> bool {
+        self.connected
+    }
+
+    pub fn send(&mut self, channel: Channel, data: &[u8]) -> Result<(), String> {
+        self.endpoint
+            .send(self.server, channel, &tag(MessageTag::User, data))
+    }
+
+    // Has to be called once per frame. Snapshots are kept instead of being returned as events.
+    pub fn update(&mut self) -> Result<Vec<NetEvent>, String> {
+        let mut events = Vec::new();
+        for event in self.endpoint.poll()? {
+            match event {
+                NetEvent::Connected(_) => {
+                    self.connected = true;
+                    events.push(event);
+                }
+                NetEvent::Disconnected(_) => {
+                    self.connected = false;
+                    events.push(event);
+                }
+                NetEvent::Message { data, .. }
+                    if data.first() == Some(&(MessageTag::Snapshot as u8)) =>
+                {
+                    if let Some(snapshot) = decode_snapshot(&data[1..]) {
+                        self.receive_snapshot(snapshot);
+                    }
+                }
+                event => events.extend(untag(vec![event])),
+            }
+        }
+
+        Ok(events)
+    }
+
+    pub fn latest_snapshot(&self) -> Option<&Snapshot> {
+        self.snapshots.back()
+    }
+
+    // Latest state received for the key, without interpolation.
+    pub fn state<T: Replicate>(&self, key: u32) -> Option<T> {
+        self.snapshots
+            .iter()
+            .rev()
+            .find_map(|s| s.states.get(&key))
+            .and_then(|data| T::decode(data))
+    }
+
+    // State interpolation_delay ago, between the two snapshots received around then.
+    pub fn interpolated<T: Replicate>(&self, key: u32) -> Option<T> {
+        let time = Instant::now().checked_sub(self.interpolation_delay)?;
+        let with_key = || {
+            self.snapshots
+                .iter()
+                .filter(|s| s.states.contains_key(&key))
+        };
+
+        let before = with_key().rev().find(|s| s.received <= time);
+        let after = with_key().find(|s| s.received > time);
+
+        match (before, after) {
+            (Some(a), Some(b)) => {
+                let span = (b.received - a.received).as_secs_f64();
+                let t = if span > 0. {
+                    (time - a.received).as_secs_f64() / span
+                } else {
+                    1.
+                };
+                let a = T::decode(&a.states[&key])?;
+                let b = T::decode(&b.states[&key])?;
+                Some(a.interpolate(&b, t))
+            }
+            (Some(s), None) | (None, Some(s)) => T::decode(&s.states[&key]),
+            (None, None) => None,
+        }
+    }
+
+    fn receive_snapshot(&mut self, snapshot: Snapshot) {
+        match self.snapshots.back_mut() {
+            // Another part of the last snapshot
+            Some(last) if last.tick == snapshot.tick => {
+                last.states.extend(snapshot.states);
+                return;
+            }
+            // Unreliable packets may arrive late, older snapshots are dropped
+            Some(last) if last.tick > snapshot.tick => return,
+            _ => {}
+        }
+
+        self.snapshots.push_back(snapshot);
+        if self.snapshots.len() > SNAPSHOT_BUFFER {
+            self.snapshots.pop_front();
+        }
+    }
+}
+
+impl Replicate for f64 {
+    fn encode(&self) -> Vec<u8> {
+        self.to_le_bytes().to_vec()
+    }
+
+    fn decode(data: &[u8]) -> Option<Self> {
+        Some(f64::from_le_bytes(data.try_into().ok()?))
+    }
+
+    fn interpolate(&self, other: &Self, t: f64) -> Self {
+        self + (other - self) * t
+    }
+}
+
+impl Replicate for Vec2 {
+    fn encode(&self) -> Vec<u8> {
+        [self.x.to_le_bytes(), self.y.to_le_bytes()].concat()
+    }
+
+    fn decode(data: &[u8]) -> Option<Self> {
+        if data.len() != 16 {
+            return None;
+        }
+        Some(Vec2::new(
+            f64::decode(&data[..8])?,
+            f64::decode(&data[8..])?,
+        ))
+    }
+
+    fn interpolate(&self, other: &Self, t: f64) -> Self {
+        self.lerp(*other, t)
+    }
+}
+
+fn tag(tag: MessageTag, data: &[u8]) -> Vec<u8> {
+    let mut message = vec![tag as u8];
+    message.extend_from_slice(data);
+    message
+}
+
+// Strips the tag of user messages, dropping the messages that aren't.
+fn untag(events: Vec<NetEvent>) -> Vec<NetEvent> {
+    events
+        .into_iter()
+        .filter_map(|event| match event {
+            NetEvent::Message {
+                from,
+                channel,
+                data,
+            } => (data.first() == Some(&(MessageTag::User as u8))).then(|| NetEvent::Message {
+                from,
+                channel,
+                data: data[1..].to_vec(),
+            }),
+            event => Some(event),
+        })
+        .collect()
+}
+
+// Tagged parts of a snapshot, each fitting in a message. A snapshot without states still has
+// one part, for clients to know the tick.
+fn encode_snapshot(tick: u64, states: &BTreeMap<u32, Vec<u8>>) -> Vec<Vec<u8>> {
+    let header = |count: u16| {
+        let mut part = vec![MessageTag::Snapshot as u8];
+        part.extend(tick.to_le_bytes());
+        part.extend(count.to_le_bytes());
+        part
+    };
+
+    let mut parts = Vec::new();
+    let mut part = header(0);
+    let mut count: u16 = 0;
+    for (key, state) in states {
+        if count > 0 && part.len() + STATE_HEADER_SIZE + state.len() > MAX_MESSAGE_SIZE {
+            part[9..11].copy_from_slice(&count.to_le_bytes());
+            parts.push(std::mem::replace(&mut part, header(0)));
+            count = 0;
+        }
+        // Within MAX_STATE_SIZE, checked by Server::replicate
+        let len = u16::try_from(state.len()).expect("state bigger than a message");
+        part.extend(key.to_le_bytes());
+        part.extend(len.to_le_bytes());
+        part.extend(state);
+        count += 1;
+    }
+    part[9..11].copy_from_slice(&count.to_le_bytes());
+    parts.push(part);
+    parts
+}
+
+fn decode_snapshot(data: &[u8]) -> Option<Snapshot> {
+    let tick = u64::from_le_bytes(data.get(..8)?.try_into().ok()?);
+    let count = u16::from_le_bytes(data.get(8..10)?.try_into().ok()?);
+
+    let mut states = BTreeMap::new();
+    let mut offset = 10;
+    for _ in 0..count {
+        let key = u32::from_le_bytes(data.get(offset..offset + 4)?.try_into().ok()?);
+        let len = u16::from_le_bytes(data.get(offset + 4..offset + 6)?.try_into().ok()?) as usize;
+        let state = data.get(offset + 6..offset + 6 + len)?;
+        states.insert(key, state.to_vec());
+        offset += 6 + len;
+    }
+
+    Some(Snapshot {
+        tick,
+        received: Instant::now(),
+        states,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn states(count: u32, size: usize) -> BTreeMap<u32, Vec<u8>> {
+        (0..count).map(|key| (key, vec![key as u8; size])).collect()
+    }
+
+    #[test]
+    fn snapshot_round_trips() {
+        let states = states(3, 16);
+        let parts = encode_snapshot(42, &states);
+        assert_eq!(parts.len(), 1);
+        assert_eq!(parts[0][0], MessageTag::Snapshot as u8);
+
+        let snapshot = decode_snapshot(&parts[0][1..]).unwrap();
+        assert_eq!(snapshot.tick, 42);
+        assert_eq!(snapshot.states, states);
+    }
+
+    #[test]
+    fn empty_snapshot_has_a_part() {
+        let parts = encode_snapshot(7, &BTreeMap::new());
+        assert_eq!(parts.len(), 1);
+        let snapshot = decode_snapshot(&parts[0][1..]).unwrap();
+        assert_eq!(snapshot.tick, 7);
+        assert!(snapshot.states.is_empty());
+    }
+
+    #[test]
+    fn big_snapshot_is_split() {
+        let states = states(200, 32);
+        let parts = encode_snapshot(1, &states);
+        assert!(parts.len() > 1);
+
+        let mut decoded = BTreeMap::new();
+        for part in &parts {
+            assert!(part.len() <= MAX_MESSAGE_SIZE);
+            let snapshot = decode_snapshot(&part[1..]).unwrap();
+            assert_eq!(snapshot.tick, 1);
+            decoded.extend(snapshot.states);
+        }
+        assert_eq!(decoded, states);
+    }
+
+    #[test]
+    fn largest_state_fits() {
+        let states = states(2, MAX_STATE_SIZE);
+        let parts = encode_snapshot(1, &states);
+        assert_eq!(parts.len(), 2);
+        assert!(parts.iter().all(|p| p.len() <= MAX_MESSAGE_SIZE));
+    }
+
+    #[test]
+    fn truncated_snapshot_is_rejected() {
+        let parts = encode_snapshot(1, &states(2, 8));
+        let data = &parts[0][1..];
+        assert!(decode_snapshot(&data[..data.len() - 1]).is_none());
+        assert!(decode_snapshot(&data[..9]).is_none());
+    }
+
+    struct Blob(Vec<u8>);
+
+    impl Replicate for Blob {
+        fn encode(&self) -> Vec<u8> {
+            self.0.clone()
+        }
+
+        fn decode(data: &[u8]) -> Option<Self> {
+            Some(Blob(data.to_vec()))
+        }
+
+        fn interpolate(&self, other: &Self, _: f64) -> Self {
+            Blob(other.0.clone())
+        }
+    }
+
+    #[test]
+    fn oversized_state_is_rejected() {
+        let mut server = Server::new("127.0.0.1:0", 20).unwrap();
+        assert!(server.replicate(0, &Blob(vec![0; MAX_STATE_SIZE])).is_ok());
+        assert!(server
+            .replicate(1, &Blob(vec![0; MAX_STATE_SIZE + 1]))
+            .is_err());
+        assert_eq!(server.states.len(), 1);
+    }
+
+    #[test]
+    fn parts_of_a_snapshot_are_merged() {
+        let mut client = Client::connect("127.0.0.1:9").unwrap();
+        let states = states(200, 32);
+        for part in encode_snapshot(3, &states) {
+            client.receive_snapshot(decode_snapshot(&part[1..]).unwrap());
+        }
+        // Late part of an older snapshot
+        let old = encode_snapshot(2, &BTreeMap::from([(500, vec![1])]));
+        client.receive_snapshot(decode_snapshot(&old[0][1..]).unwrap());
+
+        assert_eq!(client.snapshots.len(), 1);
+        assert_eq!(client.latest_snapshot().unwrap().states, states);
+    }
+
+    #[test]
+    fn vec2_round_trips() {
+        let v = Vec2::new(1.5, -2.25);
+        assert_eq!(Vec2::decode(&v.encode()), Some(v));
+        assert_eq!(Vec2::decode(&[0; 15]), None);
+        assert_eq!(
+            Vec2::new(0., 0.).interpolate(&Vec2::new(2., 4.), 0.5),
+            Vec2::new(1., 2.)
+        );
+    }
+}
@@ -0,0 +1,154 @@
+use std::collections::BTreeSet;
+
+use parry2d_f64::{bounding_volume::BoundingVolume, math::Pose, query, shape::SharedShape};
+
+use super::{Body, BodyId, BodyKind};
+use crate::Vec2;
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Debug)]
+pub struct AreaId(pub(super) usize);
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum AreaEffect {
+    // Replaces the world's gravity for the bodies inside, the gravity of overlapping areas
+    // adding up.
+    Gravity(Vec2),
+    // Gravity of this acceleration toward the area's position, e.g. around planetoids.
+    Attractor(f64),
+    // Pushes bodies toward the wind's velocity, with a force of drag per unit of speed
+    // difference so that light bodies are blown away faster.
+    Wind { velocity: Vec2, drag: f64 },
+    // Floats bodies lighter than density and slows down the bodies inside by drag per second.
+    // The part of a body under water is estimated from bounding boxes, so areas should be
+    // boxes with the water's surface at the top.
+    Water { density: f64, drag: f64 },
+}
+
+// Region applying an effect to the dynamic bodies overlapping it, without colliding with them.
+#[derive(Clone)]
+pub struct Area {
+    pub shape: SharedShape,
+    pub position: Vec2,
+    pub rotation: f64,
+    pub effect: AreaEffect,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum AreaEvent {
+    Entered(AreaId, BodyId),
+    Exited(AreaId, BodyId),
+}
+
+impl Area {
+    pub fn new(shape: SharedShape, position: Vec2, effect: AreaEffect) -> Self {
+        Area {
+            shape,
+            position,
+            rotation: 0.,
+            effect,
+        }
+    }
+
+    pub fn pose(&self) -> Pose {
+        Pose::new(self.position, self.rotation)
+    }
+}
+
+// Indices of the areas and the non static bodies overlapping them.
+pub(super) fn overlaps(
+    areas: &[Option<Area>],
+    bodies: &[Option<Body>],
+) -> BTreeSet<(usize, usize)> {
+    let mut overlaps = BTreeSet::new();
+    for (i, area) in areas.iter().enumerate() {
+        let Some(area) = area else { continue };
+        let aabb = area.shape.compute_aabb(&area.pose());
+
+        for (j, body) in bodies.iter().enumerate() {
+            let Some(body) = body.as_ref().filter(|b| b.kind != BodyKind::Static) else {
+                continue;
+            };
+            if !aabb.intersects(&body.shape.compute_aabb(&body.pose())) {
+                continue;
+            }
+
+            let test = query::intersection_test(
+                &area.pose(),
+                &*area.shape.0,
+                &body.pose(),
+                &*body.shape.0,
+            );
+            if test.is_ok_and(|t| t.intersecting) {
+                overlaps.insert((i, j));
+            }
+        }
+    }
+    overlaps
+}
+
+// Applies the forces of the areas to the bodies overlapping them and returns the gravity of
+// every body.
+pub(super) fn apply(
+    areas: &[Option<Area>],
+    bodies: &mut [Option<Body>],
+    overlaps: &BTreeSet<(usize, usize)>,
+    gravity: Vec2,
+    dt: f64,
+) -> Vec<Vec2> {
+    let mut gravities: Vec<Option<Vec2>> = vec![None; bodies.len()];
+    for &(i, j) in overlaps {
+        let (Some(area), Some(body)) = (&areas[i], &bodies[j]) else {
+            continue;
+        };
+        let acceleration = match area.effect {
+            AreaEffect::Gravity(gravity) => gravity,
+            AreaEffect::Attractor(acceleration) => {
+                (area.position - body.position).normalize_or_zero() * acceleration
+            }
+            _ => continue,
+        };
+        *gravities[j].get_or_insert(Vec2::ZERO) += acceleration;
+    }
+    let gravities: Vec<Vec2> = gravities
+        .into_iter()
+        .map(|g| g.unwrap_or(gravity))
+        .collect();
+
+    for &(i, j) in overlaps {
+        let (Some(area), Some(body)) = (&areas[i], &mut bodies[j]) else {
+            continue;
+        };
+        if body.kind != BodyKind::Dynamic {
+            continue;
+        }
+
+        match area.effect {
+            AreaEffect::Wind { velocity, drag } => body.force += (velocity - body.velocity) * drag,
+            AreaEffect::Water { density, drag } => {
+                let submerged = submerged(area, body);
+                let volume = body.shape.mass_properties(1.).mass() * submerged;
+                body.force -= gravities[j] * body.gravity_scale * density * volume;
+
+                let damping = 1. / (1. + drag * submerged * dt);
+                body.velocity *= damping;
+                body.angular_velocity *= damping;
+            }
+            AreaEffect::Gravity(_) | AreaEffect::Attractor(_) => {}
+        }
+    }
+
+    gravities
+}
+
+// Part of the body's bounding box inside the area's.
+fn submerged(area: &Area, body: &Body) -> f64 {
+    let water = area.shape.compute_aabb(&area.pose());
+    let aabb = body.shape.compute_aabb(&body.pose());
+    let size = aabb.maxs - aabb.mins;
+    let inside = (aabb.maxs.min(water.maxs) - aabb.mins.max(water.mins)).max(Vec2::ZERO);
+    if size.x > 0. && size.y > 0. {
+        (inside.x * inside.y) / (size.x * size.y)
+    } else {
+        0.
+    }
+}
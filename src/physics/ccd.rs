@@ -0,0 +1,120 @@
+use parry2d_f64::{
+    bounding_volume::{Aabb, BoundingVolume},
+    math::Pose,
+    query::{self, ShapeCastOptions},
+};
+
+use super::{passes_through, Body, BodyKind, PhysicsWorld};
+use crate::Vec2;
+
+// Bounces a body can make during a step, after which it stops at the next impact and the rest
+// of the step is dropped, e.g. for a bullet caught between two walls.
+const MAX_SUBSTEPS: usize = 4;
+
+// Position, rotation and velocity the body at index i ends the step with. Bodies with ccd move
+// until the first body in their way, bounce off it, then move for the rest of the step.
+// Others move for the whole step. None for static bodies.
+pub(super) fn advance(world: &PhysicsWorld, i: usize, dt: f64) -> Option<(Vec2, f64, Vec2)> {
+    let body = world.bodies[i]
+        .as_ref()
+        .filter(|b| b.kind != BodyKind::Static)?;
+    let mut position = body.position;
+    let mut rotation = body.rotation;
+    let mut velocity = body.velocity;
+
+    let mut elapsed = 0.;
+    for substep in 0..=MAX_SUBSTEPS {
+        let remaining = dt - elapsed;
+        let impact = (body.ccd && body.kind == BodyKind::Dynamic)
+            .then(|| {
+                let pose = Pose::new(position, rotation);
+                first_impact(world, i, pose, velocity, elapsed, remaining)
+            })
+            .flatten();
+
+        let time = impact.map_or(remaining, |(time, _, _)| time);
+        position += velocity * time;
+        rotation += body.angular_velocity * time;
+        elapsed += time;
+
+        let Some((_, j, normal)) = impact.filter(|_| substep < MAX_SUBSTEPS) else {
+            break;
+        };
+        // The other body is bounced off as if it couldn't move, the solver pushing them apart
+        // in the next step since they're then in contact
+        let other = world.bodies[j].as_ref()?;
+        let restitution = world
+            .restitution_combine
+            .apply(body.material.restitution, other.material.restitution);
+        let speed = (velocity - other.velocity).dot(normal);
+        velocity -= normal * speed * (1. + restitution);
+    }
+
+    Some((position, rotation, velocity))
+}
+
+// Time of the first impact of the body at index i, moving from pose at velocity, with another
+// body during the next dt, after elapsed seconds of the step. Returns the time, the index of
+// the other body and the normal pointing from the body to it.
+fn first_impact(
+    world: &PhysicsWorld,
+    i: usize,
+    pose: Pose,
+    velocity: Vec2,
+    elapsed: f64,
+    dt: f64,
+) -> Option<(f64, usize, Vec2)> {
+    let body = world.bodies[i].as_ref()?;
+    let path = swept_aabb(body, pose, velocity, dt);
+    let mut first = None;
+    for (j, other) in world.bodies.iter().enumerate() {
+        let Some(other) = other else { continue };
+        let pair = (i.min(j), i.max(j));
+        if i == j || world.connected(pair) || world.passing.contains(&pair) {
+            continue;
+        }
+        let other_pose = Pose::new(
+            other.position + other.velocity * elapsed,
+            other.rotation + other.angular_velocity * elapsed,
+        );
+        if !path.intersects(&swept_aabb(other, other_pose, other.velocity, dt)) {
+            continue;
+        }
+
+        let options = ShapeCastOptions {
+            max_time_of_impact: first.map_or(dt, |(time, _, _)| time),
+            target_distance: 0.,
+            stop_at_penetration: false,
+            compute_impact_geometry_on_penetration: true,
+        };
+        let hit = query::cast_shapes(
+            &pose,
+            velocity,
+            &*body.shape.0,
+            &other_pose,
+            other.velocity,
+            &*other.shape.0,
+            options,
+        );
+        let Ok(Some(hit)) = hit else { continue };
+
+        // Hits the body slides along or moves away from are ignored, for it not to be stopped
+        // again by what it just bounced off
+        let normal = pose.transform_vector(hit.normal1);
+        if (velocity - other.velocity).dot(normal) <= 0. {
+            continue;
+        }
+        if passes_through(body, normal) || passes_through(other, -normal) {
+            continue;
+        }
+        first = Some((hit.time_of_impact, j, normal));
+    }
+
+    first
+}
+
+// Box containing the body over the next dt.
+fn swept_aabb(body: &Body, pose: Pose, velocity: Vec2, dt: f64) -> Aabb {
+    let aabb = body.shape.compute_aabb(&pose);
+    aabb.merged(&aabb.translated(velocity * dt))
+}
@@ -0,0 +1,279 @@
+use super::{solver::pair_mut, Body, BodyId};
+use crate::{math::Vec2Ext, Vec2};
+
+// Part of the joint error corrected each step.
+const CORRECTION: f64 = 0.2;
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Debug)]
+pub struct JointId(pub(super) usize);
+
+// Drives a joint at a target speed, in units or radians per second, using at most max_force.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Motor {
+    pub speed: f64,
+    pub max_force: f64,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum JointKind {
+    // Keeps the anchors between min and max apart, a rope when min is 0 and a rod when both are
+    // equal.
+    Distance {
+        min: f64,
+        max: f64,
+    },
+    // Pins the anchors together, the bodies turning around them. Limits are on the angle of b
+    // relative to a, from when the joint was created.
+    Revolute {
+        limits: Option<(f64, f64)>,
+        motor: Option<Motor>,
+    },
+    // Lets the anchors slide along an axis fixed to a, without the bodies turning relative to
+    // each other. Limits are on the anchors' offset along the axis.
+    Prismatic {
+        axis: Vec2,
+        limits: Option<(f64, f64)>,
+        motor: Option<Motor>,
+    },
+}
+
+// Anchors are relative to the bodies' position, in their local space.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Joint {
+    pub a: BodyId,
+    pub b: BodyId,
+    pub anchor_a: Vec2,
+    pub anchor_b: Vec2,
+    pub kind: JointKind,
+    // Whether the two bodies still collide with each other.
+    pub collide_connected: bool,
+    pub(super) reference_angle: f64,
+}
+
+// Impulses accumulated by a joint during a step, so that limits and motors are clamped over the
+// whole step rather than each iteration.
+#[derive(Clone, Copy, Default)]
+pub(super) struct JointImpulses {
+    limit: f64,
+    motor: f64,
+}
+
+impl Joint {
+    pub fn new(a: BodyId, anchor_a: Vec2, b: BodyId, anchor_b: Vec2, kind: JointKind) -> Self {
+        let kind = match kind {
+            JointKind::Prismatic {
+                axis,
+                limits,
+                motor,
+            } => JointKind::Prismatic {
+                axis: axis.normalize_or(Vec2::X),
+                limits,
+                motor,
+            },
+            kind => kind,
+        };
+
+        Joint {
+            a,
+            b,
+            anchor_a,
+            anchor_b,
+            kind,
+            collide_connected: false,
+            reference_angle: 0.,
+        }
+    }
+
+    pub fn distance(a: BodyId, anchor_a: Vec2, b: BodyId, anchor_b: Vec2, length: f64) -> Self {
+        let kind = JointKind::Distance {
+            min: length,
+            max: length,
+        };
+        Joint::new(a, anchor_a, b, anchor_b, kind)
+    }
+
+    pub fn revolute(a: BodyId, anchor_a: Vec2, b: BodyId, anchor_b: Vec2) -> Self {
+        let kind = JointKind::Revolute {
+            limits: None,
+            motor: None,
+        };
+        Joint::new(a, anchor_a, b, anchor_b, kind)
+    }
+
+    pub fn prismatic(a: BodyId, anchor_a: Vec2, b: BodyId, anchor_b: Vec2, axis: Vec2) -> Self {
+        let kind = JointKind::Prismatic {
+            axis,
+            limits: None,
+            motor: None,
+        };
+        Joint::new(a, anchor_a, b, anchor_b, kind)
+    }
+}
+
+pub(super) fn solve(
+    bodies: &mut [Option<Body>],
+    joint: &Joint,
+    impulses: &mut JointImpulses,
+    dt: f64,
+) {
+    if joint.a == joint.b {
+        return;
+    }
+    let (a, b) = pair_mut(bodies, joint.a.0, joint.b.0);
+
+    let ra = joint.anchor_a.rotated(a.rotation);
+    let rb = joint.anchor_b.rotated(b.rotation);
+    let offset = (b.position + rb) - (a.position + ra);
+    let angle = b.rotation - a.rotation - joint.reference_angle;
+    // Velocity correcting part of an error each step
+    let bias = |error: f64| error * CORRECTION / dt;
+    let limit = &mut impulses.limit;
+
+    match joint.kind {
+        JointKind::Distance { min, max } => {
+            let length = offset.length();
+            let Some(direction) = offset.try_normalize() else {
+                return;
+            };
+            let arms = (ra.perp_dot(direction), rb.perp_dot(direction));
+
+            if min == max {
+                solve_linear(a, b, direction, arms, bias(length - min), |i| i);
+            } else if length > max {
+                solve_linear(a, b, direction, arms, bias(length - max), |i| {
+                    accumulate(limit, i, f64::NEG_INFINITY, 0.)
+                });
+            } else if length < min {
+                solve_linear(a, b, direction, arms, bias(length - min), |i| {
+                    accumulate(limit, i, 0., f64::INFINITY)
+                });
+            }
+        }
+        JointKind::Revolute { limits, motor } => {
+            if let Some(motor) = motor {
+                let max = motor.max_force * dt;
+                solve_angular(a, b, -motor.speed, |i| {
+                    accumulate(&mut impulses.motor, i, -max, max)
+                });
+            }
+            match limits {
+                Some((min, _)) if angle < min => solve_angular(a, b, bias(angle - min), |i| {
+                    accumulate(limit, i, 0., f64::INFINITY)
+                }),
+                Some((_, max)) if angle > max => solve_angular(a, b, bias(angle - max), |i| {
+                    accumulate(limit, i, f64::NEG_INFINITY, 0.)
+                }),
+                _ => {}
+            }
+
+            solve_point(a, b, ra, rb, offset * CORRECTION / dt);
+        }
+        JointKind::Prismatic {
+            axis,
+            limits,
+            motor,
+        } => {
+            let axis = axis.rotated(a.rotation);
+            let normal = axis.perp();
+            // The constraint is measured at b's anchor, where a's arm reaches too
+            let arms =
+                |direction: Vec2| ((offset + ra).perp_dot(direction), rb.perp_dot(direction));
+            let translation = offset.dot(axis);
+
+            if let Some(motor) = motor {
+                let max = motor.max_force * dt;
+                solve_linear(a, b, axis, arms(axis), -motor.speed, |i| {
+                    accumulate(&mut impulses.motor, i, -max, max)
+                });
+            }
+            match limits {
+                Some((min, _)) if translation < min => {
+                    solve_linear(a, b, axis, arms(axis), bias(translation - min), |i| {
+                        accumulate(limit, i, 0., f64::INFINITY)
+                    })
+                }
+                Some((_, max)) if translation > max => {
+                    solve_linear(a, b, axis, arms(axis), bias(translation - max), |i| {
+                        accumulate(limit, i, f64::NEG_INFINITY, 0.)
+                    })
+                }
+                _ => {}
+            }
+
+            solve_angular(a, b, bias(angle), |i| i);
+            solve_linear(a, b, normal, arms(normal), bias(offset.dot(normal)), |i| i);
+        }
+    }
+}
+
+// Pushes b along direction and a the other way until their relative velocity along it is
+// -bias. clamp receives the impulse computed for this iteration and returns the one to apply.
+fn solve_linear(
+    a: &mut Body,
+    b: &mut Body,
+    direction: Vec2,
+    (arm_a, arm_b): (f64, f64),
+    bias: f64,
+    clamp: impl FnOnce(f64) -> f64,
+) {
+    let k = a.inv_mass()
+        + b.inv_mass()
+        + a.inv_inertia() * arm_a * arm_a
+        + b.inv_inertia() * arm_b * arm_b;
+    if k == 0. {
+        return;
+    }
+
+    let velocity = (b.velocity - a.velocity).dot(direction) + b.angular_velocity * arm_b
+        - a.angular_velocity * arm_a;
+    let impulse = clamp(-(velocity + bias) / k);
+
+    a.velocity -= direction * impulse * a.inv_mass();
+    a.angular_velocity -= impulse * arm_a * a.inv_inertia();
+    b.velocity += direction * impulse * b.inv_mass();
+    b.angular_velocity += impulse * arm_b * b.inv_inertia();
+}
+
+// Moves the anchors together, solving both axes at once since they are coupled by the bodies'
+// rotation.
+fn solve_point(a: &mut Body, b: &mut Body, ra: Vec2, rb: Vec2, bias: Vec2) {
+    let (ma, mb, ia, ib) = (a.inv_mass(), b.inv_mass(), a.inv_inertia(), b.inv_inertia());
+    let k11 = ma + mb + ia * ra.y * ra.y + ib * rb.y * rb.y;
+    let k12 = -ia * ra.x * ra.y - ib * rb.x * rb.y;
+    let k22 = ma + mb + ia * ra.x * ra.x + ib * rb.x * rb.x;
+    let determinant = k11 * k22 - k12 * k12;
+    if determinant == 0. {
+        return;
+    }
+
+    let velocity =
+        b.velocity + rb.perp() * b.angular_velocity - a.velocity - ra.perp() * a.angular_velocity;
+    let v = -(velocity + bias);
+    let impulse = Vec2::new(k22 * v.x - k12 * v.y, k11 * v.y - k12 * v.x) / determinant;
+
+    a.velocity -= impulse * ma;
+    a.angular_velocity -= ra.perp_dot(impulse) * ia;
+    b.velocity += impulse * mb;
+    b.angular_velocity += rb.perp_dot(impulse) * ib;
+}
+
+// Same for the rotation of b relative to a.
+fn solve_angular(a: &mut Body, b: &mut Body, bias: f64, clamp: impl FnOnce(f64) -> f64) {
+    let k = a.inv_inertia() + b.inv_inertia();
+    if k == 0. {
+        return;
+    }
+
+    let velocity = b.angular_velocity - a.angular_velocity;
+    let impulse = clamp(-(velocity + bias) / k);
+    a.angular_velocity -= impulse * a.inv_inertia();
+    b.angular_velocity += impulse * b.inv_inertia();
+}
+
+// Adds the impulse to the total, keeping the total between min and max, and returns the part
+// that was actually added.
+fn accumulate(total: &mut f64, impulse: f64, min: f64, max: f64) -> f64 {
+    let previous = *total;
+    *total = (previous + impulse).clamp(min, max);
+    *total - previous
+}
@@ -0,0 +1,54 @@
+use crate::Vec2;
+
+// Surface of a body.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Material {
+    // 0 for ice, around 1 for rubber.
+    pub friction: f64,
+    // Part of the speed kept when bouncing, 0 to stop dead and 1 to bounce back as fast.
+    pub restitution: f64,
+    // Velocity of the surface itself, in local space, which drags what it touches along without
+    // the body moving, e.g. for conveyor belts.
+    pub surface_velocity: Vec2,
+}
+
+// How the values of the two bodies in contact are combined.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Combine {
+    Average,
+    Min,
+    Max,
+    Multiply,
+    GeometricMean,
+}
+
+impl Material {
+    pub const ICE: Material = Material::new(0.02, 0.);
+    pub const RUBBER: Material = Material::new(1., 0.8);
+
+    pub const fn new(friction: f64, restitution: f64) -> Self {
+        Material {
+            friction,
+            restitution,
+            surface_velocity: Vec2::ZERO,
+        }
+    }
+}
+
+impl Default for Material {
+    fn default() -> Self {
+        Material::new(0.5, 0.)
+    }
+}
+
+impl Combine {
+    pub fn apply(self, a: f64, b: f64) -> f64 {
+        match self {
+            Combine::Average => (a + b) * 0.5,
+            Combine::Min => a.min(b),
+            Combine::Max => a.max(b),
+            Combine::Multiply => a * b,
+            Combine::GeometricMean => (a * b).sqrt(),
+        }
+    }
+}
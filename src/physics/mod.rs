@@ -1 +1,937 @@
+use std::collections::{BTreeMap, BTreeSet};
 
+use parry2d_f64::{
+    bounding_volume::{Aabb, BoundingVolume},
+    math::Pose,
+    shape::{Shape, SharedShape},
+};
+
+use crate::{
+    desync::{StateHash, StateHasher},
+    math::Vec2Ext,
+    profile_scope, Vec2,
+};
+
+pub use areas::{Area, AreaEffect, AreaEvent, AreaId};
+pub use joints::{Joint, JointId, JointKind, Motor};
+pub use material::{Combine, Material};
+pub use query::RayHit;
+
+mod areas;
+mod ccd;
+mod joints;
+mod material;
+mod query;
+mod solver;
+
+// Normal and tangent impulses by bodies and features in contact.
+type Impulses = BTreeMap<(usize, usize, (u32, u32)), (f64, f64)>;
+
+// Contacts with one-way bodies are kept when their normal is within about 45 degrees of the side
+// they collide on.
+const ONE_WAY_NORMAL: f64 = 0.7;
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Debug)]
+pub struct BodyId(usize);
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum BodyKind {
+    // Never moves.
+    Static,
+    // Moved by gravity, forces and collisions.
+    Dynamic,
+    // Moved by its velocity only, pushes dynamic bodies without being pushed back.
+    Kinematic,
+}
+
+// Bodies rotate around their position, which is the center of their shape.
+#[derive(Clone)]
+pub struct Body {
+    pub kind: BodyKind,
+    pub shape: SharedShape,
+    pub position: Vec2,
+    pub rotation: f64,
+    pub velocity: Vec2,
+    pub angular_velocity: f64,
+    pub material: Material,
+    pub gravity_scale: f64,
+    // Keeps the body upright, like most characters.
+    pub fixed_rotation: bool,
+    // Only collides with bodies coming from this side, in local space, the others passing
+    // through. E.g. (0, -1) for platforms characters jump onto from below.
+    pub one_way: Option<Vec2>,
+    // Bounces the body off the bodies in its way during a step, so that it doesn't go through
+    // thin ones when moving fast. Meant for bullets, as it costs a shape cast per nearby body.
+    pub ccd: bool,
+    // Transform before the last step, to interpolate between steps.
+    previous_position: Vec2,
+    previous_rotation: f64,
+    inv_mass: f64,
+    inv_inertia: f64,
+    force: Vec2,
+    torque: f64,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum PhysicsEvent {
+    // Seconds update couldn't catch up with in max_steps steps, which the world won't simulate.
+    TimeDropped(f64),
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct Contact {
+    pub a: BodyId,
+    pub b: BodyId,
+    pub point: Vec2,
+    // Pointing from a to b.
+    pub normal: Vec2,
+    pub depth: f64,
+}
+
+// The step is deterministic: given the same bodies added and removed in the same order and the
+// same dt, two worlds always end up in the same state. Bodies are stored and iterated by id,
+// nothing depends on addresses, hashing or time, and ids of removed bodies are reused in a fixed
+// order. This holds across runs and machines running the same build; sin and cos come from the
+// platform math library so different targets may still diverge.
+#[derive(Clone)]
+pub struct PhysicsWorld {
+    // Y points down by default, like screen coordinates, see GraphicsOptions::down.
+    pub gravity: Vec2,
+    // Solver iterations per step, more makes stacks more stable.
+    pub iterations: usize,
+    // Duration of the steps made by update.
+    pub timestep: f64,
+    // Steps update makes at most, when the frame time spikes after a hitch. Time beyond them is
+    // dropped rather than caught up with, which would slow the next frames down even more.
+    pub max_steps: u32,
+    // Default to the geometric mean of the frictions and the highest restitution.
+    pub friction_combine: Combine,
+    pub restitution_combine: Combine,
+    bodies: Vec<Option<Body>>,
+    free: Vec<usize>,
+    joints: Vec<Option<Joint>>,
+    areas: Vec<Option<Area>>,
+    // Bodies each body is jointed to without colliding with them, by body index.
+    jointed: Vec<BTreeSet<usize>>,
+    // Time left to step by update.
+    accumulator: f64,
+    contacts: Vec<Contact>,
+    // Impulses of the last step, the solver starts from them.
+    impulses: Impulses,
+    // Pairs of bodies passing through a one-way body, ignored until they stop overlapping.
+    passing: BTreeSet<(usize, usize)>,
+    // Areas and bodies overlapping them.
+    overlaps: BTreeSet<(usize, usize)>,
+    area_events: Vec<AreaEvent>,
+    events: Vec<PhysicsEvent>,
+}
+
+// Whole state of a world, to step it again from there, e.g. for rollback netcode. Snapshots can
+// be shared between threads to run queries on them while the world keeps stepping.
+#[derive(Clone)]
+pub struct PhysicsSnapshot {
+    bodies: Vec<Option<Body>>,
+    free: Vec<usize>,
+    joints: Vec<Option<Joint>>,
+    areas: Vec<Option<Area>>,
+    contacts: Vec<Contact>,
+    impulses: Impulses,
+    passing: BTreeSet<(usize, usize)>,
+    overlaps: BTreeSet<(usize, usize)>,
+}
+
+crate::reflect!(Body {
+    position,
+    rotation,
+    velocity,
+    angular_velocity,
+    gravity_scale,
+    fixed_rotation,
+    ccd,
+});
+
+impl BodyId {
+    // Ids of removed bodies are reused by the next ones added.
+    pub fn index(&self) -> usize {
+        self.0
+    }
+}
+
+impl Body {
+    pub fn new(kind: BodyKind, shape: SharedShape, position: Vec2) -> Self {
+        let mut body = Body {
+            kind,
+            shape,
+            position,
+            rotation: 0.,
+            velocity: Vec2::ZERO,
+            angular_velocity: 0.,
+            material: Material::default(),
+            gravity_scale: 1.,
+            fixed_rotation: false,
+            one_way: None,
+            ccd: false,
+            previous_position: position,
+            previous_rotation: 0.,
+            inv_mass: 0.,
+            inv_inertia: 0.,
+            force: Vec2::ZERO,
+            torque: 0.,
+        };
+        body.set_density(1.);
+        body
+    }
+
+    pub fn set_density(&mut self, density: f64) {
+        let properties = self.shape.mass_properties(density);
+        self.inv_mass = properties.inv_mass;
+        self.inv_inertia = properties.inv_principal_inertia;
+    }
+
+    // Infinite for static and kinematic bodies.
+    pub fn mass(&self) -> f64 {
+        let inv_mass = self.inv_mass();
+        if inv_mass > 0. {
+            1. / inv_mass
+        } else {
+            f64::INFINITY
+        }
+    }
+
+    pub fn pose(&self) -> Pose {
+        Pose::new(self.position, self.rotation)
+    }
+
+    // Moves the body without it being drawn sliding to its new position.
+    pub fn teleport(&mut self, position: Vec2, rotation: f64) {
+        self.position = position;
+        self.rotation = rotation;
+        self.previous_position = position;
+        self.previous_rotation = rotation;
+    }
+
+    // Position and rotation to draw the body at, alpha going from 0 at the transform before the
+    // last step to 1 at the current one.
+    pub fn interpolated(&self, alpha: f64) -> (Vec2, f64) {
+        (
+            self.previous_position.lerp(self.position, alpha),
+            self.previous_rotation + (self.rotation - self.previous_rotation) * alpha,
+        )
+    }
+
+    // Forces and torques are applied during the next step then cleared.
+    pub fn apply_force(&mut self, force: Vec2) {
+        self.force += force;
+    }
+
+    pub fn apply_torque(&mut self, torque: f64) {
+        self.torque += torque;
+    }
+
+    pub fn apply_impulse(&mut self, impulse: Vec2) {
+        self.velocity += impulse * self.inv_mass();
+    }
+
+    fn inv_mass(&self) -> f64 {
+        match self.kind {
+            BodyKind::Dynamic => self.inv_mass,
+            BodyKind::Static | BodyKind::Kinematic => 0.,
+        }
+    }
+
+    fn inv_inertia(&self) -> f64 {
+        match self.kind {
+            BodyKind::Dynamic if !self.fixed_rotation => self.inv_inertia,
+            _ => 0.,
+        }
+    }
+}
+
+impl PhysicsWorld {
+    pub fn new(gravity: Vec2) -> Self {
+        PhysicsWorld {
+            gravity,
+            iterations: 8,
+            timestep: 1. / 60.,
+            max_steps: 8,
+            friction_combine: Combine::GeometricMean,
+            restitution_combine: Combine::Max,
+            bodies: Vec::new(),
+            free: Vec::new(),
+            joints: Vec::new(),
+            areas: Vec::new(),
+            jointed: Vec::new(),
+            accumulator: 0.,
+            contacts: Vec::new(),
+            impulses: BTreeMap::new(),
+            passing: BTreeSet::new(),
+            overlaps: BTreeSet::new(),
+            area_events: Vec::new(),
+            events: Vec::new(),
+        }
+    }
+
+    pub fn add(&mut self, body: Body) -> BodyId {
+        match self.free.pop() {
+            Some(index) => {
+                self.bodies[index] = Some(body);
+                BodyId(index)
+            }
+            None => {
+                self.bodies.push(Some(body));
+                BodyId(self.bodies.len() - 1)
+            }
+        }
+    }
+
+    pub fn remove(&mut self, id: BodyId) -> Option<Body> {
+        let body = self.bodies.get_mut(id.0)?.take()?;
+        self.free.push(id.0);
+        for joint in &mut self.joints {
+            if joint.as_ref().is_some_and(|j| j.a == id || j.b == id) {
+                *joint = None;
+            }
+        }
+        self.contacts.retain(|c| c.a != id && c.b != id);
+        self.impulses
+            .retain(|(a, b, _), _| *a != id.0 && *b != id.0);
+        self.passing.retain(|(a, b)| *a != id.0 && *b != id.0);
+        self.overlaps.retain(|(_, b)| *b != id.0);
+        Some(body)
+    }
+
+    pub fn body(&self, id: BodyId) -> Option<&Body> {
+        self.bodies.get(id.0)?.as_ref()
+    }
+
+    pub fn body_mut(&mut self, id: BodyId) -> Option<&mut Body> {
+        self.bodies.get_mut(id.0)?.as_mut()
+    }
+
+    // In id order.
+    pub fn bodies(&self) -> impl Iterator<Item = (BodyId, &Body)> {
+        self.bodies
+            .iter()
+            .enumerate()
+            .filter_map(|(i, b)| Some((BodyId(i), b.as_ref()?)))
+    }
+
+    // The angle limits of revolute joints are relative to the bodies' rotation at this point.
+    // Fails if one of the bodies doesn't exist.
+    pub fn add_joint(&mut self, joint: Joint) -> Result<JointId, String> {
+        let (Some(a), Some(b)) = (self.body(joint.a), self.body(joint.b)) else {
+            return Err("joint between missing bodies".to_string());
+        };
+
+        let joint = Joint {
+            reference_angle: b.rotation - a.rotation,
+            ..joint
+        };
+        // Unlike body ids, joint ids aren't reused
+        self.joints.push(Some(joint));
+        Ok(JointId(self.joints.len() - 1))
+    }
+
+    pub fn remove_joint(&mut self, id: JointId) -> Option<Joint> {
+        self.joints.get_mut(id.0)?.take()
+    }
+
+    pub fn joint(&self, id: JointId) -> Option<&Joint> {
+        self.joints.get(id.0)?.as_ref()
+    }
+
+    pub fn joint_mut(&mut self, id: JointId) -> Option<&mut Joint> {
+        self.joints.get_mut(id.0)?.as_mut()
+    }
+
+    pub fn joints(&self) -> impl Iterator<Item = (JointId, &Joint)> {
+        self.joints
+            .iter()
+            .enumerate()
+            .filter_map(|(i, j)| Some((JointId(i), j.as_ref()?)))
+    }
+
+    // Unlike body ids, area ids aren't reused.
+    pub fn add_area(&mut self, area: Area) -> AreaId {
+        self.areas.push(Some(area));
+        AreaId(self.areas.len() - 1)
+    }
+
+    // No exit events are sent for the bodies that were inside.
+    pub fn remove_area(&mut self, id: AreaId) -> Option<Area> {
+        let area = self.areas.get_mut(id.0)?.take()?;
+        self.overlaps.retain(|(a, _)| *a != id.0);
+        Some(area)
+    }
+
+    pub fn area(&self, id: AreaId) -> Option<&Area> {
+        self.areas.get(id.0)?.as_ref()
+    }
+
+    pub fn area_mut(&mut self, id: AreaId) -> Option<&mut Area> {
+        self.areas.get_mut(id.0)?.as_mut()
+    }
+
+    pub fn areas(&self) -> impl Iterator<Item = (AreaId, &Area)> {
+        self.areas
+            .iter()
+            .enumerate()
+            .filter_map(|(i, a)| Some((AreaId(i), a.as_ref()?)))
+    }
+
+    // Bodies currently overlapping the area, in id order.
+    pub fn bodies_in(&self, id: AreaId) -> impl Iterator<Item = BodyId> + '_ {
+        self.overlaps
+            .iter()
+            .filter(move |(a, _)| *a == id.0)
+            .map(|(_, b)| BodyId(*b))
+    }
+
+    // Bodies that exited then entered areas during the last step, by area then body.
+    pub fn area_events(&self) -> &[AreaEvent] {
+        &self.area_events
+    }
+
+    // Contacts found during the last step.
+    pub fn contacts(&self) -> &[Contact] {
+        &self.contacts
+    }
+
+    // Lets the body fall through the one-way bodies it's standing on, until it stops
+    // overlapping them.
+    pub fn drop_through(&mut self, id: BodyId) {
+        for contact in &self.contacts {
+            let other = if contact.a == id {
+                contact.b
+            } else {
+                contact.a
+            };
+            if (contact.a == id || contact.b == id)
+                && self.body(other).is_some_and(|b| b.one_way.is_some())
+            {
+                self.passing.insert((contact.a.0, contact.b.0));
+            }
+        }
+    }
+
+    // Closest body hit by the ray within max_distance.
+    pub fn cast_ray(&self, origin: Vec2, direction: Vec2, max_distance: f64) -> Option<RayHit> {
+        query::cast_ray(&self.bodies, origin, direction, max_distance)
+    }
+
+    // Whether no body for which blocks returns true is in the way from one point to the other,
+    // e.g. for guards seeing the player. blocks has to reject the bodies of the viewer and its
+    // target, which the segment starts and ends in.
+    pub fn has_line_of_sight<F: FnMut(BodyId, &Body) -> bool>(
+        &self,
+        from: Vec2,
+        to: Vec2,
+        blocks: F,
+    ) -> bool {
+        query::has_line_of_sight(&self.bodies, from, to, blocks)
+    }
+
+    // Bodies containing the point, in id order.
+    pub fn bodies_at(&self, point: Vec2) -> Vec<BodyId> {
+        query::bodies_at(&self.bodies, point)
+    }
+
+    // Bodies overlapping the shape at that pose, in id order.
+    pub fn bodies_overlapping(&self, shape: &dyn Shape, pose: &Pose) -> Vec<BodyId> {
+        query::bodies_overlapping(&self.bodies, shape, pose)
+    }
+
+    // Steps the world by timestep as many times as fit in the time elapsed since the last call,
+    // up to max_steps, the time left being kept for the next one. Has to be called once per
+    // frame.
+    pub fn update(&mut self, dt: f64) {
+        self.events.clear();
+        self.accumulator += dt;
+
+        // Less than a step is kept, so that the next frame doesn't have to catch up either
+        let max_time = self.max_steps as f64 * self.timestep;
+        if self.accumulator >= max_time + self.timestep {
+            let kept = self.accumulator % self.timestep;
+            let dropped = self.accumulator - max_time - kept;
+            log::debug!("physics dropped {:.1} ms", dropped * 1e3);
+            self.events.push(PhysicsEvent::TimeDropped(dropped));
+            self.accumulator = max_time + kept;
+        }
+
+        while self.accumulator >= self.timestep {
+            self.step(self.timestep);
+            self.accumulator -= self.timestep;
+        }
+    }
+
+    // Events of the last update.
+    pub fn events(&self) -> &[PhysicsEvent] {
+        &self.events
+    }
+
+    // Part of a step left in update's accumulator, to interpolate the bodies drawn with.
+    pub fn alpha(&self) -> f64 {
+        (self.accumulator / self.timestep).clamp(0., 1.)
+    }
+
+    // Position and rotation to draw the body at, between the last two steps made by update, see
+    // GraphicsPipeline::draw_body.
+    pub fn interpolated(&self, id: BodyId) -> Option<(Vec2, f64)> {
+        Some(self.body(id)?.interpolated(self.alpha()))
+    }
+
+    // Should be called with a fixed dt for the simulation to be stable and deterministic.
+    pub fn step(&mut self, dt: f64) {
+        self.step_with(dt, |_, _, _| true);
+    }
+
+    // Same as step, pre_solve being called with every contact point found and the two bodies
+    // before solving them. Points it returns false for are ignored during this step, e.g. to
+    // let some bodies through others depending on their velocity.
+    pub fn step_with<F: FnMut(&Contact, &Body, &Body) -> bool>(&mut self, dt: f64, pre_solve: F) {
+        profile_scope!("physics");
+        self.update_jointed();
+        for body in self.bodies.iter_mut().flatten() {
+            body.previous_position = body.position;
+            body.previous_rotation = body.rotation;
+        }
+
+        let overlaps = areas::overlaps(&self.areas, &self.bodies);
+        let exited = self
+            .overlaps
+            .difference(&overlaps)
+            .map(|&(a, b)| AreaEvent::Exited(AreaId(a), BodyId(b)));
+        let entered = overlaps
+            .difference(&self.overlaps)
+            .map(|&(a, b)| AreaEvent::Entered(AreaId(a), BodyId(b)));
+        self.area_events = exited.chain(entered).collect();
+        self.overlaps = overlaps;
+
+        let gravities = areas::apply(
+            &self.areas,
+            &mut self.bodies,
+            &self.overlaps,
+            self.gravity,
+            dt,
+        );
+        for (body, gravity) in self.bodies.iter_mut().zip(gravities) {
+            let Some(body) = body else { continue };
+            if body.kind == BodyKind::Dynamic {
+                body.velocity += (gravity * body.gravity_scale + body.force * body.inv_mass()) * dt;
+                body.angular_velocity += body.torque * body.inv_inertia() * dt;
+            }
+            body.force = Vec2::ZERO;
+            body.torque = 0.;
+        }
+
+        let mut points = self.find_contacts(pre_solve);
+        for p in &mut points {
+            if let Some((normal, tangent)) = self.impulses.get(&(p.a, p.b, p.features)) {
+                p.normal_impulse = *normal;
+                p.tangent_impulse = *tangent;
+            }
+        }
+        solver::prepare(&mut self.bodies, &mut points);
+        let mut joint_impulses = vec![joints::JointImpulses::default(); self.joints.len()];
+        for _ in 0..self.iterations {
+            for (joint, impulses) in self.joints.iter().zip(&mut joint_impulses) {
+                if let Some(joint) = joint {
+                    joints::solve(&mut self.bodies, joint, impulses, dt);
+                }
+            }
+            solver::solve_velocities(&mut self.bodies, &mut points);
+        }
+
+        let motions: Vec<_> = (0..self.bodies.len())
+            .map(|i| ccd::advance(self, i, dt))
+            .collect();
+        for (body, motion) in self.bodies.iter_mut().zip(motions) {
+            if let (Some(body), Some((position, rotation, velocity))) = (body, motion) {
+                body.position = position;
+                body.rotation = rotation;
+                body.velocity = velocity;
+            }
+        }
+        solver::correct_positions(&mut self.bodies, &points);
+
+        self.impulses = points
+            .iter()
+            .map(|p| {
+                (
+                    (p.a, p.b, p.features),
+                    (p.normal_impulse, p.tangent_impulse),
+                )
+            })
+            .collect();
+        self.contacts = points.iter().map(contact).collect();
+    }
+
+    pub fn snapshot(&self) -> PhysicsSnapshot {
+        PhysicsSnapshot {
+            bodies: self.bodies.clone(),
+            free: self.free.clone(),
+            joints: self.joints.clone(),
+            areas: self.areas.clone(),
+            contacts: self.contacts.clone(),
+            impulses: self.impulses.clone(),
+            passing: self.passing.clone(),
+            overlaps: self.overlaps.clone(),
+        }
+    }
+
+    // Settings such as gravity aren't part of snapshots and are kept.
+    pub fn restore(&mut self, snapshot: &PhysicsSnapshot) {
+        self.bodies.clone_from(&snapshot.bodies);
+        self.free.clone_from(&snapshot.free);
+        self.joints.clone_from(&snapshot.joints);
+        self.areas.clone_from(&snapshot.areas);
+        self.contacts.clone_from(&snapshot.contacts);
+        self.impulses.clone_from(&snapshot.impulses);
+        self.passing.clone_from(&snapshot.passing);
+        self.overlaps.clone_from(&snapshot.overlaps);
+        self.area_events.clear();
+    }
+
+    // Every pair is tested in id order, pairs that can't move relative to each other skipped.
+    fn find_contacts<F: FnMut(&Contact, &Body, &Body) -> bool>(
+        &mut self,
+        mut pre_solve: F,
+    ) -> Vec<solver::ContactPoint> {
+        let pairs = self.broad_phase();
+        let combine = (self.friction_combine, self.restitution_combine);
+        let manifolds = solver::narrow_phase(&self.bodies, &pairs, combine);
+
+        let mut points = Vec::new();
+        let mut passing = BTreeSet::new();
+        for ((i, j), mut pair) in pairs.into_iter().zip(manifolds) {
+            let (Some(a), Some(b)) = (&self.bodies[i], &self.bodies[j]) else {
+                continue;
+            };
+            // Once a body starts passing through a one-way body it's let through until they
+            // separate, even if it then overlaps it from the colliding side
+            if self.passing.contains(&(i, j))
+                || pair
+                    .iter()
+                    .any(|p| passes_through(a, p.normal) || passes_through(b, -p.normal))
+            {
+                if !pair.is_empty() {
+                    passing.insert((i, j));
+                }
+                continue;
+            }
+
+            pair.retain(|p| pre_solve(&contact(p), a, b));
+            points.append(&mut pair);
+        }
+
+        self.passing = passing;
+        points
+    }
+
+    // Pairs of bodies whose bounding boxes overlap, lowest index first, in order. The boxes are
+    // swept from left to right, each tested against the ones it starts before the end of.
+    fn broad_phase(&self) -> Vec<(usize, usize)> {
+        let mut aabbs: Vec<_> = self
+            .bodies
+            .iter()
+            .enumerate()
+            .filter_map(|(i, b)| b.as_ref().map(|b| (i, b, b.shape.compute_aabb(&b.pose()))))
+            .collect();
+        // The sort is stable, boxes starting at the same x staying in index order
+        aabbs.sort_by(|(_, _, a), (_, _, b)| a.mins.x.total_cmp(&b.mins.x));
+
+        let mut pairs = Vec::new();
+        let mut open: Vec<(usize, &Body, Aabb)> = Vec::new();
+        for (i, body, aabb) in aabbs {
+            open.retain(|(_, _, other)| other.maxs.x >= aabb.mins.x);
+            for (j, other, other_aabb) in &open {
+                let pair = (i.min(*j), i.max(*j));
+                if (body.kind == BodyKind::Dynamic || other.kind == BodyKind::Dynamic)
+                    && aabb.intersects(other_aabb)
+                    && !self.connected(pair)
+                {
+                    pairs.push(pair);
+                }
+            }
+            open.push((i, body, aabb));
+        }
+
+        pairs.sort_unstable();
+        pairs
+    }
+
+    // Whether the bodies of the pair are connected by a joint that keeps them from colliding.
+    fn connected(&self, (a, b): (usize, usize)) -> bool {
+        self.jointed
+            .get(a)
+            .is_some_and(|jointed| jointed.contains(&b))
+    }
+
+    // Rebuilt every step rather than kept up to date, as joints can be changed by joint_mut.
+    fn update_jointed(&mut self) {
+        self.jointed.truncate(self.bodies.len());
+        self.jointed.resize(self.bodies.len(), BTreeSet::new());
+        self.jointed.iter_mut().for_each(BTreeSet::clear);
+        for joint in self.joints.iter().flatten() {
+            let (a, b) = (joint.a.0, joint.b.0);
+            if joint.collide_connected || a.max(b) >= self.jointed.len() {
+                continue;
+            }
+            self.jointed[a].insert(b);
+            self.jointed[b].insert(a);
+        }
+    }
+}
+
+fn contact(p: &solver::ContactPoint) -> Contact {
+    Contact {
+        a: BodyId(p.a),
+        b: BodyId(p.b),
+        point: p.point,
+        normal: p.normal,
+        depth: p.depth,
+    }
+}
+
+// Whether a contact with the given normal, pointing away from the body, goes through it.
+fn passes_through(body: &Body, normal: Vec2) -> bool {
+    body.one_way
+        .is_some_and(|side| normal.dot(side.rotated(body.rotation)) < ONE_WAY_NORMAL)
+}
+
+impl PhysicsSnapshot {
+    pub fn body(&self, id: BodyId) -> Option<&Body> {
+        self.bodies.get(id.0)?.as_ref()
+    }
+
+    pub fn bodies(&self) -> impl Iterator<Item = (BodyId, &Body)> {
+        self.bodies
+            .iter()
+            .enumerate()
+            .filter_map(|(i, b)| Some((BodyId(i), b.as_ref()?)))
+    }
+
+    pub fn cast_ray(&self, origin: Vec2, direction: Vec2, max_distance: f64) -> Option<RayHit> {
+        query::cast_ray(&self.bodies, origin, direction, max_distance)
+    }
+
+    pub fn has_line_of_sight<F: FnMut(BodyId, &Body) -> bool>(
+        &self,
+        from: Vec2,
+        to: Vec2,
+        blocks: F,
+    ) -> bool {
+        query::has_line_of_sight(&self.bodies, from, to, blocks)
+    }
+
+    pub fn bodies_at(&self, point: Vec2) -> Vec<BodyId> {
+        query::bodies_at(&self.bodies, point)
+    }
+
+    pub fn bodies_overlapping(&self, shape: &dyn Shape, pose: &Pose) -> Vec<BodyId> {
+        query::bodies_overlapping(&self.bodies, shape, pose)
+    }
+}
+
+impl Default for PhysicsWorld {
+    fn default() -> Self {
+        PhysicsWorld::new(Vec2::new(0., 9.81))
+    }
+}
+
+// What bodies do, the rest of their state deriving from it or being set by the game.
+impl StateHash for Body {
+    fn hash_state(&self, hasher: &mut StateHasher) {
+        hasher.write(&(self.kind as u32));
+        hasher.write(&self.position);
+        hasher.write(&self.rotation);
+        hasher.write(&self.velocity);
+        hasher.write(&self.angular_velocity);
+    }
+}
+
+// Bodies by id, removed ones included for ids to be reused the same way.
+impl StateHash for PhysicsWorld {
+    fn hash_state(&self, hasher: &mut StateHasher) {
+        hasher.write(&self.bodies.len());
+        for body in &self.bodies {
+            hasher.write(&body.is_some());
+            if let Some(body) = body {
+                hasher.write(body);
+            }
+        }
+        hasher.write(self.free.as_slice());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use parry2d_f64::shape::SharedShape;
+
+    use super::*;
+
+    // Pile of boxes and balls falling on the ground, some of them jointed, with a body removed
+    // and one added in its place for ids to be reused.
+    fn build_world() -> PhysicsWorld {
+        let mut world = PhysicsWorld::new(Vec2::new(0., 9.81));
+        world.add(Body::new(
+            BodyKind::Static,
+            SharedShape::cuboid(20., 0.5),
+            Vec2::new(0., 10.),
+        ));
+        let mut previous = None;
+        for i in 0..12 {
+            let shape = if i % 3 == 0 {
+                SharedShape::ball(0.4)
+            } else {
+                SharedShape::cuboid(0.5, 0.3)
+            };
+            let position = Vec2::new((i % 4) as f64 * 0.9 - 1.3, 8. - (i / 4) as f64 * 1.1);
+            let mut body = Body::new(BodyKind::Dynamic, shape, position);
+            body.rotation = i as f64 * 0.1;
+            body.angular_velocity = (i as f64 - 6.) * 0.3;
+            let id = world.add(body);
+            if let Some(previous) = previous.filter(|_| i % 4 == 1) {
+                world
+                    .add_joint(Joint::distance(previous, Vec2::ZERO, id, Vec2::ZERO, 1.))
+                    .unwrap();
+            }
+            previous = Some(id);
+        }
+        world.remove(BodyId(5));
+        world.add(Body::new(
+            BodyKind::Dynamic,
+            SharedShape::ball(0.6),
+            Vec2::new(0.2, 2.),
+        ));
+        world
+    }
+
+    fn bits(world: &PhysicsWorld) -> Vec<Option<[u64; 6]>> {
+        world
+            .bodies
+            .iter()
+            .map(|body| {
+                body.as_ref().map(|b| {
+                    [
+                        b.position.x.to_bits(),
+                        b.position.y.to_bits(),
+                        b.rotation.to_bits(),
+                        b.velocity.x.to_bits(),
+                        b.velocity.y.to_bits(),
+                        b.angular_velocity.to_bits(),
+                    ]
+                })
+            })
+            .collect()
+    }
+
+    fn hash(world: &PhysicsWorld) -> u64 {
+        let mut hasher = StateHasher::new();
+        hasher.write(world);
+        hasher.finish()
+    }
+
+    #[test]
+    fn identical_worlds_step_identically() {
+        let mut a = build_world();
+        let mut b = build_world();
+        for _ in 0..300 {
+            a.step(1. / 60.);
+            b.step(1. / 60.);
+        }
+
+        assert_eq!(bits(&a), bits(&b));
+        assert_eq!(hash(&a), hash(&b));
+        // The pile did move and settle on the ground
+        assert_ne!(bits(&a), bits(&build_world()));
+        assert!(a
+            .bodies()
+            .filter(|(_, body)| body.kind == BodyKind::Dynamic)
+            .all(|(_, body)| body.position.y > 7. && body.position.y < 9.5));
+    }
+
+    #[test]
+    fn restored_world_steps_identically() {
+        let mut world = build_world();
+        for _ in 0..60 {
+            world.step(1. / 60.);
+        }
+
+        let snapshot = world.snapshot();
+        for _ in 0..120 {
+            world.step(1. / 60.);
+        }
+        let first = (bits(&world), hash(&world));
+
+        world.restore(&snapshot);
+        for _ in 0..120 {
+            world.step(1. / 60.);
+        }
+        assert_eq!((bits(&world), hash(&world)), first);
+    }
+
+    #[test]
+    fn broad_phase_finds_every_overlapping_pair() {
+        let mut world = build_world();
+        for _ in 0..60 {
+            world.step(1. / 60.);
+        }
+
+        let mut expected = Vec::new();
+        for (a, body_a) in world.bodies() {
+            for (b, body_b) in world.bodies().filter(|(b, _)| b.0 > a.0) {
+                let aabb_a = body_a.shape.compute_aabb(&body_a.pose());
+                let aabb_b = body_b.shape.compute_aabb(&body_b.pose());
+                let jointed = world.joints().any(|(_, j)| {
+                    !j.collide_connected && ((j.a, j.b) == (a, b) || (j.b, j.a) == (a, b))
+                });
+                if aabb_a.intersects(&aabb_b)
+                    && (body_a.kind == BodyKind::Dynamic || body_b.kind == BodyKind::Dynamic)
+                    && !jointed
+                {
+                    expected.push((a.0, b.0));
+                }
+            }
+        }
+
+        assert!(expected.len() > 10);
+        assert_eq!(world.broad_phase(), expected);
+    }
+
+    // A bullet crossing a whole wall each step, moving along (1, slope)
+    fn shoot(restitution: f64, slope: f64) -> Body {
+        let mut world = PhysicsWorld::new(Vec2::ZERO);
+        world.add(Body::new(
+            BodyKind::Static,
+            SharedShape::cuboid(0.05, 50.),
+            Vec2::new(5., 0.),
+        ));
+        let mut bullet = Body::new(BodyKind::Dynamic, SharedShape::ball(0.1), Vec2::ZERO);
+        bullet.velocity = Vec2::new(600., 600. * slope);
+        bullet.material.restitution = restitution;
+        bullet.ccd = true;
+        let bullet = world.add(bullet);
+
+        world.step(1. / 60.);
+        world.body(bullet).unwrap().clone()
+    }
+
+    #[test]
+    fn ccd_bodies_bounce_for_the_rest_of_the_step() {
+        let bullet = shoot(1., 0.);
+
+        // Back from the wall by the distance left to travel after reaching it
+        assert!((bullet.position.x + 0.3).abs() < 1e-6);
+        assert!((bullet.velocity.x + 600.).abs() < 1e-6);
+    }
+
+    #[test]
+    fn ccd_bodies_slide_for_the_rest_of_the_step() {
+        let bullet = shoot(0., 1.);
+
+        assert!((bullet.position.x - 4.85).abs() < 1e-6);
+        assert!((bullet.position.y - 10.).abs() < 1e-6);
+        assert!(bullet.velocity.x.abs() < 1e-6);
+    }
+}
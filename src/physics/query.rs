@@ -0,0 +1,103 @@
+use parry2d_f64::{
+    bounding_volume::BoundingVolume,
+    math::Pose,
+    query::{self, Ray},
+    shape::Shape,
+};
+
+use super::{Body, BodyId};
+use crate::Vec2;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct RayHit {
+    pub body: BodyId,
+    pub point: Vec2,
+    // Pointing out of the body hit.
+    pub normal: Vec2,
+    pub distance: f64,
+}
+
+// Rays that start inside a body hit it at distance 0.
+pub(super) fn cast_ray(
+    bodies: &[Option<Body>],
+    origin: Vec2,
+    direction: Vec2,
+    max_distance: f64,
+) -> Option<RayHit> {
+    let direction = direction.try_normalize()?;
+    let ray = Ray::new(origin, direction);
+
+    let mut closest: Option<RayHit> = None;
+    for (i, body) in bodies.iter().enumerate() {
+        let Some(body) = body else { continue };
+        let max_distance = closest.map_or(max_distance, |hit| hit.distance);
+        let Some(hit) = body
+            .shape
+            .cast_ray_and_get_normal(&body.pose(), &ray, max_distance, true)
+        else {
+            continue;
+        };
+
+        // Ties go to the lowest id for the result not to depend on anything else
+        if closest.is_none_or(|c| hit.time_of_impact < c.distance) {
+            closest = Some(RayHit {
+                body: BodyId(i),
+                point: ray.point_at(hit.time_of_impact),
+                normal: hit.normal,
+                distance: hit.time_of_impact,
+            });
+        }
+    }
+    closest
+}
+
+// Bodies the segment starts or ends in block it, unless blocks rejects them.
+pub(super) fn has_line_of_sight<F: FnMut(BodyId, &Body) -> bool>(
+    bodies: &[Option<Body>],
+    from: Vec2,
+    to: Vec2,
+    mut blocks: F,
+) -> bool {
+    let distance = (to - from).length();
+    let Some(direction) = (to - from).try_normalize() else {
+        return true;
+    };
+    let ray = Ray::new(from, direction);
+
+    !bodies
+        .iter()
+        .enumerate()
+        .filter_map(|(i, b)| Some((BodyId(i), b.as_ref()?)))
+        .any(|(id, body)| {
+            blocks(id, body) && body.shape.intersects_ray(&body.pose(), &ray, distance)
+        })
+}
+
+pub(super) fn bodies_at(bodies: &[Option<Body>], point: Vec2) -> Vec<BodyId> {
+    bodies
+        .iter()
+        .enumerate()
+        .filter_map(|(i, b)| Some((i, b.as_ref()?)))
+        .filter(|(_, b)| b.shape.contains_point(&b.pose(), point))
+        .map(|(i, _)| BodyId(i))
+        .collect()
+}
+
+pub(super) fn bodies_overlapping(
+    bodies: &[Option<Body>],
+    shape: &dyn Shape,
+    pose: &Pose,
+) -> Vec<BodyId> {
+    let aabb = shape.compute_aabb(pose);
+    bodies
+        .iter()
+        .enumerate()
+        .filter_map(|(i, b)| Some((i, b.as_ref()?)))
+        .filter(|(_, b)| aabb.intersects(&b.shape.compute_aabb(&b.pose())))
+        .filter(|(_, b)| {
+            query::intersection_test(pose, shape, &b.pose(), &*b.shape.0)
+                .is_ok_and(|t| t.intersecting)
+        })
+        .map(|(i, _)| BodyId(i))
+        .collect()
+}
@@ -0,0 +1,247 @@
+use parry2d_f64::query::{ContactManifold, DefaultQueryDispatcher, PersistentQueryDispatcher};
+
+use super::{Body, Combine};
+use crate::Vec2;
+
+// Penetration left uncorrected so that resting contacts stay in contact between steps.
+const SLOP: f64 = 0.005;
+// Part of the penetration corrected each step, correcting all of it makes stacks jitter.
+const CORRECTION: f64 = 0.4;
+// Below this approach speed, bodies don't bounce.
+const BOUNCE_THRESHOLD: f64 = 1.;
+// Below this number of pairs, spawning threads costs more than it saves.
+#[cfg(feature = "parallel")]
+const PARALLEL_PAIRS: usize = 256;
+
+pub(super) struct ContactPoint {
+    pub a: usize,
+    pub b: usize,
+    pub point: Vec2,
+    // Pointing from a to b.
+    pub normal: Vec2,
+    pub depth: f64,
+    // Features of the shapes in contact, to find the point again in the next step.
+    pub features: (u32, u32),
+    // From the body positions to the point.
+    ra: Vec2,
+    rb: Vec2,
+    friction: f64,
+    restitution: f64,
+    // Speed of b's surface relative to a's along the tangent.
+    surface_speed: f64,
+    normal_mass: f64,
+    tangent_mass: f64,
+    bounce: f64,
+    pub normal_impulse: f64,
+    pub tangent_impulse: f64,
+}
+
+fn collide(
+    (i, a): (usize, &Body),
+    (j, b): (usize, &Body),
+    (friction, restitution): (Combine, Combine),
+    points: &mut Vec<ContactPoint>,
+) {
+    let (pose_a, pose_b) = (a.pose(), b.pose());
+    let mut manifolds: Vec<ContactManifold<(), ()>> = Vec::new();
+    let result = DefaultQueryDispatcher.contact_manifolds(
+        &pose_a.inv_mul(&pose_b),
+        &*a.shape.0,
+        &*b.shape.0,
+        0.,
+        &mut manifolds,
+        &mut None,
+    );
+    if result.is_err() {
+        log::warn!("unsupported collision between bodies {i} and {j}");
+        return;
+    }
+
+    let surface_velocity = pose_b.transform_vector(b.material.surface_velocity)
+        - pose_a.transform_vector(a.material.surface_velocity);
+    for manifold in &manifolds {
+        let normal = pose_a.transform_vector(manifold.local_n1);
+        for contact in &manifold.points {
+            if contact.dist > 0. {
+                continue;
+            }
+
+            let point_a = pose_a.transform_point(contact.local_p1);
+            let point_b = pose_b.transform_point(contact.local_p2);
+            let point = (point_a + point_b) * 0.5;
+            points.push(ContactPoint {
+                a: i,
+                b: j,
+                point,
+                normal,
+                depth: -contact.dist,
+                features: (contact.fid1.0, contact.fid2.0),
+                ra: point - a.position,
+                rb: point - b.position,
+                friction: friction.apply(a.material.friction, b.material.friction),
+                restitution: restitution.apply(a.material.restitution, b.material.restitution),
+                surface_speed: surface_velocity.dot(normal.perp()),
+                normal_mass: 0.,
+                tangent_mass: 0.,
+                bounce: 0.,
+                normal_impulse: 0.,
+                tangent_impulse: 0.,
+            });
+        }
+    }
+}
+
+// Contact points of every pair, each pair's points being collected separately in the same
+// order. With the parallel feature, large sets of pairs are split between threads, the result
+// staying the same.
+#[cfg(not(feature = "parallel"))]
+pub(super) fn narrow_phase(
+    bodies: &[Option<Body>],
+    pairs: &[(usize, usize)],
+    combine: (Combine, Combine),
+) -> Vec<Vec<ContactPoint>> {
+    pairs
+        .iter()
+        .map(|&pair| collide_pair(bodies, pair, combine))
+        .collect()
+}
+
+#[cfg(feature = "parallel")]
+pub(super) fn narrow_phase(
+    bodies: &[Option<Body>],
+    pairs: &[(usize, usize)],
+    combine: (Combine, Combine),
+) -> Vec<Vec<ContactPoint>> {
+    let threads = std::thread::available_parallelism().map_or(1, |n| n.get());
+    if threads == 1 || pairs.len() < PARALLEL_PAIRS {
+        return pairs
+            .iter()
+            .map(|&pair| collide_pair(bodies, pair, combine))
+            .collect();
+    }
+
+    let chunk = pairs.len().div_ceil(threads);
+    std::thread::scope(|scope| {
+        let handles: Vec<_> = pairs
+            .chunks(chunk)
+            .map(|pairs| {
+                scope.spawn(move || {
+                    pairs
+                        .iter()
+                        .map(|&pair| collide_pair(bodies, pair, combine))
+                        .collect::<Vec<_>>()
+                })
+            })
+            .collect();
+        handles
+            .into_iter()
+            .flat_map(|handle| handle.join().expect("narrow phase thread panicked"))
+            .collect()
+    })
+}
+
+fn collide_pair(
+    bodies: &[Option<Body>],
+    (i, j): (usize, usize),
+    combine: (Combine, Combine),
+) -> Vec<ContactPoint> {
+    let mut points = Vec::new();
+    if let (Some(a), Some(b)) = (&bodies[i], &bodies[j]) {
+        collide((i, a), (j, b), combine, &mut points);
+    }
+    points
+}
+
+// Starts from the impulses of the previous step, which are close to the solution when bodies
+// are resting on each other.
+pub(super) fn prepare(bodies: &mut [Option<Body>], points: &mut [ContactPoint]) {
+    for p in points {
+        let (a, b) = pair_mut(bodies, p.a, p.b);
+
+        let tangent = p.normal.perp();
+        p.normal_mass = effective_mass(a, b, p.ra, p.rb, p.normal);
+        p.tangent_mass = effective_mass(a, b, p.ra, p.rb, tangent);
+
+        let approach = relative_velocity(a, b, p.ra, p.rb).dot(p.normal);
+        if approach < -BOUNCE_THRESHOLD {
+            p.bounce = -p.restitution * approach;
+        }
+
+        let impulse = p.normal * p.normal_impulse + tangent * p.tangent_impulse;
+        apply_impulse(a, b, p.ra, p.rb, impulse);
+    }
+}
+
+// Sequential impulses, each point's accumulated impulse clamped so contacts only push.
+pub(super) fn solve_velocities(bodies: &mut [Option<Body>], points: &mut [ContactPoint]) {
+    for p in points {
+        let (a, b) = pair_mut(bodies, p.a, p.b);
+
+        let velocity = relative_velocity(a, b, p.ra, p.rb).dot(p.normal);
+        let impulse = p.normal_mass * (p.bounce - velocity);
+        let total = (p.normal_impulse + impulse).max(0.);
+        apply_impulse(a, b, p.ra, p.rb, p.normal * (total - p.normal_impulse));
+        p.normal_impulse = total;
+
+        let tangent = p.normal.perp();
+        let velocity = relative_velocity(a, b, p.ra, p.rb).dot(tangent) + p.surface_speed;
+        let max_friction = p.friction * p.normal_impulse;
+        let impulse = -p.tangent_mass * velocity;
+        let total = (p.tangent_impulse + impulse).clamp(-max_friction, max_friction);
+        apply_impulse(a, b, p.ra, p.rb, tangent * (total - p.tangent_impulse));
+        p.tangent_impulse = total;
+    }
+}
+
+pub(super) fn correct_positions(bodies: &mut [Option<Body>], points: &[ContactPoint]) {
+    for p in points {
+        let (a, b) = pair_mut(bodies, p.a, p.b);
+        let total = a.inv_mass() + b.inv_mass();
+        if total == 0. {
+            continue;
+        }
+
+        let correction = p.normal * (CORRECTION * (p.depth - SLOP).max(0.) / total);
+        a.position -= correction * a.inv_mass();
+        b.position += correction * b.inv_mass();
+    }
+}
+
+fn effective_mass(a: &Body, b: &Body, ra: Vec2, rb: Vec2, direction: Vec2) -> f64 {
+    let (rna, rnb) = (ra.perp_dot(direction), rb.perp_dot(direction));
+    let k = a.inv_mass() + b.inv_mass() + a.inv_inertia() * rna * rna + b.inv_inertia() * rnb * rnb;
+    if k > 0. {
+        1. / k
+    } else {
+        0.
+    }
+}
+
+// Velocity of b relative to a at the contact point.
+fn relative_velocity(a: &Body, b: &Body, ra: Vec2, rb: Vec2) -> Vec2 {
+    b.velocity + rb.perp() * b.angular_velocity - a.velocity - ra.perp() * a.angular_velocity
+}
+
+// Applies the impulse to b and its opposite to a.
+fn apply_impulse(a: &mut Body, b: &mut Body, ra: Vec2, rb: Vec2, impulse: Vec2) {
+    a.velocity -= impulse * a.inv_mass();
+    a.angular_velocity -= ra.perp_dot(impulse) * a.inv_inertia();
+    b.velocity += impulse * b.inv_mass();
+    b.angular_velocity += rb.perp_dot(impulse) * b.inv_inertia();
+}
+
+// a and b have to be different bodies that weren't removed.
+pub(super) fn pair_mut(bodies: &mut [Option<Body>], a: usize, b: usize) -> (&mut Body, &mut Body) {
+    let (first, second) = if a < b {
+        let (left, right) = bodies.split_at_mut(b);
+        (&mut left[a], &mut right[0])
+    } else {
+        let (left, right) = bodies.split_at_mut(a);
+        (&mut right[0], &mut left[b])
+    };
+
+    match (first, second) {
+        (Some(a), Some(b)) => (a, b),
+        _ => unreachable!("constraint between removed bodies"),
+    }
+}
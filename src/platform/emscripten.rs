@@ -0,0 +1,59 @@
+use std::{
+    ffi::CString,
+    os::raw::{c_char, c_int, c_void},
+    path::Path,
+};
+
+use super::{Fetched, Frame};
+
+extern "C" {
+    fn emscripten_set_main_loop_arg(
+        func: extern "C" fn(*mut c_void),
+        arg: *mut c_void,
+        fps: c_int,
+        simulate_infinite_loop: c_int,
+    );
+    fn emscripten_cancel_main_loop();
+    fn emscripten_async_wget_data(
+        url: *const c_char,
+        arg: *mut c_void,
+        onload: extern "C" fn(*mut c_void, *mut c_void, c_int),
+        onerror: extern "C" fn(*mut c_void),
+    );
+}
+
+// The frame is leaked, the loop living as long as the page.
+pub(super) fn set_main_loop(frame: Frame) {
+    extern "C" fn call(arg: *mut c_void) {
+        let frame = unsafe { &mut *(arg as *mut Frame) };
+        if !frame() {
+            unsafe { emscripten_cancel_main_loop() };
+        }
+    }
+
+    let arg = Box::into_raw(Box::new(frame)) as *mut c_void;
+    // 0 fps follows requestAnimationFrame, and simulating an infinite loop unwinds the stack
+    // like the native loop would never return
+    unsafe { emscripten_set_main_loop_arg(call, arg, 0, 1) };
+}
+
+pub(super) fn fetch(path: &Path, result: Fetched) {
+    extern "C" fn onload(arg: *mut c_void, data: *mut c_void, size: c_int) {
+        let result = unsafe { Box::from_raw(arg as *mut Fetched) };
+        // The buffer is freed by emscripten once this returns
+        let bytes = unsafe { std::slice::from_raw_parts(data as *const u8, size as usize) };
+        *result.lock().unwrap() = Some(Ok(bytes.to_vec()));
+    }
+
+    extern "C" fn onerror(arg: *mut c_void) {
+        let result = unsafe { Box::from_raw(arg as *mut Fetched) };
+        *result.lock().unwrap() = Some(Err("request failed".to_string()));
+    }
+
+    let Ok(url) = CString::new(path.to_string_lossy().as_bytes()) else {
+        *result.lock().unwrap() = Some(Err(format!("invalid path {}", path.display())));
+        return;
+    };
+    let arg = Box::into_raw(Box::new(result)) as *mut c_void;
+    unsafe { emscripten_async_wget_data(url.as_ptr(), arg, onload, onerror) };
+}
@@ -0,0 +1,70 @@
+use std::{
+    path::Path,
+    sync::{Arc, Mutex},
+    time::Instant,
+};
+
+#[cfg(target_os = "emscripten")]
+mod emscripten;
+
+type Frame = Box<dyn FnMut() -> bool>;
+type Fetched = Arc<Mutex<Option<Result<Vec<u8>, String>>>>;
+
+// File being read without blocking the frame, see fetch.
+pub struct Fetch {
+    result: Fetched,
+}
+
+// Calls frame until it returns false, with the duration of the previous frame in seconds. On
+// the web the browser calls frame back once per display refresh, as pages can't loop, so run
+// never returns there and whatever follows it doesn't run.
+pub fn run<F: FnMut(f64) -> bool + 'static>(mut frame: F) {
+    let mut last = Instant::now();
+    let frame: Frame = Box::new(move || {
+        let now = Instant::now();
+        let dt = now.duration_since(last).as_secs_f64();
+        last = now;
+        frame(dt)
+    });
+
+    #[cfg(target_os = "emscripten")]
+    emscripten::set_main_loop(frame);
+
+    #[cfg(not(target_os = "emscripten"))]
+    {
+        let mut frame = frame;
+        while frame() {}
+    }
+}
+
+// Reads the file in the background, on the web by requesting it from the server relatively to
+// the page. Files preloaded in the build can be read with std::fs directly instead.
+pub fn fetch<P: AsRef<Path>>(path: P) -> Fetch {
+    let result: Fetched = Arc::new(Mutex::new(None));
+
+    #[cfg(target_os = "emscripten")]
+    emscripten::fetch(path.as_ref(), result.clone());
+
+    #[cfg(not(target_os = "emscripten"))]
+    {
+        let path = path.as_ref().to_path_buf();
+        let result = result.clone();
+        crate::jobs::spawn(move || {
+            let read = std::fs::read(&path).map_err(|e| format!("{}: {}", path.display(), e));
+            *result.lock().unwrap() = Some(read);
+        });
+    }
+
+    Fetch { result }
+}
+
+impl Fetch {
+    pub fn is_done(&self) -> bool {
+        self.result.lock().unwrap().is_some()
+    }
+
+    // The content of the file once it's read, taken by the first call.
+    pub fn take(&self) -> Option<Result<Vec<u8>, String>> {
+        self.result.lock().unwrap().take()
+    }
+}
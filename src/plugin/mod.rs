@@ -0,0 +1,381 @@
+use std::{
+    any::Any,
+    path::{Path, PathBuf},
+};
+
+use sdl2::{event::Event, video::Window, VideoSubsystem};
+
+use crate::{
+    args::Args,
+    focus::{Focus, FocusOptions},
+    graphics::{self, CanvasRenderer, CreateRenderer, GraphicsOptions, GraphicsPipeline},
+    inputs::{self, Control, InputScheme},
+    physics::PhysicsWorld,
+    random,
+    schedule::{Resources, Schedule, System},
+    Engine, Vec2,
+};
+
+// Receives every event polled by Engine::update, with the engine's resources.
+pub type EventHandler = Box<dyn FnMut(&Event, &mut Resources)>;
+
+// Optional part of the engine, e.g. audio or a debug overlay, adding its resources, systems
+// and event handlers while the engine is built.
+pub trait Plugin<T: InputScheme> {
+    fn build(&self, engine: &mut EngineBuilder<T>);
+
+    // Plugins are only added once per name.
+    fn name(&self) -> &str {
+        std::any::type_name::<Self>()
+    }
+}
+
+// Options of the engine, checked when it's built. Controllers are enabled by default, audio
+// and physics aren't.
+pub struct EngineBuilder<T: InputScheme> {
+    pub title: String,
+    pub graphics_options: GraphicsOptions,
+    pub resources: Resources,
+    pub schedule: Schedule<Engine<T>>,
+    resizable: bool,
+    // Centered when None.
+    window_position: Option<(i32, i32)>,
+    audio: bool,
+    controllers: bool,
+    // Gravity of the physics world, without one when None.
+    physics: Option<Vec2>,
+    timestep: f64,
+    create_renderer: CreateRenderer,
+    focus_options: FocusOptions,
+    headless: bool,
+    record_input: Option<PathBuf>,
+    // Seeded from the time when None.
+    seed: Option<u64>,
+    bindings: Vec<(T, Vec<Control>)>,
+    event_handlers: Vec<EventHandler>,
+    plugins: Vec<String>,
+}
+
+impl<T: InputScheme> EngineBuilder<T> {
+    pub fn new(title: &str) -> Self {
+        EngineBuilder {
+            title: title.to_string(),
+            graphics_options: GraphicsOptions::default(),
+            resources: Resources::new(),
+            schedule: Schedule::new(),
+            resizable: false,
+            window_position: None,
+            audio: false,
+            controllers: true,
+            physics: None,
+            timestep: 1. / 60.,
+            create_renderer: CanvasRenderer::create,
+            focus_options: FocusOptions::default(),
+            headless: false,
+            record_input: None,
+            seed: None,
+            bindings: Vec::new(),
+            event_handlers: Vec::new(),
+            plugins: Vec::new(),
+        }
+    }
+
+    pub fn graphics_options(&mut self, options: GraphicsOptions) -> &mut Self {
+        self.graphics_options = options;
+        self
+    }
+
+    pub fn window_size(&mut self, width: u32, height: u32) -> &mut Self {
+        self.graphics_options.window_size = (width, height);
+        self
+    }
+
+    pub fn fullscreen(&mut self, fullscreen: bool) -> &mut Self {
+        self.graphics_options.fullscreen = fullscreen;
+        self
+    }
+
+    pub fn vsync(&mut self, vsync: bool) -> &mut Self {
+        self.graphics_options.vsync = vsync;
+        self
+    }
+
+    pub fn resizable(&mut self, resizable: bool) -> &mut Self {
+        self.resizable = resizable;
+        self
+    }
+
+    // Position of the window's top left corner on the desktop, it's centered by default.
+    pub fn window_position(&mut self, x: i32, y: i32) -> &mut Self {
+        self.window_position = Some((x, y));
+        self
+    }
+
+    // Centers the window on the display, see graphics::displays.
+    pub fn display(&mut self, index: i32) -> &mut Self {
+        let position = graphics::centered_on(index);
+        self.window_position = Some((position, position));
+        self
+    }
+
+    // Backend drawing every window, SDL's renderer by default.
+    pub fn renderer(&mut self, create_renderer: CreateRenderer) -> &mut Self {
+        self.create_renderer = create_renderer;
+        self
+    }
+
+    // Initializes SDL's audio subsystem, see Engine::audio.
+    pub fn audio(&mut self, enabled: bool) -> &mut Self {
+        self.audio = enabled;
+        self
+    }
+
+    // Without controllers, gamepads plugged in are ignored.
+    pub fn controllers(&mut self, enabled: bool) -> &mut Self {
+        self.controllers = enabled;
+        self
+    }
+
+    // Creates Engine::physics, stepping by the engine's timestep.
+    pub fn physics(&mut self, gravity: Vec2) -> &mut Self {
+        self.physics = Some(gravity);
+        self
+    }
+
+    // Duration of the fixed updates of the schedule and of the physics steps, 1/60s by default.
+    pub fn timestep(&mut self, timestep: f64) -> &mut Self {
+        self.timestep = timestep;
+        self
+    }
+
+    // Pausing and muting on focus loss are enabled by default.
+    pub fn focus_options(&mut self, options: FocusOptions) -> &mut Self {
+        self.focus_options = options;
+        self
+    }
+
+    // Runs on SDL's dummy video driver, for automated runs on machines without a display. The
+    // engine works as usual but nothing is shown, audio and vsync are disabled.
+    pub fn headless(&mut self, headless: bool) -> &mut Self {
+        self.headless = headless;
+        self
+    }
+
+    // See InputsPipeline::record_to.
+    pub fn record_input<P: Into<PathBuf>>(&mut self, path: P) -> &mut Self {
+        self.record_input = Some(path.into());
+        self
+    }
+
+    pub fn seed(&mut self, seed: u64) -> &mut Self {
+        self.seed = Some(seed);
+        self
+    }
+
+    // Applies the command line flags that were given, the scene and the other arguments being
+    // left to the game.
+    pub fn args(&mut self, args: &Args) -> &mut Self {
+        if let Some(fullscreen) = args.fullscreen {
+            self.fullscreen(fullscreen);
+        }
+        if let Some((width, height)) = args.resolution {
+            self.window_size(width, height);
+        }
+        if args.headless {
+            self.headless(true);
+        }
+        if let Some(path) = &args.record_input {
+            self.record_input(path.clone());
+        }
+        if let Some(seed) = args.seed {
+            self.seed(seed);
+        }
+        self
+    }
+
+    // Registered when the engine is built, see InputsPipeline::register.
+    pub fn register_input(&mut self, input_id: T, controls: &[Control]) -> &mut Self {
+        self.bindings.push((input_id, controls.to_vec()));
+        self
+    }
+
+    // Plugins can add other plugins, those already added being skipped.
+    pub fn add_plugin<P: Plugin<T>>(&mut self, plugin: P) -> &mut Self {
+        let name = plugin.name().to_string();
+        if self.has_plugin(&name) {
+            log::debug!("plugin {name} already added");
+            return self;
+        }
+
+        log::debug!("adding plugin {name}");
+        self.plugins.push(name);
+        plugin.build(self);
+        self
+    }
+
+    pub fn has_plugin(&self, name: &str) -> bool {
+        self.plugins.iter().any(|p| p == name)
+    }
+
+    pub fn insert_resource<R: Any + Send + Sync>(&mut self, value: R) -> &mut Self {
+        self.resources.insert(value);
+        self
+    }
+
+    pub fn add_system(&mut self, system: System) -> &mut Self {
+        self.schedule.add(system);
+        self
+    }
+
+    // Handlers are called in the order they were added.
+    pub fn add_event_handler<F: FnMut(&Event, &mut Resources) + 'static>(
+        &mut self,
+        handler: F,
+    ) -> &mut Self {
+        self.event_handlers.push(Box::new(handler));
+        self
+    }
+
+    // Routes files dropped on a window to handler by extension, e.g. &["png", "bmp"] to load
+    // images. Extensions are compared ignoring case, an empty list accepting every file.
+    pub fn on_drop<F: FnMut(&Path, &mut Resources) + 'static>(
+        &mut self,
+        extensions: &[&str],
+        mut handler: F,
+    ) -> &mut Self {
+        let extensions: Vec<String> = extensions.iter().map(|e| e.to_lowercase()).collect();
+        self.add_event_handler(move |event, resources| {
+            let Event::DropFile { filename, .. } = event else {
+                return;
+            };
+            let path = Path::new(filename);
+            let extension = path.extension().map(|e| e.to_string_lossy().to_lowercase());
+            if extensions.is_empty() || extension.is_some_and(|e| extensions.contains(&e)) {
+                handler(path, resources);
+            }
+        })
+    }
+
+    pub fn build(self) -> Result<Engine<T>, String> {
+        let mut graphics_options = self.graphics_options;
+        let (width, height) = graphics_options.window_size;
+        if width == 0 || height == 0 {
+            return Err(format!("invalid window size {width}x{height}"));
+        }
+        if graphics_options.pixel_per_unit == 0 {
+            return Err("pixel_per_unit must be positive".to_string());
+        }
+        if !(self.timestep.is_finite() && self.timestep > 0.) {
+            return Err(format!("invalid timestep {}", self.timestep));
+        }
+        let max_delta = self.focus_options.max_delta;
+        if !(max_delta.is_finite() && max_delta > 0.) {
+            return Err(format!("invalid max_delta {max_delta}"));
+        }
+
+        if self.headless {
+            sdl2::hint::set("SDL_VIDEODRIVER", "dummy");
+            graphics_options.vsync = false;
+        }
+        let ctx = sdl2::init().map_err(|e| format!("SDL: {e}"))?;
+
+        // Setup GrahicsPipeline
+        let video_subsystem = ctx.video().map_err(|e| format!("video: {e}"))?;
+        let window = create_window(
+            &video_subsystem,
+            &self.title,
+            &graphics_options,
+            self.resizable,
+            self.window_position,
+        )?;
+        let renderer = (self.create_renderer)(window, &graphics_options)?;
+        let graphics_ppl = GraphicsPipeline::with_renderer(graphics_options, renderer);
+
+        // Setup InputsPipeline
+        let event_pump = ctx.event_pump().map_err(|e| format!("events: {e}"))?;
+        let controller_subsystem = if self.controllers {
+            Some(
+                ctx.game_controller()
+                    .map_err(|e| format!("controllers: {e}"))?,
+            )
+        } else {
+            None
+        };
+        let mut inputs_ppl = inputs::InputsPipeline::new(
+            event_pump,
+            controller_subsystem,
+            video_subsystem.text_input(),
+        );
+        for (id, controls) in &self.bindings {
+            inputs_ppl
+                .register(*id, controls)
+                .map_err(|e| format!("inputs: {e}"))?;
+        }
+        if let Some(path) = &self.record_input {
+            inputs_ppl
+                .record_to(path)
+                .map_err(|e| format!("input recording: {e}"))?;
+        }
+
+        let audio = if self.audio && !self.headless {
+            Some(ctx.audio().map_err(|e| format!("audio: {e}"))?)
+        } else {
+            None
+        };
+        let physics = self.physics.map(|gravity| {
+            let mut world = PhysicsWorld::new(gravity);
+            world.timestep = self.timestep;
+            world
+        });
+        let mut schedule = self.schedule;
+        schedule.timestep = self.timestep;
+
+        let clipboard = video_subsystem.clipboard();
+
+        Ok(Engine {
+            graphics_ppl,
+            inputs_ppl,
+            random: self
+                .seed
+                .map_or_else(random::Random::default, random::Random::new),
+            physics,
+            focus: Focus::new(self.focus_options),
+            resources: self.resources,
+            schedule,
+            event_handlers: self.event_handlers,
+            audio,
+            video: video_subsystem,
+            windows: Vec::new(),
+            create_renderer: self.create_renderer,
+            clipboard,
+        })
+    }
+}
+
+// Centered on the desktop when position is None.
+pub(crate) fn create_window(
+    video: &VideoSubsystem,
+    title: &str,
+    options: &GraphicsOptions,
+    resizable: bool,
+    position: Option<(i32, i32)>,
+) -> Result<Window, String> {
+    let (width, height) = options.window_size;
+    let mut window_builder = video.window(title, width, height);
+    match position {
+        Some((x, y)) => window_builder.position(x, y),
+        None => window_builder.position_centered(),
+    };
+    if options.fullscreen {
+        window_builder.fullscreen_desktop();
+    }
+    if resizable {
+        window_builder.resizable();
+    }
+    if options.high_dpi {
+        window_builder.allow_highdpi();
+    }
+    #[cfg(feature = "opengl")]
+    window_builder.opengl();
+    window_builder.build().map_err(|e| format!("window: {e}"))
+}
@@ -0,0 +1,243 @@
+use std::cell::{Cell, RefCell};
+
+use crate::{
+    physics::{Body, BodyId, PhysicsWorld},
+    Vec2,
+};
+
+thread_local! {
+    // Stats of the living pools by id, for the profiler's overlay.
+    static POOLS: RefCell<Vec<(u64, &'static str, PoolStats)>> = const { RefCell::new(Vec::new()) };
+    static NEXT_POOL: Cell<u64> = const { Cell::new(0) };
+}
+
+// Object kept by a pool, told when it's acquired and released to turn its parts on and off,
+// e.g. to take its body out of the world. Pooled objects are only drawn by the game while
+// they're active, by iterating over the pool.
+pub trait Poolable {
+    fn activate(&mut self, _physics: Option<&mut PhysicsWorld>) {}
+
+    fn deactivate(&mut self, physics: Option<&mut PhysicsWorld>);
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub struct PoolId(usize);
+
+#[derive(Clone, Copy, PartialEq, Eq, Default, Debug)]
+pub struct PoolStats {
+    pub capacity: usize,
+    pub active: usize,
+    // Most objects active at once.
+    pub peak: usize,
+    // Objects created after the pool was filled, because every one was active.
+    pub grown: usize,
+}
+
+// Objects created up front and reused, e.g. for bullets or particles spawned by the hundreds
+// every second, so that spawning doesn't allocate. Acquiring from a pool whose objects are all
+// active creates another one. Objects are iterated in a fixed order, their ids being reused.
+pub struct Pool<T: Poolable> {
+    id: u64,
+    name: &'static str,
+    items: Vec<T>,
+    active: Vec<bool>,
+    // Inactive objects, the last released being reused first.
+    free: Vec<usize>,
+    create: Box<dyn FnMut() -> T>,
+    stats: PoolStats,
+}
+
+// Body in the world while its object is active. Each spawn adds a copy of the template,
+// which shares its collider, so that no shape is built again.
+#[derive(Clone)]
+pub struct PooledBody {
+    pub template: Body,
+    id: Option<BodyId>,
+}
+
+impl<T: Poolable> Pool<T> {
+    // The name tells the pool apart in the profiler's overlay.
+    pub fn new<F: FnMut() -> T + 'static>(name: &'static str, count: usize, mut create: F) -> Self {
+        let items: Vec<T> = (0..count).map(|_| create()).collect();
+        let id = NEXT_POOL.with(|n| n.replace(n.get() + 1));
+        let pool = Pool {
+            id,
+            name,
+            active: vec![false; count],
+            free: (0..count).rev().collect(),
+            items,
+            create: Box::new(create),
+            stats: PoolStats {
+                capacity: count,
+                ..PoolStats::default()
+            },
+        };
+        pool.publish();
+        pool
+    }
+
+    pub fn acquire(&mut self, physics: Option<&mut PhysicsWorld>) -> (PoolId, &mut T) {
+        let index = match self.free.pop() {
+            Some(index) => index,
+            None => {
+                log::debug!("pool {} grown to {}", self.name, self.items.len() + 1);
+                self.items.push((self.create)());
+                self.active.push(false);
+                self.stats.capacity += 1;
+                self.stats.grown += 1;
+                self.items.len() - 1
+            }
+        };
+
+        self.active[index] = true;
+        self.stats.active += 1;
+        self.stats.peak = self.stats.peak.max(self.stats.active);
+        self.publish();
+
+        let item = &mut self.items[index];
+        item.activate(physics);
+        (PoolId(index), item)
+    }
+
+    // Returns false if the object wasn't active.
+    pub fn release(&mut self, id: PoolId, physics: Option<&mut PhysicsWorld>) -> bool {
+        if !self.is_active(id) {
+            return false;
+        }
+
+        self.items[id.0].deactivate(physics);
+        self.active[id.0] = false;
+        self.free.push(id.0);
+        self.stats.active -= 1;
+        self.publish();
+        true
+    }
+
+    // Releases the active objects for which release returns true, e.g. bullets leaving the
+    // screen. Returns how many were.
+    pub fn release_if<F: FnMut(&T) -> bool>(
+        &mut self,
+        mut physics: Option<&mut PhysicsWorld>,
+        mut release: F,
+    ) -> usize {
+        let ids: Vec<PoolId> = self
+            .iter()
+            .filter(|(_, item)| release(item))
+            .map(|(id, _)| id)
+            .collect();
+        for &id in &ids {
+            self.release(id, physics.as_deref_mut());
+        }
+        ids.len()
+    }
+
+    pub fn release_all(&mut self, physics: Option<&mut PhysicsWorld>) -> usize {
+        self.release_if(physics, |_| true)
+    }
+
+    // None if the object isn't active.
+    pub fn get(&self, id: PoolId) -> Option<&T> {
+        self.is_active(id).then(|| &self.items[id.0])
+    }
+
+    pub fn get_mut(&mut self, id: PoolId) -> Option<&mut T> {
+        self.is_active(id).then(|| &mut self.items[id.0])
+    }
+
+    pub fn is_active(&self, id: PoolId) -> bool {
+        self.active.get(id.0).copied().unwrap_or(false)
+    }
+
+    // Active objects.
+    pub fn iter(&self) -> impl Iterator<Item = (PoolId, &T)> {
+        self.items
+            .iter()
+            .zip(&self.active)
+            .enumerate()
+            .filter(|(_, (_, active))| **active)
+            .map(|(i, (item, _))| (PoolId(i), item))
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = (PoolId, &mut T)> {
+        self.items
+            .iter_mut()
+            .zip(&self.active)
+            .enumerate()
+            .filter(|(_, (_, active))| **active)
+            .map(|(i, (item, _))| (PoolId(i), item))
+    }
+
+    pub fn len(&self) -> usize {
+        self.stats.active
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.stats.active == 0
+    }
+
+    pub fn stats(&self) -> PoolStats {
+        self.stats
+    }
+
+    fn publish(&self) {
+        POOLS.with_borrow_mut(
+            |pools| match pools.iter_mut().find(|(id, ..)| *id == self.id) {
+                Some((.., stats)) => *stats = self.stats,
+                None => pools.push((self.id, self.name, self.stats)),
+            },
+        );
+    }
+}
+
+impl<T: Poolable> Drop for Pool<T> {
+    fn drop(&mut self) {
+        // Dropped while the thread's pools are being destroyed, e.g. in a thread local
+        let _ = POOLS.try_with(|pools| pools.borrow_mut().retain(|(id, ..)| *id != self.id));
+    }
+}
+
+impl PooledBody {
+    pub fn new(template: Body) -> Self {
+        PooledBody { template, id: None }
+    }
+
+    // None while the object is pooled.
+    pub fn id(&self) -> Option<BodyId> {
+        self.id
+    }
+
+    // Adds the template to the world at the given transform, replacing the body already added.
+    pub fn spawn(&mut self, physics: &mut PhysicsWorld, position: Vec2, rotation: f64) -> BodyId {
+        self.despawn(physics);
+        let mut body = self.template.clone();
+        body.teleport(position, rotation);
+        let id = physics.add(body);
+        self.id = Some(id);
+        id
+    }
+
+    pub fn despawn(&mut self, physics: &mut PhysicsWorld) {
+        if let Some(id) = self.id.take() {
+            physics.remove(id);
+        }
+    }
+}
+
+// Pooled bodies are spawned by the game once it knows where, and despawned when released.
+impl Poolable for PooledBody {
+    fn deactivate(&mut self, physics: Option<&mut PhysicsWorld>) {
+        if let Some(physics) = physics {
+            self.despawn(physics);
+        }
+    }
+}
+
+// Stats of the thread's pools, in the order they were created.
+pub fn all_stats() -> Vec<(&'static str, PoolStats)> {
+    POOLS.with_borrow(|pools| {
+        pools
+            .iter()
+            .map(|(_, name, stats)| (*name, *stats))
+            .collect()
+    })
+}
@@ -0,0 +1,293 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+use crate::{
+    config::{parse_toml, Document, Value},
+    graphics::{Color, ColorExt},
+    Vec2,
+};
+
+// Key instantiating another prefab in place of a node, the node's other keys overriding its
+// properties.
+const PREFAB_KEY: &str = "prefab";
+
+// Property values by key. Keys written "child.key" apply to a child, e.g. "weapon.damage".
+pub type Overrides = BTreeMap<String, Value>;
+
+// Template of a game object, written with the settings' TOML subset. Keys outside of any table
+// are the root's properties and each table is a child named after it:
+//
+// prefab = "enemy"
+// health = 10
+// tint = "#80ff80"
+//
+// [weapon]
+// prefab = "sword"
+// offset = [0.5, 0.0]
+//
+// The properties are only data, the game reads them to spawn its own objects.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Prefab {
+    document: Document,
+}
+
+// Prefab with its nested prefabs and overrides resolved. Children are ordered by name.
+#[derive(Clone, PartialEq, Debug, Default)]
+pub struct Instance {
+    // Name of the prefab, empty for children defined inline.
+    pub prefab: String,
+    pub properties: BTreeMap<String, Value>,
+    pub children: BTreeMap<String, Instance>,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Debug)]
+pub struct InstanceId(u64);
+
+// Instance kept up to date with its prefab.
+struct Spawned {
+    prefab: String,
+    overrides: Overrides,
+    instance: Instance,
+}
+
+// Prefabs by name. In debug builds, update reloads the files that changed and gives the spawned
+// instances that changed with them.
+#[derive(Default)]
+pub struct Prefabs {
+    prefabs: HashMap<String, Prefab>,
+    files: HashMap<String, (PathBuf, Option<SystemTime>)>,
+    spawned: BTreeMap<InstanceId, Spawned>,
+    next_id: u64,
+}
+
+impl Prefab {
+    pub fn parse(source: &str) -> Result<Self, String> {
+        let document = parse_toml(source)?;
+        for (table, values) in &document {
+            if values.get(PREFAB_KEY).is_some_and(|p| p.as_str().is_none()) {
+                return Err(format!("{PREFAB_KEY} of \"{table}\" should be a string"));
+            }
+        }
+        Ok(Prefab { document })
+    }
+
+    pub fn properties(&self) -> impl Iterator<Item = (&str, &Value)> {
+        self.document
+            .get("")
+            .into_iter()
+            .flatten()
+            .filter(|(key, _)| *key != PREFAB_KEY)
+            .map(|(key, value)| (key.as_str(), value))
+    }
+}
+
+impl Instance {
+    pub fn get(&self, key: &str) -> Option<&Value> {
+        self.properties.get(key)
+    }
+
+    pub fn f64(&self, key: &str) -> Option<f64> {
+        self.get(key)?.as_f64()
+    }
+
+    pub fn str(&self, key: &str) -> Option<&str> {
+        self.get(key)?.as_str()
+    }
+
+    pub fn bool(&self, key: &str) -> Option<bool> {
+        self.get(key)?.as_bool()
+    }
+
+    // From an array of two numbers.
+    pub fn vec2(&self, key: &str) -> Option<Vec2> {
+        match self.get(key)?.as_array()? {
+            [x, y] => Some(Vec2::new(x.as_f64()?, y.as_f64()?)),
+            _ => None,
+        }
+    }
+
+    // From a hex string.
+    pub fn color(&self, key: &str) -> Option<Color> {
+        Color::from_hex(self.str(key)?).ok()
+    }
+
+    pub fn child(&self, name: &str) -> Option<&Instance> {
+        self.children.get(name)
+    }
+
+    fn apply(&mut self, overrides: &Overrides) {
+        for (key, value) in overrides {
+            let mut node = &mut *self;
+            let mut path: Vec<&str> = key.split('.').collect();
+            let key = path.pop().unwrap_or_default();
+            for child in path {
+                node = node.children.entry(child.to_string()).or_default();
+            }
+            node.properties.insert(key.to_string(), value.clone());
+        }
+    }
+}
+
+impl Prefabs {
+    pub fn new() -> Self {
+        Prefabs::default()
+    }
+
+    pub fn load<P: AsRef<Path>>(&mut self, name: &str, path: P) -> Result<(), String> {
+        let path = path.as_ref().to_path_buf();
+        let modified = modified(&path);
+        let source = std::fs::read_to_string(&path).map_err(|e| e.to_string())?;
+        let prefab = Prefab::parse(&source).map_err(|e| format!("{}: {}", path.display(), e))?;
+
+        self.files.insert(name.to_string(), (path, modified));
+        self.prefabs.insert(name.to_string(), prefab);
+        Ok(())
+    }
+
+    // Replaces the prefab of the same name, spawned instances are updated by the next update.
+    pub fn insert(&mut self, name: &str, prefab: Prefab) {
+        self.prefabs.insert(name.to_string(), prefab);
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Prefab> {
+        self.prefabs.get(name)
+    }
+
+    // Fails if the prefab or one it contains doesn't exist, or if a prefab contains itself.
+    pub fn instantiate(&self, name: &str, overrides: &Overrides) -> Result<Instance, String> {
+        let mut instance = self.resolve(name, &mut Vec::new())?;
+        instance.apply(overrides);
+        Ok(instance)
+    }
+
+    // Same as instantiate, the instance being tracked for update until despawned.
+    pub fn spawn(
+        &mut self,
+        name: &str,
+        overrides: Overrides,
+    ) -> Result<(InstanceId, Instance), String> {
+        let instance = self.instantiate(name, &overrides)?;
+        let id = InstanceId(self.next_id);
+        self.next_id += 1;
+
+        self.spawned.insert(
+            id,
+            Spawned {
+                prefab: name.to_string(),
+                overrides,
+                instance: instance.clone(),
+            },
+        );
+        Ok((id, instance))
+    }
+
+    pub fn despawn(&mut self, id: InstanceId) {
+        self.spawned.remove(&id);
+    }
+
+    // Reloads the prefab files that changed, in debug builds only, then returns the spawned
+    // instances that changed since they were spawned or last updated. A prefab failing to
+    // reload keeps its previous version.
+    pub fn update(&mut self) -> Vec<(InstanceId, Instance)> {
+        if cfg!(debug_assertions) {
+            self.reload();
+        }
+
+        let mut changed = Vec::new();
+        let ids: Vec<InstanceId> = self.spawned.keys().copied().collect();
+        for id in ids {
+            let spawned = &self.spawned[&id];
+            let instance = match self.instantiate(&spawned.prefab, &spawned.overrides) {
+                Ok(instance) => instance,
+                Err(e) => {
+                    log::warn!("failed to update prefab instance {}: {e}", spawned.prefab);
+                    continue;
+                }
+            };
+
+            if let Some(spawned) = self.spawned.get_mut(&id) {
+                if spawned.instance != instance {
+                    spawned.instance = instance.clone();
+                    changed.push((id, instance));
+                }
+            }
+        }
+        changed
+    }
+
+    fn reload(&mut self) {
+        for (name, (path, last_modified)) in &mut self.files {
+            let modified = modified(path);
+            if modified == *last_modified {
+                continue;
+            }
+
+            *last_modified = modified;
+            let result = std::fs::read_to_string(&*path)
+                .map_err(|e| e.to_string())
+                .and_then(|source| Prefab::parse(&source));
+            match result {
+                Ok(prefab) => {
+                    log::info!("reloaded prefab {name}");
+                    self.prefabs.insert(name.clone(), prefab);
+                }
+                Err(e) => log::warn!("failed to reload prefab {name}: {e}"),
+            }
+        }
+    }
+
+    // stack holds the prefabs being resolved, to detect cycles.
+    fn resolve(&self, name: &str, stack: &mut Vec<String>) -> Result<Instance, String> {
+        if stack.iter().any(|s| s == name) {
+            return Err(format!("prefab \"{name}\" contains itself"));
+        }
+        let prefab = self
+            .prefabs
+            .get(name)
+            .ok_or_else(|| format!("unknown prefab \"{name}\""))?;
+        stack.push(name.to_string());
+
+        let empty = BTreeMap::new();
+        let root = prefab.document.get("").unwrap_or(&empty);
+        let mut instance = self.node(root, stack)?;
+        for (child, values) in prefab.document.iter().filter(|(t, _)| !t.is_empty()) {
+            let node = self.node(values, stack)?;
+            let entry = instance.children.entry(child.clone()).or_default();
+            // A child without prefab refines the one of the base prefab
+            if node.prefab.is_empty() {
+                entry.properties.extend(node.properties);
+                entry.children.extend(node.children);
+            } else {
+                *entry = node;
+            }
+        }
+
+        stack.pop();
+        instance.prefab = name.to_string();
+        Ok(instance)
+    }
+
+    // Instance of the node's prefab, if it has one, with the node's properties.
+    fn node(
+        &self,
+        values: &BTreeMap<String, Value>,
+        stack: &mut Vec<String>,
+    ) -> Result<Instance, String> {
+        let mut instance = match values.get(PREFAB_KEY).and_then(Value::as_str) {
+            Some(prefab) => self.resolve(prefab, stack)?,
+            None => Instance::default(),
+        };
+
+        for (key, value) in values.iter().filter(|(key, _)| *key != PREFAB_KEY) {
+            instance.properties.insert(key.clone(), value.clone());
+        }
+        Ok(instance)
+    }
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|m| m.modified()).ok()
+}
@@ -0,0 +1,254 @@
+use std::{
+    io::{Read, Write},
+    time::{Duration, Instant, UNIX_EPOCH},
+};
+
+use super::{Activity, PresenceBackend};
+
+// How long to wait before connecting again once Discord isn't reachable.
+const RETRY_INTERVAL: Duration = Duration::from_secs(15);
+
+const HANDSHAKE: u32 = 0;
+const FRAME: u32 = 1;
+
+#[cfg(unix)]
+type Connection = std::os::unix::net::UnixStream;
+#[cfg(windows)]
+type Connection = std::fs::File;
+
+// Discord's rich presence through the local IPC socket of the desktop client, without its SDK.
+// The connection is opened lazily and again whenever the client restarts, the last activity
+// being sent once it's back.
+pub struct DiscordPresence {
+    client_id: String,
+    connection: Option<Connection>,
+    last_attempt: Option<Instant>,
+    activity: Option<Activity>,
+    sent: bool,
+    nonce: u64,
+}
+
+impl DiscordPresence {
+    // The id of the application registered on Discord's developer portal.
+    pub fn new(client_id: &str) -> Self {
+        DiscordPresence {
+            client_id: client_id.to_string(),
+            connection: None,
+            last_attempt: None,
+            activity: None,
+            sent: true,
+            nonce: 0,
+        }
+    }
+
+    fn connect(&mut self) -> Result<(), String> {
+        if self
+            .last_attempt
+            .is_some_and(|t| t.elapsed() < RETRY_INTERVAL)
+        {
+            return Ok(());
+        }
+        self.last_attempt = Some(Instant::now());
+
+        let mut connection = (0..10)
+            .find_map(|i| open(i).ok())
+            .ok_or("Discord isn't running")?;
+        let handshake = format!("{{\"v\":1,\"client_id\":\"{}\"}}", escape(&self.client_id));
+        send(&mut connection, HANDSHAKE, &handshake)?;
+        log::debug!("connected to Discord");
+        self.connection = Some(connection);
+        Ok(())
+    }
+
+    fn send_activity(&mut self) -> Result<(), String> {
+        let Some(connection) = &mut self.connection else {
+            return Ok(());
+        };
+
+        let activity = match &self.activity {
+            Some(activity) => {
+                let mut fields = Vec::new();
+                if !activity.state.is_empty() {
+                    fields.push(format!("\"state\":\"{}\"", escape(&activity.state)));
+                }
+                if !activity.details.is_empty() {
+                    fields.push(format!("\"details\":\"{}\"", escape(&activity.details)));
+                }
+                let timestamps: Vec<String> = [
+                    ("start", activity.timestamps.start),
+                    ("end", activity.timestamps.end),
+                ]
+                .into_iter()
+                .filter_map(|(name, time)| {
+                    let millis = time?.duration_since(UNIX_EPOCH).ok()?.as_millis();
+                    Some(format!("\"{name}\":{millis}"))
+                })
+                .collect();
+                if !timestamps.is_empty() {
+                    fields.push(format!("\"timestamps\":{{{}}}", timestamps.join(",")));
+                }
+                format!("{{{}}}", fields.join(","))
+            }
+            None => "null".to_string(),
+        };
+
+        self.nonce += 1;
+        let payload = format!(
+            "{{\"cmd\":\"SET_ACTIVITY\",\"args\":{{\"pid\":{},\"activity\":{}}},\"nonce\":\"{}\"}}",
+            std::process::id(),
+            activity,
+            self.nonce
+        );
+        send(connection, FRAME, &payload)
+    }
+}
+
+impl PresenceBackend for DiscordPresence {
+    fn set_activity(&mut self, activity: Option<&Activity>) -> Result<(), String> {
+        self.activity = activity.cloned();
+        self.sent = false;
+        Ok(())
+    }
+
+    fn update(&mut self) -> Result<(), String> {
+        if self.connection.is_none() {
+            // Not being able to connect is expected when Discord isn't running
+            let _ = self.connect();
+            if self.connection.is_none() {
+                return Ok(());
+            }
+            // Sent again to the new connection
+            self.sent = self.activity.is_none();
+        }
+
+        if self.connection.as_mut().is_some_and(|c| !drain(c)) {
+            self.connection = None;
+            self.sent = false;
+            return Err("Discord closed the connection".to_string());
+        }
+        if self.sent {
+            return Ok(());
+        }
+        self.sent = true;
+        if let Err(e) = self.send_activity() {
+            self.connection = None;
+            self.sent = false;
+            return Err(format!("lost the connection to Discord: {e}"));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(unix)]
+fn open(index: u32) -> std::io::Result<Connection> {
+    let directory = ["XDG_RUNTIME_DIR", "TMPDIR", "TMP", "TEMP"]
+        .iter()
+        .find_map(|name| std::env::var_os(name).filter(|v| !v.is_empty()))
+        .unwrap_or_else(|| "/tmp".into());
+    let path = std::path::Path::new(&directory).join(format!("discord-ipc-{index}"));
+    let connection = Connection::connect(path)?;
+    connection.set_nonblocking(true)?;
+    Ok(connection)
+}
+
+#[cfg(windows)]
+fn open(index: u32) -> std::io::Result<Connection> {
+    std::fs::OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open(format!(r"\\?\pipe\discord-ipc-{index}"))
+}
+
+// Frames are an opcode and a length, both little endian, followed by JSON.
+fn send(connection: &mut Connection, opcode: u32, payload: &str) -> Result<(), String> {
+    let mut frame = opcode.to_le_bytes().to_vec();
+    frame.extend((payload.len() as u32).to_le_bytes());
+    frame.extend(payload.as_bytes());
+    connection.write_all(&frame).map_err(|e| e.to_string())
+}
+
+// Replies are ignored, only read so that they don't fill the socket's buffer. Returns false
+// once the connection is closed. Pipes can't be read without blocking, Windows' are left alone.
+fn drain(connection: &mut Connection) -> bool {
+    if cfg!(windows) {
+        return true;
+    }
+    let mut buffer = [0; 1024];
+    loop {
+        match connection.read(&mut buffer) {
+            Ok(0) => return false,
+            Ok(_) => continue,
+            Err(e) => return e.kind() == std::io::ErrorKind::WouldBlock,
+        }
+    }
+}
+
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            c if (c as u32) < 0x20 => escaped.push_str(&format!("\\u{:04x}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use std::os::unix::net::{UnixListener, UnixStream};
+
+    use super::*;
+
+    fn read_frame(stream: &mut UnixStream) -> (u32, String) {
+        let mut header = [0; 8];
+        stream.read_exact(&mut header).unwrap();
+        let opcode = u32::from_le_bytes(header[..4].try_into().unwrap());
+        let length = u32::from_le_bytes(header[4..].try_into().unwrap());
+        let mut payload = vec![0; length as usize];
+        stream.read_exact(&mut payload).unwrap();
+        (opcode, String::from_utf8(payload).unwrap())
+    }
+
+    #[test]
+    fn activity_is_sent_to_the_socket() {
+        let directory = std::env::temp_dir().join(format!("engine-discord-{}", std::process::id()));
+        std::fs::create_dir_all(&directory).unwrap();
+        let listener = UnixListener::bind(directory.join("discord-ipc-0")).unwrap();
+        // Only read by open
+        std::env::set_var("XDG_RUNTIME_DIR", &directory);
+
+        let mut presence = DiscordPresence::new("1234");
+        let activity = Activity {
+            state: "In \"town\"".to_string(),
+            details: "Level 2".to_string(),
+            ..Default::default()
+        };
+        presence.set_activity(Some(&activity)).unwrap();
+        presence.update().unwrap();
+
+        let (mut stream, _) = listener.accept().unwrap();
+        assert_eq!(
+            read_frame(&mut stream),
+            (HANDSHAKE, r#"{"v":1,"client_id":"1234"}"#.to_string())
+        );
+        let expected = format!(
+            r#"{{"cmd":"SET_ACTIVITY","args":{{"pid":{},"activity":{{"state":"In \"town\"","details":"Level 2"}}}},"nonce":"1"}}"#,
+            std::process::id()
+        );
+        assert_eq!(read_frame(&mut stream), (FRAME, expected));
+
+        // Unchanged activities aren't sent again
+        presence.update().unwrap();
+        stream.set_nonblocking(true).unwrap();
+        let read = stream.read(&mut [0; 1]).map_err(|e| e.kind());
+        assert_eq!(read, Err(std::io::ErrorKind::WouldBlock));
+        drop(stream);
+        assert!(presence.update().is_err());
+
+        std::fs::remove_dir_all(&directory).unwrap();
+    }
+}
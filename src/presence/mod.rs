@@ -0,0 +1,155 @@
+use std::{
+    cell::Cell,
+    time::{Duration, Instant, SystemTime},
+};
+
+use crate::{
+    inputs::InputScheme,
+    plugin::{EngineBuilder, Plugin},
+    schedule::{Stage, System},
+};
+
+#[cfg(feature = "discord")]
+pub use discord::DiscordPresence;
+
+#[cfg(feature = "discord")]
+mod discord;
+
+// Platforms rate limit updates, changes in between are sent together.
+const MIN_INTERVAL: Duration = Duration::from_secs(4);
+
+// Elapsed or remaining time shown by the platform.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub struct Timestamps {
+    pub start: Option<SystemTime>,
+    pub end: Option<SystemTime>,
+}
+
+// What the player is doing, e.g. details "Forest, level 3" and state "Exploring".
+#[derive(Clone, PartialEq, Eq, Debug, Default)]
+pub struct Activity {
+    pub state: String,
+    pub details: String,
+    pub timestamps: Timestamps,
+}
+
+// Client of a platform showing the activity to the player's friends, e.g. Discord or Steam.
+pub trait PresenceBackend: Send + Sync {
+    // None clears the activity.
+    fn set_activity(&mut self, activity: Option<&Activity>) -> Result<(), String>;
+
+    // Called every frame, e.g. to keep the connection alive.
+    fn update(&mut self) -> Result<(), String> {
+        Ok(())
+    }
+}
+
+// Shows nothing, for platforms without presence.
+pub struct NoPresence;
+
+// Resource through which systems set the activity, sent by the plugin's system.
+pub struct Presence {
+    backend: Box<dyn PresenceBackend>,
+    activity: Option<Activity>,
+    changed: bool,
+    last_sent: Option<Instant>,
+}
+
+// Adds the Presence resource and updates its backend in the update stage. Without a backend,
+// the activity is kept but isn't shown anywhere.
+pub struct PresencePlugin {
+    backend: Cell<Option<Box<dyn PresenceBackend>>>,
+}
+
+impl Timestamps {
+    // Elapsed time since now.
+    pub fn since_now() -> Self {
+        Timestamps {
+            start: Some(SystemTime::now()),
+            end: None,
+        }
+    }
+}
+
+impl PresenceBackend for NoPresence {
+    fn set_activity(&mut self, _: Option<&Activity>) -> Result<(), String> {
+        Ok(())
+    }
+}
+
+impl Presence {
+    pub fn new(backend: Box<dyn PresenceBackend>) -> Self {
+        Presence {
+            backend,
+            activity: None,
+            changed: false,
+            last_sent: None,
+        }
+    }
+
+    pub fn activity(&self) -> Option<&Activity> {
+        self.activity.as_ref()
+    }
+
+    pub fn set_presence(&mut self, state: &str, details: &str, timestamps: Timestamps) {
+        self.set_activity(Some(Activity {
+            state: state.to_string(),
+            details: details.to_string(),
+            timestamps,
+        }));
+    }
+
+    pub fn set_activity(&mut self, activity: Option<Activity>) {
+        if self.activity != activity {
+            self.activity = activity;
+            self.changed = true;
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.set_activity(None);
+    }
+
+    // Sends the activity if it changed, unless the last one was sent too recently.
+    pub fn update(&mut self) {
+        if let Err(e) = self.backend.update() {
+            log::warn!("presence: {e}");
+        }
+
+        let ready = self.last_sent.is_none_or(|t| t.elapsed() >= MIN_INTERVAL);
+        if !self.changed || !ready {
+            return;
+        }
+        self.changed = false;
+        self.last_sent = Some(Instant::now());
+        if let Err(e) = self.backend.set_activity(self.activity.as_ref()) {
+            log::warn!("presence: {e}");
+        }
+    }
+}
+
+impl PresencePlugin {
+    pub fn new(backend: Box<dyn PresenceBackend>) -> Self {
+        PresencePlugin {
+            backend: Cell::new(Some(backend)),
+        }
+    }
+}
+
+impl Default for PresencePlugin {
+    fn default() -> Self {
+        PresencePlugin::new(Box::new(NoPresence))
+    }
+}
+
+impl<T: InputScheme> Plugin<T> for PresencePlugin {
+    fn build(&self, engine: &mut EngineBuilder<T>) {
+        let backend = self.backend.take().unwrap_or_else(|| Box::new(NoPresence));
+        engine.insert_resource(Presence::new(backend)).add_system(
+            System::new("presence", Stage::Update, |context| {
+                context.write::<Presence>().update();
+            })
+            .writes::<Presence>(),
+        );
+    }
+}
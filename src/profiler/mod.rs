@@ -0,0 +1,206 @@
+use std::{
+    cell::RefCell,
+    fmt::Write as _,
+    path::Path,
+    time::{Duration, Instant},
+};
+
+use crate::{
+    graphics::{BitmapFont, DrawParams, GraphicsPipeline},
+    pool, Point,
+};
+
+// Times the rest of the enclosing block under the given name.
+#[macro_export]
+macro_rules! profile_scope {
+    ($name:expr) => {
+        let _profile_scope = $crate::profiler::Scope::new($name);
+    };
+}
+
+// Time spent in all the scopes with the same name and nesting depth during a frame.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Timing {
+    pub name: &'static str,
+    pub depth: usize,
+    pub calls: u32,
+    pub total: Duration,
+}
+
+// Records its duration when dropped, created by profile_scope.
+pub struct Scope {
+    name: &'static str,
+    start: Instant,
+}
+
+struct TraceEvent {
+    name: &'static str,
+    start: Duration,
+    duration: Duration,
+    depth: usize,
+}
+
+// Scopes are recorded per thread, the frames of the main thread are the ones ended by
+// Engine::update.
+#[derive(Default)]
+struct Profiler {
+    depth: usize,
+    frame_start: Option<Instant>,
+    current: Vec<Timing>,
+    last: Vec<Timing>,
+    last_duration: Duration,
+    trace: Option<(Instant, Vec<TraceEvent>)>,
+}
+
+impl Profiler {
+    fn timing(&mut self, name: &'static str, depth: usize) -> &mut Timing {
+        let index = match self
+            .current
+            .iter()
+            .position(|t| t.name == name && t.depth == depth)
+        {
+            Some(index) => index,
+            None => {
+                self.current.push(Timing {
+                    name,
+                    depth,
+                    calls: 0,
+                    total: Duration::ZERO,
+                });
+                self.current.len() - 1
+            }
+        };
+        &mut self.current[index]
+    }
+}
+
+thread_local! {
+    static PROFILER: RefCell<Profiler> = RefCell::new(Profiler::default());
+}
+
+impl Scope {
+    pub fn new(name: &'static str) -> Self {
+        PROFILER.with_borrow_mut(|p| {
+            // Added when starting so that scopes are listed before the ones they contain
+            let depth = p.depth;
+            p.timing(name, depth);
+            p.depth += 1;
+        });
+        Scope {
+            name,
+            start: Instant::now(),
+        }
+    }
+}
+
+impl Drop for Scope {
+    fn drop(&mut self) {
+        let duration = self.start.elapsed();
+        PROFILER.with_borrow_mut(|p| {
+            p.depth -= 1;
+            let depth = p.depth;
+
+            let timing = p.timing(self.name, depth);
+            timing.calls += 1;
+            timing.total += duration;
+
+            if let Some((trace_start, events)) = &mut p.trace {
+                events.push(TraceEvent {
+                    name: self.name,
+                    start: self.start.saturating_duration_since(*trace_start),
+                    duration,
+                    depth,
+                });
+            }
+        });
+    }
+}
+
+// Ends the current frame, its timings are then returned by last_frame.
+pub fn frame() {
+    PROFILER.with_borrow_mut(|p| {
+        let now = Instant::now();
+        p.last_duration = p.frame_start.map_or(Duration::ZERO, |start| now - start);
+        p.frame_start = Some(now);
+        p.last = std::mem::take(&mut p.current);
+    });
+}
+
+// Timings of the last frame, in the order the scopes first started.
+pub fn last_frame() -> Vec<Timing> {
+    PROFILER.with_borrow(|p| p.last.clone())
+}
+
+pub fn last_frame_duration() -> Duration {
+    PROFILER.with_borrow(|p| p.last_duration)
+}
+
+// Records every scope until stop_trace, replacing the trace being recorded if any.
+pub fn start_trace() {
+    PROFILER.with_borrow_mut(|p| p.trace = Some((Instant::now(), Vec::new())));
+}
+
+// Writes the recorded scopes in the Chrome trace event format, which chrome://tracing and
+// Perfetto open.
+pub fn stop_trace<P: AsRef<Path>>(path: P) -> Result<(), String> {
+    let Some((_, events)) = PROFILER.with_borrow_mut(|p| p.trace.take()) else {
+        return Err("no trace is being recorded".to_string());
+    };
+
+    let mut json = String::from("{\"traceEvents\":[");
+    for (i, event) in events.iter().enumerate() {
+        if i > 0 {
+            json.push(',');
+        }
+        let _ = write!(
+            json,
+            "{{\"name\":\"{}\",\"ph\":\"X\",\"ts\":{},\"dur\":{},\"pid\":0,\"tid\":0,\"args\":{{\"depth\":{}}}}}",
+            event.name.replace('\\', "\\\\").replace('"', "\\\""),
+            event.start.as_secs_f64() * 1e6,
+            event.duration.as_secs_f64() * 1e6,
+            event.depth,
+        );
+    }
+    json.push_str("]}");
+
+    std::fs::write(path, json).map_err(|e| e.to_string())
+}
+
+// Draws the last frame's draw calls and timings in screen space, one indented line per scope,
+// followed by the pools' objects in use.
+pub fn draw_overlay(
+    graphics_ppl: &mut GraphicsPipeline,
+    font: &BitmapFont,
+    position: Point,
+    scale: f64,
+) {
+    let mut text = format!(
+        "frame {:.2} ms\n",
+        last_frame_duration().as_secs_f64() * 1e3
+    );
+    let stats = graphics_ppl.stats();
+    let _ = writeln!(
+        text,
+        "draws {} submitted, {} culled, {} batches",
+        stats.submitted, stats.culled, stats.batches
+    );
+    for timing in last_frame() {
+        let _ = writeln!(
+            text,
+            "{}{} {:.2} ms ({})",
+            "  ".repeat(timing.depth + 1),
+            timing.name,
+            timing.total.as_secs_f64() * 1e3,
+            timing.calls
+        );
+    }
+    for (name, stats) in pool::all_stats() {
+        let _ = writeln!(
+            text,
+            "pool {} {}/{} (peak {}, grown {})",
+            name, stats.active, stats.capacity, stats.peak, stats.grown
+        );
+    }
+
+    graphics_ppl.draw_text_screen(font, &text, position, scale, &DrawParams::default());
+}
@@ -0,0 +1,284 @@
+use crate::graphics::Color;
+
+// Built-in glyphs are this many pixels high, their width depending on their label.
+pub(super) const GLYPH_HEIGHT: u32 = 24;
+
+// Size of the font's pixels, glyphs being 5x7 pixels.
+const FONT_SCALE: u32 = 2;
+const ADVANCE: u32 = 6 * FONT_SCALE;
+const TEXT_HEIGHT: u32 = 7 * FONT_SCALE;
+
+const DARK: Color = Color::RGB(55, 55, 65);
+const KEY: Color = Color::RGB(225, 225, 230);
+const KEY_SIDE: Color = Color::RGB(150, 150, 160);
+const KEY_TEXT: Color = Color::RGB(30, 30, 35);
+
+// What a glyph is made of, drawn side by side.
+#[derive(Clone, PartialEq, Debug)]
+pub(super) enum Part {
+    Key(String),
+    // Colored round button with a letter.
+    Face(String, Color),
+    // Round button with a PlayStation symbol.
+    Symbol(Symbol, Color),
+    Pad(String),
+    Separator(char),
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub(super) enum Symbol {
+    Cross,
+    Circle,
+    Square,
+    Triangle,
+}
+
+// RGBA pixels of a glyph or of the atlas.
+pub(super) struct Image {
+    pub width: u32,
+    pub height: u32,
+    pub pixels: Vec<u8>,
+}
+
+impl Image {
+    pub(super) fn new(width: u32, height: u32) -> Self {
+        Image {
+            width,
+            height,
+            pixels: vec![0; (width * height * 4) as usize],
+        }
+    }
+
+    // The parts side by side, 2 pixels apart.
+    pub(super) fn from_parts(parts: &[Part]) -> Self {
+        let widths: Vec<u32> = parts.iter().map(part_width).collect();
+        let width = widths.iter().sum::<u32>() + widths.len().saturating_sub(1) as u32 * 2;
+        let mut image = Image::new(width.max(1), GLYPH_HEIGHT);
+
+        let mut x = 0;
+        for (part, width) in parts.iter().zip(widths) {
+            image.draw_part(part, x as f64, width);
+            x += width + 2;
+        }
+        image
+    }
+
+    pub(super) fn copy(&mut self, image: &Image, x: u32, y: u32) {
+        for row in 0..image.height {
+            let src = (row * image.width * 4) as usize;
+            let dst = (((y + row) * self.width + x) * 4) as usize;
+            let len = (image.width * 4) as usize;
+            self.pixels[dst..dst + len].copy_from_slice(&image.pixels[src..src + len]);
+        }
+    }
+
+    fn draw_part(&mut self, part: &Part, x: f64, width: u32) {
+        let w = width as f64;
+        let h = GLYPH_HEIGHT as f64;
+        let center = (x + w / 2., h / 2.);
+        match part {
+            Part::Key(label) => {
+                // Lighter top over a darker side, like a key cap
+                self.fill(|px, py| rounded_rect(px, py, (x, 0.), (w, h), 4.), KEY_SIDE);
+                self.fill(|px, py| rounded_rect(px, py, (x, 0.), (w, h - 3.), 4.), KEY);
+                self.text(label, center.0, (h - 3.) / 2., KEY_TEXT);
+            }
+            Part::Face(label, color) => {
+                self.fill(|px, py| circle(px, py, center, h / 2.), *color);
+                self.text(label, center.0, center.1, Color::WHITE);
+            }
+            Part::Symbol(symbol, color) => {
+                self.fill(|px, py| circle(px, py, center, h / 2.), DARK);
+                let (cx, cy) = center;
+                match symbol {
+                    Symbol::Cross => self.fill(
+                        |px, py| {
+                            let a = segment(px, py, (cx - 5., cy - 5.), (cx + 5., cy + 5.));
+                            let b = segment(px, py, (cx + 5., cy - 5.), (cx - 5., cy + 5.));
+                            a.min(b) - 1.
+                        },
+                        *color,
+                    ),
+                    Symbol::Circle => {
+                        self.fill(|px, py| circle(px, py, center, 5.5).abs() - 1., *color)
+                    }
+                    Symbol::Square => self.fill(
+                        |px, py| {
+                            rounded_rect(px, py, (cx - 5., cy - 5.), (10., 10.), 0.).abs() - 1.
+                        },
+                        *color,
+                    ),
+                    Symbol::Triangle => self.fill(
+                        |px, py| {
+                            let corners = [(cx, cy - 6.), (cx + 6., cy + 4.5), (cx - 6., cy + 4.5)];
+                            (0..3)
+                                .map(|i| segment(px, py, corners[i], corners[(i + 1) % 3]))
+                                .fold(f64::MAX, f64::min)
+                                - 1.
+                        },
+                        *color,
+                    ),
+                }
+            }
+            Part::Pad(label) => {
+                self.fill(|px, py| rounded_rect(px, py, (x, 0.), (w, h), h / 2.), DARK);
+                self.text(label, center.0, center.1, Color::WHITE);
+            }
+            Part::Separator(c) => self.text(&c.to_string(), center.0, center.1, Color::WHITE),
+        }
+    }
+
+    // Fills where distance is negative, edges being antialiased.
+    fn fill<F: Fn(f64, f64) -> f64>(&mut self, distance: F, color: Color) {
+        for y in 0..self.height {
+            for x in 0..self.width {
+                let coverage = (0.5 - distance(x as f64 + 0.5, y as f64 + 0.5)).clamp(0., 1.);
+                if coverage > 0. {
+                    self.blend(x, y, color, coverage);
+                }
+            }
+        }
+    }
+
+    // Centered on (x, y).
+    fn text(&mut self, text: &str, x: f64, y: f64, color: Color) {
+        let width = text_width(text);
+        let left = (x - width as f64 / 2.).round() as i32;
+        let top = (y - TEXT_HEIGHT as f64 / 2.).round() as i32;
+        for (i, c) in text.chars().enumerate() {
+            let rows = font_glyph(c);
+            for (row, bits) in rows.iter().enumerate() {
+                for column in 0..5 {
+                    if bits & (0x10 >> column) == 0 {
+                        continue;
+                    }
+                    for (dx, dy) in
+                        (0..FONT_SCALE).flat_map(|dx| (0..FONT_SCALE).map(move |dy| (dx, dy)))
+                    {
+                        let px = left + (i as u32 * ADVANCE + column * FONT_SCALE + dx) as i32;
+                        let py = top + (row as u32 * FONT_SCALE + dy) as i32;
+                        if px >= 0
+                            && py >= 0
+                            && (px as u32) < self.width
+                            && (py as u32) < self.height
+                        {
+                            self.blend(px as u32, py as u32, color, 1.);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    fn blend(&mut self, x: u32, y: u32, color: Color, coverage: f64) {
+        let index = ((y * self.width + x) * 4) as usize;
+        let pixel = &mut self.pixels[index..index + 4];
+        let alpha = coverage * color.a as f64 / 255.;
+        let dst_alpha = pixel[3] as f64 / 255.;
+        let out_alpha = alpha + dst_alpha * (1. - alpha);
+        if out_alpha <= 0. {
+            return;
+        }
+        for (channel, src) in pixel.iter_mut().zip([color.r, color.g, color.b]) {
+            let value =
+                (src as f64 * alpha + *channel as f64 * dst_alpha * (1. - alpha)) / out_alpha;
+            *channel = value.round() as u8;
+        }
+        pixel[3] = (out_alpha * 255.).round() as u8;
+    }
+}
+
+fn part_width(part: &Part) -> u32 {
+    match part {
+        Part::Key(label) => (text_width(label) + 12).max(GLYPH_HEIGHT),
+        Part::Pad(label) => (text_width(label) + 14).max(GLYPH_HEIGHT),
+        Part::Face(..) | Part::Symbol(..) => GLYPH_HEIGHT,
+        Part::Separator(_) => ADVANCE,
+    }
+}
+
+fn text_width(text: &str) -> u32 {
+    (text.chars().count() as u32 * ADVANCE).saturating_sub(FONT_SCALE)
+}
+
+fn circle(x: f64, y: f64, center: (f64, f64), radius: f64) -> f64 {
+    ((x - center.0).powi(2) + (y - center.1).powi(2)).sqrt() - radius
+}
+
+fn rounded_rect(x: f64, y: f64, position: (f64, f64), size: (f64, f64), radius: f64) -> f64 {
+    let half = (size.0 / 2., size.1 / 2.);
+    let radius = radius.min(half.0).min(half.1);
+    let dx = (x - position.0 - half.0).abs() - half.0 + radius;
+    let dy = (y - position.1 - half.1).abs() - half.1 + radius;
+    let outside = (dx.max(0.).powi(2) + dy.max(0.).powi(2)).sqrt();
+    outside + dx.max(dy).min(0.) - radius
+}
+
+fn segment(x: f64, y: f64, a: (f64, f64), b: (f64, f64)) -> f64 {
+    let (abx, aby) = (b.0 - a.0, b.1 - a.1);
+    let (apx, apy) = (x - a.0, y - a.1);
+    let t = ((apx * abx + apy * aby) / (abx * abx + aby * aby)).clamp(0., 1.);
+    ((apx - abx * t).powi(2) + (apy - aby * t).powi(2)).sqrt()
+}
+
+// 5x7 pixels, rows from the top with the leftmost pixel in the 5th bit. Lowercase letters are
+// drawn as uppercase ones, unknown chars as '?'.
+fn font_glyph(c: char) -> [u8; 7] {
+    match c.to_ascii_uppercase() {
+        'A' => [0x0e, 0x11, 0x11, 0x1f, 0x11, 0x11, 0x11],
+        'B' => [0x1e, 0x11, 0x11, 0x1e, 0x11, 0x11, 0x1e],
+        'C' => [0x0e, 0x11, 0x10, 0x10, 0x10, 0x11, 0x0e],
+        'D' => [0x1e, 0x11, 0x11, 0x11, 0x11, 0x11, 0x1e],
+        'E' => [0x1f, 0x10, 0x10, 0x1e, 0x10, 0x10, 0x1f],
+        'F' => [0x1f, 0x10, 0x10, 0x1e, 0x10, 0x10, 0x10],
+        'G' => [0x0e, 0x11, 0x10, 0x17, 0x11, 0x11, 0x0f],
+        'H' => [0x11, 0x11, 0x11, 0x1f, 0x11, 0x11, 0x11],
+        'I' => [0x0e, 0x04, 0x04, 0x04, 0x04, 0x04, 0x0e],
+        'J' => [0x07, 0x02, 0x02, 0x02, 0x02, 0x12, 0x0c],
+        'K' => [0x11, 0x12, 0x14, 0x18, 0x14, 0x12, 0x11],
+        'L' => [0x10, 0x10, 0x10, 0x10, 0x10, 0x10, 0x1f],
+        'M' => [0x11, 0x1b, 0x15, 0x15, 0x11, 0x11, 0x11],
+        'N' => [0x11, 0x11, 0x19, 0x15, 0x13, 0x11, 0x11],
+        'O' => [0x0e, 0x11, 0x11, 0x11, 0x11, 0x11, 0x0e],
+        'P' => [0x1e, 0x11, 0x11, 0x1e, 0x10, 0x10, 0x10],
+        'Q' => [0x0e, 0x11, 0x11, 0x11, 0x15, 0x12, 0x0d],
+        'R' => [0x1e, 0x11, 0x11, 0x1e, 0x14, 0x12, 0x11],
+        'S' => [0x0f, 0x10, 0x10, 0x0e, 0x01, 0x01, 0x1e],
+        'T' => [0x1f, 0x04, 0x04, 0x04, 0x04, 0x04, 0x04],
+        'U' => [0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x0e],
+        'V' => [0x11, 0x11, 0x11, 0x11, 0x11, 0x0a, 0x04],
+        'W' => [0x11, 0x11, 0x11, 0x15, 0x15, 0x15, 0x0a],
+        'X' => [0x11, 0x11, 0x0a, 0x04, 0x0a, 0x11, 0x11],
+        'Y' => [0x11, 0x11, 0x11, 0x0a, 0x04, 0x04, 0x04],
+        'Z' => [0x1f, 0x01, 0x02, 0x04, 0x08, 0x10, 0x1f],
+        '0' => [0x0e, 0x11, 0x13, 0x15, 0x19, 0x11, 0x0e],
+        '1' => [0x04, 0x0c, 0x04, 0x04, 0x04, 0x04, 0x0e],
+        '2' => [0x0e, 0x11, 0x01, 0x02, 0x04, 0x08, 0x1f],
+        '3' => [0x1f, 0x02, 0x04, 0x02, 0x01, 0x11, 0x0e],
+        '4' => [0x02, 0x06, 0x0a, 0x12, 0x1f, 0x02, 0x02],
+        '5' => [0x1f, 0x10, 0x1e, 0x01, 0x01, 0x11, 0x0e],
+        '6' => [0x06, 0x08, 0x10, 0x1e, 0x11, 0x11, 0x0e],
+        '7' => [0x1f, 0x01, 0x02, 0x04, 0x08, 0x08, 0x08],
+        '8' => [0x0e, 0x11, 0x11, 0x0e, 0x11, 0x11, 0x0e],
+        '9' => [0x0e, 0x11, 0x11, 0x0f, 0x01, 0x02, 0x0c],
+        ' ' => [0; 7],
+        '+' => [0x00, 0x04, 0x04, 0x1f, 0x04, 0x04, 0x00],
+        '-' => [0x00, 0x00, 0x00, 0x1f, 0x00, 0x00, 0x00],
+        '=' => [0x00, 0x00, 0x1f, 0x00, 0x1f, 0x00, 0x00],
+        '*' => [0x00, 0x04, 0x15, 0x0e, 0x15, 0x04, 0x00],
+        '/' => [0x00, 0x01, 0x02, 0x04, 0x08, 0x10, 0x00],
+        '\\' => [0x00, 0x10, 0x08, 0x04, 0x02, 0x01, 0x00],
+        '.' => [0x00, 0x00, 0x00, 0x00, 0x00, 0x0c, 0x0c],
+        ',' => [0x00, 0x00, 0x00, 0x00, 0x0c, 0x04, 0x08],
+        ';' => [0x00, 0x0c, 0x0c, 0x00, 0x0c, 0x04, 0x08],
+        '\'' => [0x0c, 0x04, 0x08, 0x00, 0x00, 0x00, 0x00],
+        '`' => [0x08, 0x04, 0x02, 0x00, 0x00, 0x00, 0x00],
+        '[' => [0x0e, 0x08, 0x08, 0x08, 0x08, 0x08, 0x0e],
+        ']' => [0x0e, 0x02, 0x02, 0x02, 0x02, 0x02, 0x0e],
+        '↑' => [0x04, 0x0e, 0x15, 0x04, 0x04, 0x04, 0x04],
+        '↓' => [0x04, 0x04, 0x04, 0x04, 0x15, 0x0e, 0x04],
+        '←' => [0x00, 0x04, 0x08, 0x1f, 0x08, 0x04, 0x00],
+        '→' => [0x00, 0x04, 0x02, 0x1f, 0x02, 0x04, 0x00],
+        _ => [0x0e, 0x11, 0x01, 0x02, 0x04, 0x00, 0x04],
+    }
+}
@@ -0,0 +1,239 @@
+use std::collections::HashMap;
+
+use draw::{Image, Part, Symbol, GLYPH_HEIGHT};
+
+use crate::{
+    graphics::{Color, DrawParams, GraphicsPipeline, PixelRect, TextureId},
+    inputs::{
+        control_label, AxisControl, ButtonControl, Control, Device, GamepadButton, InputScheme,
+        InputsPipeline,
+    },
+    Point,
+};
+
+mod draw;
+
+const ATLAS_SIZE: u32 = 512;
+
+// Part of a texture showing a control, e.g. a key cap or a gamepad button.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Glyph {
+    pub texture: TextureId,
+    pub src: PixelRect,
+}
+
+// Glyphs of controls for on-screen prompts, showing the controls of the device the player last
+// used. Built-in glyphs are drawn on demand in an atlas, for keyboards and mice and for Xbox,
+// PlayStation and Nintendo controllers, games being able to replace them with their own art.
+#[derive(Default)]
+pub struct Prompts {
+    atlas: Option<Atlas>,
+    glyphs: HashMap<(Device, Control), Glyph>,
+}
+
+// Glyphs packed in rows of the same height.
+struct Atlas {
+    texture: TextureId,
+    image: Image,
+    cursor: (u32, u32),
+}
+
+impl Prompts {
+    pub fn new() -> Self {
+        Prompts::default()
+    }
+
+    // Replaces the built-in glyph of a control on a device.
+    pub fn set_glyph(&mut self, device: Device, control: Control, glyph: Glyph) {
+        self.glyphs.insert((device, control), glyph);
+    }
+
+    pub fn glyph(
+        &mut self,
+        graphics_ppl: &mut GraphicsPipeline,
+        control: &Control,
+        device: Device,
+    ) -> Result<Glyph, String> {
+        // Keyboards look the same whatever the player's gamepad
+        let device = if device.has(control) {
+            device
+        } else {
+            Device::KeyboardMouse
+        };
+        if let Some(glyph) = self.glyphs.get(&(device, *control)) {
+            return Ok(*glyph);
+        }
+
+        let image = Image::from_parts(&parts(control, device));
+        let atlas = match &mut self.atlas {
+            Some(atlas) => atlas,
+            None => self.atlas.insert(Atlas::new(graphics_ppl)?),
+        };
+        let glyph = atlas.add(graphics_ppl, &image)?;
+        self.glyphs.insert((device, *control), glyph);
+        Ok(glyph)
+    }
+
+    // Glyphs of an input's controls on the device the player last used, in binding order.
+    pub fn input_glyphs<T: InputScheme>(
+        &mut self,
+        graphics_ppl: &mut GraphicsPipeline,
+        inputs_ppl: &InputsPipeline<T>,
+        input_id: &T,
+        player: usize,
+    ) -> Vec<Glyph> {
+        let device = inputs_ppl.last_device(player);
+        inputs_ppl
+            .device_controls(input_id, device)
+            .iter()
+            .filter_map(|c| match self.glyph(graphics_ppl, c, device) {
+                Ok(glyph) => Some(glyph),
+                Err(e) => {
+                    log::warn!("failed to draw the glyph of {c}: {e}");
+                    None
+                }
+            })
+            .collect()
+    }
+
+    // Draws the glyph of the input's first control on the device the player last used, height
+    // pixels high, e.g. between the words of "press [A] to jump". Returns the width drawn, 0
+    // when the input has no control on that device.
+    pub fn draw_input<T: InputScheme>(
+        &mut self,
+        graphics_ppl: &mut GraphicsPipeline,
+        inputs_ppl: &InputsPipeline<T>,
+        input_id: &T,
+        player: usize,
+        position: Point,
+        height: u32,
+    ) -> u32 {
+        let Some(glyph) = self
+            .input_glyphs(graphics_ppl, inputs_ppl, input_id, player)
+            .first()
+            .copied()
+        else {
+            return 0;
+        };
+
+        let width = glyph.src.width() * height / glyph.src.height().max(1);
+        let dest = PixelRect::new(position.x, position.y, width.max(1), height.max(1));
+        graphics_ppl.draw_sprite_screen(
+            glyph.texture,
+            Some(glyph.src),
+            dest,
+            &DrawParams::default(),
+        );
+        width
+    }
+}
+
+impl Atlas {
+    fn new(graphics_ppl: &mut GraphicsPipeline) -> Result<Self, String> {
+        let image = Image::new(ATLAS_SIZE, ATLAS_SIZE);
+        let texture = graphics_ppl.create_texture(image.width, image.height, &image.pixels)?;
+        Ok(Atlas {
+            texture,
+            image,
+            cursor: (0, 0),
+        })
+    }
+
+    fn add(&mut self, graphics_ppl: &mut GraphicsPipeline, image: &Image) -> Result<Glyph, String> {
+        if image.width > ATLAS_SIZE {
+            return Err("the glyph is too large".to_string());
+        }
+        // Glyphs are a pixel apart so that filtering doesn't bleed between them
+        if self.cursor.0 + image.width > ATLAS_SIZE {
+            self.cursor = (0, self.cursor.1 + GLYPH_HEIGHT + 1);
+        }
+        if self.cursor.1 + GLYPH_HEIGHT > ATLAS_SIZE {
+            return Err("the glyph atlas is full".to_string());
+        }
+
+        let (x, y) = self.cursor;
+        self.image.copy(image, x, y);
+        graphics_ppl.update_texture(self.texture, &self.image.pixels)?;
+        self.cursor.0 += image.width + 1;
+        Ok(Glyph {
+            texture: self.texture,
+            src: PixelRect::new(x as i32, y as i32, image.width, image.height),
+        })
+    }
+}
+
+fn parts(control: &Control, device: Device) -> Vec<Part> {
+    let label = |control: Control| control_label(&control, device);
+    let key = |control: Control| Part::Key(arrow(&label(control)));
+    match *control {
+        Control::Button(ButtonControl::Keyboard(_) | ButtonControl::Mouse(_)) => {
+            vec![key(*control)]
+        }
+        Control::Button(ButtonControl::KeyboardChord(first, second)) => vec![
+            key(Control::Button(ButtonControl::Keyboard(first))),
+            Part::Separator('+'),
+            key(Control::Button(ButtonControl::Keyboard(second))),
+        ],
+        Control::Axis(AxisControl::Keyboard(min, max)) => vec![
+            key(Control::Button(ButtonControl::Keyboard(min))),
+            Part::Separator('/'),
+            key(Control::Button(ButtonControl::Keyboard(max))),
+        ],
+        Control::Button(ButtonControl::Gamepad(button)) => vec![button_part(button, device)],
+        Control::Button(ButtonControl::GamepadChord(first, second)) => vec![
+            button_part(first, device),
+            Part::Separator('+'),
+            button_part(second, device),
+        ],
+        Control::Button(ButtonControl::GamepadAxis(..))
+        | Control::Axis(AxisControl::Gamepad(_)) => {
+            vec![Part::Pad(label(*control))]
+        }
+        Control::Axis(AxisControl::GamepadButton(button, _)) => vec![button_part(button, device)],
+    }
+}
+
+fn button_part(button: GamepadButton, device: Device) -> Part {
+    let label = control_label(&Control::Button(ButtonControl::Gamepad(button)), device);
+    let face = matches!(
+        button,
+        GamepadButton::A | GamepadButton::B | GamepadButton::X | GamepadButton::Y
+    );
+    if !face {
+        return Part::Pad(arrow(&label));
+    }
+
+    match device {
+        Device::PlayStation => {
+            let (symbol, color) = match button {
+                GamepadButton::A => (Symbol::Cross, Color::RGB(125, 180, 235)),
+                GamepadButton::B => (Symbol::Circle, Color::RGB(255, 105, 105)),
+                GamepadButton::X => (Symbol::Square, Color::RGB(215, 140, 215)),
+                _ => (Symbol::Triangle, Color::RGB(65, 225, 160)),
+            };
+            Part::Symbol(symbol, color)
+        }
+        Device::Nintendo => Part::Face(label, Color::RGB(55, 55, 65)),
+        _ => {
+            let color = match button {
+                GamepadButton::A => Color::RGB(95, 175, 65),
+                GamepadButton::B => Color::RGB(225, 70, 55),
+                GamepadButton::X => Color::RGB(55, 120, 225),
+                _ => Color::RGB(235, 185, 45),
+            };
+            Part::Face(label, color)
+        }
+    }
+}
+
+// Directions are drawn as arrows.
+fn arrow(label: &str) -> String {
+    match label {
+        "Up" => "↑",
+        "Down" => "↓",
+        "Left" => "←",
+        "Right" => "→",
+        _ => label,
+    }
+    .to_string()
+}
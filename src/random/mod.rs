@@ -0,0 +1,298 @@
+use std::{
+    collections::HashMap,
+    ops::{Range, RangeInclusive},
+};
+
+use crate::{
+    desync::{StateHash, StateHasher},
+    Vec2,
+};
+
+// xoshiro256** generator, seeded through splitmix64. Streams with the same seed always
+// produce the same numbers, on every platform.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Rng {
+    state: [u64; 4],
+}
+
+// Engine wide randomness. Named streams are derived from the seed and their name only, so
+// drawing from one of them doesn't change what the others produce.
+#[derive(Clone, Debug)]
+pub struct Random {
+    seed: u64,
+    global: Rng,
+    streams: HashMap<String, Rng>,
+}
+
+pub trait RandomRange {
+    type Output;
+
+    fn sample(self, rng: &mut Rng) -> Self::Output;
+}
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        let mut seed = seed;
+        let mut next = || {
+            seed = seed.wrapping_add(0x9e3779b97f4a7c15);
+            let mut z = seed;
+            z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+            z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+            z ^ (z >> 31)
+        };
+
+        Rng {
+            state: [next(), next(), next(), next()],
+        }
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        let result = self.state[1].wrapping_mul(5).rotate_left(7).wrapping_mul(9);
+        let t = self.state[1] << 17;
+
+        self.state[2] ^= self.state[0];
+        self.state[3] ^= self.state[1];
+        self.state[1] ^= self.state[2];
+        self.state[0] ^= self.state[3];
+        self.state[2] ^= t;
+        self.state[3] = self.state[3].rotate_left(45);
+
+        result
+    }
+
+    // Between 0 included and 1 excluded.
+    pub fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    pub fn range<R: RandomRange>(&mut self, range: R) -> R::Output {
+        range.sample(self)
+    }
+
+    pub fn chance(&mut self, probability: f64) -> bool {
+        self.next_f64() < probability
+    }
+
+    pub fn pick<'a, T>(&mut self, items: &'a [T]) -> Option<&'a T> {
+        if items.is_empty() {
+            return None;
+        }
+        items.get(self.range(0..items.len()))
+    }
+
+    pub fn shuffle<T>(&mut self, items: &mut [T]) {
+        for i in (1..items.len()).rev() {
+            items.swap(i, self.range(0..=i));
+        }
+    }
+
+    // Random direction of length 1.
+    pub fn unit_vec2(&mut self) -> Vec2 {
+        Vec2::from_angle(self.next_f64() * std::f64::consts::TAU)
+    }
+
+    // Unbiased integer between 0 included and bound excluded.
+    fn below(&mut self, bound: u64) -> u64 {
+        if bound == 0 {
+            return self.next_u64();
+        }
+
+        let threshold = bound.wrapping_neg() % bound;
+        loop {
+            let value = self.next_u64();
+            if value >= threshold {
+                return value % bound;
+            }
+        }
+    }
+}
+
+impl Random {
+    pub fn new(seed: u64) -> Self {
+        Random {
+            seed,
+            global: Rng::new(seed),
+            streams: HashMap::new(),
+        }
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    // Restarts the global stream and every named one from the new seed.
+    pub fn reseed(&mut self, seed: u64) {
+        *self = Random::new(seed);
+    }
+
+    pub fn global(&mut self) -> &mut Rng {
+        &mut self.global
+    }
+
+    pub fn stream(&mut self, name: &str) -> &mut Rng {
+        let seed = self.seed;
+        self.streams
+            .entry(name.to_string())
+            .or_insert_with(|| Rng::new(seed ^ fnv1a(name)))
+    }
+}
+
+impl Default for Random {
+    // Seeded from the current time, which is logged to reproduce the run with Random::new.
+    fn default() -> Self {
+        let seed = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_nanos() as u64)
+            .unwrap_or_default();
+        log::info!("random seed {seed}");
+        Random::new(seed)
+    }
+}
+
+impl StateHash for Rng {
+    fn hash_state(&self, hasher: &mut StateHasher) {
+        hasher.write(self.state.as_slice());
+    }
+}
+
+// Named streams are hashed by name, not in the map's order which differs between runs.
+impl StateHash for Random {
+    fn hash_state(&self, hasher: &mut StateHasher) {
+        hasher.write(&self.seed);
+        hasher.write(&self.global);
+        let mut streams: Vec<(&String, &Rng)> = self.streams.iter().collect();
+        streams.sort_unstable_by_key(|(name, _)| *name);
+        hasher.write(&streams.len());
+        for (name, rng) in streams {
+            hasher.write(name);
+            hasher.write(rng);
+        }
+    }
+}
+
+impl RandomRange for Range<f64> {
+    type Output = f64;
+
+    fn sample(self, rng: &mut Rng) -> f64 {
+        self.start + rng.next_f64() * (self.end - self.start)
+    }
+}
+
+impl RandomRange for Range<i32> {
+    type Output = i32;
+
+    // Returns start if the range is empty.
+    fn sample(self, rng: &mut Rng) -> i32 {
+        if self.end <= self.start {
+            return self.start;
+        }
+        (self.start as i64 + rng.below((self.end as i64 - self.start as i64) as u64) as i64) as i32
+    }
+}
+
+impl RandomRange for RangeInclusive<i32> {
+    type Output = i32;
+
+    fn sample(self, rng: &mut Rng) -> i32 {
+        let (start, end) = self.into_inner();
+        if end <= start {
+            return start;
+        }
+        (start as i64 + rng.below((end as i64 - start as i64 + 1) as u64) as i64) as i32
+    }
+}
+
+impl RandomRange for Range<usize> {
+    type Output = usize;
+
+    fn sample(self, rng: &mut Rng) -> usize {
+        if self.end <= self.start {
+            return self.start;
+        }
+        self.start + rng.below((self.end - self.start) as u64) as usize
+    }
+}
+
+impl RandomRange for RangeInclusive<usize> {
+    type Output = usize;
+
+    fn sample(self, rng: &mut Rng) -> usize {
+        let (start, end) = self.into_inner();
+        if end <= start {
+            return start;
+        }
+        start + rng.below(((end - start) as u64).wrapping_add(1)) as usize
+    }
+}
+
+// Stable across platforms and compiler versions, unlike the std hasher.
+fn fnv1a(text: &str) -> u64 {
+    text.bytes().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Reference values computed from the published splitmix64 and xoshiro256** algorithms.
+    #[test]
+    fn streams_produce_the_same_values_for_a_seed() {
+        let mut rng = Rng::new(0);
+        let values = [rng.next_u64(), rng.next_u64(), rng.next_u64()];
+        assert_eq!(
+            values,
+            [0x99ec5f36cb75f2b4, 0xbf6e1f784956452a, 0x1a5f849d4933e6e0]
+        );
+
+        let mut rng = Rng::new(42);
+        assert_eq!(rng.next_u64(), 0x15780b2e0c2ec716);
+        assert_eq!(rng.next_u64(), 0x6104d9866d113a7e);
+
+        let mut rng = Rng::new(7);
+        assert_eq!(rng.next_f64(), 0.7005764821796896);
+        assert_eq!(rng.next_f64(), 0.2787512294737843);
+
+        let mut rng = Rng::new(7);
+        let draws: Vec<i32> = (0..8).map(|_| rng.range(0..10)).collect();
+        assert_eq!(draws, [4, 4, 8, 4, 4, 1, 6, 6]);
+    }
+
+    #[test]
+    fn named_streams_only_depend_on_the_seed_and_name() {
+        let mut random = Random::new(42);
+        random.global().next_u64();
+        random.stream("ai").next_u64();
+        assert_eq!(random.stream("loot").next_u64(), 0x8b0a1e19eadef861);
+        assert_eq!(random.stream("loot").next_u64(), 0x339eb5f5b83fb31a);
+
+        random.reseed(42);
+        assert_eq!(random.stream("loot").next_u64(), 0x8b0a1e19eadef861);
+        assert_eq!(random.global().next_u64(), 0x15780b2e0c2ec716);
+    }
+
+    #[test]
+    fn ranges_stay_within_their_bounds() {
+        let mut rng = Rng::new(3);
+        for _ in 0..1000 {
+            assert!((-3..=3).contains(&rng.range(-3..=3)));
+            assert!((i32::MIN..i32::MAX).contains(&rng.range(i32::MIN..i32::MAX)));
+            let x = rng.range(-1.5..2.);
+            assert!((-1.5..2.).contains(&x));
+            assert!((rng.unit_vec2().length() - 1.).abs() < 1e-9);
+        }
+        let (start, end) = (5, 2);
+        assert_eq!(rng.range(start..end), 5);
+        assert_eq!(rng.range(start..=end), 5);
+        // The whole range's length doesn't fit
+        rng.range(0..=usize::MAX);
+        assert_eq!(rng.pick::<u8>(&[]), None);
+
+        let mut items: Vec<u32> = (0..20).collect();
+        rng.shuffle(&mut items);
+        assert_ne!(items, (0..20).collect::<Vec<_>>());
+        items.sort_unstable();
+        assert_eq!(items, (0..20).collect::<Vec<_>>());
+    }
+}
@@ -0,0 +1,168 @@
+use std::collections::BTreeMap;
+
+use crate::{
+    config::Value,
+    graphics::{Color, ColorExt},
+    Vec2,
+};
+
+// Implements Reflect for a struct from the fields to expose, which have to implement Property:
+//
+// reflect!(Enemy { speed, tint, size });
+#[macro_export]
+macro_rules! reflect {
+    ($type:ty { $($field:ident),* $(,)? }) => {
+        impl $crate::reflect::Reflect for $type {
+            fn fields(&self) -> &'static [&'static str] {
+                &[$(stringify!($field)),*]
+            }
+
+            fn get(&self, field: &str) -> Option<$crate::config::Value> {
+                match field {
+                    $(stringify!($field) => {
+                        Some($crate::reflect::Property::to_value(&self.$field))
+                    })*
+                    _ => None,
+                }
+            }
+
+            fn set(&mut self, field: &str, value: &$crate::config::Value) -> Result<(), String> {
+                match field {
+                    $(stringify!($field) => {
+                        self.$field = $crate::reflect::Property::from_value(value)
+                            .ok_or_else(|| format!("invalid value {value} for {field}"))?;
+                        Ok(())
+                    })*
+                    _ => Err(format!("unknown field {field}")),
+                }
+            }
+        }
+    };
+}
+
+// Field type converted from and to the values of settings files, so that reflected values are
+// stored the same way prefab properties and settings are.
+pub trait Property: Sized {
+    fn to_value(&self) -> Value;
+    fn from_value(value: &Value) -> Option<Self>;
+}
+
+// Named fields read and written at runtime, e.g. by the inspector or to save an object's state
+// as prefab overrides. Usually implemented with reflect!.
+pub trait Reflect {
+    fn fields(&self) -> &'static [&'static str];
+    fn get(&self, field: &str) -> Option<Value>;
+    fn set(&mut self, field: &str, value: &Value) -> Result<(), String>;
+
+    fn values(&self) -> BTreeMap<String, Value> {
+        self.fields()
+            .iter()
+            .filter_map(|field| Some((field.to_string(), self.get(field)?)))
+            .collect()
+    }
+
+    // Fields missing from the values are left as they are, unknown ones are errors.
+    fn apply(&mut self, values: &BTreeMap<String, Value>) -> Result<(), String> {
+        for (field, value) in values {
+            self.set(field, value)?;
+        }
+        Ok(())
+    }
+}
+
+impl Property for bool {
+    fn to_value(&self) -> Value {
+        Value::Bool(*self)
+    }
+
+    fn from_value(value: &Value) -> Option<Self> {
+        value.as_bool()
+    }
+}
+
+impl Property for f64 {
+    fn to_value(&self) -> Value {
+        Value::Float(*self)
+    }
+
+    fn from_value(value: &Value) -> Option<Self> {
+        value.as_f64()
+    }
+}
+
+impl Property for f32 {
+    fn to_value(&self) -> Value {
+        Value::Float(*self as f64)
+    }
+
+    fn from_value(value: &Value) -> Option<Self> {
+        value.as_f64().map(|x| x as f32)
+    }
+}
+
+macro_rules! integer_property {
+    ($($type:ty),*) => {
+        $(impl Property for $type {
+            fn to_value(&self) -> Value {
+                Value::Integer(*self as i64)
+            }
+
+            fn from_value(value: &Value) -> Option<Self> {
+                value.as_i64()?.try_into().ok()
+            }
+        })*
+    };
+}
+
+integer_property!(i8, i16, i32, i64, u8, u16, u32, usize);
+
+impl Property for String {
+    fn to_value(&self) -> Value {
+        Value::Text(self.clone())
+    }
+
+    fn from_value(value: &Value) -> Option<Self> {
+        value.as_str().map(str::to_string)
+    }
+}
+
+impl Property for Vec2 {
+    fn to_value(&self) -> Value {
+        (*self).into()
+    }
+
+    fn from_value(value: &Value) -> Option<Self> {
+        match value.as_array()? {
+            [x, y] => Some(Vec2::new(x.as_f64()?, y.as_f64()?)),
+            _ => None,
+        }
+    }
+}
+
+// As a hex string, like prefab colors.
+impl Property for Color {
+    fn to_value(&self) -> Value {
+        Value::Text(self.to_hex())
+    }
+
+    fn from_value(value: &Value) -> Option<Self> {
+        Color::from_hex(value.as_str()?).ok()
+    }
+}
+
+impl<P: Property> Property for Option<P> {
+    // None is written as an empty array, which no other property reads.
+    fn to_value(&self) -> Value {
+        match self {
+            Some(value) => value.to_value(),
+            None => Value::Array(Vec::new()),
+        }
+    }
+
+    fn from_value(value: &Value) -> Option<Self> {
+        match value.as_array() {
+            Some([]) => Some(None),
+            _ => P::from_value(value).map(Some),
+        }
+    }
+}
@@ -0,0 +1,222 @@
+use std::{collections::BTreeMap, path::Path};
+
+use crate::{
+    config::{parse_toml, write_toml, Document, Value},
+    graphics::{TileId, Tilemap},
+    prefab::{Instance, Overrides, Prefabs},
+    Vec2,
+};
+
+const PREFAB_KEY: &str = "prefab";
+const POSITION_KEY: &str = "position";
+const SCALE_KEY: &str = "scale";
+const TILES_KEY: &str = "tiles";
+
+// Level made of prefab instances and tiles, written with the settings' TOML subset. Each table
+// is an object named after it, the tiles being rows of tile ids outside of any table, -1 for
+// empty cells:
+//
+// tiles = [[0, 1, 1], [-1, 4, 4]]
+//
+// [chest_1]
+// prefab = "chest"
+// position = [2.0, 3.5]
+// scale = [1.0, 1.0]
+// gold = 20
+//
+// Keys other than prefab, position and scale override the prefab's properties.
+#[derive(Clone, PartialEq, Debug, Default)]
+pub struct Scene {
+    pub objects: BTreeMap<String, SceneObject>,
+    // Rows of the tilemap, top to bottom.
+    pub tiles: Vec<Vec<Option<TileId>>>,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct SceneObject {
+    pub prefab: String,
+    pub position: Vec2,
+    pub scale: Vec2,
+    pub overrides: Overrides,
+}
+
+impl SceneObject {
+    pub fn new(prefab: &str, position: Vec2) -> Self {
+        SceneObject {
+            prefab: prefab.to_string(),
+            position,
+            scale: Vec2::ONE,
+            overrides: Overrides::new(),
+        }
+    }
+
+    // Instance of the prefab with the overrides, position and scale being set as properties.
+    pub fn instantiate(&self, prefabs: &Prefabs) -> Result<Instance, String> {
+        let mut instance = prefabs.instantiate(&self.prefab, &self.overrides)?;
+        instance
+            .properties
+            .insert(POSITION_KEY.to_string(), self.position.into());
+        instance
+            .properties
+            .insert(SCALE_KEY.to_string(), self.scale.into());
+        Ok(instance)
+    }
+}
+
+impl Scene {
+    pub fn new() -> Self {
+        Scene::default()
+    }
+
+    pub fn parse(source: &str) -> Result<Self, String> {
+        let document = parse_toml(source)?;
+        let mut scene = Scene::new();
+
+        for (name, values) in &document {
+            if name.is_empty() {
+                scene.tiles = match values.get(TILES_KEY) {
+                    Some(tiles) => parse_tiles(tiles).ok_or("invalid tiles")?,
+                    None => Vec::new(),
+                };
+                continue;
+            }
+
+            let error = |key: &str| format!("invalid {key} of \"{name}\"");
+            let prefab = values
+                .get(PREFAB_KEY)
+                .and_then(Value::as_str)
+                .ok_or_else(|| error(PREFAB_KEY))?;
+            let vec2 = |key: &str, default: Vec2| match values.get(key) {
+                Some(value) => parse_vec2(value).ok_or_else(|| error(key)),
+                None => Ok(default),
+            };
+
+            let object = SceneObject {
+                prefab: prefab.to_string(),
+                position: vec2(POSITION_KEY, Vec2::ZERO)?,
+                scale: vec2(SCALE_KEY, Vec2::ONE)?,
+                overrides: values
+                    .iter()
+                    .filter(|(key, _)| {
+                        ![PREFAB_KEY, POSITION_KEY, SCALE_KEY].contains(&key.as_str())
+                    })
+                    .map(|(key, value)| (key.clone(), value.clone()))
+                    .collect(),
+            };
+            scene.objects.insert(name.clone(), object);
+        }
+        Ok(scene)
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, String> {
+        let path = path.as_ref();
+        let source = std::fs::read_to_string(path).map_err(|e| e.to_string())?;
+        Scene::parse(&source).map_err(|e| format!("{}: {}", path.display(), e))
+    }
+
+    pub fn to_toml(&self) -> String {
+        let mut document = Document::new();
+        if !self.tiles.is_empty() {
+            let rows = self
+                .tiles
+                .iter()
+                .map(|row| {
+                    Value::Array(
+                        row.iter()
+                            .map(|tile| Value::Integer(tile.map_or(-1, i64::from)))
+                            .collect(),
+                    )
+                })
+                .collect();
+            document
+                .entry(String::new())
+                .or_default()
+                .insert(TILES_KEY.to_string(), Value::Array(rows));
+        }
+
+        for (name, object) in &self.objects {
+            let mut values = object.overrides.clone();
+            values.insert(PREFAB_KEY.to_string(), object.prefab.as_str().into());
+            values.insert(POSITION_KEY.to_string(), object.position.into());
+            values.insert(SCALE_KEY.to_string(), object.scale.into());
+            document.insert(name.clone(), values);
+        }
+        write_toml(&document)
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), String> {
+        std::fs::write(path, self.to_toml()).map_err(|e| e.to_string())
+    }
+
+    // Name for a new object of the prefab, e.g. "chest_3".
+    pub fn unused_name(&self, prefab: &str) -> String {
+        (1..)
+            .map(|i| format!("{prefab}_{i}"))
+            .find(|name| !self.objects.contains_key(name))
+            .unwrap_or_default()
+    }
+
+    // The object's instances by name, objects that fail to instantiate being skipped.
+    pub fn instantiate(&self, prefabs: &Prefabs) -> Vec<(String, Instance)> {
+        self.objects
+            .iter()
+            .filter_map(|(name, object)| match object.instantiate(prefabs) {
+                Ok(instance) => Some((name.clone(), instance)),
+                Err(e) => {
+                    log::warn!("failed to instantiate scene object {name}: {e}");
+                    None
+                }
+            })
+            .collect()
+    }
+
+    // Sets the tiles of the tilemap, the cells outside of the scene's tiles being emptied.
+    pub fn apply_tiles(&self, tilemap: &mut Tilemap) {
+        let (width, height) = tilemap.size();
+        for y in 0..height {
+            for x in 0..width {
+                let tile = self
+                    .tiles
+                    .get(y as usize)
+                    .and_then(|row| row.get(x as usize))
+                    .copied()
+                    .flatten();
+                tilemap.set_tile(x, y, tile);
+            }
+        }
+    }
+
+    pub fn capture_tiles(&mut self, tilemap: &Tilemap) {
+        let (width, height) = tilemap.size();
+        self.tiles = (0..height)
+            .map(|y| (0..width).map(|x| tilemap.tile(x, y)).collect())
+            .collect();
+    }
+}
+
+fn parse_vec2(value: &Value) -> Option<Vec2> {
+    match value.as_array()? {
+        [x, y] => Some(Vec2::new(x.as_f64()?, y.as_f64()?)),
+        _ => None,
+    }
+}
+
+fn parse_tiles(value: &Value) -> Option<Vec<Vec<Option<TileId>>>> {
+    value
+        .as_array()?
+        .iter()
+        .map(|row| {
+            row.as_array()?
+                .iter()
+                .map(|tile| {
+                    let tile = tile.as_i64()?;
+                    if tile < 0 {
+                        Some(None)
+                    } else {
+                        u32::try_from(tile).ok().map(Some)
+                    }
+                })
+                .collect()
+        })
+        .collect()
+}
@@ -0,0 +1,312 @@
+use std::any::{type_name, Any, TypeId};
+
+use crate::profile_scope;
+
+pub use resources::{Ref, RefMut, Resources};
+
+mod resources;
+
+// Stages run in this order every frame, fixed update as many times as fit in the frame.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Debug)]
+pub enum Stage {
+    Input,
+    FixedUpdate,
+    Update,
+    Render,
+}
+
+// Resource updated by the schedule before every stage, delta being the timestep during fixed
+// updates.
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
+pub struct Time {
+    pub delta: f64,
+    pub elapsed: f64,
+}
+
+pub type Condition = Box<dyn Fn(&Resources) -> bool + Send + Sync>;
+
+type Exclusive<C> = Box<dyn FnMut(&mut C, &mut Resources)>;
+
+// Function run on the resources it declared access to. Systems of a stage that don't write what
+// the others use run at the same time with the parallel feature.
+pub struct System {
+    pub name: &'static str,
+    pub stage: Stage,
+    reads: Vec<(TypeId, &'static str)>,
+    writes: Vec<(TypeId, &'static str)>,
+    condition: Option<Condition>,
+    run: Box<dyn FnMut(&SystemContext) + Send>,
+}
+
+// Resources a system can use while running, only the declared ones.
+pub struct SystemContext<'a> {
+    resources: &'a Resources,
+    system: &'a str,
+    reads: &'a [(TypeId, &'static str)],
+    writes: &'a [(TypeId, &'static str)],
+}
+
+enum Entry<C> {
+    System(System),
+    // Runs alone on the main thread, with the whole game context, e.g. to draw.
+    Exclusive {
+        name: &'static str,
+        stage: Stage,
+        condition: Option<Condition>,
+        run: Exclusive<C>,
+    },
+}
+
+// Runs systems stage after stage, in the order they were added except for those running in
+// parallel. C is the context of exclusive systems, usually the Engine.
+pub struct Schedule<C> {
+    // Duration of a fixed update.
+    pub timestep: f64,
+    // Fixed updates run per frame at most, the time left being dropped, so that a slow frame
+    // doesn't make the next ones slower.
+    pub max_fixed_updates: u32,
+    entries: Vec<Entry<C>>,
+    accumulator: f64,
+    elapsed: f64,
+}
+
+impl Stage {
+    pub const ALL: [Stage; 4] = [
+        Stage::Input,
+        Stage::FixedUpdate,
+        Stage::Update,
+        Stage::Render,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Stage::Input => "input",
+            Stage::FixedUpdate => "fixed update",
+            Stage::Update => "update",
+            Stage::Render => "render",
+        }
+    }
+}
+
+impl System {
+    pub fn new<F: FnMut(&SystemContext) + Send + 'static>(
+        name: &'static str,
+        stage: Stage,
+        run: F,
+    ) -> Self {
+        System {
+            name,
+            stage,
+            reads: Vec::new(),
+            writes: Vec::new(),
+            condition: None,
+            run: Box::new(run),
+        }
+    }
+
+    pub fn reads<T: Any + Send + Sync>(mut self) -> Self {
+        self.reads.push((TypeId::of::<T>(), type_name::<T>()));
+        self
+    }
+
+    pub fn writes<T: Any + Send + Sync>(mut self) -> Self {
+        self.writes.push((TypeId::of::<T>(), type_name::<T>()));
+        self
+    }
+
+    // The system is skipped when the condition is false, e.g. while the game is paused.
+    pub fn run_if<F: Fn(&Resources) -> bool + Send + Sync + 'static>(
+        mut self,
+        condition: F,
+    ) -> Self {
+        self.condition = Some(Box::new(condition));
+        self
+    }
+
+    fn conflicts(&self, other: &System) -> bool {
+        let writes = |a: &System, b: &System| {
+            a.writes
+                .iter()
+                .any(|(w, _)| b.reads.iter().chain(&b.writes).any(|(id, _)| id == w))
+        };
+        writes(self, other) || writes(other, self)
+    }
+
+    fn run(&mut self, resources: &Resources) {
+        let context = SystemContext {
+            resources,
+            system: self.name,
+            reads: &self.reads,
+            writes: &self.writes,
+        };
+        (self.run)(&context);
+    }
+}
+
+impl SystemContext<'_> {
+    // Panics if the system didn't declare reading or writing T.
+    pub fn read<T: Any + Send + Sync>(&self) -> Ref<'_, T> {
+        let id = TypeId::of::<T>();
+        if !self.reads.iter().chain(self.writes).any(|(r, _)| *r == id) {
+            panic!(
+                "system {} reads undeclared {}",
+                self.system,
+                type_name::<T>()
+            );
+        }
+        self.resources.read()
+    }
+
+    // Panics if the system didn't declare writing T.
+    pub fn write<T: Any + Send + Sync>(&self) -> RefMut<'_, T> {
+        let id = TypeId::of::<T>();
+        if !self.writes.iter().any(|(w, _)| *w == id) {
+            panic!(
+                "system {} writes undeclared {}",
+                self.system,
+                type_name::<T>()
+            );
+        }
+        self.resources.write()
+    }
+}
+
+impl<C> Entry<C> {
+    fn stage(&self) -> Stage {
+        match self {
+            Entry::System(system) => system.stage,
+            Entry::Exclusive { stage, .. } => *stage,
+        }
+    }
+
+    fn should_run(&self, resources: &Resources) -> bool {
+        let condition = match self {
+            Entry::System(system) => &system.condition,
+            Entry::Exclusive { condition, .. } => condition,
+        };
+        condition.as_ref().is_none_or(|c| c(resources))
+    }
+}
+
+impl<C> Schedule<C> {
+    pub fn new() -> Self {
+        Schedule {
+            timestep: 1. / 60.,
+            max_fixed_updates: 5,
+            entries: Vec::new(),
+            accumulator: 0.,
+            elapsed: 0.,
+        }
+    }
+
+    pub fn add(&mut self, system: System) {
+        self.entries.push(Entry::System(system));
+    }
+
+    // condition is the same as System::run_if's.
+    pub fn add_exclusive<F: FnMut(&mut C, &mut Resources) + 'static>(
+        &mut self,
+        name: &'static str,
+        stage: Stage,
+        condition: Option<Condition>,
+        run: F,
+    ) {
+        self.entries.push(Entry::Exclusive {
+            name,
+            stage,
+            condition,
+            run: Box::new(run),
+        });
+    }
+
+    // Runs every stage once, except fixed update, dt being the duration of the frame.
+    pub fn run(&mut self, resources: &mut Resources, context: &mut C, dt: f64) {
+        self.elapsed += dt;
+        self.accumulator += dt;
+        for stage in Stage::ALL {
+            if stage != Stage::FixedUpdate {
+                self.run_stage(stage, resources, context, dt);
+                continue;
+            }
+
+            let mut updates = 0;
+            while self.accumulator >= self.timestep {
+                if updates == self.max_fixed_updates {
+                    self.accumulator = 0.;
+                    break;
+                }
+                self.run_stage(stage, resources, context, self.timestep);
+                self.accumulator -= self.timestep;
+                updates += 1;
+            }
+        }
+    }
+
+    // Part of a fixed update left, to interpolate what's drawn.
+    pub fn alpha(&self) -> f64 {
+        (self.accumulator / self.timestep).clamp(0., 1.)
+    }
+
+    fn run_stage(&mut self, stage: Stage, resources: &mut Resources, context: &mut C, delta: f64) {
+        profile_scope!(stage.name());
+        resources.insert(Time {
+            delta,
+            elapsed: self.elapsed,
+        });
+
+        // Consecutive systems not conflicting with each other are run together, the others
+        // after the ones added before them
+        let mut batch: Vec<&mut System> = Vec::new();
+        for entry in self.entries.iter_mut().filter(|e| e.stage() == stage) {
+            if !entry.should_run(resources) {
+                continue;
+            }
+
+            match entry {
+                Entry::System(system) => {
+                    if batch.iter().any(|other| other.conflicts(system)) {
+                        run_batch(&mut batch, resources);
+                    }
+                    batch.push(system);
+                }
+                Entry::Exclusive { name, run, .. } => {
+                    run_batch(&mut batch, resources);
+                    profile_scope!(name);
+                    run(context, resources);
+                }
+            }
+        }
+        run_batch(&mut batch, resources);
+    }
+}
+
+impl<C> Default for Schedule<C> {
+    fn default() -> Self {
+        Schedule::new()
+    }
+}
+
+#[cfg(not(feature = "parallel"))]
+fn run_batch(batch: &mut Vec<&mut System>, resources: &Resources) {
+    for system in batch.drain(..) {
+        profile_scope!(system.name);
+        system.run(resources);
+    }
+}
+
+// The first system runs on the calling thread, the others on their own.
+#[cfg(feature = "parallel")]
+fn run_batch(batch: &mut Vec<&mut System>, resources: &Resources) {
+    let mut systems = batch.drain(..);
+    let Some(first) = systems.next() else {
+        return;
+    };
+
+    std::thread::scope(|scope| {
+        for system in systems {
+            scope.spawn(move || system.run(resources));
+        }
+        profile_scope!(first.name);
+        first.run(resources);
+    });
+}
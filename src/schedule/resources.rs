@@ -0,0 +1,113 @@
+use std::{
+    any::{type_name, Any, TypeId},
+    collections::HashMap,
+    marker::PhantomData,
+    ops::{Deref, DerefMut},
+    sync::{RwLock, RwLockReadGuard, RwLockWriteGuard},
+};
+
+type Resource = Box<dyn Any + Send + Sync>;
+
+// Game state shared by systems, one value per type.
+#[derive(Default)]
+pub struct Resources {
+    values: HashMap<TypeId, RwLock<Resource>>,
+}
+
+pub struct Ref<'a, T> {
+    guard: RwLockReadGuard<'a, Resource>,
+    marker: PhantomData<T>,
+}
+
+pub struct RefMut<'a, T> {
+    guard: RwLockWriteGuard<'a, Resource>,
+    marker: PhantomData<T>,
+}
+
+impl Resources {
+    pub fn new() -> Self {
+        Resources::default()
+    }
+
+    // Replaces the previous value of the same type.
+    pub fn insert<T: Any + Send + Sync>(&mut self, value: T) {
+        self.values
+            .insert(TypeId::of::<T>(), RwLock::new(Box::new(value)));
+    }
+
+    pub fn remove<T: Any + Send + Sync>(&mut self) -> Option<T> {
+        let value = self.values.remove(&TypeId::of::<T>())?;
+        let value = value.into_inner().unwrap_or_else(|e| e.into_inner());
+        value.downcast().ok().map(|v| *v)
+    }
+
+    pub fn contains<T: Any + Send + Sync>(&self) -> bool {
+        self.values.contains_key(&TypeId::of::<T>())
+    }
+
+    pub fn get_mut<T: Any + Send + Sync>(&mut self) -> Option<&mut T> {
+        let value = self.values.get_mut(&TypeId::of::<T>())?;
+        value
+            .get_mut()
+            .unwrap_or_else(|e| e.into_inner())
+            .downcast_mut()
+    }
+
+    // Panics if the value is missing, or written by another thread.
+    pub fn read<T: Any + Send + Sync>(&self) -> Ref<'_, T> {
+        let guard = self
+            .lock(TypeId::of::<T>(), type_name::<T>())
+            .try_read()
+            .unwrap_or_else(|_| panic!("resource {} is being written", type_name::<T>()));
+        Ref {
+            guard,
+            marker: PhantomData,
+        }
+    }
+
+    // Panics if the value is missing, or used by another thread.
+    pub fn write<T: Any + Send + Sync>(&self) -> RefMut<'_, T> {
+        let guard = self
+            .lock(TypeId::of::<T>(), type_name::<T>())
+            .try_write()
+            .unwrap_or_else(|_| panic!("resource {} is being used", type_name::<T>()));
+        RefMut {
+            guard,
+            marker: PhantomData,
+        }
+    }
+
+    fn lock(&self, id: TypeId, name: &str) -> &RwLock<Resource> {
+        self.values
+            .get(&id)
+            .unwrap_or_else(|| panic!("missing resource {name}"))
+    }
+}
+
+impl<T: Any> Deref for Ref<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        self.guard
+            .downcast_ref()
+            .expect("resource of the wrong type")
+    }
+}
+
+impl<T: Any> Deref for RefMut<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        self.guard
+            .downcast_ref()
+            .expect("resource of the wrong type")
+    }
+}
+
+impl<T: Any> DerefMut for RefMut<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        self.guard
+            .downcast_mut()
+            .expect("resource of the wrong type")
+    }
+}
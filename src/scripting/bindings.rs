@@ -0,0 +1,287 @@
+use std::{
+    cell::RefCell,
+    collections::{HashMap, HashSet},
+    rc::Rc,
+};
+
+use crate::{
+    graphics::{Color, ColorExt, DrawParams, GraphicsPipeline},
+    inputs::{ButtonState, Input, InputScheme, InputsPipeline},
+    physics::PhysicsWorld,
+    Vec2,
+};
+
+use super::{ScriptBackend, ScriptValue};
+
+// Requests of the scripts the game carries out, the engine having no entities of its own.
+#[derive(Clone, PartialEq, Debug)]
+pub enum ScriptEvent {
+    // spawn(prefab, x, y), e.g. to instantiate the prefab in the scene.
+    Spawn { prefab: String, position: Vec2 },
+}
+
+// Engine functions for scripts, sharing state with the game through sync, draw and take_events:
+// - input_held(name), input_pressed(name) and input_value(name) read the inputs by the name
+//   they display as, input_value being an axis' value or 1 for a held button
+// - draw_rect(x, y, width, height, color, filled) draws a debug rect centered on x, y in world
+//   space, color being a hex string, "#00ff00" by default, and filled false by default
+// - spawn(prefab, x, y) queues a ScriptEvent::Spawn
+// Collisions are reported to the scripts by dispatch_contacts.
+pub struct EngineBindings {
+    shared: Rc<RefCell<Shared>>,
+}
+
+#[derive(Default)]
+struct Shared {
+    inputs: HashMap<String, InputState>,
+    rects: Vec<(Vec2, Vec2, Color, bool)>,
+    events: Vec<ScriptEvent>,
+}
+
+#[derive(Clone, Copy, Default)]
+struct InputState {
+    held: bool,
+    pressed: bool,
+    value: f64,
+}
+
+impl EngineBindings {
+    pub fn register<B: ScriptBackend>(backend: &mut B) -> Self {
+        let shared = Rc::new(RefCell::new(Shared::default()));
+
+        type Read = fn(&InputState) -> ScriptValue;
+        let reads: [(&str, Read); 3] = [
+            ("input_held", |i| ScriptValue::Bool(i.held)),
+            ("input_pressed", |i| ScriptValue::Bool(i.pressed)),
+            ("input_value", |i| ScriptValue::Number(i.value)),
+        ];
+        for (name, read) in reads {
+            let shared = shared.clone();
+            backend.register(
+                name,
+                Box::new(move |args| {
+                    let input = text(args, 0)?;
+                    let shared = shared.borrow();
+                    let state = shared
+                        .inputs
+                        .get(input)
+                        .ok_or_else(|| format!("unknown input '{input}'"))?;
+                    Ok(read(state))
+                }),
+            );
+        }
+
+        let rects = shared.clone();
+        backend.register(
+            "draw_rect",
+            Box::new(move |args| {
+                let position = Vec2::new(number(args, 0)?, number(args, 1)?);
+                let size = Vec2::new(number(args, 2)?, number(args, 3)?);
+                let color = match args.get(4) {
+                    None | Some(ScriptValue::Nil) => Color::GREEN,
+                    Some(_) => Color::from_hex(text(args, 4)?)?,
+                };
+                let filled = matches!(args.get(5), Some(ScriptValue::Bool(true)));
+                rects
+                    .borrow_mut()
+                    .rects
+                    .push((position, size, color, filled));
+                Ok(ScriptValue::Nil)
+            }),
+        );
+
+        let events = shared.clone();
+        backend.register(
+            "spawn",
+            Box::new(move |args| {
+                let prefab = text(args, 0)?.to_string();
+                let position = Vec2::new(number(args, 1)?, number(args, 2)?);
+                events
+                    .borrow_mut()
+                    .events
+                    .push(ScriptEvent::Spawn { prefab, position });
+                Ok(ScriptValue::Nil)
+            }),
+        );
+
+        EngineBindings { shared }
+    }
+
+    // Has to be called every frame before the scripts run, for them to read this frame's inputs.
+    pub fn sync<T: InputScheme>(&self, inputs: &InputsPipeline<T>) {
+        let mut shared = self.shared.borrow_mut();
+        shared.inputs.clear();
+        for id in inputs.inputs() {
+            let state = match inputs.read(&id) {
+                Some(Input::Button(data)) => {
+                    let held = data.value == ButtonState::Down;
+                    InputState {
+                        held,
+                        pressed: inputs.just_pressed(&id),
+                        value: if held { 1. } else { 0. },
+                    }
+                }
+                Some(Input::Axis(data)) => InputState {
+                    held: data.value != 0.,
+                    pressed: false,
+                    value: data.value,
+                },
+                None => continue,
+            };
+            shared.inputs.insert(id.to_string(), state);
+        }
+    }
+
+    // Calls the scripts' on_contact(a, b) with the body ids of every pair of bodies touching in
+    // the last physics step, once per pair whatever their number of contact points, if they
+    // define it, stopping at the first error.
+    pub fn dispatch_contacts<B: ScriptBackend>(
+        &self,
+        backend: &mut B,
+        physics: &PhysicsWorld,
+    ) -> Result<(), String> {
+        if !backend.defines("on_contact") {
+            return Ok(());
+        }
+        let mut called = HashSet::new();
+        for contact in physics.contacts() {
+            let pair = (contact.a.index(), contact.b.index());
+            if called.insert(pair) {
+                let ids = [pair.0, pair.1].map(|i| ScriptValue::Number(i as f64));
+                backend.call("on_contact", &ids)?;
+            }
+        }
+        Ok(())
+    }
+
+    // Draws the rects queued by the scripts since the last call.
+    pub fn draw(&self, graphics_ppl: &mut GraphicsPipeline) {
+        let rects = std::mem::take(&mut self.shared.borrow_mut().rects);
+        for (position, size, color, filled) in rects {
+            graphics_ppl.draw_rect(&position, &size, &color, filled, &DrawParams::default());
+        }
+    }
+
+    pub fn take_events(&self) -> Vec<ScriptEvent> {
+        std::mem::take(&mut self.shared.borrow_mut().events)
+    }
+}
+
+fn number(args: &[ScriptValue], index: usize) -> Result<f64, String> {
+    match args.get(index) {
+        Some(ScriptValue::Number(n)) => Ok(*n),
+        _ => Err(format!("argument {} should be a number", index + 1)),
+    }
+}
+
+fn text(args: &[ScriptValue], index: usize) -> Result<&str, String> {
+    match args.get(index) {
+        Some(ScriptValue::Text(text)) => Ok(text),
+        _ => Err(format!("argument {} should be a string", index + 1)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use parry2d_f64::shape::SharedShape;
+
+    use super::*;
+    use crate::{
+        physics::{Body, BodyKind},
+        scripting::Quill,
+    };
+
+    #[test]
+    fn scripts_queue_spawns_and_rects() {
+        let mut quill = Quill::new();
+        let bindings = EngineBindings::register(&mut quill);
+        let source = "
+            function update()
+                spawn('enemy', 1, 2.5)
+                draw_rect(0, 0, 1, 1)
+                draw_rect(0, 0, 1, 1, '#ff0000', true)
+            end
+        ";
+        quill.load("test", source).unwrap();
+        quill.call("update", &[]).unwrap();
+
+        let spawn = ScriptEvent::Spawn {
+            prefab: "enemy".to_string(),
+            position: Vec2::new(1., 2.5),
+        };
+        assert_eq!(bindings.take_events(), vec![spawn]);
+        assert!(bindings.take_events().is_empty());
+        {
+            let rects = &bindings.shared.borrow().rects;
+            assert_eq!(rects.len(), 2);
+            assert_eq!((rects[1].2, rects[1].3), (Color::RGB(255, 0, 0), true));
+        }
+
+        quill
+            .load("test", "function update() spawn('enemy') end")
+            .unwrap();
+        let error = quill.call("update", &[]).unwrap_err();
+        assert_eq!(error, "test:1: spawn: argument 2 should be a number");
+    }
+
+    #[test]
+    fn contacts_reach_the_scripts() {
+        let mut world = PhysicsWorld::new(Vec2::ZERO);
+        let ground = Body::new(BodyKind::Static, SharedShape::cuboid(5., 0.5), Vec2::ZERO);
+        let ball = Body::new(
+            BodyKind::Dynamic,
+            SharedShape::ball(0.5),
+            Vec2::new(0., -0.9),
+        );
+        let (ground, ball) = (world.add(ground), world.add(ball));
+        world.step(1. / 60.);
+        assert!(!world.contacts().is_empty());
+
+        let mut quill = Quill::new();
+        let bindings = EngineBindings::register(&mut quill);
+        bindings.dispatch_contacts(&mut quill, &world).unwrap();
+
+        let source = "
+            contacts = ''
+            function on_contact(a, b)
+                contacts = contacts .. a .. '-' .. b .. ' '
+            end
+        ";
+        quill.load("test", source).unwrap();
+        bindings.dispatch_contacts(&mut quill, &world).unwrap();
+        let expected: String = world
+            .contacts()
+            .iter()
+            .map(|c| format!("{}-{} ", c.a.index(), c.b.index()))
+            .collect();
+        assert!(expected.contains(&ground.index().to_string()));
+        assert!(expected.contains(&ball.index().to_string()));
+        assert_eq!(quill.global("contacts"), ScriptValue::Text(expected));
+    }
+
+    #[test]
+    fn contacts_are_dispatched_once_per_pair() {
+        let mut world = PhysicsWorld::new(Vec2::ZERO);
+        let ground = Body::new(BodyKind::Static, SharedShape::cuboid(5., 0.5), Vec2::ZERO);
+        let crate_ = Body::new(
+            BodyKind::Dynamic,
+            SharedShape::cuboid(0.5, 0.5),
+            Vec2::new(0., -0.95),
+        );
+        world.add(ground);
+        world.add(crate_);
+        world.step(1. / 60.);
+        // Resting flat on the ground, the box touches it at both of its bottom corners
+        assert!(world.contacts().len() >= 2);
+
+        let mut quill = Quill::new();
+        let bindings = EngineBindings::register(&mut quill);
+        let source = "
+            calls = 0
+            function on_contact(a, b) calls = calls + 1 end
+        ";
+        quill.load("test", source).unwrap();
+        bindings.dispatch_contacts(&mut quill, &world).unwrap();
+        assert_eq!(quill.global("calls"), ScriptValue::Number(1.));
+    }
+}
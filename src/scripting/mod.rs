@@ -0,0 +1,111 @@
+use std::{
+    collections::HashMap,
+    fmt,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+pub use bindings::{EngineBindings, ScriptEvent};
+pub use quill::Quill;
+
+mod bindings;
+mod quill;
+
+#[derive(Clone, PartialEq, Debug, Default)]
+pub enum ScriptValue {
+    #[default]
+    Nil,
+    Bool(bool),
+    Number(f64),
+    Text(String),
+}
+
+// Rust function callable from scripts, typically a closure sharing engine state with the game.
+pub type HostFunction = Box<dyn FnMut(&[ScriptValue]) -> Result<ScriptValue, String>>;
+
+// Interface to a script language runtime, e.g. the built-in Quill or one on top of mlua or rhai.
+pub trait ScriptBackend {
+    // Loading a script again under the same name replaces its previous version, which should
+    // be kept if the new one fails to load.
+    fn load(&mut self, name: &str, source: &str) -> Result<(), String>;
+
+    fn register(&mut self, name: &str, function: HostFunction);
+
+    fn call(&mut self, function: &str, args: &[ScriptValue]) -> Result<ScriptValue, String>;
+
+    // Whether a loaded script or a host function has the name.
+    fn defines(&self, function: &str) -> bool;
+}
+
+// Loads scripts from disk into a backend and reloads them when their file changes.
+// update has to be called regularly, once per frame or less.
+pub struct Scripts<B: ScriptBackend> {
+    pub backend: B,
+    files: HashMap<String, (PathBuf, Option<SystemTime>)>,
+}
+
+impl fmt::Display for ScriptValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ScriptValue::Nil => write!(f, "nil"),
+            ScriptValue::Bool(b) => write!(f, "{b}"),
+            ScriptValue::Number(n) => write!(f, "{n}"),
+            ScriptValue::Text(t) => write!(f, "{t}"),
+        }
+    }
+}
+
+impl<B: ScriptBackend> Scripts<B> {
+    pub fn new(backend: B) -> Self {
+        Scripts {
+            backend,
+            files: HashMap::new(),
+        }
+    }
+
+    pub fn load<P: AsRef<Path>>(&mut self, name: &str, path: P) -> Result<(), String> {
+        let path = path.as_ref().to_path_buf();
+        let modified = modified(&path);
+        let source = std::fs::read_to_string(&path).map_err(|e| e.to_string())?;
+
+        self.files.insert(name.to_string(), (path, modified));
+        self.backend.load(name, &source)
+    }
+
+    pub fn defines(&self, function: &str) -> bool {
+        self.backend.defines(function)
+    }
+
+    // Fails if none of the scripts define the function.
+    pub fn call(&mut self, function: &str, args: &[ScriptValue]) -> Result<ScriptValue, String> {
+        self.backend.call(function, args)
+    }
+
+    // Reloads the scripts whose file changed, returns the names of the reloaded ones along with
+    // the result of the reload.
+    pub fn update(&mut self) -> Vec<(String, Result<(), String>)> {
+        let mut reloaded = Vec::new();
+        for (name, (path, last_modified)) in &mut self.files {
+            let modified = modified(path);
+            if modified == *last_modified {
+                continue;
+            }
+
+            *last_modified = modified;
+            let result = std::fs::read_to_string(&*path)
+                .map_err(|e| e.to_string())
+                .and_then(|source| self.backend.load(name, &source));
+            match &result {
+                Ok(()) => log::info!("reloaded script {name}"),
+                Err(e) => log::warn!("failed to reload script {name}: {e}"),
+            }
+            reloaded.push((name.clone(), result));
+        }
+
+        reloaded
+    }
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|m| m.modified()).ok()
+}
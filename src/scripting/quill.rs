@@ -0,0 +1,1219 @@
+use std::{collections::HashMap, rc::Rc};
+
+use super::{HostFunction, ScriptBackend, ScriptValue};
+
+// Calls deeper than this fail instead of overflowing the stack.
+const MAX_DEPTH: usize = 200;
+
+const KEYWORDS: [&str; 19] = [
+    "and", "break", "do", "else", "elseif", "end", "false", "for", "function", "if", "local",
+    "nil", "not", "or", "repeat", "return", "then", "true", "while",
+];
+
+// Longest first for ".." not to be read as two dots.
+const SYMBOLS: [&str; 20] = [
+    "==", "~=", "<=", ">=", "..", "+", "-", "*", "/", "%", "^", "#", "<", ">", "=", "(", ")", ",",
+    ";", ".",
+];
+
+// Quill, the engine's built-in script language, for gameplay logic without a native dependency.
+// Its syntax borrows from Lua but it isn't Lua: there are no tables, closures nor standard
+// library, functions and variables live in separate namespaces and scripts written for Lua
+// generally won't run. Games needing Lua put mlua or rhai behind a ScriptBackend instead.
+//
+// chunk      = { statement [";"] }
+// block      = { statement [";"] } ["return" [expression] [";"]]
+// statement  = "local" names ["=" expressions]
+//            | names "=" expressions
+//            | name "(" [expressions] ")"
+//            | "if" expression "then" block { "elseif" expression "then" block }
+//              ["else" block] "end"
+//            | "while" expression "do" block "end"
+//            | "for" name "=" expression "," expression ["," expression] "do" block "end"
+//            | "function" name "(" [names] ")" block "end"
+//            | "do" block "end"
+//            | "break"
+// expression = "nil" | "true" | "false" | number | string | name
+//            | name "(" [expressions] ")" | "(" expression ")"
+//            | unary expression | expression binary expression
+// names      = name { "," name }
+// expressions = expression { "," expression }
+//
+// Operators by priority, from the loosest: or, and, comparisons (== ~= < <= > >=), .., + and -,
+// * / and %, unary operators (- not #), ^. ".." and "^" are right associative, "#" being the
+// length of a string. "and" and "or" only evaluate their right side when needed.
+//
+// Values are nil, booleans, numbers and strings, only nil and false being false. Numbers are
+// decimal, with an optional fraction and exponent, or 0x hexadecimal integers. Strings are
+// quoted with ' or " and escape \n, \t, \r, \0, \\ and quotes, or are long strings between [[
+// and ]] escaping nothing. Comments start with "--", long ones being between --[[ and ]].
+//
+// Functions return one value, nil by default, and only see their parameters, their locals and
+// the globals, assigning a name without local setting a global. Functions of every loaded
+// script are callable from the others, host functions being called when no script defines the
+// name.
+pub struct Quill {
+    // Statements a call can run before failing, so that an infinite loop doesn't freeze the
+    // game.
+    pub max_steps: u64,
+    globals: HashMap<String, ScriptValue>,
+    functions: HashMap<String, Rc<Function>>,
+    host: HashMap<String, HostFunction>,
+    // Locals of the running function, innermost last.
+    locals: Vec<(String, ScriptValue)>,
+    depth: usize,
+    steps: u64,
+    // Script and line running, for errors.
+    location: (Rc<str>, usize),
+}
+
+struct Function {
+    script: Rc<str>,
+    params: Vec<String>,
+    body: Vec<Statement>,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+enum Token {
+    Name(String),
+    Number(f64),
+    Text(String),
+    Symbol(&'static str),
+    End,
+}
+
+struct Statement {
+    kind: StatementKind,
+    line: usize,
+}
+
+enum StatementKind {
+    Local(Vec<String>, Vec<Expression>),
+    Assign(Vec<String>, Vec<Expression>),
+    Call(String, Vec<Expression>),
+    If(Vec<(Expression, Vec<Statement>)>, Vec<Statement>),
+    While(Expression, Vec<Statement>),
+    For {
+        variable: String,
+        start: Expression,
+        end: Expression,
+        step: Option<Expression>,
+        body: Vec<Statement>,
+    },
+    Function(String, Rc<Function>),
+    Do(Vec<Statement>),
+    Return(Option<Expression>),
+    Break,
+}
+
+enum Expression {
+    Value(ScriptValue),
+    Name(String),
+    Call(String, Vec<Expression>),
+    Not(Box<Expression>),
+    Negate(Box<Expression>),
+    Length(Box<Expression>),
+    Binary(Operator, Box<Expression>, Box<Expression>),
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum Operator {
+    Or,
+    And,
+    Equal,
+    NotEqual,
+    Less,
+    LessEqual,
+    Greater,
+    GreaterEqual,
+    Concat,
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    Modulo,
+    Power,
+}
+
+enum Flow {
+    Normal,
+    Break,
+    Return(ScriptValue),
+}
+
+struct Parser {
+    tokens: Vec<(Token, usize)>,
+    position: usize,
+    script: Rc<str>,
+}
+
+impl Quill {
+    pub fn new() -> Self {
+        Quill {
+            max_steps: 10_000_000,
+            globals: HashMap::new(),
+            functions: HashMap::new(),
+            host: HashMap::new(),
+            locals: Vec::new(),
+            depth: 0,
+            steps: 0,
+            location: ("".into(), 0),
+        }
+    }
+
+    pub fn global(&self, name: &str) -> ScriptValue {
+        self.globals.get(name).cloned().unwrap_or_default()
+    }
+
+    pub fn set_global(&mut self, name: &str, value: ScriptValue) {
+        match value {
+            ScriptValue::Nil => self.globals.remove(name),
+            value => self.globals.insert(name.to_string(), value),
+        };
+    }
+
+    fn error(&self, message: &str) -> String {
+        format!("{}:{}: {}", self.location.0, self.location.1, message)
+    }
+
+    fn step(&mut self) -> Result<(), String> {
+        self.steps += 1;
+        if self.steps > self.max_steps {
+            return Err(self.error(&format!("ran more than {} statements", self.max_steps)));
+        }
+        Ok(())
+    }
+
+    fn call_function(&mut self, name: &str, args: Vec<ScriptValue>) -> Result<ScriptValue, String> {
+        if let Some(function) = self.functions.get(name).cloned() {
+            return self.invoke(&function, args);
+        }
+        let result = match self.host.get_mut(name) {
+            Some(host) => host(&args),
+            None => return Err(self.error(&format!("unknown function '{name}'"))),
+        };
+        result.map_err(|e| self.error(&format!("{name}: {e}")))
+    }
+
+    fn invoke(
+        &mut self,
+        function: &Function,
+        args: Vec<ScriptValue>,
+    ) -> Result<ScriptValue, String> {
+        if self.depth >= MAX_DEPTH {
+            return Err(self.error("stack overflow"));
+        }
+
+        // Missing arguments are nil, extra ones are dropped
+        let mut args = args.into_iter();
+        let locals = function
+            .params
+            .iter()
+            .map(|p| (p.clone(), args.next().unwrap_or_default()))
+            .collect();
+        let caller_locals = std::mem::replace(&mut self.locals, locals);
+        let caller_location = self.location.clone();
+        self.location.0 = function.script.clone();
+        self.depth += 1;
+
+        let flow = self.execute_block(&function.body);
+
+        self.depth -= 1;
+        self.locals = caller_locals;
+        self.location = caller_location;
+        match flow? {
+            Flow::Return(value) => Ok(value),
+            _ => Ok(ScriptValue::Nil),
+        }
+    }
+
+    fn execute_block(&mut self, block: &[Statement]) -> Result<Flow, String> {
+        let scope = self.locals.len();
+        let mut flow = Ok(Flow::Normal);
+        for statement in block {
+            flow = self.execute(statement);
+            if !matches!(flow, Ok(Flow::Normal)) {
+                break;
+            }
+        }
+        self.locals.truncate(scope);
+        flow
+    }
+
+    fn execute(&mut self, statement: &Statement) -> Result<Flow, String> {
+        self.location.1 = statement.line;
+        self.step()?;
+
+        match &statement.kind {
+            StatementKind::Local(names, values) => {
+                let values = self.evaluate_list(values, names.len())?;
+                self.locals.extend(names.iter().cloned().zip(values));
+            }
+            StatementKind::Assign(names, values) => {
+                let values = self.evaluate_list(values, names.len())?;
+                for (name, value) in names.iter().zip(values) {
+                    match self.locals.iter_mut().rev().find(|(n, _)| n == name) {
+                        Some((_, local)) => *local = value,
+                        None => self.set_global(name, value),
+                    }
+                }
+            }
+            StatementKind::Call(function, args) => {
+                self.evaluate_call(function, args)?;
+            }
+            StatementKind::If(branches, otherwise) => {
+                for (condition, block) in branches {
+                    if truthy(&self.evaluate(condition)?) {
+                        return self.execute_block(block);
+                    }
+                }
+                return self.execute_block(otherwise);
+            }
+            StatementKind::While(condition, body) => {
+                while truthy(&self.evaluate(condition)?) {
+                    match self.execute_block(body)? {
+                        Flow::Break => break,
+                        Flow::Return(value) => return Ok(Flow::Return(value)),
+                        Flow::Normal => {}
+                    }
+                    self.location.1 = statement.line;
+                    self.step()?;
+                }
+            }
+            StatementKind::For {
+                variable,
+                start,
+                end,
+                step,
+                body,
+            } => {
+                let start = self.for_number(start, "initial")?;
+                let end = self.for_number(end, "limit")?;
+                let step = match step {
+                    Some(step) => self.for_number(step, "step")?,
+                    None => 1.,
+                };
+                if step == 0. {
+                    return Err(self.error("'for' step is zero"));
+                }
+
+                let mut value = start;
+                while (step > 0. && value <= end) || (step < 0. && value >= end) {
+                    self.locals
+                        .push((variable.clone(), ScriptValue::Number(value)));
+                    let flow = self.execute_block(body);
+                    self.locals.pop();
+                    match flow? {
+                        Flow::Break => break,
+                        Flow::Return(value) => return Ok(Flow::Return(value)),
+                        Flow::Normal => {}
+                    }
+                    self.location.1 = statement.line;
+                    self.step()?;
+                    value += step;
+                }
+            }
+            StatementKind::Function(name, function) => {
+                self.functions.insert(name.clone(), function.clone());
+            }
+            StatementKind::Do(block) => return self.execute_block(block),
+            StatementKind::Return(value) => {
+                let value = match value {
+                    Some(value) => self.evaluate(value)?,
+                    None => ScriptValue::Nil,
+                };
+                return Ok(Flow::Return(value));
+            }
+            StatementKind::Break => return Ok(Flow::Break),
+        }
+        Ok(Flow::Normal)
+    }
+
+    fn for_number(&mut self, expression: &Expression, name: &str) -> Result<f64, String> {
+        match self.evaluate(expression)? {
+            ScriptValue::Number(n) => Ok(n),
+            _ => Err(self.error(&format!("'for' {name} value must be a number"))),
+        }
+    }
+
+    // Values for count names, nil for the missing ones.
+    fn evaluate_list(
+        &mut self,
+        expressions: &[Expression],
+        count: usize,
+    ) -> Result<Vec<ScriptValue>, String> {
+        let mut values = expressions
+            .iter()
+            .map(|e| self.evaluate(e))
+            .collect::<Result<Vec<_>, _>>()?;
+        values.resize(count, ScriptValue::Nil);
+        Ok(values)
+    }
+
+    fn evaluate_call(
+        &mut self,
+        function: &str,
+        args: &[Expression],
+    ) -> Result<ScriptValue, String> {
+        let args = args
+            .iter()
+            .map(|a| self.evaluate(a))
+            .collect::<Result<Vec<_>, _>>()?;
+        self.call_function(function, args)
+    }
+
+    fn evaluate(&mut self, expression: &Expression) -> Result<ScriptValue, String> {
+        let value = match expression {
+            Expression::Value(value) => value.clone(),
+            Expression::Name(name) => match self.locals.iter().rev().find(|(n, _)| n == name) {
+                Some((_, value)) => value.clone(),
+                None => self.global(name),
+            },
+            Expression::Call(function, args) => self.evaluate_call(function, args)?,
+            Expression::Not(operand) => ScriptValue::Bool(!truthy(&self.evaluate(operand)?)),
+            Expression::Negate(operand) => match self.evaluate(operand)? {
+                ScriptValue::Number(n) => ScriptValue::Number(-n),
+                value => return Err(self.arithmetic_error(&value)),
+            },
+            Expression::Length(operand) => match self.evaluate(operand)? {
+                ScriptValue::Text(text) => ScriptValue::Number(text.len() as f64),
+                value => {
+                    let message = format!("attempt to get length of a {} value", type_name(&value));
+                    return Err(self.error(&message));
+                }
+            },
+            Expression::Binary(Operator::And, left, right) => {
+                let left = self.evaluate(left)?;
+                match truthy(&left) {
+                    true => self.evaluate(right)?,
+                    false => left,
+                }
+            }
+            Expression::Binary(Operator::Or, left, right) => {
+                let left = self.evaluate(left)?;
+                match truthy(&left) {
+                    true => left,
+                    false => self.evaluate(right)?,
+                }
+            }
+            Expression::Binary(operator, left, right) => {
+                let left = self.evaluate(left)?;
+                let right = self.evaluate(right)?;
+                self.binary(*operator, left, right)?
+            }
+        };
+        Ok(value)
+    }
+
+    fn binary(
+        &self,
+        operator: Operator,
+        left: ScriptValue,
+        right: ScriptValue,
+    ) -> Result<ScriptValue, String> {
+        use ScriptValue::{Bool, Number, Text};
+
+        let value = match (operator, &left, &right) {
+            (Operator::Equal, _, _) => Bool(left == right),
+            (Operator::NotEqual, _, _) => Bool(left != right),
+            (Operator::Less, Number(a), Number(b)) => Bool(a < b),
+            (Operator::LessEqual, Number(a), Number(b)) => Bool(a <= b),
+            (Operator::Greater, Number(a), Number(b)) => Bool(a > b),
+            (Operator::GreaterEqual, Number(a), Number(b)) => Bool(a >= b),
+            (Operator::Less, Text(a), Text(b)) => Bool(a < b),
+            (Operator::LessEqual, Text(a), Text(b)) => Bool(a <= b),
+            (Operator::Greater, Text(a), Text(b)) => Bool(a > b),
+            (Operator::GreaterEqual, Text(a), Text(b)) => Bool(a >= b),
+            (
+                Operator::Less | Operator::LessEqual | Operator::Greater | Operator::GreaterEqual,
+                _,
+                _,
+            ) => {
+                let message = format!(
+                    "attempt to compare {} with {}",
+                    type_name(&left),
+                    type_name(&right)
+                );
+                return Err(self.error(&message));
+            }
+            (Operator::Concat, Text(_) | Number(_), Text(_) | Number(_)) => {
+                Text(format!("{left}{right}"))
+            }
+            (Operator::Concat, _, _) => {
+                let value = match left {
+                    Text(_) | Number(_) => &right,
+                    _ => &left,
+                };
+                let message = format!("attempt to concatenate a {} value", type_name(value));
+                return Err(self.error(&message));
+            }
+            (_, Number(a), Number(b)) => Number(match operator {
+                Operator::Add => a + b,
+                Operator::Subtract => a - b,
+                Operator::Multiply => a * b,
+                Operator::Divide => a / b,
+                // Takes the sign of the divisor
+                Operator::Modulo => a - (a / b).floor() * b,
+                Operator::Power => a.powf(*b),
+                _ => unreachable!("handled above"),
+            }),
+            (_, Number(_), value) | (_, value, _) => return Err(self.arithmetic_error(value)),
+        };
+        Ok(value)
+    }
+
+    fn arithmetic_error(&self, value: &ScriptValue) -> String {
+        let message = format!(
+            "attempt to perform arithmetic on a {} value",
+            type_name(value)
+        );
+        self.error(&message)
+    }
+}
+
+impl Default for Quill {
+    fn default() -> Self {
+        Quill::new()
+    }
+}
+
+impl ScriptBackend for Quill {
+    // The script's top level runs once loaded, e.g. to set globals. Functions the previous
+    // version defined and the new one doesn't are removed.
+    fn load(&mut self, name: &str, source: &str) -> Result<(), String> {
+        let script: Rc<str> = name.into();
+        let body = Parser::new(source, script.clone())?.parse_chunk()?;
+
+        let globals = self.globals.clone();
+        let functions = self.functions.clone();
+        self.functions.retain(|_, f| f.script != script);
+
+        self.steps = 0;
+        self.location = (script.clone(), 0);
+        let chunk = Function {
+            script,
+            params: Vec::new(),
+            body,
+        };
+        if let Err(e) = self.invoke(&chunk, Vec::new()) {
+            self.globals = globals;
+            self.functions = functions;
+            return Err(e);
+        }
+        Ok(())
+    }
+
+    fn register(&mut self, name: &str, function: HostFunction) {
+        self.host.insert(name.to_string(), function);
+    }
+
+    fn call(&mut self, function: &str, args: &[ScriptValue]) -> Result<ScriptValue, String> {
+        self.steps = 0;
+        self.location = ("".into(), 0);
+        self.call_function(function, args.to_vec())
+    }
+
+    fn defines(&self, function: &str) -> bool {
+        self.functions.contains_key(function) || self.host.contains_key(function)
+    }
+}
+
+impl Parser {
+    fn new(source: &str, script: Rc<str>) -> Result<Self, String> {
+        let tokens = tokenize(source).map_err(|(line, e)| format!("{script}:{line}: {e}"))?;
+        Ok(Parser {
+            tokens,
+            position: 0,
+            script,
+        })
+    }
+
+    fn parse_chunk(&mut self) -> Result<Vec<Statement>, String> {
+        let block = self.parse_block()?;
+        if self.peek() != &Token::End {
+            return Err(self.unexpected());
+        }
+        Ok(block)
+    }
+
+    fn peek(&self) -> &Token {
+        &self.tokens[self.position].0
+    }
+
+    fn line(&self) -> usize {
+        self.tokens[self.position].1
+    }
+
+    fn next(&mut self) -> Token {
+        let token = self.tokens[self.position].0.clone();
+        if token != Token::End {
+            self.position += 1;
+        }
+        token
+    }
+
+    fn error(&self, message: &str) -> String {
+        format!("{}:{}: {}", self.script, self.line(), message)
+    }
+
+    fn unexpected(&self) -> String {
+        let near = match self.peek() {
+            Token::Name(name) => format!("'{name}'"),
+            Token::Number(n) => format!("'{n}'"),
+            Token::Text(text) => format!("'\"{text}\"'"),
+            Token::Symbol(symbol) => format!("'{symbol}'"),
+            Token::End => "end of file".to_string(),
+        };
+        self.error(&format!("unexpected {near}"))
+    }
+
+    fn is_keyword(&self, keyword: &str) -> bool {
+        matches!(self.peek(), Token::Name(name) if name == keyword)
+    }
+
+    fn is_symbol(&self, symbol: &str) -> bool {
+        matches!(self.peek(), Token::Symbol(s) if *s == symbol)
+    }
+
+    fn accept_symbol(&mut self, symbol: &str) -> bool {
+        let accepted = self.is_symbol(symbol);
+        if accepted {
+            self.next();
+        }
+        accepted
+    }
+
+    fn expect_symbol(&mut self, symbol: &str) -> Result<(), String> {
+        match self.accept_symbol(symbol) {
+            true => Ok(()),
+            false => Err(self.error(&format!("'{symbol}' expected"))),
+        }
+    }
+
+    fn expect_keyword(&mut self, keyword: &str) -> Result<(), String> {
+        if !self.is_keyword(keyword) {
+            return Err(self.error(&format!("'{keyword}' expected")));
+        }
+        self.next();
+        Ok(())
+    }
+
+    fn expect_name(&mut self) -> Result<String, String> {
+        match self.peek() {
+            Token::Name(name) if !KEYWORDS.contains(&name.as_str()) => {
+                let name = name.clone();
+                self.next();
+                Ok(name)
+            }
+            _ => Err(self.error("name expected")),
+        }
+    }
+
+    fn names(&mut self) -> Result<Vec<String>, String> {
+        let mut names = vec![self.expect_name()?];
+        while self.accept_symbol(",") {
+            names.push(self.expect_name()?);
+        }
+        Ok(names)
+    }
+
+    fn expressions(&mut self) -> Result<Vec<Expression>, String> {
+        let mut expressions = vec![self.parse_expression(0)?];
+        while self.accept_symbol(",") {
+            expressions.push(self.parse_expression(0)?);
+        }
+        Ok(expressions)
+    }
+
+    // Until a keyword closing the block, return being the last statement.
+    fn parse_block(&mut self) -> Result<Vec<Statement>, String> {
+        let mut block = Vec::new();
+        loop {
+            while self.accept_symbol(";") {}
+            let ends = ["end", "else", "elseif"].iter().any(|k| self.is_keyword(k));
+            if ends || self.peek() == &Token::End {
+                return Ok(block);
+            }
+
+            let returns = self.is_keyword("return");
+            block.push(self.parse_statement()?);
+            if returns {
+                while self.accept_symbol(";") {}
+                return Ok(block);
+            }
+        }
+    }
+
+    fn parse_statement(&mut self) -> Result<Statement, String> {
+        let line = self.line();
+        let Token::Name(word) = self.peek().clone() else {
+            return Err(self.unexpected());
+        };
+
+        let kind = match word.as_str() {
+            "local" => {
+                self.next();
+                if self.is_keyword("function") {
+                    return Err(self.error("local functions aren't supported"));
+                }
+                let names = self.names()?;
+                let values = match self.accept_symbol("=") {
+                    true => self.expressions()?,
+                    false => Vec::new(),
+                };
+                StatementKind::Local(names, values)
+            }
+            "if" => {
+                self.next();
+                let mut branches = Vec::new();
+                let mut otherwise = Vec::new();
+                loop {
+                    let condition = self.parse_expression(0)?;
+                    self.expect_keyword("then")?;
+                    branches.push((condition, self.parse_block()?));
+                    match self.next() {
+                        Token::Name(k) if k == "elseif" => continue,
+                        Token::Name(k) if k == "else" => {
+                            otherwise = self.parse_block()?;
+                            self.expect_keyword("end")?;
+                        }
+                        Token::Name(k) if k == "end" => {}
+                        _ => return Err(self.error("'end' expected")),
+                    }
+                    break;
+                }
+                StatementKind::If(branches, otherwise)
+            }
+            "while" => {
+                self.next();
+                let condition = self.parse_expression(0)?;
+                self.expect_keyword("do")?;
+                let body = self.parse_block()?;
+                self.expect_keyword("end")?;
+                StatementKind::While(condition, body)
+            }
+            "for" => {
+                self.next();
+                let variable = self.expect_name()?;
+                if !self.accept_symbol("=") {
+                    return Err(self.error("only numeric for loops are supported"));
+                }
+                let start = self.parse_expression(0)?;
+                self.expect_symbol(",")?;
+                let end = self.parse_expression(0)?;
+                let step = match self.accept_symbol(",") {
+                    true => Some(self.parse_expression(0)?),
+                    false => None,
+                };
+                self.expect_keyword("do")?;
+                let body = self.parse_block()?;
+                self.expect_keyword("end")?;
+                StatementKind::For {
+                    variable,
+                    start,
+                    end,
+                    step,
+                    body,
+                }
+            }
+            "function" => {
+                self.next();
+                let name = self.expect_name()?;
+                self.expect_symbol("(")?;
+                let params = match self.is_symbol(")") {
+                    true => Vec::new(),
+                    false => self.names()?,
+                };
+                self.expect_symbol(")")?;
+                let body = self.parse_block()?;
+                self.expect_keyword("end")?;
+                let function = Function {
+                    script: self.script.clone(),
+                    params,
+                    body,
+                };
+                StatementKind::Function(name, Rc::new(function))
+            }
+            "do" => {
+                self.next();
+                let body = self.parse_block()?;
+                self.expect_keyword("end")?;
+                StatementKind::Do(body)
+            }
+            "return" => {
+                self.next();
+                let ends = ["end", "else", "elseif"].iter().any(|k| self.is_keyword(k));
+                if ends || self.is_symbol(";") || self.peek() == &Token::End {
+                    StatementKind::Return(None)
+                } else {
+                    let value = self.parse_expression(0)?;
+                    if self.is_symbol(",") {
+                        return Err(self.error("only one value can be returned"));
+                    }
+                    StatementKind::Return(Some(value))
+                }
+            }
+            "break" => {
+                self.next();
+                StatementKind::Break
+            }
+            "repeat" => return Err(self.error("repeat loops aren't supported")),
+            word if KEYWORDS.contains(&word) => return Err(self.unexpected()),
+            _ => {
+                let name = self.expect_name()?;
+                if self.accept_symbol("(") {
+                    StatementKind::Call(name, self.arguments()?)
+                } else {
+                    let mut names = vec![name];
+                    while self.accept_symbol(",") {
+                        names.push(self.expect_name()?);
+                    }
+                    if self.is_symbol(".") {
+                        return Err(self.error("tables aren't supported"));
+                    }
+                    self.expect_symbol("=")?;
+                    StatementKind::Assign(names, self.expressions()?)
+                }
+            }
+        };
+        Ok(Statement { kind, line })
+    }
+
+    // After the opening parenthesis.
+    fn arguments(&mut self) -> Result<Vec<Expression>, String> {
+        if self.accept_symbol(")") {
+            return Ok(Vec::new());
+        }
+        let args = self.expressions()?;
+        self.expect_symbol(")")?;
+        Ok(args)
+    }
+
+    // Precedence climbing, operators binding tighter than limit.
+    fn parse_expression(&mut self, limit: u8) -> Result<Expression, String> {
+        let mut left = if self.accept_symbol("-") {
+            Expression::Negate(Box::new(self.parse_expression(UNARY_PRIORITY)?))
+        } else if self.accept_symbol("#") {
+            Expression::Length(Box::new(self.parse_expression(UNARY_PRIORITY)?))
+        } else if self.is_keyword("not") {
+            self.next();
+            Expression::Not(Box::new(self.parse_expression(UNARY_PRIORITY)?))
+        } else {
+            self.parse_simple()?
+        };
+
+        while let Some((operator, (left_priority, right_priority))) = self.operator() {
+            if left_priority <= limit {
+                break;
+            }
+            self.next();
+            let right = self.parse_expression(right_priority)?;
+            left = Expression::Binary(operator, Box::new(left), Box::new(right));
+        }
+        Ok(left)
+    }
+
+    fn operator(&self) -> Option<(Operator, (u8, u8))> {
+        let operator = match self.peek() {
+            Token::Name(name) if name == "or" => Operator::Or,
+            Token::Name(name) if name == "and" => Operator::And,
+            Token::Symbol(symbol) => match *symbol {
+                "==" => Operator::Equal,
+                "~=" => Operator::NotEqual,
+                "<" => Operator::Less,
+                "<=" => Operator::LessEqual,
+                ">" => Operator::Greater,
+                ">=" => Operator::GreaterEqual,
+                ".." => Operator::Concat,
+                "+" => Operator::Add,
+                "-" => Operator::Subtract,
+                "*" => Operator::Multiply,
+                "/" => Operator::Divide,
+                "%" => Operator::Modulo,
+                "^" => Operator::Power,
+                _ => return None,
+            },
+            _ => return None,
+        };
+        Some((operator, priority(operator)))
+    }
+
+    fn parse_simple(&mut self) -> Result<Expression, String> {
+        let expression = match self.peek().clone() {
+            Token::Number(n) => Expression::Value(ScriptValue::Number(n)),
+            Token::Text(text) => Expression::Value(ScriptValue::Text(text)),
+            Token::Name(name) if name == "nil" => Expression::Value(ScriptValue::Nil),
+            Token::Name(name) if name == "true" => Expression::Value(ScriptValue::Bool(true)),
+            Token::Name(name) if name == "false" => Expression::Value(ScriptValue::Bool(false)),
+            Token::Symbol("(") => {
+                self.next();
+                let expression = self.parse_expression(0)?;
+                self.expect_symbol(")")?;
+                return Ok(expression);
+            }
+            Token::Name(name) if !KEYWORDS.contains(&name.as_str()) => {
+                self.next();
+                if self.accept_symbol("(") {
+                    return Ok(Expression::Call(name, self.arguments()?));
+                }
+                if self.is_symbol(".") {
+                    return Err(self.error("tables aren't supported"));
+                }
+                return Ok(Expression::Name(name));
+            }
+            _ => return Err(self.unexpected()),
+        };
+        self.next();
+        Ok(expression)
+    }
+}
+
+const UNARY_PRIORITY: u8 = 12;
+
+// Left and right priorities, a higher right one making the operator right associative.
+fn priority(operator: Operator) -> (u8, u8) {
+    match operator {
+        Operator::Or => (1, 1),
+        Operator::And => (2, 2),
+        Operator::Equal
+        | Operator::NotEqual
+        | Operator::Less
+        | Operator::LessEqual
+        | Operator::Greater
+        | Operator::GreaterEqual => (3, 3),
+        Operator::Concat => (9, 8),
+        Operator::Add | Operator::Subtract => (10, 10),
+        Operator::Multiply | Operator::Divide | Operator::Modulo => (11, 11),
+        Operator::Power => (14, 13),
+    }
+}
+
+fn truthy(value: &ScriptValue) -> bool {
+    !matches!(value, ScriptValue::Nil | ScriptValue::Bool(false))
+}
+
+fn type_name(value: &ScriptValue) -> &'static str {
+    match value {
+        ScriptValue::Nil => "nil",
+        ScriptValue::Bool(_) => "boolean",
+        ScriptValue::Number(_) => "number",
+        ScriptValue::Text(_) => "string",
+    }
+}
+
+// Tokens with their line, the last one being End. Errors come with their line.
+fn tokenize(source: &str) -> Result<Vec<(Token, usize)>, (usize, String)> {
+    let chars: Vec<char> = source.chars().collect();
+    let starts_with = |i: usize, text: &str| {
+        text.chars()
+            .enumerate()
+            .all(|(j, c)| chars.get(i + j) == Some(&c))
+    };
+    let mut tokens = Vec::new();
+    let mut line = 1;
+    let mut i = 0;
+
+    while i < chars.len() {
+        let c = chars[i];
+        let start_line = line;
+        if c == '\n' {
+            line += 1;
+            i += 1;
+        } else if c.is_whitespace() {
+            i += 1;
+        } else if starts_with(i, "--[[") || starts_with(i, "[[") {
+            // Long comments and strings, which don't escape anything
+            let comment = c == '-';
+            let start = i + if comment { 4 } else { 2 };
+            let end = (start..chars.len())
+                .find(|j| starts_with(*j, "]]"))
+                .ok_or((start_line, "unfinished long bracket".to_string()))?;
+            let text: String = chars[start..end].iter().collect();
+            line += text.matches('\n').count();
+            if !comment {
+                // A first newline is skipped
+                let text = text.strip_prefix('\n').unwrap_or(&text).to_string();
+                tokens.push((Token::Text(text), start_line));
+            }
+            i = end + 2;
+        } else if starts_with(i, "--") {
+            while i < chars.len() && chars[i] != '\n' {
+                i += 1;
+            }
+        } else if c == '"' || c == '\'' {
+            let mut text = String::new();
+            i += 1;
+            loop {
+                match chars.get(i) {
+                    None | Some('\n') => return Err((line, "unfinished string".to_string())),
+                    Some(q) if *q == c => break,
+                    Some('\\') => {
+                        let escaped = match chars.get(i + 1) {
+                            Some('n') => '\n',
+                            Some('t') => '\t',
+                            Some('r') => '\r',
+                            Some('0') => '\0',
+                            Some(e @ ('\\' | '"' | '\'')) => *e,
+                            _ => return Err((line, "invalid escape sequence".to_string())),
+                        };
+                        text.push(escaped);
+                        i += 2;
+                    }
+                    Some(c) => {
+                        text.push(*c);
+                        i += 1;
+                    }
+                }
+            }
+            tokens.push((Token::Text(text), line));
+            i += 1;
+        } else if c.is_ascii_digit()
+            || (c == '.' && chars.get(i + 1).is_some_and(char::is_ascii_digit))
+        {
+            let start = i;
+            let hex = starts_with(i, "0x") || starts_with(i, "0X");
+            if hex {
+                i += 2;
+            }
+            while i < chars.len() {
+                let c = chars[i];
+                let exponent = !hex && (c == 'e' || c == 'E');
+                if exponent && matches!(chars.get(i + 1), Some('+' | '-')) {
+                    i += 2;
+                } else if c.is_ascii_alphanumeric() || c == '.' {
+                    i += 1;
+                } else {
+                    break;
+                }
+            }
+            let text: String = chars[start..i].iter().collect();
+            let number = if hex {
+                i64::from_str_radix(&text[2..], 16).ok().map(|n| n as f64)
+            } else {
+                text.parse().ok()
+            };
+            let number = number.ok_or((line, format!("malformed number '{text}'")))?;
+            tokens.push((Token::Number(number), line));
+        } else if c.is_alphabetic() || c == '_' {
+            let start = i;
+            while i < chars.len() && (chars[i].is_alphanumeric() || chars[i] == '_') {
+                i += 1;
+            }
+            tokens.push((Token::Name(chars[start..i].iter().collect()), line));
+        } else {
+            let symbol = SYMBOLS
+                .iter()
+                .find(|s| starts_with(i, s))
+                .ok_or((line, format!("unexpected symbol '{c}'")))?;
+            tokens.push((Token::Symbol(symbol), line));
+            i += symbol.len();
+        }
+    }
+
+    tokens.push((Token::End, line));
+    Ok(tokens)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(source: &str) -> Result<ScriptValue, String> {
+        let mut quill = Quill::new();
+        quill.load("test", source)?;
+        quill.call("main", &[])
+    }
+
+    fn number(source: &str) -> f64 {
+        match run(source) {
+            Ok(ScriptValue::Number(n)) => n,
+            result => panic!("expected a number, got {result:?}"),
+        }
+    }
+
+    #[test]
+    fn operators_follow_their_precedence() {
+        assert_eq!(
+            number("function main() return 1 + 2 * 3 ^ 2 - -4 % 3 end"),
+            17.
+        );
+        assert_eq!(number("function main() return 2 ^ 3 ^ 2 end"), 512.);
+        assert_eq!(number("function main() return -2 ^ 2 end"), -4.);
+        assert_eq!(number("function main() return 7 % -3 end"), -2.);
+        assert_eq!(number("function main() return #\"hello\" + 0x10 end"), 21.);
+        assert_eq!(
+            run("function main() return \"a\" .. 1 .. 'b' .. 2.5 end"),
+            Ok(ScriptValue::Text("a1b2.5".to_string()))
+        );
+        assert_eq!(
+            run("function main() return 1 < 2 and (\"a\" < \"b\") and not nil end"),
+            Ok(ScriptValue::Bool(true))
+        );
+        assert_eq!(
+            run("function main() return nil or false or 'x' end"),
+            Ok(ScriptValue::Text("x".to_string()))
+        );
+    }
+
+    #[test]
+    fn control_flow() {
+        let source = "
+            -- Recursion, loops and break
+            function fib(n)
+                if n < 2 then
+                    return n
+                elseif n == 2 then
+                    return 1
+                else
+                    return fib(n - 1) + fib(n - 2)
+                end
+            end
+
+            function main()
+                local total = fib(10)
+                for i = 10, 1, -2 do
+                    total = total + i
+                end
+                local i = 0
+                while true do
+                    i = i + 1
+                    if i >= 5 then break end
+                end
+                return total + i
+            end
+        ";
+        assert_eq!(number(source), 55. + 30. + 5.);
+    }
+
+    #[test]
+    fn locals_are_scoped() {
+        let source = "
+            x = 1
+            function main()
+                local x = 2
+                do
+                    local x = 3
+                end
+                local a, b = x, nil
+                y = a
+                return x + x
+            end
+        ";
+        let mut quill = Quill::new();
+        quill.load("test", source).unwrap();
+        assert_eq!(quill.call("main", &[]), Ok(ScriptValue::Number(4.)));
+        assert_eq!(quill.global("x"), ScriptValue::Number(1.));
+        assert_eq!(quill.global("y"), ScriptValue::Number(2.));
+        assert_eq!(quill.global("b"), ScriptValue::Nil);
+    }
+
+    #[test]
+    fn host_functions_are_called() {
+        let mut quill = Quill::new();
+        quill.register(
+            "add",
+            Box::new(|args| match args {
+                [ScriptValue::Number(a), ScriptValue::Number(b)] => Ok(ScriptValue::Number(a + b)),
+                _ => Err("expected two numbers".to_string()),
+            }),
+        );
+        quill
+            .load(
+                "test",
+                "count = 0 function tick(n) count = add(count, n) return count end",
+            )
+            .unwrap();
+        quill.call("tick", &[ScriptValue::Number(2.)]).unwrap();
+        assert_eq!(
+            quill.call("tick", &[ScriptValue::Number(3.)]),
+            Ok(ScriptValue::Number(5.))
+        );
+        assert!(quill.defines("add") && quill.defines("tick") && !quill.defines("main"));
+        assert_eq!(
+            quill.call("tick", &[ScriptValue::Text("a".to_string())]),
+            Err("test:1: add: expected two numbers".to_string())
+        );
+    }
+
+    #[test]
+    fn reloads_keep_the_previous_version_on_error() {
+        let mut quill = Quill::new();
+        quill
+            .load("a", "function f() return 1 end function g() return 2 end")
+            .unwrap();
+        quill.load("b", "function h() return f() + 10 end").unwrap();
+
+        assert!(quill.load("a", "function f() return end end").is_err());
+        assert!(quill
+            .load("a", "x = 1 function f() return 3 end error()")
+            .is_err());
+        assert_eq!(quill.call("h", &[]), Ok(ScriptValue::Number(11.)));
+        assert_eq!(quill.global("x"), ScriptValue::Nil);
+
+        quill.load("a", "function f() return 3 end").unwrap();
+        assert_eq!(quill.call("h", &[]), Ok(ScriptValue::Number(13.)));
+        assert!(!quill.defines("g"));
+    }
+
+    #[test]
+    fn errors_have_the_script_and_line() {
+        let source = "function main()\n  local a = 1\n  return a + nil\nend";
+        assert_eq!(
+            run(source),
+            Err("test:3: attempt to perform arithmetic on a nil value".to_string())
+        );
+        assert_eq!(
+            run("function main()\n  return 1 +\nend"),
+            Err("test:3: unexpected 'end'".to_string())
+        );
+        assert_eq!(
+            run("s = 'abc"),
+            Err("test:1: unfinished string".to_string())
+        );
+        assert_eq!(
+            run("function main() return missing() end"),
+            Err("test:1: unknown function 'missing'".to_string())
+        );
+        assert_eq!(
+            run("t = {}"),
+            Err("test:1: unexpected symbol '{'".to_string())
+        );
+    }
+
+    #[test]
+    fn runaway_scripts_are_stopped() {
+        let mut quill = Quill::new();
+        quill.max_steps = 1000;
+        quill
+            .load(
+                "test",
+                "function spin() while true do end end function deep() return deep() end",
+            )
+            .unwrap();
+        assert!(quill
+            .call("spin", &[])
+            .unwrap_err()
+            .contains("ran more than 1000"));
+        assert!(quill
+            .call("deep", &[])
+            .unwrap_err()
+            .contains("stack overflow"));
+    }
+
+    #[test]
+    fn long_strings_and_comments() {
+        let source = "
+            --[[ a comment
+            over lines ]]
+            function main()
+                return [[
+first
+second]]
+            end
+        ";
+        assert_eq!(
+            run(source),
+            Ok(ScriptValue::Text("first\nsecond".to_string()))
+        );
+    }
+}
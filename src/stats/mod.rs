@@ -0,0 +1,310 @@
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use crate::config::{parse_toml, write_toml, Config, Document, Value};
+
+const STATS_TABLE: &str = "stats";
+const ACHIEVEMENTS_TABLE: &str = "achievements";
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum Condition {
+    // Only unlocked by Stats::unlock.
+    Manual,
+    AtLeast(String, f64),
+    All(Vec<Condition>),
+    Any(Vec<Condition>),
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct Achievement {
+    pub id: String,
+    pub name: String,
+    pub description: String,
+    // Kept secret by the UI until unlocked.
+    pub hidden: bool,
+    pub condition: Condition,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum StatsEvent {
+    Unlocked(String),
+}
+
+// Platform's own stats and achievements, e.g. Steam's, kept in sync with the local ones.
+pub trait StatsBackend {
+    fn set_stat(&mut self, name: &str, value: f64) -> Result<(), String>;
+
+    fn unlock(&mut self, achievement: &str) -> Result<(), String>;
+
+    // Called by Stats::save, e.g. to upload the changes.
+    fn store(&mut self) -> Result<(), String> {
+        Ok(())
+    }
+
+    // Achievements unlocked on another machine, unlocked locally when the backend is added.
+    fn unlocked(&mut self) -> Vec<String> {
+        Vec::new()
+    }
+}
+
+// Counters defined by the game and the achievements they unlock, stored in stats.toml next to
+// the settings by default. Achievements are checked whenever a stat changes.
+pub struct Stats {
+    achievements: Vec<Achievement>,
+    values: BTreeMap<String, f64>,
+    // Unix time of each unlock.
+    unlocked: BTreeMap<String, i64>,
+    backends: Vec<Box<dyn StatsBackend>>,
+    events: Vec<StatsEvent>,
+    path: PathBuf,
+}
+
+impl Achievement {
+    pub fn new(id: &str, name: &str, condition: Condition) -> Self {
+        Achievement {
+            id: id.to_string(),
+            name: name.to_string(),
+            description: String::new(),
+            hidden: false,
+            condition,
+        }
+    }
+
+    pub fn with_description(mut self, description: &str) -> Self {
+        self.description = description.to_string();
+        self
+    }
+
+    pub fn hidden(mut self) -> Self {
+        self.hidden = true;
+        self
+    }
+}
+
+impl Condition {
+    pub fn at_least(stat: &str, value: f64) -> Self {
+        Condition::AtLeast(stat.to_string(), value)
+    }
+
+    fn is_met(&self, values: &BTreeMap<String, f64>) -> bool {
+        match self {
+            Condition::Manual => false,
+            Condition::AtLeast(stat, target) => values.get(stat).copied().unwrap_or(0.) >= *target,
+            Condition::All(conditions) => conditions.iter().all(|c| c.is_met(values)),
+            Condition::Any(conditions) => conditions.iter().any(|c| c.is_met(values)),
+        }
+    }
+
+    // Between 0 and 1, Any taking its most advanced condition.
+    fn progress(&self, values: &BTreeMap<String, f64>) -> f64 {
+        match self {
+            Condition::Manual => 0.,
+            Condition::AtLeast(_, target) if *target <= 0. => 1.,
+            Condition::AtLeast(stat, target) => {
+                (values.get(stat).copied().unwrap_or(0.) / target).clamp(0., 1.)
+            }
+            Condition::All(conditions) if conditions.is_empty() => 1.,
+            Condition::All(conditions) => {
+                conditions.iter().map(|c| c.progress(values)).sum::<f64>() / conditions.len() as f64
+            }
+            Condition::Any(conditions) => conditions
+                .iter()
+                .map(|c| c.progress(values))
+                .fold(0., f64::max),
+        }
+    }
+}
+
+impl Stats {
+    pub fn new<P: AsRef<Path>>(path: P) -> Self {
+        Stats {
+            achievements: Vec::new(),
+            values: BTreeMap::new(),
+            unlocked: BTreeMap::new(),
+            backends: Vec::new(),
+            events: Vec::new(),
+            path: path.as_ref().to_path_buf(),
+        }
+    }
+
+    // Loads stats.toml from the game's config directory, see Config::directory.
+    pub fn load(game: &str) -> Result<Self, String> {
+        let directory = Config::directory(game).ok_or("no config directory")?;
+        Stats::load_from(directory.join("stats.toml"))
+    }
+
+    // Starts from zero if the file doesn't exist.
+    pub fn load_from<P: AsRef<Path>>(path: P) -> Result<Self, String> {
+        let mut stats = Stats::new(&path);
+        let path = path.as_ref();
+        let source = match std::fs::read_to_string(path) {
+            Ok(source) => source,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(stats),
+            Err(e) => return Err(e.to_string()),
+        };
+
+        let document = parse_toml(&source).map_err(|e| format!("{}: {}", path.display(), e))?;
+        let table = |name: &str| document.get(name).into_iter().flatten();
+        stats.values = table(STATS_TABLE)
+            .filter_map(|(name, value)| Some((name.clone(), value.as_f64()?)))
+            .collect();
+        stats.unlocked = table(ACHIEVEMENTS_TABLE)
+            .filter_map(|(id, time)| Some((id.clone(), time.as_i64()?)))
+            .collect();
+        Ok(stats)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn save(&mut self) -> Result<(), String> {
+        let mut document = Document::new();
+        document.insert(
+            STATS_TABLE.to_string(),
+            self.values
+                .iter()
+                .map(|(name, value)| (name.clone(), Value::Float(*value)))
+                .collect(),
+        );
+        document.insert(
+            ACHIEVEMENTS_TABLE.to_string(),
+            self.unlocked
+                .iter()
+                .map(|(id, time)| (id.clone(), Value::Integer(*time)))
+                .collect(),
+        );
+
+        if let Some(directory) = self.path.parent() {
+            std::fs::create_dir_all(directory).map_err(|e| e.to_string())?;
+        }
+        std::fs::write(&self.path, write_toml(&document)).map_err(|e| e.to_string())?;
+
+        for backend in &mut self.backends {
+            if let Err(e) = backend.store() {
+                log::warn!("failed to store stats: {e}");
+            }
+        }
+        Ok(())
+    }
+
+    // Achievements whose condition is already met are unlocked right away.
+    pub fn define(&mut self, achievement: Achievement) {
+        let id = achievement.id.clone();
+        let met = achievement.condition.is_met(&self.values);
+        self.achievements.retain(|a| a.id != id);
+        self.achievements.push(achievement);
+        if met {
+            self.unlock(&id);
+        }
+    }
+
+    pub fn achievements(&self) -> &[Achievement] {
+        &self.achievements
+    }
+
+    // The local state is sent to the backend, and what it unlocked elsewhere is unlocked here.
+    pub fn add_backend(&mut self, mut backend: Box<dyn StatsBackend>) {
+        for (name, value) in &self.values {
+            if let Err(e) = backend.set_stat(name, *value) {
+                log::warn!("failed to set stat {name}: {e}");
+            }
+        }
+        for id in self.unlocked.keys() {
+            if let Err(e) = backend.unlock(id) {
+                log::warn!("failed to unlock {id}: {e}");
+            }
+        }
+        let unlocked = backend.unlocked();
+        self.backends.push(backend);
+        for id in unlocked {
+            self.unlock(&id);
+        }
+    }
+
+    pub fn get(&self, stat: &str) -> f64 {
+        self.values.get(stat).copied().unwrap_or(0.)
+    }
+
+    pub fn set(&mut self, stat: &str, value: f64) {
+        if self.values.get(stat) == Some(&value) {
+            return;
+        }
+        self.values.insert(stat.to_string(), value);
+        for backend in &mut self.backends {
+            if let Err(e) = backend.set_stat(stat, value) {
+                log::warn!("failed to set stat {stat}: {e}");
+            }
+        }
+        self.check();
+    }
+
+    pub fn add(&mut self, stat: &str, amount: f64) {
+        self.set(stat, self.get(stat) + amount);
+    }
+
+    // Keeps the highest value, e.g. for best scores.
+    pub fn set_max(&mut self, stat: &str, value: f64) {
+        if value > self.get(stat) {
+            self.set(stat, value);
+        }
+    }
+
+    // Returns false if the achievement was already unlocked.
+    pub fn unlock(&mut self, id: &str) -> bool {
+        if self.unlocked.contains_key(id) {
+            return false;
+        }
+
+        let time = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_secs() as i64);
+        self.unlocked.insert(id.to_string(), time);
+        for backend in &mut self.backends {
+            if let Err(e) = backend.unlock(id) {
+                log::warn!("failed to unlock {id}: {e}");
+            }
+        }
+        log::info!("unlocked {id}");
+        self.events.push(StatsEvent::Unlocked(id.to_string()));
+        true
+    }
+
+    pub fn is_unlocked(&self, id: &str) -> bool {
+        self.unlocked.contains_key(id)
+    }
+
+    // Unix time of the unlock.
+    pub fn unlocked_at(&self, id: &str) -> Option<i64> {
+        self.unlocked.get(id).copied()
+    }
+
+    // Between 0 and 1, None for undefined achievements.
+    pub fn progress(&self, id: &str) -> Option<f64> {
+        if self.is_unlocked(id) {
+            return Some(1.);
+        }
+        let achievement = self.achievements.iter().find(|a| a.id == id)?;
+        Some(achievement.condition.progress(&self.values))
+    }
+
+    // Emitted since the last call, e.g. for the UI to show them.
+    pub fn take_events(&mut self) -> Vec<StatsEvent> {
+        std::mem::take(&mut self.events)
+    }
+
+    fn check(&mut self) {
+        let met: Vec<String> = self
+            .achievements
+            .iter()
+            .filter(|a| !self.unlocked.contains_key(&a.id) && a.condition.is_met(&self.values))
+            .map(|a| a.id.clone())
+            .collect();
+        for id in met {
+            self.unlock(&id);
+        }
+    }
+}
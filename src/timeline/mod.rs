@@ -0,0 +1,352 @@
+use std::{collections::BTreeMap, path::Path};
+
+use crate::{
+    config::{parse_toml, Value},
+    graphics::Camera,
+    Vec2,
+};
+
+const DURATION_KEY: &str = "duration";
+const CAMERA_KEY: &str = "camera";
+const CUES_KEY: &str = "cues";
+const PATH_KEY: &str = "path";
+
+// Progress between two keys, from the previous key to the one it's set on.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum Ease {
+    #[default]
+    Linear,
+    In,
+    Out,
+    InOut,
+    // Jumps to the key when it's reached.
+    Step,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Key {
+    pub time: f64,
+    pub position: Vec2,
+    pub ease: Ease,
+}
+
+// Something happening at a point of a timeline, which the game plays, e.g. by starting a
+// dialogue. Wait pauses the timeline until TimelinePlayer::resume, e.g. until the dialogue
+// started just before is over.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum Cue {
+    Animation { entity: String, name: String },
+    Dialogue(String),
+    Audio(String),
+    Wait,
+    Event(String),
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum TimelineEvent {
+    Cue(Cue),
+    Finished { skipped: bool },
+}
+
+// Scripted sequence, e.g. a cutscene, made of keys moving the camera and entities and of cues,
+// times being in seconds. It can be built in code or written with the settings' TOML subset,
+// keys being [time, x, y] or [time, x, y, ease] and cues [time, kind, arguments...]:
+//
+// duration = 6.0
+// camera = [[0.0, 0.0, 0.0], [2.0, 10.0, 0.0, "in_out"]]
+// cues = [[0.0, "animation", "hero", "walk"], [3.0, "dialogue", "intro"], [3.0, "wait"]]
+//
+// [hero]
+// path = [[0.0, 1.0, 2.0], [3.0, 5.0, 2.0]]
+//
+// Entities are tables named after the objects of the game they move. Cues at the same time
+// play in the order they're written.
+#[derive(Clone, PartialEq, Debug, Default)]
+pub struct Timeline {
+    // Extended to the last key or cue.
+    pub duration: f64,
+    camera: Vec<Key>,
+    paths: BTreeMap<String, Vec<Key>>,
+    cues: Vec<(f64, Cue)>,
+}
+
+// Plays a timeline, which is passed to every call like dialogues to DialogueRunner.
+#[derive(Clone, PartialEq, Debug, Default)]
+pub struct TimelinePlayer {
+    time: f64,
+    playing: bool,
+    waiting: bool,
+    // Next cue to play.
+    next: usize,
+}
+
+impl Ease {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "linear" => Some(Ease::Linear),
+            "in" => Some(Ease::In),
+            "out" => Some(Ease::Out),
+            "in_out" => Some(Ease::InOut),
+            "step" => Some(Ease::Step),
+            _ => None,
+        }
+    }
+
+    // Eased progress, t going from 0 to 1.
+    pub fn apply(&self, t: f64) -> f64 {
+        let t = t.clamp(0., 1.);
+        match self {
+            Ease::Linear => t,
+            Ease::In => t * t * t,
+            Ease::Out => 1. - (1. - t).powi(3),
+            Ease::InOut if t < 0.5 => 4. * t * t * t,
+            Ease::InOut => 1. - (-2. * t + 2.).powi(3) / 2.,
+            Ease::Step => (t >= 1.) as u8 as f64,
+        }
+    }
+}
+
+impl Timeline {
+    pub fn new() -> Self {
+        Timeline::default()
+    }
+
+    pub fn parse(source: &str) -> Result<Self, String> {
+        let document = parse_toml(source)?;
+        let mut timeline = Timeline::new();
+
+        for (name, values) in &document {
+            if !name.is_empty() {
+                let path = values
+                    .get(PATH_KEY)
+                    .ok_or_else(|| format!("\"{name}\" has no {PATH_KEY}"))?;
+                for key in parse_keys(path).map_err(|e| format!("{PATH_KEY} of \"{name}\": {e}"))? {
+                    timeline.add_key(name, key);
+                }
+                continue;
+            }
+
+            if let Some(camera) = values.get(CAMERA_KEY) {
+                for key in parse_keys(camera).map_err(|e| format!("{CAMERA_KEY}: {e}"))? {
+                    timeline.add_camera_key(key);
+                }
+            }
+            if let Some(cues) = values.get(CUES_KEY) {
+                let cues = cues.as_array().ok_or("expected an array of cues")?;
+                for (i, cue) in cues.iter().enumerate() {
+                    let (time, cue) = parse_cue(cue).ok_or_else(|| format!("invalid cue {i}"))?;
+                    timeline.add_cue(time, cue);
+                }
+            }
+            if let Some(duration) = values.get(DURATION_KEY) {
+                let duration = duration.as_f64().ok_or("invalid duration")?;
+                timeline.duration = timeline.duration.max(duration);
+            }
+        }
+        Ok(timeline)
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, String> {
+        let path = path.as_ref();
+        let source = std::fs::read_to_string(path).map_err(|e| e.to_string())?;
+        Timeline::parse(&source).map_err(|e| format!("{}: {}", path.display(), e))
+    }
+
+    pub fn add_camera_key(&mut self, key: Key) {
+        self.duration = self.duration.max(key.time);
+        insert_key(&mut self.camera, key);
+    }
+
+    pub fn add_key(&mut self, entity: &str, key: Key) {
+        self.duration = self.duration.max(key.time);
+        insert_key(self.paths.entry(entity.to_string()).or_default(), key);
+    }
+
+    // After the cues already added at the same time.
+    pub fn add_cue(&mut self, time: f64, cue: Cue) {
+        self.duration = self.duration.max(time);
+        let index = self.cues.partition_point(|(t, _)| *t <= time);
+        self.cues.insert(index, (time, cue));
+    }
+
+    // Entities moved by the timeline.
+    pub fn entities(&self) -> impl Iterator<Item = &str> {
+        self.paths.keys().map(|e| e.as_str())
+    }
+
+    // Position of the camera at a time, None without camera keys.
+    pub fn camera_at(&self, time: f64) -> Option<Vec2> {
+        sample(&self.camera, time)
+    }
+
+    pub fn position_at(&self, entity: &str, time: f64) -> Option<Vec2> {
+        sample(self.paths.get(entity)?, time)
+    }
+}
+
+impl TimelinePlayer {
+    pub fn new() -> Self {
+        TimelinePlayer::default()
+    }
+
+    // Restarts from the beginning.
+    pub fn play(&mut self) {
+        *self = TimelinePlayer {
+            playing: true,
+            ..TimelinePlayer::default()
+        };
+    }
+
+    pub fn is_playing(&self) -> bool {
+        self.playing
+    }
+
+    // Whether the timeline is paused on a Wait cue.
+    pub fn is_waiting(&self) -> bool {
+        self.waiting
+    }
+
+    pub fn time(&self) -> f64 {
+        self.time
+    }
+
+    pub fn resume(&mut self) {
+        self.waiting = false;
+    }
+
+    pub fn stop(&mut self) {
+        self.playing = false;
+        self.waiting = false;
+    }
+
+    // Advances by dt seconds, returning the cues reached and Finished at the end. Time stops at
+    // a Wait cue, the rest of dt being dropped.
+    pub fn update(&mut self, timeline: &Timeline, dt: f64) -> Vec<TimelineEvent> {
+        let mut events = Vec::new();
+        if !self.playing || self.waiting {
+            return events;
+        }
+
+        let end = self.time + dt;
+        while let Some((time, cue)) = timeline.cues.get(self.next).filter(|(t, _)| *t <= end) {
+            self.next += 1;
+            events.push(TimelineEvent::Cue(cue.clone()));
+            if *cue == Cue::Wait {
+                self.time = *time;
+                self.waiting = true;
+                return events;
+            }
+        }
+
+        self.time = end.min(timeline.duration);
+        if end >= timeline.duration {
+            self.playing = false;
+            events.push(TimelineEvent::Finished { skipped: false });
+        }
+        events
+    }
+
+    // Jumps to the end. Animation and Event cues left are returned in order for the game to
+    // end up in the same state, the others, e.g. dialogues, being skipped.
+    pub fn skip(&mut self, timeline: &Timeline) -> Vec<TimelineEvent> {
+        if !self.playing {
+            return Vec::new();
+        }
+
+        let mut events: Vec<TimelineEvent> = timeline.cues[self.next.min(timeline.cues.len())..]
+            .iter()
+            .filter(|(_, cue)| matches!(cue, Cue::Animation { .. } | Cue::Event(_)))
+            .map(|(_, cue)| TimelineEvent::Cue(cue.clone()))
+            .collect();
+        events.push(TimelineEvent::Finished { skipped: true });
+
+        self.time = timeline.duration;
+        self.next = timeline.cues.len();
+        self.stop();
+        events
+    }
+
+    // Position of the camera and entities at the current time, None while stopped.
+    pub fn camera(&self, timeline: &Timeline) -> Option<Vec2> {
+        self.playing.then(|| timeline.camera_at(self.time))?
+    }
+
+    pub fn position(&self, timeline: &Timeline, entity: &str) -> Option<Vec2> {
+        self.playing
+            .then(|| timeline.position_at(entity, self.time))?
+    }
+
+    // Moves the camera while the timeline has it.
+    pub fn apply_camera(&self, timeline: &Timeline, camera: &mut Camera) {
+        if let Some(position) = self.camera(timeline) {
+            camera.position = position;
+        }
+    }
+}
+
+// After the keys already added at the same time.
+fn insert_key(keys: &mut Vec<Key>, key: Key) {
+    let index = keys.partition_point(|k| k.time <= key.time);
+    keys.insert(index, key);
+}
+
+// Holds the first and last keys before and after them.
+fn sample(keys: &[Key], time: f64) -> Option<Vec2> {
+    let next = keys.partition_point(|k| k.time <= time);
+    let Some(key) = keys.get(next) else {
+        return keys.last().map(|k| k.position);
+    };
+    let Some(previous) = next.checked_sub(1).map(|i| keys[i]) else {
+        return Some(key.position);
+    };
+
+    let t = (time - previous.time) / (key.time - previous.time);
+    Some(previous.position.lerp(key.position, key.ease.apply(t)))
+}
+
+fn parse_keys(value: &Value) -> Result<Vec<Key>, String> {
+    let keys = value.as_array().ok_or("expected an array of keys")?;
+    keys.iter()
+        .enumerate()
+        .map(|(i, key)| parse_key(key).ok_or_else(|| format!("invalid key {i}")))
+        .collect()
+}
+
+fn parse_key(value: &Value) -> Option<Key> {
+    let values = value.as_array()?;
+    let ease = match values.get(3) {
+        Some(ease) => Ease::from_name(ease.as_str()?)?,
+        None => Ease::default(),
+    };
+    if values.len() > 4 {
+        return None;
+    }
+    Some(Key {
+        time: values.first()?.as_f64()?,
+        position: Vec2::new(values.get(1)?.as_f64()?, values.get(2)?.as_f64()?),
+        ease,
+    })
+}
+
+fn parse_cue(value: &Value) -> Option<(f64, Cue)> {
+    let values = value.as_array()?;
+    let time = values.first()?.as_f64()?;
+    let kind = values.get(1)?.as_str()?;
+    let arguments: Vec<String> = values[2..]
+        .iter()
+        .map(|v| v.as_str().map(str::to_string))
+        .collect::<Option<_>>()?;
+
+    let cue = match (kind, arguments.as_slice()) {
+        ("animation", [entity, name]) => Cue::Animation {
+            entity: entity.clone(),
+            name: name.clone(),
+        },
+        ("dialogue", [node]) => Cue::Dialogue(node.clone()),
+        ("audio", [name]) => Cue::Audio(name.clone()),
+        ("wait", []) => Cue::Wait,
+        ("event", [name]) => Cue::Event(name.clone()),
+        _ => return None,
+    };
+    Some((time, cue))
+}
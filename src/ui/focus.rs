@@ -0,0 +1,266 @@
+use crate::{
+    graphics::{DrawParams, GraphicsPipeline, PixelRect},
+    inputs::{ButtonControl, GamepadAxis, GamepadButton, InputScheme, InputsPipeline, Scancode},
+    Vec2,
+};
+
+use super::{Ui, UiEvent, WidgetId, WidgetKind};
+
+const STICK_THRESHOLD: f64 = 0.5;
+const SLIDER_STEPS: f64 = 10.;
+
+pub type FocusIndicator = Box<dyn Fn(&mut GraphicsPipeline, PixelRect)>;
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum Direction {
+    Up,
+    Down,
+    Left,
+    Right,
+}
+
+// Explicit focus links, directions left to None fall back to the closest widget on screen.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub struct Neighbors {
+    pub up: Option<WidgetId>,
+    pub down: Option<WidgetId>,
+    pub left: Option<WidgetId>,
+    pub right: Option<WidgetId>,
+}
+
+// State of the navigation controls, used to only react when they get pressed.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub(super) struct NavigationState {
+    direction: Option<Direction>,
+    activate: bool,
+    cancel: bool,
+}
+
+impl Neighbors {
+    fn get(&self, direction: Direction) -> Option<WidgetId> {
+        match direction {
+            Direction::Up => self.up,
+            Direction::Down => self.down,
+            Direction::Left => self.left,
+            Direction::Right => self.right,
+        }
+    }
+}
+
+impl NavigationState {
+    fn read<T: InputScheme>(inputs: &InputsPipeline<T>) -> Self {
+        let held = |controls: &[ButtonControl]| controls.iter().any(|c| inputs.is_held(c));
+        let stick_x = inputs.gamepad_axis(GamepadAxis::LeftX);
+        let stick_y = inputs.gamepad_axis(GamepadAxis::LeftY);
+
+        let direction = if held(&[
+            ButtonControl::Keyboard(Scancode::Up),
+            ButtonControl::Gamepad(GamepadButton::DPadUp),
+        ]) || stick_y < -STICK_THRESHOLD
+        {
+            Some(Direction::Up)
+        } else if held(&[
+            ButtonControl::Keyboard(Scancode::Down),
+            ButtonControl::Gamepad(GamepadButton::DPadDown),
+        ]) || stick_y > STICK_THRESHOLD
+        {
+            Some(Direction::Down)
+        } else if held(&[
+            ButtonControl::Keyboard(Scancode::Left),
+            ButtonControl::Gamepad(GamepadButton::DPadLeft),
+        ]) || stick_x < -STICK_THRESHOLD
+        {
+            Some(Direction::Left)
+        } else if held(&[
+            ButtonControl::Keyboard(Scancode::Right),
+            ButtonControl::Gamepad(GamepadButton::DPadRight),
+        ]) || stick_x > STICK_THRESHOLD
+        {
+            Some(Direction::Right)
+        } else {
+            None
+        };
+
+        NavigationState {
+            direction,
+            activate: held(&[
+                ButtonControl::Keyboard(Scancode::Return),
+                ButtonControl::Keyboard(Scancode::KpEnter),
+                ButtonControl::Gamepad(GamepadButton::A),
+            ]),
+            cancel: held(&[
+                ButtonControl::Keyboard(Scancode::Escape),
+                ButtonControl::Gamepad(GamepadButton::B),
+            ]),
+        }
+    }
+}
+
+impl Ui {
+    pub fn focused(&self) -> Option<WidgetId> {
+        self.focused
+    }
+
+    pub fn is_focused(&self, id: WidgetId) -> bool {
+        self.focused == Some(id)
+    }
+
+    pub fn set_focus(&mut self, id: Option<WidgetId>) {
+        if self.focused != id {
+            self.focused = id;
+            self.events.push(UiEvent::FocusChanged(id));
+        }
+    }
+
+    pub fn set_neighbors(&mut self, id: WidgetId, neighbors: Neighbors) {
+        self.neighbors.insert(id, neighbors);
+    }
+
+    // Replaces the default outline drawn around the focused widget.
+    pub fn set_focus_indicator(&mut self, indicator: Option<FocusIndicator>) {
+        self.focus_indicator = indicator;
+    }
+
+    pub(super) fn navigate<T: InputScheme>(
+        &mut self,
+        inputs: &InputsPipeline<T>,
+        window_size: (u32, u32),
+    ) {
+        let state = NavigationState::read(inputs);
+        let previous = std::mem::replace(&mut self.navigation, state);
+
+        if self.focused.is_some_and(|f| !self.is_focusable(f)) {
+            self.set_focus(None);
+        }
+
+        if state.cancel && !previous.cancel {
+            self.events.push(UiEvent::Cancelled);
+        }
+
+        if state.activate && !previous.activate {
+            if let Some(focused) = self.focused {
+                self.activate(focused);
+            }
+        }
+
+        let Some(direction) = state.direction.filter(|d| previous.direction != Some(*d)) else {
+            return;
+        };
+
+        let Some(focused) = self.focused else {
+            let first = self.ids().find(|id| self.is_focusable(*id));
+            self.set_focus(first);
+            return;
+        };
+
+        if matches!(direction, Direction::Left | Direction::Right)
+            && self.step_slider(focused, direction)
+        {
+            return;
+        }
+
+        let next = self
+            .neighbors
+            .get(&focused)
+            .and_then(|n| n.get(direction))
+            .filter(|n| self.is_focusable(*n))
+            .or_else(|| self.closest_in_direction(focused, direction, window_size));
+
+        if next.is_some() {
+            self.set_focus(next);
+        }
+    }
+
+    pub(super) fn draw_focus(&self, graphics_ppl: &mut GraphicsPipeline) {
+        let Some(focused) = self.focused else {
+            return;
+        };
+
+        let Some(rect) = self.rect(focused, graphics_ppl.options.window_size) else {
+            return;
+        };
+
+        match &self.focus_indicator {
+            Some(indicator) => indicator(graphics_ppl, rect),
+            None => {
+                let outline = PixelRect::new(
+                    rect.x() - 2,
+                    rect.y() - 2,
+                    rect.width() + 4,
+                    rect.height() + 4,
+                );
+                graphics_ppl.draw_rect_screen(
+                    outline,
+                    &self.style.accent_color,
+                    false,
+                    &DrawParams::default(),
+                );
+            }
+        }
+    }
+
+    fn is_focusable(&self, id: WidgetId) -> bool {
+        self.is_visible(id) && self.widget(id).is_some_and(|w| w.kind.is_interactive())
+    }
+
+    // Returns false if the widget isn't a slider.
+    fn step_slider(&mut self, id: WidgetId, direction: Direction) -> bool {
+        let Some(widget) = self.widget_mut(id) else {
+            return false;
+        };
+
+        let WidgetKind::Slider { value, min, max } = &mut widget.kind else {
+            return false;
+        };
+
+        let step = (*max - *min) / SLIDER_STEPS;
+        let step = if direction == Direction::Left {
+            -step
+        } else {
+            step
+        };
+        let new_value = (*value + step).clamp(min.min(*max), max.max(*min));
+        if new_value != *value {
+            *value = new_value;
+            self.events.push(UiEvent::ValueChanged(id, new_value));
+        }
+
+        true
+    }
+
+    // Closest focusable widget whose center lies in the direction, favoring aligned ones.
+    fn closest_in_direction(
+        &self,
+        from: WidgetId,
+        direction: Direction,
+        window_size: (u32, u32),
+    ) -> Option<WidgetId> {
+        let center = |id: WidgetId| {
+            let c = self.rect(id, window_size).unwrap().center();
+            Vec2::new(c.x() as f64, c.y() as f64)
+        };
+
+        let origin = center(from);
+        let axis = match direction {
+            Direction::Up => Vec2::new(0., -1.),
+            Direction::Down => Vec2::new(0., 1.),
+            Direction::Left => Vec2::new(-1., 0.),
+            Direction::Right => Vec2::new(1., 0.),
+        };
+
+        self.ids()
+            .filter(|id| *id != from && self.is_focusable(*id))
+            .filter_map(|id| {
+                let delta = center(id) - origin;
+                let along = delta.dot(axis);
+                if along <= 0. {
+                    return None;
+                }
+
+                let across = (delta - axis * along).length();
+                Some((id, along + across * 2.))
+            })
+            .min_by(|a, b| a.1.total_cmp(&b.1))
+            .map(|(id, _)| id)
+    }
+}
@@ -0,0 +1,499 @@
+use std::collections::HashMap;
+
+use crate::{
+    graphics::{BitmapFont, Color, DrawParams, GraphicsPipeline, Margins, PixelRect, TextureId},
+    i18n::Localization,
+    inputs::{ButtonControl, InputScheme, InputsPipeline, MouseButton},
+    profile_scope, Point, Vec2,
+};
+
+pub use focus::{Direction, FocusIndicator, Neighbors};
+
+mod focus;
+
+// Anchors and pivots are expressed relatively to a rect, (0, 0) being its top left corner
+// and (1, 1) its bottom right one.
+pub mod anchor {
+    use crate::Vec2;
+
+    pub const TOP_LEFT: Vec2 = Vec2::new(0., 0.);
+    pub const TOP: Vec2 = Vec2::new(0.5, 0.);
+    pub const TOP_RIGHT: Vec2 = Vec2::new(1., 0.);
+    pub const LEFT: Vec2 = Vec2::new(0., 0.5);
+    pub const CENTER: Vec2 = Vec2::new(0.5, 0.5);
+    pub const RIGHT: Vec2 = Vec2::new(1., 0.5);
+    pub const BOTTOM_LEFT: Vec2 = Vec2::new(0., 1.);
+    pub const BOTTOM: Vec2 = Vec2::new(0.5, 1.);
+    pub const BOTTOM_RIGHT: Vec2 = Vec2::new(1., 1.);
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct WidgetId(usize);
+
+// Places the pivot point of the widget on the anchor point of its parent (or of the window),
+// then moves it by offset pixels.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Layout {
+    pub anchor: Vec2,
+    pub pivot: Vec2,
+    pub offset: Point,
+    pub size: (u32, u32),
+}
+
+pub enum WidgetKind {
+    Panel,
+    Label { text: String },
+    Button { text: String },
+    Checkbox { text: String, checked: bool },
+    Slider { value: f64, min: f64, max: f64 },
+}
+
+pub struct Widget {
+    pub layout: Layout,
+    pub kind: WidgetKind,
+    pub visible: bool,
+    parent: Option<WidgetId>,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum UiEvent {
+    Clicked(WidgetId),
+    Toggled(WidgetId, bool),
+    ValueChanged(WidgetId, f64),
+    FocusChanged(Option<WidgetId>),
+    Cancelled,
+}
+
+pub struct UiStyle {
+    pub font: Option<BitmapFont>,
+    pub text_scale: f64,
+    pub text_color: Color,
+    pub panel_color: Color,
+    pub panel_texture: Option<(TextureId, Margins)>,
+    pub button_color: Color,
+    pub button_hovered_color: Color,
+    pub button_pressed_color: Color,
+    pub accent_color: Color,
+}
+
+pub struct Ui {
+    pub style: UiStyle,
+    // When set, widget texts are keys translated in the current language on draw.
+    pub localization: Option<Localization>,
+    widgets: Vec<Option<Widget>>,
+    hovered: Option<WidgetId>,
+    pressed: Option<WidgetId>,
+    mouse_was_down: bool,
+    focused: Option<WidgetId>,
+    neighbors: HashMap<WidgetId, Neighbors>,
+    navigation: focus::NavigationState,
+    focus_indicator: Option<FocusIndicator>,
+    events: Vec<UiEvent>,
+}
+
+impl Layout {
+    pub fn new(anchor: Vec2, pivot: Vec2, offset: Point, size: (u32, u32)) -> Self {
+        Layout {
+            anchor,
+            pivot,
+            offset,
+            size,
+        }
+    }
+
+    // Pivot and anchor on the same point, which is what most layouts want.
+    pub fn anchored(anchor: Vec2, offset: Point, size: (u32, u32)) -> Self {
+        Layout::new(anchor, anchor, offset, size)
+    }
+
+    fn resolve(&self, parent: PixelRect) -> PixelRect {
+        let anchor = Vec2::new(
+            parent.x() as f64 + self.anchor.x * parent.width() as f64,
+            parent.y() as f64 + self.anchor.y * parent.height() as f64,
+        );
+        let pivot = Vec2::new(
+            self.pivot.x * self.size.0 as f64,
+            self.pivot.y * self.size.1 as f64,
+        );
+
+        PixelRect::new(
+            (anchor.x - pivot.x).round() as i32 + self.offset.x,
+            (anchor.y - pivot.y).round() as i32 + self.offset.y,
+            self.size.0,
+            self.size.1,
+        )
+    }
+}
+
+impl WidgetKind {
+    fn is_interactive(&self) -> bool {
+        matches!(
+            self,
+            WidgetKind::Button { .. } | WidgetKind::Checkbox { .. } | WidgetKind::Slider { .. }
+        )
+    }
+}
+
+impl Default for UiStyle {
+    fn default() -> Self {
+        UiStyle {
+            font: None,
+            text_scale: 1.,
+            text_color: Color::WHITE,
+            panel_color: Color::RGBA(20, 20, 30, 220),
+            panel_texture: None,
+            button_color: Color::RGB(60, 60, 80),
+            button_hovered_color: Color::RGB(80, 80, 110),
+            button_pressed_color: Color::RGB(40, 40, 55),
+            accent_color: Color::RGB(230, 180, 60),
+        }
+    }
+}
+
+impl Ui {
+    pub fn new(style: UiStyle) -> Self {
+        Ui {
+            style,
+            localization: None,
+            widgets: Vec::new(),
+            hovered: None,
+            pressed: None,
+            mouse_was_down: false,
+            focused: None,
+            neighbors: HashMap::new(),
+            navigation: focus::NavigationState::default(),
+            focus_indicator: None,
+            events: Vec::new(),
+        }
+    }
+
+    // Widgets are drawn in the order they are added, children on top of their parent.
+    pub fn add(&mut self, parent: Option<WidgetId>, layout: Layout, kind: WidgetKind) -> WidgetId {
+        self.widgets.push(Some(Widget {
+            layout,
+            kind,
+            visible: true,
+            parent,
+        }));
+        WidgetId(self.widgets.len() - 1)
+    }
+
+    pub fn add_panel(&mut self, parent: Option<WidgetId>, layout: Layout) -> WidgetId {
+        self.add(parent, layout, WidgetKind::Panel)
+    }
+
+    pub fn add_label(&mut self, parent: Option<WidgetId>, layout: Layout, text: &str) -> WidgetId {
+        let text = text.to_string();
+        self.add(parent, layout, WidgetKind::Label { text })
+    }
+
+    pub fn add_button(&mut self, parent: Option<WidgetId>, layout: Layout, text: &str) -> WidgetId {
+        let text = text.to_string();
+        self.add(parent, layout, WidgetKind::Button { text })
+    }
+
+    pub fn add_checkbox(
+        &mut self,
+        parent: Option<WidgetId>,
+        layout: Layout,
+        text: &str,
+        checked: bool,
+    ) -> WidgetId {
+        let text = text.to_string();
+        self.add(parent, layout, WidgetKind::Checkbox { text, checked })
+    }
+
+    pub fn add_slider(
+        &mut self,
+        parent: Option<WidgetId>,
+        layout: Layout,
+        value: f64,
+        min: f64,
+        max: f64,
+    ) -> WidgetId {
+        self.add(parent, layout, WidgetKind::Slider { value, min, max })
+    }
+
+    // Also removes the widget's children.
+    pub fn remove(&mut self, id: WidgetId) {
+        self.widgets[id.0] = None;
+        self.neighbors.remove(&id);
+
+        let children: Vec<WidgetId> = self
+            .ids()
+            .filter(|c| self.widgets[c.0].as_ref().unwrap().parent == Some(id))
+            .collect();
+
+        for c in children {
+            self.remove(c);
+        }
+    }
+
+    pub fn widget(&self, id: WidgetId) -> Option<&Widget> {
+        self.widgets.get(id.0)?.as_ref()
+    }
+
+    pub fn widget_mut(&mut self, id: WidgetId) -> Option<&mut Widget> {
+        self.widgets.get_mut(id.0)?.as_mut()
+    }
+
+    pub fn is_hovered(&self, id: WidgetId) -> bool {
+        self.hovered == Some(id)
+    }
+
+    pub fn is_pressed(&self, id: WidgetId) -> bool {
+        self.pressed == Some(id)
+    }
+
+    // Events produced during the last update.
+    pub fn events(&self) -> &[UiEvent] {
+        &self.events
+    }
+
+    // Rect of the widget in the window, in pixels.
+    pub fn rect(&self, id: WidgetId, window_size: (u32, u32)) -> Option<PixelRect> {
+        let widget = self.widget(id)?;
+        let parent = match widget.parent {
+            Some(p) => self.rect(p, window_size)?,
+            None => PixelRect::new(0, 0, window_size.0, window_size.1),
+        };
+
+        Some(widget.layout.resolve(parent))
+    }
+
+    // A widget is only visible if all its ancestors are.
+    pub fn is_visible(&self, id: WidgetId) -> bool {
+        match self.widget(id) {
+            Some(w) => w.visible && w.parent.is_none_or(|p| self.is_visible(p)),
+            None => false,
+        }
+    }
+
+    pub fn update<T: InputScheme>(&mut self, inputs: &InputsPipeline<T>, window_size: (u32, u32)) {
+        profile_scope!("ui");
+        self.events.clear();
+
+        let mouse = inputs.mouse_position();
+        let mouse_down = inputs.is_held(&ButtonControl::Mouse(MouseButton::Left));
+
+        // Topmost widget under the cursor, non interactive ones still block the ones below
+        self.hovered = self
+            .ids()
+            .filter(|id| self.is_visible(*id))
+            .filter(|id| {
+                self.rect(*id, window_size)
+                    .is_some_and(|r| r.contains_point((mouse.x, mouse.y)))
+            })
+            .last()
+            .filter(|id| self.widgets[id.0].as_ref().unwrap().kind.is_interactive());
+
+        if mouse_down && !self.mouse_was_down {
+            self.pressed = self.hovered;
+            if self.hovered.is_some() {
+                self.set_focus(self.hovered);
+            }
+        }
+
+        if let Some(id) = self.pressed {
+            if mouse_down {
+                self.drag(id, mouse, window_size);
+            } else {
+                if self.hovered == Some(id) {
+                    self.activate(id);
+                }
+                self.pressed = None;
+            }
+        }
+
+        self.mouse_was_down = mouse_down;
+        self.navigate(inputs, window_size);
+    }
+
+    pub fn draw(&self, graphics_ppl: &mut GraphicsPipeline) {
+        let window_size = graphics_ppl.options.window_size;
+
+        for id in self.ids() {
+            if !self.is_visible(id) {
+                continue;
+            }
+
+            let rect = self.rect(id, window_size).unwrap();
+            let widget = self.widget(id).unwrap();
+            match &widget.kind {
+                WidgetKind::Panel => match self.style.panel_texture {
+                    Some((texture, margins)) => {
+                        graphics_ppl.draw_nine_slice_screen(
+                            texture,
+                            &margins,
+                            rect,
+                            &DrawParams::default(),
+                        );
+                    }
+                    None => graphics_ppl.draw_rect_screen(
+                        rect,
+                        &self.style.panel_color,
+                        true,
+                        &DrawParams::default(),
+                    ),
+                },
+                WidgetKind::Label { text } => {
+                    self.draw_text(graphics_ppl, text, rect, false);
+                }
+                WidgetKind::Button { text } => {
+                    let color = if self.is_pressed(id) {
+                        self.style.button_pressed_color
+                    } else if self.is_hovered(id) {
+                        self.style.button_hovered_color
+                    } else {
+                        self.style.button_color
+                    };
+
+                    graphics_ppl.draw_rect_screen(rect, &color, true, &DrawParams::default());
+                    self.draw_text(graphics_ppl, text, rect, true);
+                }
+                WidgetKind::Checkbox { text, checked } => {
+                    let side = rect.height();
+                    let check_box = PixelRect::new(rect.x(), rect.y(), side, side);
+                    let color = if self.is_hovered(id) {
+                        self.style.button_hovered_color
+                    } else {
+                        self.style.button_color
+                    };
+
+                    graphics_ppl.draw_rect_screen(check_box, &color, true, &DrawParams::default());
+                    if *checked {
+                        let margin = side / 4;
+                        let check = PixelRect::new(
+                            rect.x() + margin as i32,
+                            rect.y() + margin as i32,
+                            side - margin * 2,
+                            side - margin * 2,
+                        );
+                        graphics_ppl.draw_rect_screen(
+                            check,
+                            &self.style.accent_color,
+                            true,
+                            &DrawParams::default(),
+                        );
+                    }
+
+                    let text_rect = PixelRect::new(
+                        rect.x() + (side + side / 2) as i32,
+                        rect.y(),
+                        rect.width().saturating_sub(side + side / 2).max(1),
+                        rect.height(),
+                    );
+                    self.draw_text(graphics_ppl, text, text_rect, false);
+                }
+                WidgetKind::Slider { value, min, max } => {
+                    let track = PixelRect::new(rect.x(), rect.center().y() - 2, rect.width(), 4);
+                    graphics_ppl.draw_rect_screen(
+                        track,
+                        &self.style.button_color,
+                        true,
+                        &DrawParams::default(),
+                    );
+
+                    let t = slider_ratio(*value, *min, *max);
+                    let handle_width = (rect.height() / 2).max(1);
+                    let handle = PixelRect::new(
+                        rect.x() + (t * rect.width().saturating_sub(handle_width) as f64) as i32,
+                        rect.y(),
+                        handle_width,
+                        rect.height(),
+                    );
+                    let color = if self.is_pressed(id) || self.is_hovered(id) {
+                        self.style.accent_color
+                    } else {
+                        self.style.button_hovered_color
+                    };
+                    graphics_ppl.draw_rect_screen(handle, &color, true, &DrawParams::default());
+                }
+            }
+        }
+
+        self.draw_focus(graphics_ppl);
+    }
+
+    fn ids(&self) -> impl Iterator<Item = WidgetId> + '_ {
+        self.widgets
+            .iter()
+            .enumerate()
+            .filter(|(_, w)| w.is_some())
+            .map(|(i, _)| WidgetId(i))
+    }
+
+    fn activate(&mut self, id: WidgetId) {
+        let Some(widget) = self.widget_mut(id) else {
+            return;
+        };
+
+        match &mut widget.kind {
+            WidgetKind::Button { .. } => self.events.push(UiEvent::Clicked(id)),
+            WidgetKind::Checkbox { checked, .. } => {
+                *checked = !*checked;
+                let checked = *checked;
+                self.events.push(UiEvent::Toggled(id, checked));
+            }
+            _ => {}
+        }
+    }
+
+    fn drag(&mut self, id: WidgetId, mouse: Point, window_size: (u32, u32)) {
+        let Some(rect) = self.rect(id, window_size) else {
+            return;
+        };
+
+        let Some(widget) = self.widget_mut(id) else {
+            return;
+        };
+
+        if let WidgetKind::Slider { value, min, max } = &mut widget.kind {
+            let t = ((mouse.x - rect.x()) as f64 / rect.width() as f64).clamp(0., 1.);
+            let new_value = *min + t * (*max - *min);
+            if new_value != *value {
+                *value = new_value;
+                self.events.push(UiEvent::ValueChanged(id, new_value));
+            }
+        }
+    }
+
+    fn draw_text(
+        &self,
+        graphics_ppl: &mut GraphicsPipeline,
+        text: &str,
+        rect: PixelRect,
+        centered: bool,
+    ) {
+        let Some(font) = &self.style.font else {
+            return;
+        };
+
+        let text = match &self.localization {
+            Some(localization) => localization.tr(text, &[]),
+            None => text.to_string(),
+        };
+        let scale = self.style.text_scale * graphics_ppl.options.text_scale;
+        let (width, height) = font.text_size(&text, scale);
+        let x = if centered {
+            rect.x() + (rect.width() as i32 - width as i32) / 2
+        } else {
+            rect.x()
+        };
+        let y = rect.y() + (rect.height() as i32 - height as i32) / 2;
+
+        let params = DrawParams {
+            tint: self.style.text_color,
+            ..Default::default()
+        };
+        graphics_ppl.draw_text_screen(font, &text, Point::new(x, y), scale, &params);
+    }
+}
+
+fn slider_ratio(value: f64, min: f64, max: f64) -> f64 {
+    if max == min {
+        return 0.;
+    }
+
+    ((value - min) / (max - min)).clamp(0., 1.)
+}
@@ -0,0 +1,195 @@
+use std::{
+    collections::BTreeSet,
+    path::{Path, PathBuf},
+};
+
+use zip::Archive;
+
+mod zip;
+
+// Virtual filesystem made of directories and zip archives mounted on top of each other, e.g.
+// the game's data under the mods players installed. Files are looked up by relative paths with
+// '/' separators, the mount with the highest priority having the file providing it.
+#[derive(Default)]
+pub struct Vfs {
+    // Highest priority first.
+    mounts: Vec<Mount>,
+}
+
+struct Mount {
+    path: PathBuf,
+    priority: i32,
+    source: Source,
+}
+
+enum Source {
+    Directory,
+    Archive(Archive),
+}
+
+impl Vfs {
+    pub fn new() -> Self {
+        Vfs::default()
+    }
+
+    // Mounts a directory or a .zip archive. Among mounts of the same priority, the last one
+    // mounted overrides the others.
+    pub fn mount<P: AsRef<Path>>(&mut self, path: P, priority: i32) -> Result<(), String> {
+        let path = path.as_ref();
+        let source = if path.is_dir() {
+            Source::Directory
+        } else if is_archive(path) {
+            Source::Archive(Archive::open(path)?)
+        } else {
+            return Err(format!(
+                "{} isn't a directory or a zip archive",
+                path.display()
+            ));
+        };
+
+        let index = self.mounts.partition_point(|m| m.priority > priority);
+        self.mounts.insert(
+            index,
+            Mount {
+                path: path.to_path_buf(),
+                priority,
+                source,
+            },
+        );
+        log::debug!("mounted {} with priority {priority}", path.display());
+        Ok(())
+    }
+
+    // Mounts every directory and archive in a directory with the same priority, in name order
+    // so that players can order their mods by renaming them. Returns what was mounted.
+    pub fn mount_all<P: AsRef<Path>>(
+        &mut self,
+        directory: P,
+        priority: i32,
+    ) -> Result<Vec<PathBuf>, String> {
+        let directory = directory.as_ref();
+        let mut paths: Vec<PathBuf> = std::fs::read_dir(directory)
+            .map_err(|e| format!("{}: {}", directory.display(), e))?
+            .filter_map(|entry| Some(entry.ok()?.path()))
+            .filter(|path| path.is_dir() || is_archive(path))
+            .collect();
+        paths.sort();
+
+        for path in &paths {
+            self.mount(path, priority)?;
+        }
+        Ok(paths)
+    }
+
+    // Returns false if the path wasn't mounted.
+    pub fn unmount<P: AsRef<Path>>(&mut self, path: P) -> bool {
+        let count = self.mounts.len();
+        self.mounts.retain(|m| m.path != path.as_ref());
+        self.mounts.len() != count
+    }
+
+    // Mounted paths, highest priority first.
+    pub fn mounts(&self) -> impl Iterator<Item = &Path> {
+        self.mounts.iter().map(|m| m.path.as_path())
+    }
+
+    pub fn exists(&self, path: &str) -> bool {
+        self.origin(path).is_some()
+    }
+
+    // Mount providing the file, e.g. to tell which mod overrides it.
+    pub fn origin(&self, path: &str) -> Option<&Path> {
+        let path = normalize(path).ok()?;
+        self.find(&path).map(|m| m.path.as_path())
+    }
+
+    pub fn read(&self, path: &str) -> Result<Vec<u8>, String> {
+        let normalized = normalize(path)?;
+        let mount = self
+            .find(&normalized)
+            .ok_or_else(|| format!("{path}: not found"))?;
+        let read = match &mount.source {
+            Source::Directory => {
+                std::fs::read(mount.path.join(&normalized)).map_err(|e| e.to_string())
+            }
+            Source::Archive(archive) => archive.read(&normalized),
+        };
+        read.map_err(|e| format!("{}: {}", mount.path.join(&normalized).display(), e))
+    }
+
+    pub fn read_to_string(&self, path: &str) -> Result<String, String> {
+        let data = self.read(path)?;
+        String::from_utf8(data).map_err(|_| format!("{path}: not valid UTF-8"))
+    }
+
+    // Path of the file on disk, for APIs loading from paths. None if it's in an archive.
+    pub fn real_path(&self, path: &str) -> Option<PathBuf> {
+        let path = normalize(path).ok()?;
+        let mount = self.find(&path)?;
+        match mount.source {
+            Source::Directory => Some(mount.path.join(path)),
+            Source::Archive(_) => None,
+        }
+    }
+
+    // Names of the files and directories in a directory across every mount, sorted. An empty
+    // path lists the root.
+    pub fn list(&self, directory: &str) -> Result<Vec<String>, String> {
+        let directory = normalize(directory)?;
+        let prefix = if directory.is_empty() {
+            String::new()
+        } else {
+            format!("{directory}/")
+        };
+
+        let mut names = BTreeSet::new();
+        for mount in &self.mounts {
+            match &mount.source {
+                Source::Directory => {
+                    let Ok(entries) = std::fs::read_dir(mount.path.join(&directory)) else {
+                        continue;
+                    };
+                    for entry in entries.flatten() {
+                        names.insert(entry.file_name().to_string_lossy().into_owned());
+                    }
+                }
+                Source::Archive(archive) => {
+                    for name in archive.names() {
+                        if let Some(rest) = name.strip_prefix(&prefix) {
+                            let child = rest.split('/').next().unwrap_or(rest);
+                            names.insert(child.to_string());
+                        }
+                    }
+                }
+            }
+        }
+        Ok(names.into_iter().collect())
+    }
+
+    fn find(&self, path: &str) -> Option<&Mount> {
+        self.mounts.iter().find(|m| match &m.source {
+            Source::Directory => m.path.join(path).is_file(),
+            Source::Archive(archive) => archive.contains(path),
+        })
+    }
+}
+
+fn is_archive(path: &Path) -> bool {
+    path.is_file()
+        && path
+            .extension()
+            .is_some_and(|e| e.eq_ignore_ascii_case("zip"))
+}
+
+// Paths can't leave the mounts, "." and empty components being ignored.
+fn normalize(path: &str) -> Result<String, String> {
+    let mut components = Vec::new();
+    for component in path.split(['/', '\\']) {
+        match component {
+            "" | "." => {}
+            ".." => return Err(format!("{path}: paths can't go up")),
+            component => components.push(component),
+        }
+    }
+    Ok(components.join("/"))
+}
@@ -0,0 +1,292 @@
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{Read, Seek, SeekFrom},
+    path::{Path, PathBuf},
+};
+
+use crate::animation::inflate;
+
+const END_OF_DIRECTORY: u32 = 0x06054b50;
+const DIRECTORY_ENTRY: u32 = 0x02014b50;
+const LOCAL_HEADER: u32 = 0x04034b50;
+
+const STORED: u16 = 0;
+const DEFLATED: u16 = 8;
+
+// Zip archive whose directory is read when it's opened, files being read from disk on demand.
+// Zip64 and encrypted archives aren't supported.
+pub(super) struct Archive {
+    path: PathBuf,
+    entries: HashMap<String, Entry>,
+}
+
+struct Entry {
+    method: u16,
+    crc: u32,
+    compressed_size: u32,
+    size: u32,
+    header_offset: u32,
+}
+
+impl Archive {
+    pub(super) fn open(path: &Path) -> Result<Self, String> {
+        let error = |e: String| format!("{}: {}", path.display(), e);
+        let mut file = File::open(path).map_err(|e| error(e.to_string()))?;
+        let entries = read_directory(&mut file).map_err(error)?;
+        Ok(Archive {
+            path: path.to_path_buf(),
+            entries,
+        })
+    }
+
+    pub(super) fn contains(&self, name: &str) -> bool {
+        self.entries.contains_key(name)
+    }
+
+    pub(super) fn names(&self) -> impl Iterator<Item = &str> {
+        self.entries.keys().map(|n| n.as_str())
+    }
+
+    pub(super) fn read(&self, name: &str) -> Result<Vec<u8>, String> {
+        let entry = self.entries.get(name).ok_or("not found")?;
+        let mut file = File::open(&self.path).map_err(|e| e.to_string())?;
+
+        let mut header = [0; 30];
+        file.seek(SeekFrom::Start(entry.header_offset as u64))
+            .and_then(|_| file.read_exact(&mut header))
+            .map_err(|e| e.to_string())?;
+        if u32_at(&header, 0) != LOCAL_HEADER {
+            return Err("invalid local header".to_string());
+        }
+        // The local name and extra field can differ from the directory's
+        let skip = u16_at(&header, 26) as i64 + u16_at(&header, 28) as i64;
+        let mut data = vec![0; entry.compressed_size as usize];
+        file.seek(SeekFrom::Current(skip))
+            .and_then(|_| file.read_exact(&mut data))
+            .map_err(|e| e.to_string())?;
+
+        let data = match entry.method {
+            STORED => data,
+            DEFLATED => inflate(&data)?,
+            method => return Err(format!("unsupported compression method {method}")),
+        };
+        if data.len() != entry.size as usize || crc32(&data) != entry.crc {
+            return Err("corrupted file".to_string());
+        }
+        Ok(data)
+    }
+}
+
+fn read_directory(file: &mut File) -> Result<HashMap<String, Entry>, String> {
+    // The end record is at most a 64 KiB comment away from the end of the file
+    let length = file.seek(SeekFrom::End(0)).map_err(|e| e.to_string())?;
+    let tail_length = length.min(22 + u16::MAX as u64);
+    let mut tail = vec![0; tail_length as usize];
+    file.seek(SeekFrom::Start(length - tail_length))
+        .and_then(|_| file.read_exact(&mut tail))
+        .map_err(|e| e.to_string())?;
+    let end = (0..tail.len().saturating_sub(21))
+        .rev()
+        .find(|&i| u32_at(&tail, i) == END_OF_DIRECTORY)
+        .ok_or("not a zip archive")?;
+
+    let count = u16_at(&tail, end + 10) as usize;
+    let size = u32_at(&tail, end + 12);
+    let offset = u32_at(&tail, end + 16);
+    if size == u32::MAX || offset == u32::MAX || count == u16::MAX as usize {
+        return Err("zip64 archives aren't supported".to_string());
+    }
+
+    let mut directory = vec![0; size as usize];
+    file.seek(SeekFrom::Start(offset as u64))
+        .and_then(|_| file.read_exact(&mut directory))
+        .map_err(|e| e.to_string())?;
+
+    let mut entries = HashMap::with_capacity(count);
+    let mut position = 0;
+    for _ in 0..count {
+        let record = directory
+            .get(position..position + 46)
+            .ok_or("truncated directory")?;
+        if u32_at(record, 0) != DIRECTORY_ENTRY {
+            return Err("invalid directory entry".to_string());
+        }
+        let name_length = u16_at(record, 28) as usize;
+        let skip = name_length + u16_at(record, 30) as usize + u16_at(record, 32) as usize;
+        let name = directory
+            .get(position + 46..position + 46 + name_length)
+            .ok_or("truncated directory")?;
+        let name = String::from_utf8_lossy(name).replace('\\', "/");
+
+        let encrypted = u16_at(record, 8) & 1 != 0;
+        // Directories only have entries in some archives, their files being enough
+        if !name.ends_with('/') && !encrypted {
+            entries.insert(
+                name,
+                Entry {
+                    method: u16_at(record, 10),
+                    crc: u32_at(record, 16),
+                    compressed_size: u32_at(record, 20),
+                    size: u32_at(record, 24),
+                    header_offset: u32_at(record, 42),
+                },
+            );
+        }
+        position += 46 + skip;
+    }
+    Ok(entries)
+}
+
+fn u16_at(data: &[u8], index: usize) -> u16 {
+    u16::from_le_bytes([data[index], data[index + 1]])
+}
+
+fn u32_at(data: &[u8], index: usize) -> u32 {
+    u32::from_le_bytes([
+        data[index],
+        data[index + 1],
+        data[index + 2],
+        data[index + 3],
+    ])
+}
+
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &byte in data {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ 0xedb88320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct ZipFile<'a> {
+        name: &'a str,
+        method: u16,
+        data: &'a [u8],
+        content: &'a [u8],
+    }
+
+    // Archive of the files, their local headers having an extra field the directory doesn't.
+    fn archive(files: &[ZipFile]) -> Vec<u8> {
+        let (mut zip, mut directory) = (Vec::new(), Vec::new());
+        for file in files {
+            // From the version needed to the name length, shared by both headers
+            let mut fields = Vec::new();
+            fields.extend(20u16.to_le_bytes());
+            fields.extend([0; 2]);
+            fields.extend(file.method.to_le_bytes());
+            fields.extend([0; 4]);
+            fields.extend(crc32(file.content).to_le_bytes());
+            fields.extend((file.data.len() as u32).to_le_bytes());
+            fields.extend((file.content.len() as u32).to_le_bytes());
+            fields.extend((file.name.len() as u16).to_le_bytes());
+
+            directory.extend(DIRECTORY_ENTRY.to_le_bytes());
+            directory.extend(20u16.to_le_bytes());
+            directory.extend(&fields);
+            // Extra field and comment lengths, disk and attributes
+            directory.extend([0; 12]);
+            directory.extend((zip.len() as u32).to_le_bytes());
+            directory.extend(file.name.as_bytes());
+
+            zip.extend(LOCAL_HEADER.to_le_bytes());
+            zip.extend(&fields);
+            zip.extend(4u16.to_le_bytes());
+            zip.extend(file.name.as_bytes());
+            zip.extend([0xfe, 0xca, 0, 0]);
+            zip.extend(file.data);
+        }
+
+        let offset = zip.len() as u32;
+        zip.extend(&directory);
+        zip.extend(END_OF_DIRECTORY.to_le_bytes());
+        zip.extend([0; 4]);
+        zip.extend((files.len() as u16).to_le_bytes());
+        zip.extend((files.len() as u16).to_le_bytes());
+        zip.extend((directory.len() as u32).to_le_bytes());
+        zip.extend(offset.to_le_bytes());
+        zip.extend(7u16.to_le_bytes());
+        zip.extend(b"comment");
+        zip
+    }
+
+    fn write(name: &str, zip: &[u8]) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("engine-{}-{name}.zip", std::process::id()));
+        std::fs::write(&path, zip).unwrap();
+        path
+    }
+
+    #[test]
+    fn stored_and_deflated_files_are_read() {
+        let files = [
+            ZipFile {
+                name: "data/",
+                method: STORED,
+                data: b"",
+                content: b"",
+            },
+            ZipFile {
+                name: "data/stored.txt",
+                method: STORED,
+                data: b"hello",
+                content: b"hello",
+            },
+            ZipFile {
+                name: "data\\deflated.txt",
+                method: DEFLATED,
+                data: &[0x4b, 0x4c, 0x4a, 0x4e, 0x44, 0x42, 0x00],
+                content: b"abcabcabcabcabc",
+            },
+        ];
+        let path = write("files", &archive(&files));
+        let zip = Archive::open(&path).unwrap();
+
+        let mut names: Vec<&str> = zip.names().collect();
+        names.sort();
+        assert_eq!(names, ["data/deflated.txt", "data/stored.txt"]);
+        assert!(!zip.contains("data/"));
+        assert_eq!(zip.read("data/stored.txt").unwrap(), b"hello");
+        assert_eq!(zip.read("data/deflated.txt").unwrap(), b"abcabcabcabcabc");
+        assert_eq!(zip.read("missing.txt").unwrap_err(), "not found");
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn invalid_archives_are_errors() {
+        let path = write("invalid", b"not a zip");
+        let error = Archive::open(&path).err().unwrap();
+        assert_eq!(error, format!("{}: not a zip archive", path.display()));
+        std::fs::remove_file(&path).unwrap();
+
+        let mut zip = archive(&[ZipFile {
+            name: "file.txt",
+            method: STORED,
+            data: b"hello",
+            content: b"hello",
+        }]);
+        // Changes the content without updating its crc
+        let index = zip.windows(5).position(|w| w == b"hello").unwrap();
+        zip[index] = b'j';
+        let path = write("corrupted", &zip);
+        let archive = Archive::open(&path).unwrap();
+        assert_eq!(archive.read("file.txt").unwrap_err(), "corrupted file");
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn crc32_matches_the_standard() {
+        assert_eq!(crc32(b""), 0);
+        assert_eq!(crc32(b"123456789"), 0xcbf43926);
+    }
+}